@@ -3,6 +3,7 @@ mod sys;
 pub use sys::*;
 
 use std::{
+    cell::Cell,
     ffi::CStr,
     ptr::{addr_of, addr_of_mut},
 };
@@ -39,6 +40,38 @@ pub enum UDFConversionError {
     ValueNotUnicode,
     #[error("the UDF value given is a symbol, but doesn't correspond to the boolean symbols used by CLIPS")]
     ValueNotBoolean,
+    #[error("the UDF value given is a negative integer, which can't be a duration")]
+    NegativeDuration,
+}
+
+thread_local! {
+    // `TryFrom` can't be given the environment a value came from, so the `clips` crate sets this from the environment's UTF-8 policy while it converts a UDF argument.
+    static LOSSY_TEXT: Cell<bool> = const { Cell::new(false) };
+}
+
+struct LossyTextGuard(bool);
+
+impl Drop for LossyTextGuard {
+    fn drop(&mut self) {
+        LOSSY_TEXT.with(|lossy| lossy.set(self.0));
+    }
+}
+
+// Strings, symbols and instance names that aren't valid UTF-8 are converted lossily instead of failing with `ValueNotUnicode` while `f` runs.
+pub fn with_lossy_text<R>(lossy: bool, f: impl FnOnce() -> R) -> R {
+    let _guard = LossyTextGuard(LOSSY_TEXT.with(|current| current.replace(lossy)));
+    f()
+}
+
+fn lexeme_to_string(c_str: &CStr) -> Result<String, UDFConversionError> {
+    if LOSSY_TEXT.with(Cell::get) {
+        Ok(c_str.to_string_lossy().into_owned())
+    } else {
+        c_str
+            .to_str()
+            .map(str::to_string)
+            .map_err(|_| UDFConversionError::ValueNotUnicode)
+    }
 }
 
 // TODO: do this for more types.
@@ -81,10 +114,7 @@ impl TryFrom<sys::UDFValue> for String {
 
         if type_num == sys::STRING_TYPE {
             let c_str = unsafe { CStr::from_ptr((*value.__bindgen_anon_1.lexemeValue).contents) };
-            Ok(c_str
-                .to_str()
-                .map_err(|_| UDFConversionError::ValueNotUnicode)?
-                .to_string())
+            lexeme_to_string(c_str)
         } else {
             Err(UDFConversionError::InvalidType("string"))
         }
@@ -99,12 +129,7 @@ impl TryFrom<sys::UDFValue> for CLIPSSymbol {
 
         if type_num == sys::SYMBOL_TYPE {
             let c_str = unsafe { CStr::from_ptr((*value.__bindgen_anon_1.lexemeValue).contents) };
-            Ok(CLIPSSymbol(
-                c_str
-                    .to_str()
-                    .map_err(|_| UDFConversionError::ValueNotUnicode)?
-                    .to_string(),
-            ))
+            lexeme_to_string(c_str).map(CLIPSSymbol)
         } else {
             Err(UDFConversionError::InvalidType("symbol"))
         }
@@ -120,9 +145,9 @@ impl TryFrom<sys::UDFValue> for bool {
         if type_num == sys::SYMBOL_TYPE {
             let c_str = unsafe { CStr::from_ptr((*value.__bindgen_anon_1.lexemeValue).contents) };
 
-            match c_str.to_str().unwrap() {
-                "TRUE" => Ok(true),
-                "FALSE" => Ok(false),
+            match c_str.to_bytes() {
+                b"TRUE" => Ok(true),
+                b"FALSE" => Ok(false),
                 _ => Err(UDFConversionError::ValueNotBoolean),
             }
         } else {
@@ -139,12 +164,7 @@ impl TryFrom<sys::UDFValue> for CLIPSInstanceName {
 
         if type_num == sys::INSTANCE_NAME_TYPE {
             let c_str = unsafe { CStr::from_ptr((*value.__bindgen_anon_1.lexemeValue).contents) };
-            Ok(CLIPSInstanceName(
-                c_str
-                    .to_str()
-                    .map_err(|_| UDFConversionError::ValueNotUnicode)?
-                    .to_string(),
-            ))
+            lexeme_to_string(c_str).map(CLIPSInstanceName)
         } else {
             Err(UDFConversionError::InvalidType("symbol"))
         }
@@ -164,3 +184,22 @@ impl TryFrom<sys::UDFValue> for f64 {
         }
     }
 }
+
+// Durations are integers of milliseconds, the same unit `CLIPSFrom<Duration>` in `clips` writes them in.
+#[cfg(feature = "duration")]
+impl TryFrom<sys::UDFValue> for std::time::Duration {
+    type Error = UDFConversionError;
+
+    fn try_from(value: sys::UDFValue) -> Result<Self, Self::Error> {
+        let type_num = unsafe { (*value.__bindgen_anon_1.header).type_ } as u32;
+
+        if type_num == sys::INTEGER_TYPE {
+            let millis = unsafe { (*value.__bindgen_anon_1.integerValue).contents };
+            let millis = u64::try_from(millis).map_err(|_| UDFConversionError::NegativeDuration)?;
+
+            Ok(std::time::Duration::from_millis(millis))
+        } else {
+            Err(UDFConversionError::InvalidType("integer (milliseconds)"))
+        }
+    }
+}
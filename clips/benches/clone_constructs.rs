@@ -0,0 +1,63 @@
+// Stamps out worker environments from a 1k-rule program, once by parsing the source in every worker and once by cloning the constructs of an environment that already loaded it.
+use std::time::{Duration, Instant};
+
+use clips::Environment;
+
+const RULES: usize = 1000;
+const WORKERS: usize = 20;
+
+fn ruleset() -> String {
+    let mut text = String::from("(deftemplate reading (slot sensor) (slot value))\n");
+
+    for i in 0..RULES {
+        text.push_str(&format!(
+            "(defrule check-{i}
+               (reading (sensor {i}) (value ?v&:(> ?v {i})))
+               (reading (sensor ?other&~{i}) (value ?w&:(< ?w ?v)))
+               =>
+               (assert (alarm {i} ?other)))\n"
+        ));
+    }
+
+    text
+}
+
+fn measure(name: &str, workers: &[Environment], fill: impl Fn(&Environment)) {
+    let start = Instant::now();
+
+    for worker in workers {
+        fill(worker);
+    }
+
+    report(name, start.elapsed());
+}
+
+fn report(name: &str, duration: Duration) {
+    println!(
+        "{:<8} {:>10.2?} {:>10.2?} per worker",
+        name,
+        duration,
+        duration / WORKERS as u32
+    );
+}
+
+fn main() {
+    let text = ruleset();
+
+    println!("Filling {} workers with {} rules:", WORKERS, RULES);
+
+    let workers: Vec<_> = (0..WORKERS).map(|_| Environment::new()).collect();
+    measure("parsed", &workers, |worker| {
+        worker.load_from_str(text.as_str()).unwrap()
+    });
+
+    // Without dynamic constraint checking, CLIPS warns on every binary save that the constraints are left out of the image.
+    let source = Environment::new();
+    source.set_dynamic_constraint_checking(true).unwrap();
+    source.load_from_str(text.as_str()).unwrap();
+    let workers: Vec<_> = (0..WORKERS).map(|_| Environment::new()).collect();
+    measure("cloned", &workers, |worker| {
+        let transfer = source.clone_constructs_into(worker).unwrap();
+        assert!(transfer.not_transferred.is_empty());
+    });
+}
@@ -0,0 +1,90 @@
+// Loads the same large ruleset into many environments, once copying the program text for each environment and once sharing it through an `Arc<str>`. Besides the time, it reports how many bytes were allocated on the Rust side, which is where the copies happen.
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use clips::Environment;
+
+const RULESET_SIZE: usize = 5 * 1024 * 1024;
+const ENVIRONMENTS: usize = 50;
+
+struct CountingAllocator;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+fn ruleset() -> String {
+    // CLIPS gets slow with a lot of constructs, so the size comes from long function bodies instead.
+    let body: Vec<_> = (0..2000).map(|i| i.to_string()).collect();
+    let body = body.join(" ");
+
+    let mut text = String::with_capacity(RULESET_SIZE);
+    let mut i = 0;
+    while text.len() < RULESET_SIZE {
+        text.push_str(&format!("(deffunction f{} (?x) (+ ?x {}))\n", i, body));
+        i += 1;
+    }
+
+    text
+}
+
+fn measure(name: &str, environments: &[Environment], load: impl Fn(&Environment)) {
+    let allocated_before = ALLOCATED.load(Ordering::Relaxed);
+    let start = Instant::now();
+
+    for env in environments {
+        load(env);
+    }
+
+    let duration = start.elapsed();
+    let allocated = ALLOCATED.load(Ordering::Relaxed) - allocated_before;
+    report(name, duration, allocated);
+}
+
+fn report(name: &str, duration: Duration, allocated: usize) {
+    println!(
+        "{:<8} {:>10.2?} {:>10.1} MiB allocated",
+        name,
+        duration,
+        allocated as f64 / (1024.0 * 1024.0)
+    );
+}
+
+fn main() {
+    let text = ruleset();
+    let shared: Arc<str> = Arc::from(text.as_str());
+
+    println!(
+        "Loading {:.1} MiB into {} environments:",
+        text.len() as f64 / (1024.0 * 1024.0),
+        ENVIRONMENTS
+    );
+
+    let environments: Vec<_> = (0..ENVIRONMENTS).map(|_| Environment::new()).collect();
+    measure("copied", &environments, |env| {
+        env.load_from_str(text.as_str()).unwrap()
+    });
+
+    let environments: Vec<_> = (0..ENVIRONMENTS).map(|_| Environment::new()).collect();
+    measure("shared", &environments, |env| {
+        env.load_from_str(shared.clone()).unwrap()
+    });
+}
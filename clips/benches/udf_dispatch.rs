@@ -0,0 +1,75 @@
+// Runs a rule loop that makes 1M calls to a trivial UDF, next to the same loop calling a trivial CLIPS function, so the difference is what it costs to get from CLIPS to the Rust closure. Many other UDFs are registered first, so the UDF called isn't the only one CLIPS and the crate know about.
+use std::time::{Duration, Instant};
+
+use clips::{CLIPSEnvironment, CLIPSValue, UDFType};
+
+const FIRINGS: usize = 1000;
+const CALLS_PER_FIRING: usize = 1000;
+const OTHER_UDFS: usize = 1000;
+
+fn env_calling(function: &str) -> CLIPSEnvironment {
+    let mut env = CLIPSEnvironment::new().unwrap();
+
+    for i in 0..OTHER_UDFS {
+        env.add_udf(
+            &format!("other-{i}"),
+            UDFType::Integer,
+            0,
+            0,
+            vec![],
+            Box::new(move |mut data| data.set_result(CLIPSValue::Int(i as i64)).unwrap()),
+        )
+        .unwrap();
+    }
+    env.add_udf(
+        "trivial",
+        UDFType::Integer,
+        0,
+        0,
+        vec![],
+        Box::new(|mut data| data.set_result(CLIPSValue::Int(1)).unwrap()),
+    )
+    .unwrap();
+
+    env.load_from_str(&format!(
+        "(defrule tick
+           ?count <- (count ?n&:(< ?n {FIRINGS}))
+           =>
+           (retract ?count)
+           (loop-for-count {CALLS_PER_FIRING} ({function}))
+           (assert (count (+ ?n 1))))
+         (defglobal ?*count* = (fact-index (assert (count 0))))"
+    ))
+    .unwrap();
+
+    env
+}
+
+fn measure(name: &str, function: &str) {
+    let mut env = env_calling(function);
+    let start = Instant::now();
+
+    let fired = env.run().unwrap();
+
+    assert_eq!(fired, FIRINGS);
+    report(name, start.elapsed());
+}
+
+fn report(name: &str, duration: Duration) {
+    println!(
+        "{:<8} {:>10.2?} {:>10.2?} per call",
+        name,
+        duration,
+        duration / (FIRINGS * CALLS_PER_FIRING) as u32
+    );
+}
+
+fn main() {
+    println!(
+        "Calling a function {} times from a rule loop:",
+        FIRINGS * CALLS_PER_FIRING
+    );
+
+    measure("clips", "+ 1 1");
+    measure("udf", "trivial");
+}
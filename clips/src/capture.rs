@@ -0,0 +1,305 @@
+use std::{
+    collections::VecDeque,
+    ffi::{CStr, OsString},
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use crate::{LogicalName, Router, RouterSupport};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CaptureOverflow {
+    // Makes room by dropping the oldest text, or moving it to the spill file if there is one.
+    DropOldest,
+    // Keeps what was already captured and drops the text that doesn't fit. CLIPS gives routers no way of failing a write, so the dropped text is only counted.
+    DropNewest,
+}
+
+// Where the oldest text goes when it's pushed out of the buffer. Once the file reaches `max_file_bytes`, it's renamed to `<path>.1`, the previous `<path>.1` to `<path>.2`, and so on, keeping at most `max_files` of the renamed files.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SpillFile {
+    pub path: PathBuf,
+    pub max_file_bytes: u64,
+    pub max_files: usize,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CaptureLimits {
+    pub max_bytes: usize,
+    pub overflow: CaptureOverflow,
+    // Only used with `CaptureOverflow::DropOldest`.
+    pub spill: Option<SpillFile>,
+}
+
+impl CaptureLimits {
+    pub fn new(max_bytes: usize, overflow: CaptureOverflow) -> Self {
+        Self {
+            max_bytes,
+            overflow,
+            spill: None,
+        }
+    }
+
+    pub fn with_spill(mut self, spill: SpillFile) -> Self {
+        self.spill = Some(spill);
+        self
+    }
+}
+
+// Big enough that ordinary output comes in one piece.
+const DEFAULT_WRITE_CHUNK_SIZE: usize = 64 * 1024;
+
+// Keeps what CLIPS writes to some logical names in memory instead of printing it. The text is read through a `CaptureHandle`, since the router itself is handed over to the environment.
+pub struct CaptureRouter {
+    logical_names: Vec<LogicalName>,
+    buffer: Arc<Mutex<CaptureBuffer>>,
+    write_chunk_size: usize,
+}
+
+impl CaptureRouter {
+    // Captures everything, without a limit, until `with_limits()` sets one.
+    pub fn new(logical_names: &[LogicalName]) -> Self {
+        Self {
+            logical_names: logical_names.to_vec(),
+            buffer: Arc::new(Mutex::new(CaptureBuffer::new(None))),
+            write_chunk_size: DEFAULT_WRITE_CHUNK_SIZE,
+        }
+    }
+
+    pub fn with_limits(self, limits: CaptureLimits) -> Self {
+        *self.buffer.lock().unwrap() = CaptureBuffer::new(Some(limits));
+        self
+    }
+
+    // Large writes are taken in pieces of this size, so making room for them never copies more than one piece at a time.
+    pub fn with_write_chunk_size(mut self, write_chunk_size: usize) -> Self {
+        self.write_chunk_size = write_chunk_size;
+        self
+    }
+
+    pub fn handle(&self) -> CaptureHandle {
+        CaptureHandle {
+            buffer: self.buffer.clone(),
+        }
+    }
+}
+
+impl Router for CaptureRouter {
+    fn supports(&self) -> RouterSupport {
+        RouterSupport::WRITE
+    }
+
+    fn query(&mut self, logical_name: &str) -> bool {
+        LogicalName::from_name(logical_name)
+            .is_some_and(|logical_name| self.logical_names.contains(&logical_name))
+    }
+
+    fn write(&mut self, _logical_name: &str, data: &CStr) {
+        self.buffer.lock().unwrap().write(data.to_bytes());
+    }
+
+    fn write_chunk_size(&self) -> Option<usize> {
+        Some(self.write_chunk_size)
+    }
+
+    fn write_chunked(&mut self, _logical_name: &str, chunk: &[u8], _final_chunk: bool) {
+        self.buffer.lock().unwrap().write(chunk);
+    }
+}
+
+#[derive(Clone)]
+pub struct CaptureHandle {
+    buffer: Arc<Mutex<CaptureBuffer>>,
+}
+
+impl CaptureHandle {
+    pub fn contents(&self) -> String {
+        let buffer = self.buffer.lock().unwrap();
+        let (front, back) = buffer.data.as_slices();
+        String::from_utf8_lossy(&[front, back].concat()).into_owned()
+    }
+
+    // Empties the buffer. The counters are left as they are.
+    pub fn take(&self) -> String {
+        let mut buffer = self.buffer.lock().unwrap();
+        let data: Vec<u8> = buffer.data.drain(..).collect();
+        String::from_utf8_lossy(&data).into_owned()
+    }
+
+    pub fn buffered_bytes(&self) -> usize {
+        self.buffer.lock().unwrap().data.len()
+    }
+
+    // Text that was lost for good: pushed out or turned away by the limits, or that couldn't be written to the spill file.
+    pub fn dropped_bytes(&self) -> u64 {
+        self.buffer.lock().unwrap().dropped_bytes
+    }
+
+    pub fn spilled_bytes(&self) -> u64 {
+        self.buffer.lock().unwrap().spilled_bytes
+    }
+
+    // The last error writing to or rotating the spill file. The text that was being written is counted in `dropped_bytes()`.
+    pub fn last_spill_error(&self) -> Option<String> {
+        self.buffer.lock().unwrap().last_spill_error.clone()
+    }
+}
+
+struct CaptureBuffer {
+    limits: Option<CaptureLimits>,
+    data: VecDeque<u8>,
+    spill: Option<Spill>,
+    dropped_bytes: u64,
+    spilled_bytes: u64,
+    last_spill_error: Option<String>,
+}
+
+impl CaptureBuffer {
+    fn new(limits: Option<CaptureLimits>) -> Self {
+        let spill = limits
+            .as_ref()
+            .filter(|limits| limits.overflow == CaptureOverflow::DropOldest)
+            .and_then(|limits| limits.spill.clone())
+            .map(Spill::new);
+
+        Self {
+            limits,
+            data: VecDeque::new(),
+            spill,
+            dropped_bytes: 0,
+            spilled_bytes: 0,
+            last_spill_error: None,
+        }
+    }
+
+    fn write(&mut self, mut data: &[u8]) {
+        let Some(limits) = &self.limits else {
+            self.data.extend(data);
+            return;
+        };
+
+        let overflow = (self.data.len() + data.len()).saturating_sub(limits.max_bytes);
+
+        match limits.overflow {
+            CaptureOverflow::DropNewest => {
+                let fits = data.len() - overflow;
+                self.dropped_bytes += overflow as u64;
+                data = &data[..fits];
+            }
+            CaptureOverflow::DropOldest if overflow > 0 => {
+                // A single write bigger than the whole buffer pushes out its own beginning too.
+                let from_buffer = overflow.min(self.data.len());
+                let mut evicted: Vec<u8> = self.data.drain(..from_buffer).collect();
+                evicted.extend_from_slice(&data[..overflow - from_buffer]);
+                data = &data[overflow - from_buffer..];
+
+                self.evict(&evicted);
+            }
+            CaptureOverflow::DropOldest => {}
+        }
+
+        self.data.extend(data);
+    }
+
+    fn evict(&mut self, evicted: &[u8]) {
+        let Some(spill) = &mut self.spill else {
+            self.dropped_bytes += evicted.len() as u64;
+            return;
+        };
+
+        match spill.write(evicted) {
+            Ok(()) => self.spilled_bytes += evicted.len() as u64,
+            Err(e) => {
+                self.dropped_bytes += evicted.len() as u64;
+                self.last_spill_error = Some(e.to_string());
+            }
+        }
+    }
+}
+
+struct Spill {
+    config: SpillFile,
+    file: Option<File>,
+    file_len: u64,
+}
+
+impl Spill {
+    fn new(config: SpillFile) -> Self {
+        Self {
+            config,
+            file: None,
+            file_len: 0,
+        }
+    }
+
+    fn write(&mut self, mut data: &[u8]) -> io::Result<()> {
+        let max_file_bytes = self.config.max_file_bytes.max(1);
+
+        while !data.is_empty() {
+            if self.file_len >= max_file_bytes {
+                self.rotate()?;
+            }
+
+            let file = match &mut self.file {
+                Some(file) => file,
+                None => {
+                    // A file left over from before is added to, not overwritten.
+                    let file = OpenOptions::new()
+                        .create(true)
+                        .append(true)
+                        .open(&self.config.path)?;
+                    self.file_len = file.metadata()?.len();
+                    self.file.insert(file)
+                }
+            };
+
+            let room = (max_file_bytes.saturating_sub(self.file_len) as usize).max(1);
+            let (chunk, rest) = data.split_at(room.min(data.len()));
+            file.write_all(chunk)?;
+
+            self.file_len += chunk.len() as u64;
+            data = rest;
+        }
+
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file = None;
+        self.file_len = 0;
+
+        let path = &self.config.path;
+        if self.config.max_files == 0 {
+            return remove_if_exists(path);
+        }
+
+        remove_if_exists(&rotated_path(path, self.config.max_files))?;
+        for i in (1..self.config.max_files).rev() {
+            rename_if_exists(&rotated_path(path, i), &rotated_path(path, i + 1))?;
+        }
+
+        rename_if_exists(path, &rotated_path(path, 1))
+    }
+}
+
+fn rotated_path(path: &Path, number: usize) -> PathBuf {
+    let mut name = OsString::from(path.as_os_str());
+    name.push(format!(".{}", number));
+    PathBuf::from(name)
+}
+
+fn remove_if_exists(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        res => res,
+    }
+}
+
+fn rename_if_exists(from: &Path, to: &Path) -> io::Result<()> {
+    match fs::rename(from, to) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        res => res,
+    }
+}
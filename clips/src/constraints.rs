@@ -0,0 +1,51 @@
+use crate::CLIPSError;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConstraintViolationKind {
+    Type,
+    Range,
+    AllowedValues,
+    Cardinality,
+    AllowedClasses,
+}
+
+impl ConstraintViolationKind {
+    pub(crate) fn from_error(error: &CLIPSError) -> Option<Self> {
+        match error {
+            CLIPSError::SlotTypeViolated => Some(Self::Type),
+            CLIPSError::SlotRangeViolated => Some(Self::Range),
+            CLIPSError::SlotAllowedValuesViolated => Some(Self::AllowedValues),
+            CLIPSError::SlotCardinalityViolated => Some(Self::Cardinality),
+            CLIPSError::SlotAllowedClassesViolated => Some(Self::AllowedClasses),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SlotMismatchKind {
+    // The template or class has no slot with that name.
+    UnknownSlot,
+    // The slot was declared with `(default ?NONE)`, so asserting the fact without a value for it would fail. Only checked for templates.
+    MissingValue,
+    Constraint(ConstraintViolationKind),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SlotMismatch {
+    pub slot: String,
+    pub kind: SlotMismatchKind,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ConstraintViolationSource {
+    Fact { index: i64, template: String },
+    Instance { name: String, class: String },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConstraintViolation {
+    pub source: ConstraintViolationSource,
+    pub slot: String,
+    pub kind: ConstraintViolationKind,
+}
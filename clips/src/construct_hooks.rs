@@ -0,0 +1,197 @@
+use std::{
+    collections::{HashMap, HashSet},
+    ffi::{c_char, CStr, CString},
+};
+
+use crate::{
+    load::{
+        construct_data, constructs_in_all_modules, constructs_in_module, ConstructStrFn,
+        NextConstructFn,
+    },
+    CLIPSEnvironment, ConstructKind,
+};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConstructEvent {
+    pub kind: ConstructKind,
+    pub name: String,
+    pub module: String,
+    // Whether a construct of the same kind, name and module existed before this one was parsed.
+    pub redefined: bool,
+}
+
+pub(crate) type ConstructCallback = Box<dyn FnMut(ConstructEvent) + Send>;
+
+type ParseFunction = unsafe extern "C" fn(*mut clips_sys::Environment, *const c_char) -> bool;
+
+#[derive(Default)]
+pub(crate) struct ConstructHooks {
+    callbacks: Vec<ConstructCallback>,
+    // CLIPS' own parsers, which ours call to do the parsing.
+    parse_functions: HashMap<ConstructKind, ParseFunction>,
+}
+
+impl ConstructHooks {
+    pub(crate) fn add_callback(&mut self, callback: ConstructCallback) {
+        self.callbacks.push(callback);
+    }
+}
+
+// CLIPS has no callback for when a construct is defined, so we put our own parse functions in place of CLIPS' for the kinds we report. Defmodules can't be redefined, a defglobal construct can define several globals at once, and methods and message handlers belong to their generic function or class, so those kinds aren't reported.
+pub(crate) fn hook_construct_parsers(env: *mut clips_sys::Environment, hooks: &mut ConstructHooks) {
+    let parsers: [(ConstructKind, ParseFunction); 7] = [
+        (ConstructKind::Deftemplate, parse_deftemplate),
+        (ConstructKind::Defclass, parse_defclass),
+        (ConstructKind::Defgeneric, parse_defgeneric),
+        (ConstructKind::Deffunction, parse_deffunction),
+        (ConstructKind::Defrule, parse_defrule),
+        (ConstructKind::Deffacts, parse_deffacts),
+        (ConstructKind::Definstances, parse_definstances),
+    ];
+
+    for (kind, parser) in parsers {
+        let keyword = CString::new(kind.keyword()).unwrap();
+        let construct = unsafe { clips_sys::FindConstruct(env, keyword.as_ptr()) };
+        // CLIPS may have been built without some of the constructs.
+        if construct.is_null() {
+            continue;
+        }
+
+        let Some(parse_function) = (unsafe { (*construct).parseFunction }) else {
+            continue;
+        };
+
+        hooks.parse_functions.insert(kind, parse_function);
+        unsafe { (*construct).parseFunction = Some(parser) };
+    }
+}
+
+macro_rules! hooked_parser {
+    ($parser:ident, $kind:expr, $next:expr, $name:expr, $module:expr) => {
+        extern "C" fn $parser(
+            env: *mut clips_sys::Environment,
+            read_source: *const c_char,
+        ) -> bool {
+            parse_with_hooks(env, read_source, $kind, $next, $name, $module)
+        }
+    };
+}
+
+hooked_parser!(
+    parse_deftemplate,
+    ConstructKind::Deftemplate,
+    clips_sys::GetNextDeftemplate,
+    clips_sys::DeftemplateName,
+    clips_sys::DeftemplateModule
+);
+hooked_parser!(
+    parse_defclass,
+    ConstructKind::Defclass,
+    clips_sys::GetNextDefclass,
+    clips_sys::DefclassName,
+    clips_sys::DefclassModule
+);
+hooked_parser!(
+    parse_defgeneric,
+    ConstructKind::Defgeneric,
+    clips_sys::GetNextDefgeneric,
+    clips_sys::DefgenericName,
+    clips_sys::DefgenericModule
+);
+hooked_parser!(
+    parse_deffunction,
+    ConstructKind::Deffunction,
+    clips_sys::GetNextDeffunction,
+    clips_sys::DeffunctionName,
+    clips_sys::DeffunctionModule
+);
+hooked_parser!(
+    parse_defrule,
+    ConstructKind::Defrule,
+    clips_sys::GetNextDefrule,
+    clips_sys::DefruleName,
+    clips_sys::DefruleModule
+);
+hooked_parser!(
+    parse_deffacts,
+    ConstructKind::Deffacts,
+    clips_sys::GetNextDeffacts,
+    clips_sys::DeffactsName,
+    clips_sys::DeffactsModule
+);
+hooked_parser!(
+    parse_definstances,
+    ConstructKind::Definstances,
+    clips_sys::GetNextDefinstances,
+    clips_sys::DefinstancesName,
+    clips_sys::DefinstancesModule
+);
+
+fn parse_with_hooks<T>(
+    env: *mut clips_sys::Environment,
+    read_source: *const c_char,
+    kind: ConstructKind,
+    next: NextConstructFn<T>,
+    name: ConstructStrFn<T>,
+    module: ConstructStrFn<T>,
+) -> bool {
+    // The hooks are stored back before parsing, since parsing a construct can evaluate code that builds other constructs.
+    let clips_env = CLIPSEnvironment::from_raw(env);
+    let hooks = clips_env.retrieve_construct_hooks();
+    let parse = hooks.parse_functions[&kind];
+    let listening = !hooks.callbacks.is_empty();
+    clips_env.store_construct_hooks(hooks);
+
+    if !listening || check_syntax_mode(env) {
+        return unsafe { parse(env, read_source) };
+    }
+
+    let existing: HashSet<_> = constructs_in_all_modules(env, next)
+        .into_iter()
+        .map(|construct| construct_id(construct, name, module))
+        .collect();
+
+    // CLIPS' parse functions return whether there was an error.
+    if unsafe { parse(env, read_source) } {
+        return true;
+    }
+
+    // Both new and redefined constructs are put at the end of their module, which is the current one after parsing, even if the construct's name had another module in front of it.
+    let current_module = unsafe { clips_sys::GetCurrentModule(env) };
+    let Some(&defined) = constructs_in_module(env, current_module, next).last() else {
+        return false;
+    };
+
+    let event = unsafe {
+        ConstructEvent {
+            kind,
+            name: CStr::from_ptr(name(defined)).to_string_lossy().into_owned(),
+            module: CStr::from_ptr(module(defined))
+                .to_string_lossy()
+                .into_owned(),
+            redefined: existing.contains(&construct_id(defined, name, module)),
+        }
+    };
+
+    let mut hooks = clips_env.retrieve_construct_hooks();
+    for callback in hooks.callbacks.iter_mut() {
+        callback(event.clone());
+    }
+    clips_env.store_construct_hooks(hooks);
+
+    false
+}
+
+// CLIPS keeps a single copy of every symbol, so the same module and name are always at the same addresses while something uses them, and comparing the addresses is enough.
+fn construct_id<T>(
+    construct: *mut T,
+    name: ConstructStrFn<T>,
+    module: ConstructStrFn<T>,
+) -> (*const c_char, *const c_char) {
+    unsafe { (module(construct), name(construct)) }
+}
+
+// `check-syntax` parses constructs without defining them.
+fn check_syntax_mode(env: *mut clips_sys::Environment) -> bool {
+    unsafe { (*construct_data(env)).CheckSyntaxMode }
+}
@@ -0,0 +1,190 @@
+use std::io::Read;
+
+use crate::{CLIPSError, CLIPSResult, CLIPSValue, SlotMap};
+
+// What a CSV cell turns into. Cells are trimmed before they're converted.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CsvValueKind {
+    Symbol,
+    String,
+    Int,
+    Float,
+    // Accepts `TRUE`/`FALSE` in any case, as well as `1`/`0`.
+    Bool,
+    // The cell is split on `delimiter` and every piece is converted to `item`. An empty cell is an empty multifield.
+    Multifield {
+        delimiter: char,
+        item: Box<CsvValueKind>,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CsvColumn {
+    Header(String),
+    // Starts at 0.
+    Index(usize),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CsvMapping {
+    columns: Vec<(CsvColumn, String, CsvValueKind)>,
+    has_headers: bool,
+    delimiter: u8,
+}
+
+impl Default for CsvMapping {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CsvMapping {
+    // By default, the first row holds the column names and cells are separated by commas. Columns that aren't mapped are ignored, and slots that aren't mapped get their default values.
+    pub fn new() -> Self {
+        Self {
+            columns: Vec::new(),
+            has_headers: true,
+            delimiter: b',',
+        }
+    }
+
+    pub fn column<H: Into<String>, S: Into<String>>(
+        mut self,
+        header: H,
+        slot: S,
+        kind: CsvValueKind,
+    ) -> Self {
+        self.columns
+            .push((CsvColumn::Header(header.into()), slot.into(), kind));
+        self
+    }
+
+    pub fn column_at<S: Into<String>>(mut self, index: usize, slot: S, kind: CsvValueKind) -> Self {
+        self.columns
+            .push((CsvColumn::Index(index), slot.into(), kind));
+        self
+    }
+
+    // Columns can only be referred to by their index if there are no headers.
+    pub fn has_headers(mut self, has_headers: bool) -> Self {
+        self.has_headers = has_headers;
+        self
+    }
+
+    pub fn delimiter(mut self, delimiter: u8) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+}
+
+#[derive(Debug)]
+pub struct CsvRowFailure {
+    // The line the row starts at in the CSV data, starting at 1.
+    pub line: u64,
+    pub error: CLIPSError,
+}
+
+#[derive(Debug, Default)]
+pub struct CsvImportReport {
+    pub asserted: usize,
+    pub failures: Vec<CsvRowFailure>,
+}
+
+// Converts every row into a fact for `template`. Only errors that make the whole data unusable are returned directly, a row that can't be read or converted is reported with its line instead.
+pub(crate) fn read_csv_rows<R: Read>(
+    template: &str,
+    reader: R,
+    mapping: &CsvMapping,
+) -> CLIPSResult<Vec<(u64, CLIPSResult<SlotMap>)>> {
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(mapping.has_headers)
+        .delimiter(mapping.delimiter)
+        .flexible(true)
+        .from_reader(reader);
+
+    let headers = if mapping.has_headers {
+        Some(reader.headers()?.clone())
+    } else {
+        None
+    };
+
+    let columns = mapping
+        .columns
+        .iter()
+        .map(|(column, slot, kind)| {
+            let index = match (column, &headers) {
+                (CsvColumn::Index(index), _) => *index,
+                (CsvColumn::Header(header), Some(headers)) => headers
+                    .iter()
+                    .position(|name| name.trim() == header)
+                    .ok_or_else(|| {
+                        CLIPSError::ValueMapping(format!("the CSV data has no column `{}`", header))
+                    })?,
+                (CsvColumn::Header(header), None) => {
+                    return Err(CLIPSError::ValueMapping(format!(
+                        "the column `{}` is referred to by name, but the CSV data has no headers",
+                        header
+                    )))
+                }
+            };
+
+            Ok((index, slot.as_str(), kind))
+        })
+        .collect::<CLIPSResult<Vec<_>>>()?;
+
+    let mut rows = Vec::new();
+    for record in reader.records() {
+        let (line, record) = match record {
+            Ok(record) => (
+                record.position().map_or(0, |position| position.line()),
+                record,
+            ),
+            Err(err) => {
+                let line = err.position().map_or(0, |position| position.line());
+                rows.push((line, Err(err.into())));
+                continue;
+            }
+        };
+
+        let fact = columns
+            .iter()
+            .try_fold(SlotMap::new(template), |fact, (index, slot, kind)| {
+                let cell = record.get(*index).ok_or_else(|| {
+                    CLIPSError::ValueMapping(format!("the row has no column {}", index))
+                })?;
+
+                Ok(fact.slot(*slot, convert_cell(cell, kind)?))
+            });
+
+        rows.push((line, fact));
+    }
+
+    Ok(rows)
+}
+
+fn convert_cell(cell: &str, kind: &CsvValueKind) -> CLIPSResult<CLIPSValue> {
+    let cell = cell.trim();
+    let invalid = |expected: &str| {
+        CLIPSError::ValueMapping(format!("expected {}, found `{}`", expected, cell))
+    };
+
+    let value = match kind {
+        CsvValueKind::Symbol => CLIPSValue::Symbol(cell.to_string()),
+        CsvValueKind::String => CLIPSValue::String(cell.to_string()),
+        CsvValueKind::Int => CLIPSValue::Int(cell.parse().map_err(|_| invalid("an integer"))?),
+        CsvValueKind::Float => CLIPSValue::Float(cell.parse().map_err(|_| invalid("a float"))?),
+        CsvValueKind::Bool => match cell.to_ascii_uppercase().as_str() {
+            "TRUE" | "1" => CLIPSValue::Bool(true),
+            "FALSE" | "0" => CLIPSValue::Bool(false),
+            _ => return Err(invalid("a boolean")),
+        },
+        CsvValueKind::Multifield { .. } if cell.is_empty() => CLIPSValue::Multifield(Vec::new()),
+        CsvValueKind::Multifield { delimiter, item } => CLIPSValue::Multifield(
+            cell.split(*delimiter)
+                .map(|piece| convert_cell(piece, item))
+                .collect::<CLIPSResult<_>>()?,
+        ),
+    };
+
+    Ok(value)
+}
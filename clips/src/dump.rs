@@ -0,0 +1,89 @@
+use std::io::Write;
+
+use crate::{CLIPSResult, CLIPSValue, RetrievedFact, RetrievedInstance};
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DumpStats {
+    pub facts: usize,
+    pub instances: usize,
+}
+
+// Facts are all sent before instances, in the order CLIPS keeps them.
+pub(crate) enum WorkingMemoryEntry {
+    Fact(RetrievedFact),
+    Instance(RetrievedInstance),
+}
+
+pub(crate) fn write_assert_command<W: Write>(
+    writer: &mut W,
+    fact: &RetrievedFact,
+) -> CLIPSResult<()> {
+    write!(writer, "(assert ")?;
+    write_fact(writer, &fact.template, &fact.slots)?;
+    writeln!(writer, ")")?;
+
+    Ok(())
+}
+
+// Ordered facts are written with their fields right after the template name, the way they're asserted.
+pub(crate) fn write_fact<W: Write>(
+    writer: &mut W,
+    template: &str,
+    slots: &[(String, CLIPSValue)],
+) -> CLIPSResult<()> {
+    write!(writer, "({}", template)?;
+
+    match slots {
+        [(slot, value)] if slot == "implied" => {
+            write!(writer, " ")?;
+            write_slot_value(writer, value)?;
+        }
+        slots => write_slots(writer, slots)?,
+    }
+
+    write!(writer, ")")?;
+    Ok(())
+}
+
+pub(crate) fn write_make_instance_command<W: Write>(
+    writer: &mut W,
+    instance: &RetrievedInstance,
+) -> CLIPSResult<()> {
+    write!(
+        writer,
+        "(make-instance [{}] of {}",
+        instance.name, instance.class
+    )?;
+    write_slots(writer, &instance.slots)?;
+    writeln!(writer, ")")?;
+
+    Ok(())
+}
+
+fn write_slots<W: Write>(writer: &mut W, slots: &[(String, CLIPSValue)]) -> CLIPSResult<()> {
+    for (slot, value) in slots {
+        write!(writer, " ({} ", slot)?;
+        write_slot_value(writer, value)?;
+        write!(writer, ")")?;
+    }
+
+    Ok(())
+}
+
+// Multislots take their fields without the parentheses `Display` puts around a multifield.
+fn write_slot_value<W: Write>(writer: &mut W, value: &CLIPSValue) -> CLIPSResult<()> {
+    match value {
+        CLIPSValue::Multifield(values) => {
+            for (i, value) in values.iter().enumerate() {
+                if i > 0 {
+                    write!(writer, " ")?;
+                }
+
+                write!(writer, "{}", value)?;
+            }
+        }
+        value => write!(writer, "{}", value)?,
+    }
+
+    Ok(())
+}
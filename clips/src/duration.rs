@@ -0,0 +1,27 @@
+use std::time::Duration;
+
+use crate::{CLIPSFrom, CLIPSInto, CLIPSValue};
+
+// Durations are written as integers of milliseconds, which is also what `Duration::try_from()` expects to get back. Anything below a millisecond is dropped, and durations too long for a CLIPS integer are capped at `i64::MAX` milliseconds.
+fn duration_millis(value: Duration) -> i64 {
+    i64::try_from(value.as_millis()).unwrap_or(i64::MAX)
+}
+
+impl From<Duration> for CLIPSValue {
+    fn from(value: Duration) -> Self {
+        CLIPSValue::Int(duration_millis(value))
+    }
+}
+
+// Both go through `CLIPSValue`, so the only code that hands `env` to CLIPS is the one every other value uses.
+impl CLIPSFrom<Duration> for clips_sys::CLIPSValue {
+    fn from(value: Duration, env: *mut clips_sys::Environment) -> clips_sys::CLIPSValue {
+        CLIPSInto::into(CLIPSValue::from(value), env)
+    }
+}
+
+impl CLIPSFrom<Duration> for clips_sys::UDFValue {
+    fn from(value: Duration, env: *mut clips_sys::Environment) -> clips_sys::UDFValue {
+        CLIPSInto::into(CLIPSValue::from(value), env)
+    }
+}
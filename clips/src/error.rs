@@ -1,22 +1,51 @@
+use std::{io::ErrorKind, path::PathBuf};
+
 use thiserror::Error;
 
+use crate::ParseDiagnostic;
+
 #[derive(Error, Debug)]
 pub enum CLIPSError {
     #[error("the CLIPS environment couldn't be successfully created")]
     EnvironmentNotCreated,
     #[error("the given path isn't valid unicode")]
     PathNotUnicode,
+    #[error("{context} from CLIPS isn't valid UTF-8")]
+    InvalidUtf8 { context: &'static str },
+    #[error("CLIPS values of type {0} can't be read as a CLIPSValue")]
+    UnsupportedValueType(&'static str),
     #[error("CLIPS failed to parse the given expression")]
     ParsingError,
     #[error("CLIPS failed to execute the given expression")]
     ProcessingError,
+    #[error("the query can't be used: {}", .0)]
+    InvalidQuery(&'static str),
+    #[error("the construct can't be added to the program: {}", .0)]
+    InvalidConstruct(&'static str),
+    #[error("CLIPS failed to evaluate the query: {}", .message.trim_end())]
+    QueryFailed { message: String },
     #[error("CLIPS was unable to load from the given string")]
     LoadFromString,
-    #[error("CLIPS was unable to load the given file path")]
-    BatchStar,
+    #[error("CLIPS was unable to load from the given string, so nothing was loaded{}", .diagnostic.as_ref().map_or_else(String::new, |diagnostic| format!(" (line {}: {})", diagnostic.line, diagnostic.message)))]
+    LoadFromStringRolledBack { diagnostic: Option<ParseDiagnostic> },
+    #[error("CLIPS was unable to load the file at {} ({})", .resolved_path.display(), .io_kind.map_or_else(|| "the file contents couldn't be loaded".to_string(), |kind| kind.to_string()))]
+    BatchStar {
+        resolved_path: PathBuf,
+        io_kind: Option<ErrorKind>,
+    },
+    #[error("the integer {} doesn't fit in a CLIPS integer (64 bits)", .0)]
+    IntOutOfRange(i128),
+    #[error("couldn't map between a CLIPS value and a Rust type: {}", .0)]
+    ValueMapping(String),
     #[error("the minimum number of arguments given for this UDF exceeds the given maximum number of arguments")]
     MinArgumentsExceedsMax,
-    #[error("the argument couldn't be retrieved because it's either out of bounds or not of the expected type")]
+    #[error("the argument types given for this UDF ('{0}') don't fit its arguments")]
+    InvalidUDFArgumentTypes(String),
+    #[error("the return types given for this UDF ('{0}') aren't valid")]
+    InvalidUDFReturnTypes(String),
+    #[error("the UDF wasn't called with an argument in this position")]
+    ArgumentMissing,
+    #[error("the argument exists but CLIPS couldn't retrieve its value (possibly because evaluating it failed)")]
     ArgumentNotRetrieved,
     #[error("this name is already in use")]
     NameInUse,
@@ -26,14 +55,37 @@ pub enum CLIPSError {
     ChDir,
     #[error("the CLIPS thread exited unexpectedly")]
     ThreadExited,
+    #[error("the CLIPS environment was closed")]
+    Closed,
+    #[error("the CLIPS environment exited with code {code}")]
+    EnvironmentExited { code: i32 },
+    #[error("the environment handle can't be used from a router or UDF callback running on the CLIPS thread")]
+    ReentrantCall,
+    #[error("the CLIPS environment is busy and can't accept more commands right now")]
+    Busy,
+    #[error("the CLIPS thread didn't answer in time")]
+    Timeout,
     #[error("the CLIPS environment task exited unexpectedly")]
     TaskExitedUnexpectedly,
+    #[cfg(feature = "csv")]
+    #[error("the CSV data couldn't be read: {}", .0)]
+    Csv(#[from] csv::Error),
+    #[cfg(feature = "json")]
+    #[error("line {line} of the JSON Lines data couldn't be imported: {source}")]
+    JsonLineImport {
+        line: usize,
+        source: Box<CLIPSError>,
+    },
     #[error("an IO error happened")]
     IO(#[from] std::io::Error),
     #[error("failed to convert UDF value: {0}")]
     UDFDataConversion(#[from] clips_sys::UDFConversionError),
     #[error("the fact could not be asserted in the CLIPS environment (possibly pattern matching of a fact or instance is already occurring)")]
     UnableToAssertFact,
+    #[error("the fact could not be modified in the CLIPS environment (possibly pattern matching of a fact or instance is already occurring)")]
+    UnableToModifyFact,
+    #[error("ordered facts can't be modified, only duplicated")]
+    OrderedFactNotModifiable,
     #[error("the instance could not be created in the CLIPS environment (possibly pattern matching of a fact or instance is already occurring)")]
     UnableToMakeInstance,
     #[error("an error occurred while the assertion was being processed in the rule network")]
@@ -42,6 +94,10 @@ pub enum CLIPSError {
     FactOrInstanceRemoved,
     #[error("no slot with the given name was found for the selected template")]
     SlotNotFound,
+    #[error("the slot doesn't hold a multifield")]
+    SlotNotMultifield,
+    #[error("index {index} is out of range for a multislot with {len} values")]
+    MultislotIndexOutOfRange { index: usize, len: usize },
     #[error("the value given violates the type constraint for the slot")]
     SlotTypeViolated,
     #[error("the value given violates the range constraint for the slot")]
@@ -64,6 +120,28 @@ pub enum CLIPSError {
     UnexpectedConstructType(u32),
     #[error("tried to find a defglobal, but it didn't exist")]
     DefglobalNotFound,
+    #[error("the global name '{0}' doesn't start with a module, as in 'MAIN::x'")]
+    UnqualifiedGlobalName(String),
+    #[error("no class with the given name was found")]
+    ClassNotFound,
+    #[error("no instance with the given name was found")]
+    InstanceNotFound,
+    #[error("no fact with the given index was found")]
+    FactNotFound,
+    #[error("no rule with the given name was found")]
+    RuleNotFound,
+    #[error("the requested template doesn't exist")]
+    TemplateNotFound,
+    #[error("the requested module doesn't exist")]
+    ModuleNotFound,
+    #[error("some constructs in the module couldn't be undefined: {}", .constructs.join(", "))]
+    ModuleNotUnloaded { constructs: Vec<String> },
+    #[error("no savepoint with the given name was found")]
+    SavepointNotFound,
+    #[error("the constructs changed since the savepoint was made, so it can't be rolled back to")]
+    SavepointConstructsChanged,
+    #[error("the rules fired {max_firings} times without the agenda running out of activations")]
+    FiringCapExceeded { max_firings: usize },
     #[error("unknown CLIPS error")]
     Unknown,
 }
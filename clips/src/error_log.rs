@@ -0,0 +1,83 @@
+use std::{
+    collections::VecDeque,
+    ffi::{c_void, CStr, CString},
+};
+
+use crate::{STDERR, STDWRN};
+
+pub(crate) const ERROR_LOG_ROUTER_NAME: &str = "rust-error-log";
+// Above the routers CLIPS adds for the console and files, so it sees the text before they do.
+pub(crate) const ERROR_LOG_ROUTER_PRIORITY: i32 = 40;
+// How many messages are kept. Older ones are dropped as new ones come in.
+const ERROR_LOG_CAPACITY: usize = 16;
+// A single message is cut off after this many bytes.
+const ERROR_MESSAGE_MAX_LEN: usize = 4096;
+
+// The most recent errors and warnings CLIPS printed, oldest first.
+#[derive(Debug, Default)]
+pub(crate) struct ErrorLog {
+    messages: VecDeque<String>,
+}
+
+impl ErrorLog {
+    // CLIPS writes messages in pieces, but it starts every error and warning by writing a lone `[` at the start of a line, e.g. the one in `[EXPRNPSR3] Missing function declaration for foo.`
+    fn write(&mut self, data: &str) {
+        let starts_message = match self.messages.back() {
+            None => true,
+            Some(message) => data == "[" && (message.is_empty() || message.ends_with('\n')),
+        };
+
+        if starts_message {
+            if self.messages.len() == ERROR_LOG_CAPACITY {
+                self.messages.pop_front();
+            }
+            self.messages.push_back(String::new());
+        }
+
+        let message = self.messages.back_mut().unwrap();
+        let mut len = data.len().min(ERROR_MESSAGE_MAX_LEN - message.len());
+        while !data.is_char_boundary(len) {
+            len -= 1;
+        }
+        message.push_str(&data[..len]);
+    }
+
+    pub(crate) fn last(&self) -> Option<String> {
+        self.messages().pop()
+    }
+
+    pub(crate) fn messages(&self) -> Vec<String> {
+        self.messages
+            .iter()
+            .map(|message| message.trim().to_string())
+            .filter(|message| !message.is_empty())
+            .collect()
+    }
+}
+
+pub(crate) extern "C" fn error_log_query(
+    _environment: *mut clips_sys::Environment,
+    logical_name: *const i8,
+    _context: *mut c_void,
+) -> bool {
+    let logical_name = unsafe { CStr::from_ptr(logical_name) }.to_bytes();
+    logical_name == STDERR.as_bytes() || logical_name == STDWRN.as_bytes()
+}
+
+// Keeps a copy of the text and passes it on to whichever router would have gotten it otherwise, the same way CLIPS's dribble router does.
+pub(crate) extern "C" fn error_log_write(
+    environment: *mut clips_sys::Environment,
+    logical_name: *const i8,
+    data: *const i8,
+    context: *mut c_void,
+) {
+    let error_log = unsafe { &mut *(context as *mut ErrorLog) };
+    error_log.write(&unsafe { CStr::from_ptr(data) }.to_string_lossy());
+
+    let name = CString::new(ERROR_LOG_ROUTER_NAME).unwrap();
+    unsafe {
+        clips_sys::DeactivateRouter(environment, name.as_ptr());
+        clips_sys::WriteString(environment, logical_name, data);
+        clips_sys::ActivateRouter(environment, name.as_ptr());
+    }
+}
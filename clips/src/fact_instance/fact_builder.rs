@@ -23,7 +23,12 @@ impl FactBuilderData {
         Self { fb, env }
     }
 
-    pub(crate) fn assert(self) -> CLIPSResult<()> {
+    // Clears the slot values put so far, so the builder can be reused without asserting anything.
+    pub(crate) fn abort(self) {
+        unsafe { clips_sys::FBAbort(self.fb) };
+    }
+
+    pub(crate) fn assert(self) -> CLIPSResult<*mut clips_sys::Fact> {
         let res = unsafe { clips_sys::FBAssert(self.fb) };
 
         if res.is_null() {
@@ -38,7 +43,7 @@ impl FactBuilderData {
                 _ => unreachable!(),
             }
         } else {
-            Ok(())
+            Ok(res)
         }
     }
 }
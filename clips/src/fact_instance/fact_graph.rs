@@ -0,0 +1,76 @@
+use crate::{CLIPSResult, CLIPSValue, SlotMap};
+
+// Several related facts asserted together, e.g. an order and its line items. The crate generates a symbol with `gensym*` when the graph is asserted and puts it in the link slot of every fact, so rules can join them on it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FactGraph {
+    link_slot: String,
+    facts: Vec<(String, SlotMap)>,
+}
+
+impl FactGraph {
+    // `link_slot` is the slot that gets the generated symbol in every fact added with `fact`.
+    pub fn new<S: Into<String>>(link_slot: S) -> Self {
+        Self {
+            link_slot: link_slot.into(),
+            facts: Vec::new(),
+        }
+    }
+
+    pub fn fact(mut self, fact: SlotMap) -> Self {
+        self.push(fact);
+        self
+    }
+
+    // For facts that refer to the link with a different slot name, e.g. `order-id` in a line item when the order itself uses `id`.
+    pub fn fact_linked_by<S: Into<String>>(mut self, fact: SlotMap, link_slot: S) -> Self {
+        self.facts.push((link_slot.into(), fact));
+        self
+    }
+
+    pub fn push(&mut self, fact: SlotMap) {
+        self.facts.push((self.link_slot.clone(), fact));
+    }
+
+    pub fn len(&self) -> usize {
+        self.facts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.facts.is_empty()
+    }
+
+    // The facts are asserted in the order they were added.
+    pub(crate) fn into_linked_facts(self, link: &str) -> Vec<SlotMap> {
+        self.facts
+            .into_iter()
+            .map(|(link_slot, fact)| fact.slot(link_slot, CLIPSValue::Symbol(link.to_string())))
+            .collect()
+    }
+}
+
+// Implemented by domain types that map to more than one fact.
+pub trait IntoFactGraph {
+    fn into_fact_graph(self) -> FactGraph;
+}
+
+impl IntoFactGraph for FactGraph {
+    fn into_fact_graph(self) -> FactGraph {
+        self
+    }
+}
+
+#[derive(Debug)]
+pub struct FactGraphReport {
+    // The symbol put in the link slot of every fact.
+    pub link: String,
+    // One result per fact, in the order they were added to the graph.
+    pub results: Vec<CLIPSResult<()>>,
+    // Only set when a rollback was asked for and some fact failed, in which case none of the facts are left asserted.
+    pub rolled_back: bool,
+}
+
+impl FactGraphReport {
+    pub fn is_complete(&self) -> bool {
+        self.results.iter().all(Result::is_ok)
+    }
+}
@@ -0,0 +1,33 @@
+use std::borrow::Cow;
+
+use crate::{CLIPSValueRef, RetrievedFact};
+
+// A fact as seen from inside `Environment::visit_facts()`. Its names and values are borrowed from CLIPS, so it can't be kept past the call it was given to, but anything in it can be turned into an owned value that can.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FactView<'a> {
+    pub index: i64,
+    pub template: Cow<'a, str>,
+    // Ordered facts have a single `implied` slot holding all their fields.
+    pub slots: Vec<(Cow<'a, str>, CLIPSValueRef<'a>)>,
+}
+
+impl<'a> FactView<'a> {
+    pub fn slot(&self, slot_name: &str) -> Option<&CLIPSValueRef<'a>> {
+        self.slots
+            .iter()
+            .find(|(name, _)| name == slot_name)
+            .map(|(_, value)| value)
+    }
+
+    pub fn to_retrieved_fact(&self) -> RetrievedFact {
+        RetrievedFact {
+            index: self.index,
+            template: self.template.clone().into_owned(),
+            slots: self
+                .slots
+                .iter()
+                .map(|(name, value)| (name.clone().into_owned(), value.clone().into_owned()))
+                .collect(),
+        }
+    }
+}
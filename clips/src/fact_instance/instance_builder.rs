@@ -1,9 +1,10 @@
-use std::ffi::CString;
+use std::ffi::{CStr, CString};
 
 use clips_sys::CLIPSValue;
 
 use crate::{
-    translate_put_slot_error, CLIPSError, CLIPSInto, CLIPSResult, FactOrInstanceBuilderData,
+    clips_cstr_to_string, translate_put_slot_error, CLIPSError, CLIPSInto, CLIPSResult,
+    FactOrInstanceBuilderData,
 };
 
 pub(crate) struct CLIPSInstanceBuilder {
@@ -23,7 +24,13 @@ impl InstanceBuilderData {
         Self { ib, env }
     }
 
-    pub(crate) fn make(self, instance_name: Option<&str>) -> CLIPSResult<()> {
+    // Clears the slot values put so far, so the builder can be reused without making anything.
+    pub(crate) fn abort(self) {
+        unsafe { clips_sys::IBAbort(self.ib) };
+    }
+
+    // Gives back the name of the new instance, which CLIPS picks when `instance_name` is `None`.
+    pub(crate) fn make(self, instance_name: Option<&str>) -> CLIPSResult<String> {
         let res = if let Some(instance_name) = instance_name {
             let name_cstr = CString::new(instance_name).unwrap();
             unsafe { clips_sys::IBMake(self.ib, name_cstr.as_ptr()) }
@@ -45,7 +52,11 @@ impl InstanceBuilderData {
                 _ => unreachable!(),
             }
         } else {
-            Ok(())
+            clips_cstr_to_string(
+                self.env,
+                unsafe { CStr::from_ptr(clips_sys::InstanceName(res)) },
+                "an instance name",
+            )
         }
     }
 }
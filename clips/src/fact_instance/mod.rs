@@ -6,6 +6,16 @@ mod fact_builder;
 pub use fact_builder::*;
 mod instance_builder;
 pub use instance_builder::*;
+mod slot_map;
+pub use slot_map::*;
+mod retrieved_fact;
+pub use retrieved_fact::*;
+mod fact_view;
+pub use fact_view::*;
+mod retrieved_instance;
+pub use retrieved_instance::*;
+mod fact_graph;
+pub use fact_graph::*;
 
 pub trait FactOrInstanceBuilderData {
     fn put_slot<T: CLIPSInto<CLIPSValue>>(&self, slot_name: &str, val: T) -> CLIPSResult<()>;
@@ -21,6 +31,7 @@ pub trait FactOrInstanceBuilderData {
     ) -> CLIPSResult<()>;
 }
 
+// The builder data is only ever lent out for the duration of `into_fact_or_instance()`, and can't be made outside the crate, so implementations can't keep it around after the fact or instance is built.
 pub trait IntoFactOrInstance<T: FactOrInstanceBuilderData> {
     fn definition_name(&self) -> &str;
     fn into_fact_or_instance(self: Box<Self>, data: &T) -> CLIPSResult<()>;
@@ -44,3 +55,15 @@ pub(crate) fn translate_put_slot_error(code: u32) -> CLIPSResult<()> {
         _ => unreachable!(),
     }
 }
+
+pub(crate) fn translate_fact_modifier_error(code: u32) -> CLIPSError {
+    match code {
+        clips_sys::FactModifierError_FME_RETRACTED_ERROR => CLIPSError::FactOrInstanceRemoved,
+        clips_sys::FactModifierError_FME_IMPLIED_DEFTEMPLATE_ERROR => {
+            CLIPSError::OrderedFactNotModifiable
+        }
+        clips_sys::FactModifierError_FME_COULD_NOT_MODIFY_ERROR => CLIPSError::UnableToModifyFact,
+        clips_sys::FactModifierError_FME_RULE_NETWORK_ERROR => CLIPSError::RuleNetwork,
+        _ => unreachable!(),
+    }
+}
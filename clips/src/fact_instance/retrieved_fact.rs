@@ -0,0 +1,19 @@
+use crate::CLIPSValue;
+
+// A snapshot of a fact taken from the environment. Changes to it aren't reflected back in CLIPS.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RetrievedFact {
+    pub index: i64,
+    pub template: String,
+    // Ordered facts have a single `implied` slot holding all their fields.
+    pub slots: Vec<(String, CLIPSValue)>,
+}
+
+impl RetrievedFact {
+    pub fn slot(&self, slot_name: &str) -> Option<&CLIPSValue> {
+        self.slots
+            .iter()
+            .find(|(name, _)| name == slot_name)
+            .map(|(_, value)| value)
+    }
+}
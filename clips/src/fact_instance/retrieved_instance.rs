@@ -0,0 +1,19 @@
+use crate::CLIPSValue;
+
+// A snapshot of an instance taken from the environment. Changes to it aren't reflected back in CLIPS.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RetrievedInstance {
+    pub name: String,
+    pub class: String,
+    // Includes the slots inherited from superclasses.
+    pub slots: Vec<(String, CLIPSValue)>,
+}
+
+impl RetrievedInstance {
+    pub fn slot(&self, slot_name: &str) -> Option<&CLIPSValue> {
+        self.slots
+            .iter()
+            .find(|(name, _)| name == slot_name)
+            .map(|(_, value)| value)
+    }
+}
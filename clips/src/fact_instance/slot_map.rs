@@ -0,0 +1,100 @@
+use crate::{
+    CLIPSResult, CLIPSValue, FactBuilderData, FactOrInstanceBuilderData, InstanceBuilderData,
+    IntoFactOrInstance,
+};
+
+// A generic fact/instance description built at runtime, for the cases where defining a Rust type for every template/class is overkill. The `fact!` and `instance!` macros build these.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SlotMap {
+    definition_name: String,
+    slots: Vec<(String, CLIPSValue)>,
+}
+
+impl SlotMap {
+    pub fn new<S: Into<String>>(definition_name: S) -> Self {
+        Self {
+            definition_name: definition_name.into(),
+            slots: Vec::new(),
+        }
+    }
+
+    pub fn slot<S: Into<String>, V: Into<CLIPSValue>>(mut self, slot_name: S, value: V) -> Self {
+        self.insert(slot_name, value);
+        self
+    }
+
+    // Replaces the value of the slot if it was already set, so the order the slots are put in the builder stays the order they were first given.
+    pub fn insert<S: Into<String>, V: Into<CLIPSValue>>(&mut self, slot_name: S, value: V) {
+        let slot_name = slot_name.into();
+        let value = value.into();
+
+        match self.slots.iter_mut().find(|(name, _)| *name == slot_name) {
+            Some((_, existing)) => *existing = value,
+            None => self.slots.push((slot_name, value)),
+        }
+    }
+
+    pub fn definition_name(&self) -> &str {
+        &self.definition_name
+    }
+
+    pub fn get(&self, slot_name: &str) -> Option<&CLIPSValue> {
+        self.slots
+            .iter()
+            .find(|(name, _)| name == slot_name)
+            .map(|(_, value)| value)
+    }
+
+    pub fn slots(&self) -> &[(String, CLIPSValue)] {
+        &self.slots
+    }
+
+    fn put_slots<T: FactOrInstanceBuilderData>(self, data: &T) -> CLIPSResult<()> {
+        for (slot_name, value) in self.slots {
+            data.put_slot(&slot_name, value)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl IntoFactOrInstance<FactBuilderData> for SlotMap {
+    fn definition_name(&self) -> &str {
+        &self.definition_name
+    }
+
+    fn into_fact_or_instance(self: Box<Self>, data: &FactBuilderData) -> CLIPSResult<()> {
+        self.put_slots(data)
+    }
+}
+
+impl IntoFactOrInstance<InstanceBuilderData> for SlotMap {
+    fn definition_name(&self) -> &str {
+        &self.definition_name
+    }
+
+    fn into_fact_or_instance(self: Box<Self>, data: &InstanceBuilderData) -> CLIPSResult<()> {
+        self.put_slots(data)
+    }
+}
+
+#[macro_export]
+macro_rules! fact {
+    ($template:expr $(, $slot:expr => $value:expr)* $(,)?) => {
+        $crate::SlotMap::new($template)$(.slot($slot, $value))*
+    };
+}
+
+#[macro_export]
+macro_rules! instance {
+    ($class:expr $(, $slot:expr => $value:expr)* $(,)?) => {
+        $crate::SlotMap::new($class)$(.slot($slot, $value))*
+    };
+}
+
+#[macro_export]
+macro_rules! multifield {
+    ($($value:expr),* $(,)?) => {
+        $crate::CLIPSValue::Multifield(::std::vec![$($crate::CLIPSValue::from($value)),*])
+    };
+}
@@ -0,0 +1,203 @@
+use crate::CLIPSValue;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ClassInfo {
+    pub name: String,
+    pub module: String,
+    pub is_abstract: bool,
+    pub is_reactive: bool,
+    pub direct_superclasses: Vec<String>,
+    // All the superclasses in the class precedence list, not only the direct ones.
+    pub superclasses: Vec<String>,
+    // Includes the slots inherited from superclasses.
+    pub slots: Vec<ClassSlotInfo>,
+    // Only the handlers defined directly on the class.
+    pub message_handlers: Vec<MessageHandlerInfo>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ClassSlotInfo {
+    pub name: String,
+    // `None` if the slot was declared with `(default ?NONE)`.
+    pub default: Option<CLIPSValue>,
+    pub types: Vec<String>,
+    pub multislot: bool,
+    // Only set for multislots. The maximum is `None` if there's no upper bound.
+    pub cardinality: Option<(i64, Option<i64>)>,
+    pub writable: bool,
+    pub initable: bool,
+    pub public: bool,
+    // The raw facets as CLIPS reports them with `slot-facets`, e.g. "SGL", "STC", "INH", "RW".
+    pub facets: Vec<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MessageHandlerInfo {
+    pub name: String,
+    // One of "primary", "around", "before" or "after".
+    pub handler_type: String,
+    // The implicit `self` parameter isn't counted. The maximum is `None` if the handler takes a wildcard parameter.
+    pub min_args: u16,
+    pub max_args: Option<u16>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RuleInfo {
+    pub name: String,
+    pub module: String,
+    // For rules with dynamic salience, this is the salience computed the last time it was evaluated.
+    pub salience: i32,
+    // The specificity CLIPS computed from the rule's conditions, used by the Complexity and Simplicity strategies.
+    pub complexity: u32,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ActivationInfo {
+    pub rule: String,
+    pub salience: i32,
+    // Grows with every activation CLIPS creates, which is what the Depth and Breadth strategies order by.
+    pub timetag: u64,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AgendaChange {
+    pub added: Vec<ActivationInfo>,
+    pub removed: Vec<ActivationInfo>,
+    // The current module's agenda after the change, in the order the activations would fire.
+    pub agenda: Vec<ActivationInfo>,
+}
+
+pub type AgendaChangedCallback = Box<dyn FnMut(&AgendaChange) + Send>;
+
+// Activations are told apart by their timetag, since a rule can have several on the agenda at once.
+pub(crate) struct AgendaWatcher {
+    callback: AgendaChangedCallback,
+    last_agenda: Vec<ActivationInfo>,
+}
+
+impl AgendaWatcher {
+    pub(crate) fn new(callback: AgendaChangedCallback, agenda: Vec<ActivationInfo>) -> Self {
+        Self {
+            callback,
+            last_agenda: agenda,
+        }
+    }
+
+    // CLIPS sets its agenda-changed flag even when activations come and go within the same command, so nothing is reported if the agenda ends up the same.
+    pub(crate) fn notify(&mut self, agenda: Vec<ActivationInfo>) {
+        let added: Vec<ActivationInfo> = agenda
+            .iter()
+            .filter(|a| !self.last_agenda.iter().any(|b| a.timetag == b.timetag))
+            .cloned()
+            .collect();
+        let removed: Vec<ActivationInfo> = self
+            .last_agenda
+            .iter()
+            .filter(|a| !agenda.iter().any(|b| a.timetag == b.timetag))
+            .cloned()
+            .collect();
+
+        if !added.is_empty() || !removed.is_empty() {
+            (self.callback)(&AgendaChange {
+                added,
+                removed,
+                agenda: agenda.clone(),
+            });
+        }
+
+        self.last_agenda = agenda;
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TemplateInfo {
+    pub name: String,
+    pub module: String,
+    pub slots: Vec<TemplateSlotInfo>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TemplateSlotInfo {
+    pub name: String,
+    // The primitive types the slot allows, e.g. `NUMBER` shows up as "FLOAT" and "INTEGER".
+    pub types: Vec<String>,
+    pub multislot: bool,
+    // Only set for multislots. The maximum is `None` if there's no upper bound.
+    pub cardinality: Option<(i64, Option<i64>)>,
+    // `None` if the slot doesn't restrict its values with any of the `allowed-` attributes.
+    pub allowed_values: Option<Vec<CLIPSValue>>,
+    // Either bound is `None` if it's unbounded (`?VARIABLE`).
+    pub range: (Option<f64>, Option<f64>),
+    // `None` if the slot was declared with `(default ?NONE)`. Dynamic defaults are evaluated when the info is retrieved.
+    pub default: Option<CLIPSValue>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ConstructSummary {
+    pub defmodules: usize,
+    pub defrules: usize,
+    pub deftemplates: usize,
+    // Includes the system classes CLIPS defines in every environment, e.g. `OBJECT` and `USER`.
+    pub defclasses: usize,
+    pub deffacts: usize,
+    pub definstances: usize,
+    pub deffunctions: usize,
+    pub defgenerics: usize,
+    pub defglobals: usize,
+    pub facts: usize,
+    pub instances: usize,
+    // Every construct as "<kind> <module>::<name>", sorted. Defmodules are listed as "defmodule <name>".
+    pub construct_names: Vec<String>,
+}
+
+impl ConstructSummary {
+    // A hash of `construct_names`, so two environments with the same constructs get the same fingerprint no matter what facts and instances they hold. It's FNV-1a, which doesn't depend on the Rust version or the process, so fingerprints from different builds and machines can be compared.
+    pub fn fingerprint(&self) -> u64 {
+        fnv1a_hash(self.construct_names.iter().map(String::as_str))
+    }
+}
+
+pub(crate) fn fnv1a_hash<'a>(items: impl Iterator<Item = &'a str>) -> u64 {
+    const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+    const FNV_PRIME: u64 = 0x100000001b3;
+
+    items
+        // The newline separates the items, so e.g. ["ab", "c"] and ["a", "bc"] hash differently.
+        .flat_map(|item| item.bytes().chain(std::iter::once(b'\n')))
+        .fold(FNV_OFFSET_BASIS, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(FNV_PRIME)
+        })
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SymbolTableStats {
+    pub entries: usize,
+    // The size of the entries and of the hash table buckets. Allocator overhead isn't counted.
+    pub bytes: usize,
+}
+
+// Symbols, strings and instance names all live in the symbol table, since CLIPS stores them as lexemes.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SymbolStats {
+    pub symbols: SymbolTableStats,
+    pub floats: SymbolTableStats,
+    pub integers: SymbolTableStats,
+    pub bitmaps: SymbolTableStats,
+    // Only filled by `symbol_stats_with_top()`, longest first.
+    pub longest_lexemes: Vec<LexemeUsage>,
+    // Only filled by `symbol_stats_with_top()`, most referenced first.
+    pub most_referenced_lexemes: Vec<LexemeUsage>,
+}
+
+impl SymbolStats {
+    pub fn total_bytes(&self) -> usize {
+        self.symbols.bytes + self.floats.bytes + self.integers.bytes + self.bitmaps.bytes
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LexemeUsage {
+    pub contents: String,
+    // How many places hold on to the lexeme. Every lexeme is interned once, so this is how many times it's duplicated.
+    pub references: i64,
+}
@@ -0,0 +1,70 @@
+use std::io::Write;
+
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+
+use crate::{CLIPSError, CLIPSResult, CLIPSValue, RetrievedInstance, SlotMap};
+
+// One JSON object per line, with the slots as an object keyed by slot name.
+pub(crate) fn write_instance_jsonl<W: Write>(
+    writer: &mut W,
+    instance: &RetrievedInstance,
+) -> CLIPSResult<()> {
+    let line = json!({
+        "name": instance.name,
+        "class": instance.class,
+        "slots": slots_to_json(&instance.slots)?,
+    });
+
+    serde_json::to_writer(&mut *writer, &line).map_err(|err| CLIPSError::IO(err.into()))?;
+    writer.write_all(b"\n")?;
+
+    Ok(())
+}
+
+fn slots_to_json(slots: &[(String, CLIPSValue)]) -> CLIPSResult<Map<String, Value>> {
+    slots
+        .iter()
+        .map(|(name, value)| {
+            let value = serde_json::to_value(value).map_err(|err| CLIPSError::IO(err.into()))?;
+            Ok((name.clone(), value))
+        })
+        .collect()
+}
+
+// Reads a line written by `write_instance_jsonl()` back into the instance name and its slots.
+pub(crate) fn read_instance_jsonl(line: &str) -> CLIPSResult<(String, SlotMap)> {
+    let malformed = |message: String| CLIPSError::ValueMapping(message);
+
+    let object = match serde_json::from_str(line) {
+        Ok(Value::Object(object)) => object,
+        Ok(_) => return Err(malformed("expected a JSON object".to_string())),
+        Err(err) => return Err(malformed(err.to_string())),
+    };
+
+    let field = |name: &str| {
+        object
+            .get(name)
+            .ok_or_else(|| malformed(format!("the object has no `{}` field", name)))
+    };
+
+    let name = field("name")?
+        .as_str()
+        .ok_or_else(|| malformed("`name` must be a string".to_string()))?;
+    let class = field("class")?
+        .as_str()
+        .ok_or_else(|| malformed("`class` must be a string".to_string()))?;
+    let slots = field("slots")?
+        .as_object()
+        .ok_or_else(|| malformed("`slots` must be an object".to_string()))?;
+
+    let instance = slots
+        .iter()
+        .try_fold(SlotMap::new(class), |instance, (slot, value)| {
+            let value = CLIPSValue::deserialize(value)
+                .map_err(|err| malformed(format!("slot `{}`: {}", slot, err)))?;
+            Ok::<_, CLIPSError>(instance.slot(slot.as_str(), value))
+        })?;
+
+    Ok((name.to_string(), instance))
+}
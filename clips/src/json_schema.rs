@@ -0,0 +1,128 @@
+use serde_json::{json, Map, Value};
+
+use crate::{CLIPSValue, TemplateInfo, TemplateSlotInfo};
+
+const JSON_SCHEMA_DIALECT: &str = "https://json-schema.org/draft/2020-12/schema";
+
+pub(crate) fn template_schema(info: &TemplateInfo) -> Value {
+    let properties = info
+        .slots
+        .iter()
+        .map(|slot| (slot.name.clone(), slot_schema(slot)))
+        .collect::<Map<_, _>>();
+
+    // Slots declared with `(default ?NONE)` must be given a value when the fact is asserted.
+    let required = info
+        .slots
+        .iter()
+        .filter(|slot| slot.default.is_none())
+        .map(|slot| Value::String(slot.name.clone()))
+        .collect::<Vec<_>>();
+
+    json!({
+        "$schema": JSON_SCHEMA_DIALECT,
+        "title": format!("{}::{}", info.module, info.name),
+        "type": "object",
+        "properties": properties,
+        "required": required,
+        "additionalProperties": false,
+    })
+}
+
+fn slot_schema(slot: &TemplateSlotInfo) -> Value {
+    let mut schema = value_schema(slot);
+
+    if slot.multislot {
+        let mut array_schema = Map::new();
+        array_schema.insert("type".to_string(), json!("array"));
+        array_schema.insert("items".to_string(), Value::Object(schema));
+
+        if let Some((min, max)) = slot.cardinality {
+            array_schema.insert("minItems".to_string(), json!(min));
+            if let Some(max) = max {
+                array_schema.insert("maxItems".to_string(), json!(max));
+            }
+        }
+
+        schema = array_schema;
+    }
+
+    if let Some(default) = &slot.default {
+        schema.insert("default".to_string(), clips_value_to_json(default));
+    }
+
+    Value::Object(schema)
+}
+
+// The schema for a single value of the slot, i.e. for each item of a multislot.
+fn value_schema(slot: &TemplateSlotInfo) -> Map<String, Value> {
+    let mut schema = Map::new();
+
+    let mut json_types = Vec::new();
+    for clips_type in slot.types.iter() {
+        let json_type = match clips_type.as_str() {
+            "INTEGER" => "integer",
+            "FLOAT" => "number",
+            "SYMBOL" | "STRING" | "INSTANCE-NAME" => "string",
+            // Addresses only exist inside a running environment, so there's no way to give them in JSON.
+            _ => continue,
+        };
+
+        if !json_types.contains(&json_type) {
+            json_types.push(json_type);
+        }
+    }
+
+    // Every integer is also a number, so listing both would only make the schema harder to read.
+    if json_types.contains(&"number") {
+        json_types.retain(|json_type| *json_type != "integer");
+    }
+
+    if let Some(allowed_values) = &slot.allowed_values {
+        let allowed_values = allowed_values
+            .iter()
+            .map(clips_value_to_json)
+            .collect::<Vec<_>>();
+
+        // `TRUE` and `FALSE` come back as booleans unless the environment is told otherwise.
+        if allowed_values.iter().any(Value::is_boolean) {
+            json_types.push("boolean");
+        }
+
+        schema.insert("enum".to_string(), Value::Array(allowed_values));
+    }
+
+    match json_types.as_slice() {
+        [] => {}
+        [json_type] => {
+            schema.insert("type".to_string(), json!(json_type));
+        }
+        _ => {
+            schema.insert("type".to_string(), json!(json_types));
+        }
+    }
+
+    let (min, max) = slot.range;
+    if let Some(min) = min {
+        schema.insert("minimum".to_string(), json!(min));
+    }
+    if let Some(max) = max {
+        schema.insert("maximum".to_string(), json!(max));
+    }
+
+    schema
+}
+
+fn clips_value_to_json(value: &CLIPSValue) -> Value {
+    match value {
+        CLIPSValue::Symbol(val) | CLIPSValue::String(val) => json!(val),
+        CLIPSValue::Int(val) => json!(val),
+        CLIPSValue::Float(val) => json!(val),
+        CLIPSValue::Bool(val) => json!(val),
+        // JSON text has to be valid UTF-8.
+        CLIPSValue::Lexeme(val) => json!(val.to_string_lossy()),
+        CLIPSValue::Multifield(vals) => {
+            Value::Array(vals.iter().map(clips_value_to_json).collect())
+        }
+    }
+}
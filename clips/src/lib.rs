@@ -1,12 +1,21 @@
 use std::{
-    collections::HashMap,
-    env::set_current_dir,
-    ffi::{CStr, CString},
+    any::Any,
+    cell::RefCell,
+    collections::{BTreeMap, HashMap, HashSet},
+    env::{current_dir, set_current_dir},
+    ffi::{c_char, c_long, c_void, CStr, CString},
+    fs::{self, File},
+    io::Read,
     mem::size_of,
+    panic::{self, AssertUnwindSafe},
     path::{Path, PathBuf},
     ptr,
-    sync::mpsc,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        mpsc, Arc, Condvar, Mutex,
+    },
     thread::{self, JoinHandle},
+    time::{Duration, Instant},
 };
 
 use nix::sched::{unshare, CloneFlags};
@@ -16,6 +25,8 @@ pub use clips_sys::{CLIPSInstanceName, CLIPSSymbol};
 
 mod router;
 pub use router::*;
+mod capture;
+pub use capture::*;
 mod udf;
 pub use udf::*;
 mod error;
@@ -24,6 +35,50 @@ mod value;
 pub use value::*;
 mod fact_instance;
 pub use fact_instance::*;
+mod constraints;
+pub use constraints::*;
+mod introspection;
+pub use introspection::*;
+mod working_memory;
+pub use working_memory::*;
+mod load;
+pub use load::*;
+mod program;
+pub use program::*;
+mod construct_hooks;
+pub use construct_hooks::*;
+mod pool;
+pub use pool::*;
+mod logical_support;
+use logical_support::*;
+mod provenance;
+use provenance::*;
+mod slot_watch;
+pub use slot_watch::*;
+mod dump;
+pub use dump::*;
+mod watchdog;
+pub use watchdog::*;
+mod stats;
+pub use stats::*;
+mod error_log;
+use error_log::*;
+mod mapping;
+pub mod testing;
+#[cfg(feature = "tracing")]
+mod tracing_bridge;
+#[cfg(feature = "tracing")]
+pub use tracing_bridge::*;
+#[cfg(feature = "csv")]
+mod csv_import;
+#[cfg(feature = "json")]
+mod json_export;
+#[cfg(feature = "json")]
+mod json_schema;
+#[cfg(feature = "csv")]
+pub use csv_import::*;
+#[cfg(feature = "duration")]
+mod duration;
 
 // TODO: find a way to grab these from clips_sys and still be static.
 pub static STDOUT: &str = "stdout";
@@ -31,8 +86,134 @@ pub static STDERR: &str = "stderr";
 pub static STDIN: &str = "stdin";
 pub static STDWRN: &str = "stdwrn";
 
+// CLIPS 6.4 only defines these logical names. The older `wtrace`, `wdialog`, `wdisplay`, `werror`, `wwarning` and `wprompt` names from CLIPS 6.3 aren't recognized anymore.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LogicalName {
+    Stdout,
+    Stderr,
+    Stdin,
+    Stdwrn,
+}
+
+impl LogicalName {
+    pub const ALL: [LogicalName; 4] = [
+        LogicalName::Stdout,
+        LogicalName::Stderr,
+        LogicalName::Stdin,
+        LogicalName::Stdwrn,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LogicalName::Stdout => STDOUT,
+            LogicalName::Stderr => STDERR,
+            LogicalName::Stdin => STDIN,
+            LogicalName::Stdwrn => STDWRN,
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|logical_name| logical_name.as_str() == name)
+    }
+}
+
+impl AsRef<str> for LogicalName {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
 pub type CLIPSGlobalsHierarchy = HashMap<String, HashMap<String, CLIPSValue>>;
 
+// The same as `CLIPSGlobalsHierarchy`, but modules and globals are sorted by name, so the same globals always serialise the same way.
+#[derive(Clone, Debug, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(transparent)]
+pub struct OrderedGlobalsHierarchy(pub BTreeMap<String, BTreeMap<String, CLIPSValue>>);
+
+impl std::ops::Deref for OrderedGlobalsHierarchy {
+    type Target = BTreeMap<String, BTreeMap<String, CLIPSValue>>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl std::ops::DerefMut for OrderedGlobalsHierarchy {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl From<CLIPSGlobalsHierarchy> for OrderedGlobalsHierarchy {
+    fn from(globals: CLIPSGlobalsHierarchy) -> Self {
+        Self(
+            globals
+                .into_iter()
+                .map(|(module, globals)| (module, globals.into_iter().collect()))
+                .collect(),
+        )
+    }
+}
+
+impl From<OrderedGlobalsHierarchy> for CLIPSGlobalsHierarchy {
+    fn from(globals: OrderedGlobalsHierarchy) -> Self {
+        globals
+            .0
+            .into_iter()
+            .map(|(module, globals)| (module, globals.into_iter().collect()))
+            .collect()
+    }
+}
+
+// Keys are full global names, e.g. `MAIN::x`, like `restore_globals` looks them up.
+pub fn flatten_globals(globals: &CLIPSGlobalsHierarchy) -> HashMap<String, CLIPSValue> {
+    globals
+        .iter()
+        .flat_map(|(module, globals)| {
+            globals
+                .iter()
+                .map(move |(name, value)| (format!("{}::{}", module, name), value.clone()))
+        })
+        .collect()
+}
+
+// Module and global names can't contain `::`, so every key is split at its first one.
+pub fn unflatten_globals(
+    globals: HashMap<String, CLIPSValue>,
+) -> CLIPSResult<CLIPSGlobalsHierarchy> {
+    let mut hierarchy = CLIPSGlobalsHierarchy::new();
+
+    for (full_name, value) in globals {
+        let Some((module, name)) = full_name.split_once("::") else {
+            return Err(CLIPSError::UnqualifiedGlobalName(full_name));
+        };
+
+        hierarchy
+            .entry(module.to_string())
+            .or_default()
+            .insert(name.to_string(), value);
+    }
+
+    Ok(hierarchy)
+}
+
+// The binary saves are kept in memory, one per module, since CLIPS can only save all the facts and instances by going through every module.
+struct Savepoint {
+    // The fingerprints of the construct names and of the pretty print forms, so redefining a construct with a different body is noticed too.
+    fingerprint: (u64, u64),
+    modules: Vec<ModuleSavepoint>,
+    globals: CLIPSGlobalsHierarchy,
+}
+
+// The module is kept by name, since a defmodule pointer could be left dangling by a `clear` and a reload.
+struct ModuleSavepoint {
+    module: String,
+    facts: Vec<u8>,
+    instances: Vec<u8>,
+}
+
 #[repr(u32)]
 pub enum ConflictResolutionStrategy {
     Depth = clips_sys::StrategyType_DEPTH_STRATEGY,
@@ -67,983 +248,6782 @@ pub enum CLIPSSignal {
     RunFinished { limit: Option<usize> },
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunStopReason {
+    // The agenda ran out of activations.
+    Completed,
+    // Something called `(halt)`, or the rules were halted from outside, with activations still on the agenda.
+    Halted,
+    // The next activation belongs to a rule with a breakpoint set.
+    Breakpoint,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RunOutcome {
+    pub rules_fired: usize,
+    // Only the rules fired during this run are considered, so this is `None` if none fired.
+    pub last_fired_rule: Option<String>,
+    pub stop_reason: RunStopReason,
+    // Set if the right-hand side of a fired rule failed, e.g. because a deffunction or generic function it called errored. CLIPS halts the run when that happens, so `stop_reason` is `Halted` unless nothing was left on the agenda anyway.
+    pub evaluation_error: bool,
+}
+
+// A change to one multislot of a fact. Indices start at 0, like in a `Vec`. Multifields can't be nested, so a `CLIPSValue::Multifield` value is spliced in.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MultislotEdit {
+    Append(CLIPSValue),
+    // `index` can be the multislot's length, which appends the value.
+    Insert { index: usize, value: CLIPSValue },
+    Remove { index: usize },
+}
+
+#[derive(Debug, Clone)]
+enum CommandSender {
+    Unbounded(mpsc::Sender<CLIPSEnvironmentCommand>),
+    Bounded(mpsc::SyncSender<CLIPSEnvironmentCommand>),
+}
+
+// How many facts or instances can wait between the CLIPS thread and the writer during an export.
+const EXPORT_CHANNEL_CAPACITY: usize = 64;
+
+// Why an environment stopped accepting commands.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EnvironmentFailure {
+    Closed,
+    // Loaded code called `(exit)`. The CLIPS thread is still running, but the environment is halted.
+    Exited { code: i32 },
+    // The message is only available if the panic was raised with a string, as `panic!` does.
+    Panicked { message: Option<String> },
+    // The CLIPS thread is gone for some other reason.
+    ThreadExited,
+}
+
+// Clones are handles to the same CLIPS thread, which keeps running until every handle is dropped or one of them closes the environment.
+#[derive(Debug, Clone)]
 pub struct Environment {
-    input_tx: mpsc::Sender<CLIPSEnvironmentCommand>,
-    task_handle: JoinHandle<()>,
+    input_tx: CommandSender,
+    pending_commands: Arc<AtomicUsize>,
+    // Shared with every handle to the same CLIPS thread, so all of them fail fast once one of them closes the environment.
+    closed: Arc<AtomicBool>,
+    parsing: Arc<AtomicBool>,
+    // Set once loaded code calls `(exit)`, which halts the environment instead of terminating the process.
+    exit_code: Arc<Mutex<Option<i32>>>,
+    // Set if the CLIPS thread panicked.
+    panic_message: Arc<Mutex<Option<Option<String>>>>,
+    paused: PauseState,
+    task_thread: thread::Thread,
+    // Taken by whichever handle closes the environment, so it can wait for the thread to finish.
+    task_handle: Arc<Mutex<Option<JoinHandle<()>>>>,
 }
 
 impl Environment {
     pub fn new() -> Self {
         let (input_tx, input_rx) = mpsc::channel();
 
-        let task_handle = thread::spawn(move || clips_environment_task(input_rx));
+        Self::spawn(CommandSender::Unbounded(input_tx), input_rx)
+    }
+
+    // With a bounded environment, calls will block while `capacity` commands are already waiting to be processed by the CLIPS thread, which puts a limit on the memory used by producers that are faster than CLIPS. Use `try_assert_fact()` to get `CLIPSError::Busy` instead of blocking.
+    pub fn new_bounded(capacity: usize) -> Self {
+        let (input_tx, input_rx) = mpsc::sync_channel(capacity);
+
+        Self::spawn(CommandSender::Bounded(input_tx), input_rx)
+    }
+
+    fn spawn(input_tx: CommandSender, input_rx: mpsc::Receiver<CLIPSEnvironmentCommand>) -> Self {
+        let pending_commands = Arc::new(AtomicUsize::new(0));
+        let task_pending_commands = pending_commands.clone();
+        let parsing = Arc::new(AtomicBool::new(false));
+        let task_parsing = parsing.clone();
+        let exit_code = Arc::new(Mutex::new(None));
+        let task_exit_code = exit_code.clone();
+        let panic_message = Arc::new(Mutex::new(None));
+        let task_panic_message = panic_message.clone();
+        let paused = PauseState::default();
+        let task_paused = paused.clone();
+
+        let task_handle = thread::spawn(move || {
+            let res = panic::catch_unwind(AssertUnwindSafe(|| {
+                clips_environment_task(
+                    input_rx,
+                    task_pending_commands,
+                    task_parsing,
+                    task_exit_code,
+                    task_paused,
+                )
+            }));
+
+            if let Err(payload) = res {
+                let message = payload
+                    .downcast_ref::<&str>()
+                    .map(|message| message.to_string())
+                    .or_else(|| payload.downcast_ref::<String>().cloned());
+                *task_panic_message.lock().unwrap() = Some(message);
+            }
+        });
 
         Self {
             input_tx,
-            task_handle,
+            pending_commands,
+            closed: Arc::new(AtomicBool::new(false)),
+            parsing,
+            exit_code,
+            panic_message,
+            paused,
+            task_thread: task_handle.thread().clone(),
+            task_handle: Arc::new(Mutex::new(Some(task_handle))),
+        }
+    }
+
+    // The logical names as the linked CLIPS library defines them, which should always match `LogicalName::ALL`. Useful to check at startup that routers query the right names.
+    pub fn default_router_names(&self) -> Vec<String> {
+        let names = unsafe {
+            [
+                clips_sys::STDOUT,
+                clips_sys::STDERR,
+                clips_sys::STDIN,
+                clips_sys::STDWRN,
+            ]
+        };
+
+        names
+            .into_iter()
+            .map(|name| {
+                unsafe { CStr::from_ptr(name) }
+                    .to_str()
+                    .unwrap()
+                    .to_string()
+            })
+            .collect()
+    }
+
+    // Whether the CLIPS thread is in the middle of `load_from_str`, `load_from_str_atomic` or `batch_star`. Commands sent meanwhile wait until the load is over, so the parsing location can only be read from UDFs and routers called during the load, through `UDFData::env().get_current_parsing_location()`.
+    pub fn is_parsing(&self) -> bool {
+        self.parsing.load(Ordering::Acquire)
+    }
+
+    // Whether the environment can still take commands. It doesn't mean it will answer them quickly, use `ping()` for that.
+    pub fn is_alive(&self) -> bool {
+        self.failure_reason().is_none()
+    }
+
+    // `None` while the environment is alive.
+    pub fn failure_reason(&self) -> Option<EnvironmentFailure> {
+        if self.closed.load(Ordering::Acquire) {
+            return Some(EnvironmentFailure::Closed);
+        }
+
+        if let Some(code) = *self.exit_code.lock().unwrap() {
+            return Some(EnvironmentFailure::Exited { code });
+        }
+
+        if let Some(message) = self.panic_message.lock().unwrap().clone() {
+            return Some(EnvironmentFailure::Panicked { message });
+        }
+
+        // The thread only finishes after it dropped the receiving end of the command channel, so this also covers the channel being disconnected.
+        if self
+            .task_handle
+            .lock()
+            .unwrap()
+            .as_ref()
+            .is_none_or(JoinHandle::is_finished)
+        {
+            return Some(EnvironmentFailure::ThreadExited);
+        }
+
+        None
+    }
+
+    // Sends a command that does nothing and measures how long it took to be answered, which includes the time spent behind the commands already waiting. Fails with `CLIPSError::Timeout` if there's no answer in `timeout`.
+    pub fn ping(&self, timeout: Duration) -> CLIPSResult<Duration> {
+        let (res_tx, res_rx) = oneshot::channel();
+        let start = Instant::now();
+
+        self.send_command(CLIPSEnvironmentCommand::Ping { res_tx })?;
+
+        res_rx.recv_timeout(timeout).map_err(|err| match err {
+            oneshot::RecvTimeoutError::Timeout => CLIPSError::Timeout,
+            oneshot::RecvTimeoutError::Disconnected => CLIPSError::ThreadExited,
+        })?;
+
+        Ok(start.elapsed())
+    }
+
+    // Makes the CLIPS thread panic once it gets to this command, so tests can see how handles behave after the thread is gone.
+    #[cfg(feature = "test-util")]
+    pub fn poison(&self) -> CLIPSResult<()> {
+        self.send_command(CLIPSEnvironmentCommand::Poison)
+    }
+
+    // The number of commands that were sent to the CLIPS thread but haven't started being processed yet.
+    pub fn pending_commands(&self) -> usize {
+        self.pending_commands.load(Ordering::Acquire)
+    }
+
+    // Commands can still be sent while the environment is paused, but the CLIPS thread doesn't start any of them until `resume()`, so callers block until then. A command that's already running isn't interrupted, since the thread only checks between commands (use `run_for()` or a watchdog to bound a run). `close()` resumes the environment, so the commands queued before it still run.
+    pub fn pause(&self) {
+        let (lock, _) = &*self.paused;
+        *lock.lock().unwrap() = true;
+    }
+
+    pub fn resume(&self) {
+        let (lock, condvar) = &*self.paused;
+        *lock.lock().unwrap() = false;
+        condvar.notify_all();
+    }
+
+    pub fn is_paused(&self) -> bool {
+        let (lock, _) = &*self.paused;
+        *lock.lock().unwrap()
+    }
+
+    // A router or UDF callback runs on the CLIPS thread, so if it calls back into this handle the command would wait forever behind the callback that sent it. Callbacks should use `UDFData::env()` instead, which talks to the environment directly.
+    fn is_reentrant_call(&self) -> bool {
+        thread::current().id() == self.task_thread.id()
+    }
+
+    fn check_can_send(&self) -> CLIPSResult<()> {
+        if self.is_reentrant_call() {
+            return Err(CLIPSError::ReentrantCall);
+        }
+
+        if self.closed.load(Ordering::Acquire) {
+            return Err(CLIPSError::Closed);
+        }
+
+        if let Some(code) = *self.exit_code.lock().unwrap() {
+            return Err(CLIPSError::EnvironmentExited { code });
+        }
+
+        Ok(())
+    }
+
+    fn send_command(&self, command: CLIPSEnvironmentCommand) -> CLIPSResult<()> {
+        self.check_can_send()?;
+        self.send_to_task(command)
+    }
+
+    fn send_to_task(&self, command: CLIPSEnvironmentCommand) -> CLIPSResult<()> {
+        // Incremented before sending so the CLIPS thread never sees a count that doesn't include the command it just received.
+        self.pending_commands.fetch_add(1, Ordering::AcqRel);
+
+        let res = match &self.input_tx {
+            CommandSender::Unbounded(tx) => tx.send(command).map_err(|_| CLIPSError::ThreadExited),
+            CommandSender::Bounded(tx) => tx.send(command).map_err(|_| CLIPSError::ThreadExited),
+        };
+
+        if res.is_err() {
+            self.pending_commands.fetch_sub(1, Ordering::AcqRel);
+        }
+
+        res
+    }
+
+    fn try_send_command(&self, command: CLIPSEnvironmentCommand) -> CLIPSResult<()> {
+        self.check_can_send()?;
+
+        self.pending_commands.fetch_add(1, Ordering::AcqRel);
+
+        let res = match &self.input_tx {
+            CommandSender::Unbounded(tx) => tx.send(command).map_err(|_| CLIPSError::ThreadExited),
+            CommandSender::Bounded(tx) => tx.try_send(command).map_err(|e| match e {
+                mpsc::TrySendError::Full(_) => CLIPSError::Busy,
+                mpsc::TrySendError::Disconnected(_) => CLIPSError::ThreadExited,
+            }),
+        };
+
+        if res.is_err() {
+            self.pending_commands.fetch_sub(1, Ordering::AcqRel);
         }
+
+        res
     }
 
-    pub fn close(self) -> CLIPSResult<()> {
-        self.input_tx
-            .send(CLIPSEnvironmentCommand::Close)
-            .map_err(|_| CLIPSError::ThreadExited)?;
-        self.task_handle
-            .join()
-            .map_err(|_| CLIPSError::TaskExitedUnexpectedly)?;
+    // Closes the environment for every handle to it. Closing an environment that was already closed does nothing.
+    pub fn close(&self) -> CLIPSResult<()> {
+        if self.is_reentrant_call() {
+            return Err(CLIPSError::ReentrantCall);
+        }
+
+        if self.closed.swap(true, Ordering::AcqRel) {
+            return Ok(());
+        }
+
+        self.resume();
+        self.send_to_task(CLIPSEnvironmentCommand::Close)?;
+
+        let task_handle = self.task_handle.lock().unwrap().take();
+        if let Some(task_handle) = task_handle {
+            task_handle
+                .join()
+                .map_err(|_| CLIPSError::TaskExitedUnexpectedly)?;
+        }
         Ok(())
     }
 
-    pub fn load_from_str(&self, data: &str) -> CLIPSResult<()> {
+    // Passing an `Arc<str>` lets the same program text be loaded into many environments without copying it for each one.
+    pub fn load_from_str(&self, data: impl Into<Arc<str>>) -> CLIPSResult<()> {
         let (res_tx, res_rx) = oneshot::channel();
 
-        self.input_tx
-            .send(CLIPSEnvironmentCommand::LoadFromStr {
-                data: data.to_string(),
-                res_tx,
-            })
-            .map_err(|_| CLIPSError::ThreadExited)?;
+        self.send_command(CLIPSEnvironmentCommand::LoadFromStr {
+            data: data.into(),
+            res_tx,
+        })?;
+
+        res_rx.recv().map_err(|_| CLIPSError::ThreadExited)?
+    }
+
+    // Unlike `load_from_str`, either every construct in `data` is loaded or none is. On failure, constructs that `data` redefined are built again from their previous pretty print forms, and globals get their previous values back. Defmodules can't be removed, and message handlers and methods are left as they are, so those aren't rolled back.
+    pub fn load_from_str_atomic(&self, data: impl Into<Arc<str>>) -> CLIPSResult<()> {
+        let (res_tx, res_rx) = oneshot::channel();
+
+        self.send_command(CLIPSEnvironmentCommand::LoadFromStrAtomic {
+            data: data.into(),
+            res_tx,
+        })?;
+
+        res_rx.recv().map_err(|_| CLIPSError::ThreadExited)?
+    }
+
+    // Loads `data` with `module` as the current module, so constructs that don't name a module go into it. The module is created if it doesn't exist, without importing anything. To have it import from other modules, define it with `load_from_str` first.
+    pub fn load_into_module(&self, module: &str, data: impl Into<Arc<str>>) -> CLIPSResult<()> {
+        let (res_tx, res_rx) = oneshot::channel();
+
+        self.send_command(CLIPSEnvironmentCommand::LoadIntoModule {
+            module: module.to_string(),
+            data: data.into(),
+            res_tx,
+        })?;
+
+        res_rx.recv().map_err(|_| CLIPSError::ThreadExited)?
+    }
+
+    // Undefines every construct in `module`, after retracting the facts and deleting the instances that use its templates and classes. The module itself stays, empty, since CLIPS can't undefine modules, so the same module can be loaded into again. Returns how many constructs were undefined.
+    pub fn unload_module(&self, module: &str) -> CLIPSResult<usize> {
+        let (res_tx, res_rx) = oneshot::channel();
+
+        self.send_command(CLIPSEnvironmentCommand::UnloadModule {
+            module: module.to_string(),
+            res_tx,
+        })?;
 
         res_rx.recv().map_err(|_| CLIPSError::ThreadExited)?
     }
 
+    // Fails with `CLIPSError::BatchStar` if the file can't be read, with the IO error kind, or if CLIPS reported an error for anything in it, without one. The rest of the file is still run in that case.
     pub fn batch_star(&self, file_path: PathBuf) -> CLIPSResult<()> {
         let (res_tx, res_rx) = oneshot::channel();
 
-        self.input_tx
-            .send(CLIPSEnvironmentCommand::BatchStar { file_path, res_tx })
-            .map_err(|_| CLIPSError::ThreadExited)?;
+        self.send_command(CLIPSEnvironmentCommand::BatchStar { file_path, res_tx })?;
 
         res_rx.recv().map_err(|_| CLIPSError::ThreadExited)?
     }
 
-    pub fn chdir(&self, new_dir: PathBuf) -> CLIPSResult<()> {
+    // Like `load_from_str`, but also reports how long the load took and how much it added to the Rete network.
+    pub fn load_from_str_with_report(&self, data: impl Into<Arc<str>>) -> CLIPSResult<LoadReport> {
         let (res_tx, res_rx) = oneshot::channel();
 
-        self.input_tx
-            .send(CLIPSEnvironmentCommand::ChDir { new_dir, res_tx })
-            .map_err(|_| CLIPSError::ThreadExited)?;
+        self.send_command(CLIPSEnvironmentCommand::LoadFromStrWithReport {
+            data: data.into(),
+            res_tx,
+        })?;
 
         res_rx.recv().map_err(|_| CLIPSError::ThreadExited)?
     }
 
-    pub fn run(&self) -> CLIPSResult<usize> {
+    // Like `batch_star`, but also reports how long the load took and how much it added to the Rete network. Since the file is a batch file, the duration includes running any commands in it.
+    pub fn batch_star_with_report(&self, file_path: PathBuf) -> CLIPSResult<LoadReport> {
         let (res_tx, res_rx) = oneshot::channel();
 
-        self.input_tx
-            .send(CLIPSEnvironmentCommand::Run { res_tx })
-            .map_err(|_| CLIPSError::ThreadExited)?;
+        self.send_command(CLIPSEnvironmentCommand::BatchStarWithReport { file_path, res_tx })?;
 
         res_rx.recv().map_err(|_| CLIPSError::ThreadExited)?
     }
 
-    pub fn add_udf(
-        &self,
-        name: String,
-        min_args: u16,
-        max_args: u16,
-        return_types: UDFType,
-        arg_types: Vec<UDFType>,
-        function: Box<dyn FnMut(UDFData) + Send + Sync>,
-    ) -> CLIPSResult<()> {
+    pub fn chdir(&self, new_dir: PathBuf) -> CLIPSResult<()> {
         let (res_tx, res_rx) = oneshot::channel();
 
-        self.input_tx
-            .send(CLIPSEnvironmentCommand::AddUDF {
-                name,
-                min_args,
-                max_args,
-                return_types,
-                arg_types,
-                function,
-                res_tx,
-            })
-            .map_err(|_| CLIPSError::ThreadExited)?;
+        self.send_command(CLIPSEnvironmentCommand::ChDir { new_dir, res_tx })?;
 
         res_rx.recv().map_err(|_| CLIPSError::ThreadExited)?
     }
 
-    pub fn add_router(
-        &self,
-        name: String,
-        priority: i32,
-        router: RegisterableRouter,
-    ) -> CLIPSResult<()> {
+    pub fn run(&self) -> CLIPSResult<usize> {
         let (res_tx, res_rx) = oneshot::channel();
 
-        self.input_tx
-            .send(CLIPSEnvironmentCommand::AddRouter {
-                name,
-                priority,
-                router,
-                res_tx,
-            })
-            .map_err(|_| CLIPSError::ThreadExited)?;
+        self.send_command(CLIPSEnvironmentCommand::Run { res_tx })?;
 
         res_rx.recv().map_err(|_| CLIPSError::ThreadExited)?
     }
 
-    pub fn remove_udf(&self, name: String) -> CLIPSResult<bool> {
+    pub fn run_detailed(&self) -> CLIPSResult<RunOutcome> {
         let (res_tx, res_rx) = oneshot::channel();
 
-        self.input_tx
-            .send(CLIPSEnvironmentCommand::RemoveUDF { name, res_tx })
-            .map_err(|_| CLIPSError::ThreadExited)?;
+        self.send_command(CLIPSEnvironmentCommand::RunDetailed { res_tx })?;
 
-        res_rx.recv().map_err(|_| CLIPSError::ThreadExited)
+        res_rx.recv().map_err(|_| CLIPSError::ThreadExited)?
     }
 
-    pub fn assert_fact<T: IntoFactOrInstance<FactBuilderData> + Send + Sync + 'static>(
-        &self,
-        value: T,
-    ) -> CLIPSResult<()> {
+    // Like `run_limit`, but fails with `FiringCapExceeded` if there are still activations on the agenda once `max_firings` rules fired, which is a sign the rules keep activating each other. The rules that fired aren't undone.
+    pub fn run_capped(&self, max_firings: usize) -> CLIPSResult<usize> {
         let (res_tx, res_rx) = oneshot::channel();
 
-        self.input_tx
-            .send(CLIPSEnvironmentCommand::AssertFact {
-                value: Box::new(value),
-                res_tx,
-            })
-            .map_err(|_| CLIPSError::ThreadExited)?;
+        self.send_command(CLIPSEnvironmentCommand::RunCapped {
+            max_firings,
+            res_tx,
+        })?;
 
         res_rx.recv().map_err(|_| CLIPSError::ThreadExited)?
     }
 
-    pub fn make_instance<T: IntoFactOrInstance<InstanceBuilderData> + Send + Sync + 'static>(
-        &self,
-        value: T,
-        instance_name: Option<String>,
-    ) -> CLIPSResult<()> {
+    // Runs until the agenda is empty, the rules halt or `budget` is used up, and returns how many rules fired. The time is only checked after each rule fires, so a rule that's already firing when the budget runs out finishes first.
+    pub fn run_for(&self, budget: Duration) -> CLIPSResult<usize> {
         let (res_tx, res_rx) = oneshot::channel();
 
-        self.input_tx
-            .send(CLIPSEnvironmentCommand::MakeInstance {
-                value: Box::new(value),
-                instance_name,
-                res_tx,
-            })
-            .map_err(|_| CLIPSError::ThreadExited)?;
+        self.send_command(CLIPSEnvironmentCommand::RunFor { budget, res_tx })?;
 
         res_rx.recv().map_err(|_| CLIPSError::ThreadExited)?
     }
 
-    pub fn set_dynamic_constraint_checking(&self, value: bool) -> CLIPSResult<()> {
+    pub fn stats(&self) -> CLIPSResult<EnvStats> {
         let (res_tx, res_rx) = oneshot::channel();
 
-        self.input_tx
-            .send(CLIPSEnvironmentCommand::SetDynamicConstraintChecking { value, res_tx })
-            .map_err(|_| CLIPSError::ThreadExited)?;
+        self.send_command(CLIPSEnvironmentCommand::Stats { res_tx })?;
 
-        Ok(res_rx.recv().map_err(|_| CLIPSError::ThreadExited)?)
+        res_rx.recv().map_err(|_| CLIPSError::ThreadExited)
     }
 
-    pub fn set_conflict_resolution_strategy(
-        &self,
-        value: ConflictResolutionStrategy,
-    ) -> CLIPSResult<()> {
+    // The most recent error or warning CLIPS printed, without needing a router to catch it. The text is still printed as usual. Text taken by a router with a priority above 40 that doesn't pass it on is never seen.
+    pub fn last_error_text(&self) -> CLIPSResult<Option<String>> {
         let (res_tx, res_rx) = oneshot::channel();
 
-        self.input_tx
-            .send(CLIPSEnvironmentCommand::SetConflictResolutionStrategy { value, res_tx })
-            .map_err(|_| CLIPSError::ThreadExited)?;
+        self.send_command(CLIPSEnvironmentCommand::LastErrorText { res_tx })?;
 
-        Ok(res_rx.recv().map_err(|_| CLIPSError::ThreadExited)?)
+        res_rx.recv().map_err(|_| CLIPSError::ThreadExited)
     }
 
-    pub fn get_current_parsing_location(&self) -> CLIPSResult<(String, usize)> {
+    // The last few errors and warnings CLIPS printed, oldest first.
+    pub fn recent_error_texts(&self) -> CLIPSResult<Vec<String>> {
         let (res_tx, res_rx) = oneshot::channel();
 
-        self.input_tx
-            .send(CLIPSEnvironmentCommand::GetCurrentParsingLocation { res_tx })
-            .map_err(|_| CLIPSError::ThreadExited)?;
+        self.send_command(CLIPSEnvironmentCommand::RecentErrorTexts { res_tx })?;
 
-        Ok(res_rx.recv().map_err(|_| CLIPSError::ThreadExited)?)
+        res_rx.recv().map_err(|_| CLIPSError::ThreadExited)
     }
 
-    pub fn binary_save_facts(&self, path: PathBuf) -> CLIPSResult<usize> {
+    // How many times each rule fired, by its module-qualified name, since the environment was created or last cleared, or since the last `reset_fire_counts()`. A redefined rule starts over from zero. Rules that never fired aren't included.
+    pub fn rule_fire_counts(&self) -> CLIPSResult<HashMap<String, u64>> {
         let (res_tx, res_rx) = oneshot::channel();
 
-        self.input_tx
-            .send(CLIPSEnvironmentCommand::BinarySaveFacts { path, res_tx })
-            .map_err(|_| CLIPSError::ThreadExited)?;
+        self.send_command(CLIPSEnvironmentCommand::RuleFireCounts { res_tx })?;
 
         res_rx.recv().map_err(|_| CLIPSError::ThreadExited)?
     }
 
-    pub fn binary_load_facts(&self, path: PathBuf) -> CLIPSResult<usize> {
+    pub fn reset_fire_counts(&self) -> CLIPSResult<()> {
+        let (res_tx, res_rx) = oneshot::channel();
+
+        self.send_command(CLIPSEnvironmentCommand::ResetFireCounts { res_tx })?;
+
+        res_rx.recv().map_err(|_| CLIPSError::ThreadExited)
+    }
+
+    // The callback runs on the thread that owns the CLIPS environment, after any command that changed the current module's agenda has sent its result back. It's only told about the agenda as it was when the command finished, so it gets a single call for a whole run. Any call made to this `Environment` after the one that changed the agenda returns after the callback has run. The callback must not call into this `Environment`, since the thread is busy running it.
+    pub fn set_agenda_changed_callback<F>(&self, callback: F) -> CLIPSResult<()>
+    where
+        F: FnMut(&AgendaChange) + Send + 'static,
+    {
         let (res_tx, res_rx) = oneshot::channel();
 
-        self.input_tx
-            .send(CLIPSEnvironmentCommand::BinaryLoadFacts { path, res_tx })
-            .map_err(|_| CLIPSError::ThreadExited)?;
+        self.send_command(CLIPSEnvironmentCommand::SetAgendaChangedCallback {
+            callback: Some(Box::new(callback)),
+            res_tx,
+        })?;
 
         res_rx.recv().map_err(|_| CLIPSError::ThreadExited)?
     }
 
-    pub fn binary_save_instances(&self, path: PathBuf) -> CLIPSResult<usize> {
+    pub fn clear_agenda_changed_callback(&self) -> CLIPSResult<()> {
         let (res_tx, res_rx) = oneshot::channel();
 
-        self.input_tx
-            .send(CLIPSEnvironmentCommand::BinarySaveInstances { path, res_tx })
-            .map_err(|_| CLIPSError::ThreadExited)?;
+        self.send_command(CLIPSEnvironmentCommand::SetAgendaChangedCallback {
+            callback: None,
+            res_tx,
+        })?;
 
         res_rx.recv().map_err(|_| CLIPSError::ThreadExited)?
     }
 
-    pub fn binary_load_instances(&self, path: PathBuf) -> CLIPSResult<usize> {
+    // Every run after this is watched, and the watchdog acts if one goes on for longer than `config.max_run_duration`. Replaces any watchdog set before.
+    pub fn set_watchdog(&self, config: WatchdogConfig) -> CLIPSResult<()> {
+        let (res_tx, res_rx) = oneshot::channel();
+
+        self.send_command(CLIPSEnvironmentCommand::SetWatchdog {
+            config: Some(config),
+            res_tx,
+        })?;
+
+        res_rx.recv().map_err(|_| CLIPSError::ThreadExited)
+    }
+
+    pub fn clear_watchdog(&self) -> CLIPSResult<()> {
+        let (res_tx, res_rx) = oneshot::channel();
+
+        self.send_command(CLIPSEnvironmentCommand::SetWatchdog {
+            config: None,
+            res_tx,
+        })?;
+
+        res_rx.recv().map_err(|_| CLIPSError::ThreadExited)
+    }
+
+    // The report from the last watched run. `None` if the watchdog didn't go off in it, or if its action doesn't make reports.
+    pub fn last_watchdog_report(&self) -> CLIPSResult<Option<WatchdogReport>> {
+        let (res_tx, res_rx) = oneshot::channel();
+
+        self.send_command(CLIPSEnvironmentCommand::GetLastWatchdogReport { res_tx })?;
+
+        res_rx.recv().map_err(|_| CLIPSError::ThreadExited)
+    }
+
+    // Only runs started with `run`, `run_detailed`, `run_limit`, `run_for` or `run_watching_wm` are tracked. `None` if no rule fired yet.
+    pub fn last_fired_rule(&self) -> CLIPSResult<Option<String>> {
         let (res_tx, res_rx) = oneshot::channel();
 
-        self.input_tx
-            .send(CLIPSEnvironmentCommand::BinaryLoadInstances { path, res_tx })
-            .map_err(|_| CLIPSError::ThreadExited)?;
+        self.send_command(CLIPSEnvironmentCommand::GetLastFiredRule { res_tx })?;
+
+        res_rx.recv().map_err(|_| CLIPSError::ThreadExited)
+    }
+
+    // Every fact asserted or retracted while the rules run is sent to `tx`, in the order CLIPS made the changes.
+    pub fn run_watching_wm(&self, tx: mpsc::Sender<WmChange>) -> CLIPSResult<usize> {
+        let (res_tx, res_rx) = oneshot::channel();
+
+        self.send_command(CLIPSEnvironmentCommand::RunWatchingWm { tx, res_tx })?;
 
         res_rx.recv().map_err(|_| CLIPSError::ThreadExited)?
     }
 
-    pub fn retrieve_globals_values(&self) -> CLIPSResult<CLIPSGlobalsHierarchy> {
+    // The names of the rules fired in this run, in the order they fired.
+    pub fn run_recording_fired_rules(&self) -> CLIPSResult<Vec<String>> {
         let (res_tx, res_rx) = oneshot::channel();
 
-        self.input_tx
-            .send(CLIPSEnvironmentCommand::RetrieveGlobalsValues { res_tx })
-            .map_err(|_| CLIPSError::ThreadExited)?;
+        self.send_command(CLIPSEnvironmentCommand::RunRecordingFiredRules { res_tx })?;
 
         res_rx.recv().map_err(|_| CLIPSError::ThreadExited)?
     }
 
-    pub fn restore_globals(&self, globals: CLIPSGlobalsHierarchy) -> CLIPSResult<()> {
+    // Runs, then takes every fact of `result_template` out of working memory and returns it, for rules that answer a query by asserting results. Everything happens in one command, so no other command can see or add results in between.
+    pub fn run_consume(&self, result_template: &str) -> CLIPSResult<Vec<RetrievedFact>> {
         let (res_tx, res_rx) = oneshot::channel();
 
-        self.input_tx
-            .send(CLIPSEnvironmentCommand::RestoreGlobals { globals, res_tx })
-            .map_err(|_| CLIPSError::ThreadExited)?;
+        self.send_command(CLIPSEnvironmentCommand::RunConsume {
+            result_template: result_template.to_string(),
+            res_tx,
+        })?;
 
         res_rx.recv().map_err(|_| CLIPSError::ThreadExited)?
     }
-}
 
-enum CLIPSEnvironmentCommand {
-    LoadFromStr {
-        data: String,
-        res_tx: oneshot::Sender<CLIPSResult<()>>,
-    },
-    BatchStar {
-        file_path: PathBuf,
-        res_tx: oneshot::Sender<CLIPSResult<()>>,
-    },
-    Run {
-        res_tx: oneshot::Sender<CLIPSResult<usize>>,
-    },
-    RunLimit {
-        limit: usize,
-        res_tx: oneshot::Sender<CLIPSResult<usize>>,
-    },
-    ChDir {
-        new_dir: PathBuf,
-        res_tx: oneshot::Sender<CLIPSResult<()>>,
-    },
-    AddUDF {
+    // Sends a `SlotChange` every time `modify` changes `slot` in a fact of `template`. Modifications of other slots or templates are filtered out on the CLIPS thread, so they never go through the channel. Dropping the receiver doesn't stop the filtering, `unwatch_slot()` does.
+    pub fn watch_slot(
+        &self,
+        template: &str,
+        slot: &str,
+    ) -> CLIPSResult<(SlotWatchId, mpsc::Receiver<SlotChange>)> {
+        let (tx, rx) = mpsc::channel();
+        let (res_tx, res_rx) = oneshot::channel();
+
+        self.send_command(CLIPSEnvironmentCommand::WatchSlot {
+            template: template.to_string(),
+            slot: slot.to_string(),
+            tx,
+            res_tx,
+        })?;
+
+        let id = res_rx.recv().map_err(|_| CLIPSError::ThreadExited)??;
+        Ok((id, rx))
+    }
+
+    // Returns whether the watch existed. The receiver sees the channel close once the watch is gone.
+    pub fn unwatch_slot(&self, id: SlotWatchId) -> CLIPSResult<bool> {
+        let (res_tx, res_rx) = oneshot::channel();
+
+        self.send_command(CLIPSEnvironmentCommand::UnwatchSlot { id, res_tx })?;
+
+        res_rx.recv().map_err(|_| CLIPSError::ThreadExited)
+    }
+
+    // Calls `callback` every time CLIPS defines a defrule, deftemplate, deffacts, definstances, deffunction, defgeneric or defclass, whether it's built, loaded or comes from a batch file, including the constructs defined by CLIPS code. Other kinds of constructs aren't reported. With a callback added, parsing a construct looks through the constructs of its kind that already exist, so loading many constructs gets slower.
+    pub fn on_construct_defined(
+        &self,
+        callback: Box<dyn FnMut(ConstructEvent) + Send>,
+    ) -> CLIPSResult<()> {
+        let (res_tx, res_rx) = oneshot::channel();
+
+        self.send_command(CLIPSEnvironmentCommand::OnConstructDefined { callback, res_tx })?;
+
+        res_rx.recv().map_err(|_| CLIPSError::ThreadExited)
+    }
+
+    // The activations in the current module's agenda, in the order they would fire.
+    pub fn agenda(&self) -> CLIPSResult<Vec<ActivationInfo>> {
+        let (res_tx, res_rx) = oneshot::channel();
+
+        self.send_command(CLIPSEnvironmentCommand::Agenda { res_tx })?;
+
+        res_rx.recv().map_err(|_| CLIPSError::ThreadExited)?
+    }
+
+    // The same text `(agenda)` prints in the CLIPS console for the current module.
+    pub fn agenda_pp(&self) -> CLIPSResult<String> {
+        let (res_tx, res_rx) = oneshot::channel();
+
+        self.send_command(CLIPSEnvironmentCommand::AgendaPP { res_tx })?;
+
+        res_rx.recv().map_err(|_| CLIPSError::ThreadExited)?
+    }
+
+    // Unlike `reset`, this keeps the current facts and instances: rules that already fired are activated again for them, and saliences are recomputed so the agenda is reordered.
+    pub fn refresh_agenda(&self) -> CLIPSResult<()> {
+        let (res_tx, res_rx) = oneshot::channel();
+
+        self.send_command(CLIPSEnvironmentCommand::RefreshAgenda { res_tx })?;
+
+        res_rx.recv().map_err(|_| CLIPSError::ThreadExited)?
+    }
+
+    pub fn add_udf(
+        &self,
         name: String,
         min_args: u16,
         max_args: u16,
         return_types: UDFType,
         arg_types: Vec<UDFType>,
         function: Box<dyn FnMut(UDFData) + Send + Sync>,
-        res_tx: oneshot::Sender<CLIPSResult<()>>,
-    },
-    AddRouter {
+    ) -> CLIPSResult<()> {
+        self.add_udf_with_signature(
+            UDFSignature::new(name, min_args, max_args, return_types, arg_types),
+            function,
+        )
+    }
+
+    pub fn add_udf_with_signature(
+        &self,
+        signature: UDFSignature,
+        function: Box<dyn FnMut(UDFData) + Send + Sync>,
+    ) -> CLIPSResult<()> {
+        let (res_tx, res_rx) = oneshot::channel();
+
+        self.send_command(CLIPSEnvironmentCommand::AddUDF {
+            signature,
+            function,
+            res_tx,
+        })?;
+
+        res_rx.recv().map_err(|_| CLIPSError::ThreadExited)?
+    }
+
+    // Registers `(rust-udfs)`, `(rust-version)` and `(rust-log ?level ?message)` in the environment. Their documentation is available through `list_udfs()` like any other UDF.
+    pub fn install_introspection_udfs(&self) -> CLIPSResult<()> {
+        for (signature, function) in introspection_udfs() {
+            self.add_udf_with_signature(signature, function)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn list_udfs(&self) -> CLIPSResult<Vec<UDFSignature>> {
+        let (res_tx, res_rx) = oneshot::channel();
+
+        self.send_command(CLIPSEnvironmentCommand::ListUDFs { res_tx })?;
+
+        res_rx.recv().map_err(|_| CLIPSError::ThreadExited)
+    }
+
+    pub fn add_router(
+        &self,
         name: String,
         priority: i32,
         router: RegisterableRouter,
-        res_tx: oneshot::Sender<CLIPSResult<()>>,
-    },
-    RemoveUDF {
-        name: String,
-        res_tx: oneshot::Sender<bool>,
-    },
-    AssertFact {
-        value: Box<dyn IntoFactOrInstance<FactBuilderData> + Send + Sync>,
-        res_tx: oneshot::Sender<CLIPSResult<()>>,
-    },
-    MakeInstance {
-        value: Box<dyn IntoFactOrInstance<InstanceBuilderData> + Send + Sync>,
-        instance_name: Option<String>,
-        res_tx: oneshot::Sender<CLIPSResult<()>>,
-    },
-    SetDynamicConstraintChecking {
-        value: bool,
-        res_tx: oneshot::Sender<()>,
-    },
-    SetConflictResolutionStrategy {
-        value: ConflictResolutionStrategy,
-        res_tx: oneshot::Sender<()>,
-    },
-    GetCurrentParsingLocation {
-        res_tx: oneshot::Sender<(String, usize)>,
-    },
-    BinarySaveFacts {
-        path: PathBuf,
-        res_tx: oneshot::Sender<CLIPSResult<usize>>,
-    },
-    BinaryLoadFacts {
-        path: PathBuf,
-        res_tx: oneshot::Sender<CLIPSResult<usize>>,
-    },
-    BinarySaveInstances {
-        path: PathBuf,
-        res_tx: oneshot::Sender<CLIPSResult<usize>>,
-    },
-    BinaryLoadInstances {
-        path: PathBuf,
-        res_tx: oneshot::Sender<CLIPSResult<usize>>,
-    },
-    RetrieveGlobalsValues {
-        res_tx: oneshot::Sender<CLIPSResult<CLIPSGlobalsHierarchy>>,
-    },
-    RestoreGlobals {
-        globals: CLIPSGlobalsHierarchy,
-        res_tx: oneshot::Sender<CLIPSResult<()>>,
-    },
-    Close,
-}
+    ) -> CLIPSResult<()> {
+        let (res_tx, res_rx) = oneshot::channel();
 
-fn clips_environment_task(input_rx: mpsc::Receiver<CLIPSEnvironmentCommand>) {
-    // We use `unshare()` to allow this thread setting a different `chdir` than other threads in the process. This library expects to be used in multi-threaded programs, and by default `chdir()` applies to the entire process.
-    unshare(CloneFlags::CLONE_FS).unwrap();
+        self.send_command(CLIPSEnvironmentCommand::AddRouter {
+            name,
+            priority,
+            router,
+            res_tx,
+        })?;
 
-    let mut env = CLIPSEnvironment::new().unwrap();
+        res_rx.recv().map_err(|_| CLIPSError::ThreadExited)?
+    }
 
-    // In the loop below, we'll ignore any `SendError`s that happen when sending the result of doing the work that was requested. To do this with some concise code, we must get rid of the `SendError`s  returned by each channel's `send()` call, because those errors all have different types (and thus can't be assigned to the same variable). The `StubError` below exists so we can map all `SendError`s to a `StubError` to allow the code to be concise.
-    struct StubError {}
-    fn create_stub_error<T>(_prev: SendError<T>) -> StubError {
-        StubError {}
+    // Sends what CLIPS writes to stdout, stdwrn and stderr to `tracing` instead of the console, along with an event for every rule fired and every fact asserted or retracted. Every run is wrapped in a `clips_run` span, so the events nest under the run that produced them.
+    #[cfg(feature = "tracing")]
+    pub fn install_tracing_bridge(&self) -> CLIPSResult<()> {
+        let (res_tx, res_rx) = oneshot::channel();
+
+        self.send_command(CLIPSEnvironmentCommand::InstallTracingBridge { res_tx })?;
+
+        res_rx.recv().map_err(|_| CLIPSError::ThreadExited)?
     }
 
-    loop {
-        let result_res = match input_rx.recv() {
-            Err(_) => {
-                log::info!("The input channel for the CLIPS environment is closed, so will stop the CLIPS environment task.");
-                break;
+    pub fn remove_udf(&self, name: String) -> CLIPSResult<bool> {
+        let (res_tx, res_rx) = oneshot::channel();
+
+        self.send_command(CLIPSEnvironmentCommand::RemoveUDF { name, res_tx })?;
+
+        res_rx.recv().map_err(|_| CLIPSError::ThreadExited)
+    }
+
+    // Adds a method to the generic function `generic`, creating the generic if it doesn't exist yet. There's one argument for each restriction, which holds the types or classes it accepts separated by spaces (e.g. `"INTEGER FLOAT"`), or is empty to accept anything. CLIPS picks the method, and `function` reads the arguments and sets the result like a UDF does.
+    pub fn add_generic_method(
+        &self,
+        generic: &str,
+        restrictions: &[&str],
+        function: Box<dyn FnMut(UDFData) + Send + Sync>,
+    ) -> CLIPSResult<()> {
+        let (res_tx, res_rx) = oneshot::channel();
+
+        self.send_command(CLIPSEnvironmentCommand::AddGenericMethod {
+            generic: generic.to_string(),
+            restrictions: restrictions.iter().map(|r| r.to_string()).collect(),
+            function,
+            res_tx,
+        })?;
+
+        res_rx.recv().map_err(|_| CLIPSError::ThreadExited)?
+    }
+
+    // The template name given by `definition_name()` can be module-qualified (e.g. `MAIN::order`). Otherwise, it's looked up in `module` if given, or in the current module.
+    pub fn assert_fact<T: IntoFactOrInstance<FactBuilderData> + Send + Sync + 'static>(
+        &self,
+        value: T,
+        module: Option<String>,
+    ) -> CLIPSResult<()> {
+        let (res_tx, res_rx) = oneshot::channel();
+
+        self.send_command(CLIPSEnvironmentCommand::AssertFact {
+            value: Box::new(value),
+            module,
+            res_tx,
+        })?;
+
+        res_rx.recv().map_err(|_| CLIPSError::ThreadExited)?
+    }
+
+    // Asserts the ordered fact `(head values...)` and returns its index. Values are given to CLIPS as they are, so they don't need any escaping. `head` can't name a template defined with `deftemplate`.
+    pub fn assert_ordered(&self, head: &str, values: Vec<CLIPSValue>) -> CLIPSResult<i64> {
+        let (res_tx, res_rx) = oneshot::channel();
+
+        self.send_command(CLIPSEnvironmentCommand::AssertOrdered {
+            head: head.to_string(),
+            values,
+            res_tx,
+        })?;
+
+        res_rx.recv().map_err(|_| CLIPSError::ThreadExited)?
+    }
+
+    // `fact` is written the way it would be in an `assert` command, e.g. `(point (x 1) (y 2))`, and only one fact can be given. Returns the fact's index.
+    pub fn assert_string(&self, fact: &str) -> CLIPSResult<i64> {
+        let (res_tx, res_rx) = oneshot::channel();
+
+        self.send_command(CLIPSEnvironmentCommand::AssertString {
+            fact: fact.to_string(),
+            res_tx,
+        })?;
+
+        res_rx.recv().map_err(|_| CLIPSError::ThreadExited)?
+    }
+
+    // Asserts a copy of the fact at `index` with the slots in `overrides` changed, like `(duplicate)` does, and returns the new fact's index. The fields of an ordered fact are in its `implied` slot. If nothing changes and fact duplication is off, CLIPS gives back the original fact.
+    pub fn duplicate_fact(
+        &self,
+        index: i64,
+        overrides: HashMap<String, CLIPSValue>,
+    ) -> CLIPSResult<i64> {
+        let (res_tx, res_rx) = oneshot::channel();
+
+        self.send_command(CLIPSEnvironmentCommand::DuplicateFact {
+            index,
+            overrides,
+            res_tx,
+        })?;
+
+        res_rx.recv().map_err(|_| CLIPSError::ThreadExited)?
+    }
+
+    // Modifies the fact at `index` like `(modify)` does, changing one multislot without sending the rest of it back and forth. CLIPS still copies the whole multifield. Returns the modified fact's index.
+    pub fn edit_multislot(&self, index: i64, slot: &str, edit: MultislotEdit) -> CLIPSResult<i64> {
+        let (res_tx, res_rx) = oneshot::channel();
+
+        self.send_command(CLIPSEnvironmentCommand::EditMultislot {
+            index,
+            slot: slot.to_string(),
+            edit,
+            res_tx,
+        })?;
+
+        res_rx.recv().map_err(|_| CLIPSError::ThreadExited)?
+    }
+
+    pub fn append_to_multislot(
+        &self,
+        index: i64,
+        slot: &str,
+        value: CLIPSValue,
+    ) -> CLIPSResult<i64> {
+        self.edit_multislot(index, slot, MultislotEdit::Append(value))
+    }
+
+    pub fn insert_into_multislot(
+        &self,
+        index: i64,
+        slot: &str,
+        position: usize,
+        value: CLIPSValue,
+    ) -> CLIPSResult<i64> {
+        self.edit_multislot(
+            index,
+            slot,
+            MultislotEdit::Insert {
+                index: position,
+                value,
+            },
+        )
+    }
+
+    pub fn remove_from_multislot(
+        &self,
+        index: i64,
+        slot: &str,
+        position: usize,
+    ) -> CLIPSResult<i64> {
+        self.edit_multislot(index, slot, MultislotEdit::Remove { index: position })
+    }
+
+    // Asserts every value in one command, which saves a round trip per fact. Each value gets its own result, in the order they were given, and a failure doesn't stop the ones after it.
+    pub fn assert_facts<T: IntoFactOrInstance<FactBuilderData> + Send + Sync + 'static>(
+        &self,
+        values: Vec<T>,
+        module: Option<String>,
+    ) -> CLIPSResult<Vec<CLIPSResult<()>>> {
+        let (res_tx, res_rx) = oneshot::channel();
+
+        self.send_command(CLIPSEnvironmentCommand::AssertFacts {
+            values: values
+                .into_iter()
+                .map(|value| Box::new(value) as Box<dyn IntoFactOrInstance<_> + Send + Sync>)
+                .collect(),
+            module,
+            res_tx,
+        })?;
+
+        res_rx.recv().map_err(|_| CLIPSError::ThreadExited)
+    }
+
+    // Records `provenance` as the tag of the asserted fact, which `fact_provenance()` gives back for as long as the fact isn't retracted. Facts derived by rules have no tag, and neither do facts asserted with the other `assert_*` methods. Modifying a fact with `modify` retracts it first, so it loses its tag. If an identical fact already existed and fact duplication is off, that fact gets the tag instead.
+    pub fn assert_fact_with_provenance<
+        T: IntoFactOrInstance<FactBuilderData> + Send + Sync + 'static,
+    >(
+        &self,
+        value: T,
+        module: Option<String>,
+        provenance: &str,
+    ) -> CLIPSResult<()> {
+        let (res_tx, res_rx) = oneshot::channel();
+
+        self.send_command(CLIPSEnvironmentCommand::AssertFactWithProvenance {
+            value: Box::new(value),
+            module,
+            provenance: provenance.to_string(),
+            res_tx,
+        })?;
+
+        res_rx.recv().map_err(|_| CLIPSError::ThreadExited)?
+    }
+
+    // Like `assert_facts()`, but every fact asserted gets `provenance` as its tag (see `assert_fact_with_provenance()`).
+    pub fn assert_facts_with_provenance<
+        T: IntoFactOrInstance<FactBuilderData> + Send + Sync + 'static,
+    >(
+        &self,
+        values: Vec<T>,
+        module: Option<String>,
+        provenance: &str,
+    ) -> CLIPSResult<Vec<CLIPSResult<()>>> {
+        let (res_tx, res_rx) = oneshot::channel();
+
+        self.send_command(CLIPSEnvironmentCommand::AssertFactsWithProvenance {
+            values: values
+                .into_iter()
+                .map(|value| Box::new(value) as Box<dyn IntoFactOrInstance<_> + Send + Sync>)
+                .collect(),
+            module,
+            provenance: provenance.to_string(),
+            res_tx,
+        })?;
+
+        res_rx.recv().map_err(|_| CLIPSError::ThreadExited)
+    }
+
+    pub fn fact_provenance(&self, index: i64) -> CLIPSResult<Option<String>> {
+        let (res_tx, res_rx) = oneshot::channel();
+
+        self.send_command(CLIPSEnvironmentCommand::FactProvenance { index, res_tx })?;
+
+        res_rx.recv().map_err(|_| CLIPSError::ThreadExited)
+    }
+
+    // Every fact tagged with `provenance` that is still asserted, in fact index order.
+    pub fn facts_with_provenance(&self, provenance: &str) -> CLIPSResult<Vec<RetrievedFact>> {
+        let (res_tx, res_rx) = oneshot::channel();
+
+        self.send_command(CLIPSEnvironmentCommand::FactsWithProvenance {
+            provenance: provenance.to_string(),
+            res_tx,
+        })?;
+
+        res_rx.recv().map_err(|_| CLIPSError::ThreadExited)?
+    }
+
+    // Every row is read and converted before anything is sent to the environment, then the facts are asserted with `assert_facts()`. `template` can be module-qualified. Rows that can't be read, converted or asserted are reported with the line they start at.
+    #[cfg(feature = "csv")]
+    pub fn assert_facts_from_csv(
+        &self,
+        template: &str,
+        reader: impl std::io::Read,
+        mapping: CsvMapping,
+    ) -> CLIPSResult<CsvImportReport> {
+        let mut report = CsvImportReport::default();
+
+        let mut lines = Vec::new();
+        let mut facts = Vec::new();
+        for (line, fact) in read_csv_rows(template, reader, &mapping)? {
+            match fact {
+                Ok(fact) => {
+                    lines.push(line);
+                    facts.push(fact);
+                }
+                Err(error) => report.failures.push(CsvRowFailure { line, error }),
             }
-            Ok(CLIPSEnvironmentCommand::Close) => {
-                log::info!("Got asked to close the CLIPS environment. Stopping the CLIPS environment task.");
-                break;
+        }
+
+        for (line, res) in lines.into_iter().zip(self.assert_facts(facts, None)?) {
+            match res {
+                Ok(()) => report.asserted += 1,
+                Err(error) => report.failures.push(CsvRowFailure { line, error }),
             }
-            Ok(CLIPSEnvironmentCommand::LoadFromStr { data, res_tx }) => res_tx
-                .send(env.load_from_str(&data))
-                .map_err(create_stub_error),
-            Ok(CLIPSEnvironmentCommand::Run { res_tx }) => {
+        }
+
+        report.failures.sort_by_key(|failure| failure.line);
+        Ok(report)
+    }
+
+    // All the facts are asserted in one command, so no rule fires in between. Every fact is attempted even if an earlier one fails, and with `rollback` the ones that were asserted are retracted again if any failed.
+    pub fn assert_fact_graph<T: IntoFactGraph>(
+        &self,
+        value: T,
+        module: Option<String>,
+        rollback: bool,
+    ) -> CLIPSResult<FactGraphReport> {
+        let (res_tx, res_rx) = oneshot::channel();
+
+        self.send_command(CLIPSEnvironmentCommand::AssertFactGraph {
+            graph: value.into_fact_graph(),
+            module,
+            rollback,
+            res_tx,
+        })?;
+
+        res_rx.recv().map_err(|_| CLIPSError::ThreadExited)
+    }
+
+    // The fact stays asserted only as long as the support for `tag` does, like a fact asserted from a rule with a `logical` pattern. `withdraw_support(tag)` retracts every fact asserted with the same tag at once. If the fact already existed without support, withdrawing the support doesn't retract it. Pending activations are left alone, only the crate's own rule is fired.
+    pub fn assert_fact_with_support<
+        T: IntoFactOrInstance<FactBuilderData> + Send + Sync + 'static,
+    >(
+        &self,
+        value: T,
+        module: Option<String>,
+        tag: &str,
+    ) -> CLIPSResult<()> {
+        let (res_tx, res_rx) = oneshot::channel();
+
+        self.send_command(CLIPSEnvironmentCommand::AssertFactWithSupport {
+            value: Box::new(value),
+            module,
+            tag: tag.to_string(),
+            res_tx,
+        })?;
+
+        res_rx.recv().map_err(|_| CLIPSError::ThreadExited)?
+    }
+
+    // Returns how many supported facts were asserted with `tag`. Withdrawing a tag that was never used isn't an error.
+    pub fn withdraw_support(&self, tag: &str) -> CLIPSResult<usize> {
+        let (res_tx, res_rx) = oneshot::channel();
+
+        self.send_command(CLIPSEnvironmentCommand::WithdrawSupport {
+            tag: tag.to_string(),
+            res_tx,
+        })?;
+
+        res_rx.recv().map_err(|_| CLIPSError::ThreadExited)?
+    }
+
+    // Fact duplication is set to `allow` only for this assert, and the previous setting is restored afterwards, whether the assert succeeded or not.
+    pub fn assert_fact_allow_duplicate<
+        T: IntoFactOrInstance<FactBuilderData> + Send + Sync + 'static,
+    >(
+        &self,
+        value: T,
+        module: Option<String>,
+        allow: bool,
+    ) -> CLIPSResult<()> {
+        let (res_tx, res_rx) = oneshot::channel();
+
+        self.send_command(CLIPSEnvironmentCommand::AssertFactAllowDuplicate {
+            value: Box::new(value),
+            module,
+            allow,
+            res_tx,
+        })?;
+
+        res_rx.recv().map_err(|_| CLIPSError::ThreadExited)?
+    }
+
+    // Puts every slot value through the fact builder, which checks them against the slot constraints, and then throws the fact away instead of asserting it. Working memory isn't touched.
+    pub fn validate_fact<T: IntoFactOrInstance<FactBuilderData> + Send + Sync + 'static>(
+        &self,
+        value: T,
+        module: Option<String>,
+    ) -> CLIPSResult<()> {
+        let (res_tx, res_rx) = oneshot::channel();
+
+        self.send_command(CLIPSEnvironmentCommand::ValidateFact {
+            value: Box::new(value),
+            module,
+            res_tx,
+        })?;
+
+        res_rx.recv().map_err(|_| CLIPSError::ThreadExited)?
+    }
+
+    // Checks every slot in `slots` against a template, or a class if there's no template by that name, and reports all the slots that wouldn't be accepted instead of stopping at the first. Nothing is asserted or made.
+    pub fn validate_slot_map(
+        &self,
+        template_or_class: &str,
+        slots: &HashMap<String, CLIPSValue>,
+    ) -> CLIPSResult<Vec<SlotMismatch>> {
+        let (res_tx, res_rx) = oneshot::channel();
+
+        self.send_command(CLIPSEnvironmentCommand::ValidateSlotMap {
+            template_or_class: template_or_class.to_string(),
+            slots: slots.clone(),
+            res_tx,
+        })?;
+
+        res_rx.recv().map_err(|_| CLIPSError::ThreadExited)?
+    }
+
+    pub fn try_assert_fact<T: IntoFactOrInstance<FactBuilderData> + Send + Sync + 'static>(
+        &self,
+        value: T,
+        module: Option<String>,
+    ) -> CLIPSResult<()> {
+        let (res_tx, res_rx) = oneshot::channel();
+
+        self.try_send_command(CLIPSEnvironmentCommand::AssertFact {
+            value: Box::new(value),
+            module,
+            res_tx,
+        })?;
+
+        res_rx.recv().map_err(|_| CLIPSError::ThreadExited)?
+    }
+
+    pub fn make_instance<T: IntoFactOrInstance<InstanceBuilderData> + Send + Sync + 'static>(
+        &self,
+        value: T,
+        instance_name: Option<String>,
+        module: Option<String>,
+    ) -> CLIPSResult<()> {
+        let (res_tx, res_rx) = oneshot::channel();
+
+        self.send_command(CLIPSEnvironmentCommand::MakeInstance {
+            value: Box::new(value),
+            instance_name,
+            module,
+            res_tx,
+        })?;
+
+        res_rx.recv().map_err(|_| CLIPSError::ThreadExited)?
+    }
+
+    // For instances whose slots are only known at runtime, e.g. from a form. Returns the name of the new instance, which is generated when `instance_name` is `None`.
+    pub fn make_instance_from_map(
+        &self,
+        class: &str,
+        instance_name: Option<String>,
+        slots: HashMap<String, CLIPSValue>,
+    ) -> CLIPSResult<String> {
+        let (res_tx, res_rx) = oneshot::channel();
+
+        self.send_command(CLIPSEnvironmentCommand::MakeInstanceFromMap {
+            class: class.to_string(),
+            instance_name,
+            slots,
+            res_tx,
+        })?;
+
+        res_rx.recv().map_err(|_| CLIPSError::ThreadExited)?
+    }
+
+    // Evaluates `(find-all-facts ((?f <template>)) <query>)`, so `query` refers to the fact being checked as `?f`, e.g. `(> ?f:age 30)`. The facts come back in fact index order.
+    pub fn find_all_facts(&self, template: &str, query: &str) -> CLIPSResult<Vec<RetrievedFact>> {
+        let (res_tx, res_rx) = oneshot::channel();
+
+        self.send_command(CLIPSEnvironmentCommand::FindAllFacts {
+            template: template.to_string(),
+            query: query.to_string(),
+            res_tx,
+        })?;
+
+        res_rx.recv().map_err(|_| CLIPSError::ThreadExited)?
+    }
+
+    // Calls `visitor` on the environment's thread for every fact of `template`, in fact index order. The facts' values are borrowed from CLIPS instead of copied, and only what `visitor` returns is kept, so this is cheaper than `find_all_facts()` when most of each fact is only looked at.
+    pub fn visit_facts<T, F>(&self, template: &str, mut visitor: F) -> CLIPSResult<Vec<T>>
+    where
+        T: Send + 'static,
+        F: FnMut(FactView<'_>) -> Option<T> + Send + 'static,
+    {
+        let (res_tx, res_rx) = oneshot::channel();
+        let kept = Arc::new(Mutex::new(Vec::new()));
+        let visitor_kept = kept.clone();
+
+        self.send_command(CLIPSEnvironmentCommand::VisitFacts {
+            template: template.to_string(),
+            visitor: Box::new(move |fact: FactView<'_>| {
+                if let Some(value) = visitor(fact) {
+                    visitor_kept.lock().unwrap().push(value);
+                }
+            }),
+            res_tx,
+        })?;
+
+        res_rx.recv().map_err(|_| CLIPSError::ThreadExited)??;
+
+        let kept = std::mem::take(&mut *kept.lock().unwrap());
+        Ok(kept)
+    }
+
+    // Retracts every fact `find_all_facts(template, query)` would return, all in one command. Returns how many facts were retracted, which doesn't count facts that were already gone by the time their turn came, e.g. because they lost their logical support when an earlier one was retracted.
+    pub fn retract_where(&self, template: &str, query: &str) -> CLIPSResult<usize> {
+        let (res_tx, res_rx) = oneshot::channel();
+
+        self.send_command(CLIPSEnvironmentCommand::RetractWhere {
+            template: template.to_string(),
+            query: query.to_string(),
+            res_tx,
+        })?;
+
+        res_rx.recv().map_err(|_| CLIPSError::ThreadExited)?
+    }
+
+    // Evaluates `(find-all-instances ((?ins <class>)) <query>)`, so `query` refers to the instance being checked as `?ins`, e.g. `(> ?ins:age 30)`. Instances of subclasses of `class` are also checked. The instances come back grouped by class, `class` first and then its subclasses, and in the order they were made within each class.
+    pub fn find_all_instances(
+        &self,
+        class: &str,
+        query: &str,
+    ) -> CLIPSResult<Vec<RetrievedInstance>> {
+        let (res_tx, res_rx) = oneshot::channel();
+
+        self.send_command(CLIPSEnvironmentCommand::FindAllInstances {
+            class: class.to_string(),
+            query: query.to_string(),
+            res_tx,
+        })?;
+
+        res_rx.recv().map_err(|_| CLIPSError::ThreadExited)?
+    }
+
+    // Instances made without a name will be named `<prefix>1`, `<prefix>2` and so on instead of getting CLIPS' `gen` names, which avoids collisions when instances are moved between environments. Instances made with an explicit name keep it.
+    pub fn set_instance_name_prefix(&self, prefix: &str) -> CLIPSResult<()> {
+        let (res_tx, res_rx) = oneshot::channel();
+
+        self.send_command(CLIPSEnvironmentCommand::SetInstanceNamePrefix {
+            prefix: prefix.to_string(),
+            res_tx,
+        })?;
+
+        res_rx.recv().map_err(|_| CLIPSError::ThreadExited)
+    }
+
+    // Calls `(gensym)`, taking the next symbol from the sequence rules use. Like in CLIPS, the symbol may already be in use, so `gensym_star()` is the one to use when the symbol must be new.
+    pub fn gensym(&self) -> CLIPSResult<String> {
+        let (res_tx, res_rx) = oneshot::channel();
+
+        self.send_command(CLIPSEnvironmentCommand::Gensym { res_tx })?;
+
+        res_rx.recv().map_err(|_| CLIPSError::ThreadExited)?
+    }
+
+    // Calls `(gensym*)`, which skips the symbols of the sequence that are already in use. Symbols from here and from rules calling `gensym` or `gensym*` never repeat each other.
+    pub fn gensym_star(&self) -> CLIPSResult<String> {
+        let (res_tx, res_rx) = oneshot::channel();
+
+        self.send_command(CLIPSEnvironmentCommand::GensymStar { res_tx })?;
+
+        res_rx.recv().map_err(|_| CLIPSError::ThreadExited)?
+    }
+
+    // Calls `(format nil <fmt> <args>...)`, so the text is exactly what a rule calling `format` with the same values would produce, float formatting included.
+    pub fn format(&self, fmt: &str, args: Vec<CLIPSValue>) -> CLIPSResult<String> {
+        let (res_tx, res_rx) = oneshot::channel();
+
+        self.send_command(CLIPSEnvironmentCommand::Format {
+            fmt: fmt.to_string(),
+            args,
+            res_tx,
+        })?;
+
+        res_rx.recv().map_err(|_| CLIPSError::ThreadExited)?
+    }
+
+    pub fn set_dynamic_constraint_checking(&self, value: bool) -> CLIPSResult<()> {
+        let (res_tx, res_rx) = oneshot::channel();
+
+        self.send_command(CLIPSEnvironmentCommand::SetDynamicConstraintChecking { value, res_tx })?;
+
+        Ok(res_rx.recv().map_err(|_| CLIPSError::ThreadExited)?)
+    }
+
+    // With this on, a multifield variable given to a function with `$?`, e.g. `(+ $?numbers)`, is expanded into one argument per field instead of being passed as a single multifield. It's off by default, and only affects constructs and expressions parsed after it's changed.
+    pub fn set_sequence_operator_recognition(&self, value: bool) -> CLIPSResult<()> {
+        let (res_tx, res_rx) = oneshot::channel();
+
+        self.send_command(CLIPSEnvironmentCommand::SetSequenceOperatorRecognition {
+            value,
+            res_tx,
+        })?;
+
+        res_rx.recv().map_err(|_| CLIPSError::ThreadExited)
+    }
+
+    pub fn get_sequence_operator_recognition(&self) -> CLIPSResult<bool> {
+        let (res_tx, res_rx) = oneshot::channel();
+
+        self.send_command(CLIPSEnvironmentCommand::GetSequenceOperatorRecognition { res_tx })?;
+
+        res_rx.recv().map_err(|_| CLIPSError::ThreadExited)
+    }
+
+    // Shared application state for UDFs, which get it back with `UDFData::user_data()` or `UDFData::user_data_mut()` instead of every closure capturing its own copy. The environment owns the value and drops it when it's destroyed. There's a single slot, so setting it again replaces the previous data.
+    pub fn set_user_data<T: Any + Send>(&self, value: T) -> CLIPSResult<()> {
+        let (res_tx, res_rx) = oneshot::channel();
+
+        self.send_command(CLIPSEnvironmentCommand::SetUserData {
+            data: Box::new(value),
+            res_tx,
+        })?;
+
+        res_rx.recv().map_err(|_| CLIPSError::ThreadExited)
+    }
+
+    pub fn set_value_policy(&self, policy: ValueExtractionPolicy) -> CLIPSResult<()> {
+        let (res_tx, res_rx) = oneshot::channel();
+
+        self.send_command(CLIPSEnvironmentCommand::SetValuePolicy { policy, res_tx })?;
+
+        res_rx.recv().map_err(|_| CLIPSError::ThreadExited)
+    }
+
+    pub fn value_policy(&self) -> CLIPSResult<ValueExtractionPolicy> {
+        let (res_tx, res_rx) = oneshot::channel();
+
+        self.send_command(CLIPSEnvironmentCommand::GetValuePolicy { res_tx })?;
+
+        res_rx.recv().map_err(|_| CLIPSError::ThreadExited)
+    }
+
+    // CLIPS has no boolean type, `TRUE` and `FALSE` are ordinary symbols. With this on (the default), values read from this environment turn them into `CLIPSValue::Bool`. Turn it off for knowledge bases that use them as plain symbols, so they come back as `CLIPSValue::Symbol`. This is a shortcut for `ValueExtractionPolicy::booleans_as_symbols`.
+    pub fn set_treat_boolean_symbols_as_bool(&self, value: bool) -> CLIPSResult<()> {
+        let (res_tx, res_rx) = oneshot::channel();
+
+        self.send_command(CLIPSEnvironmentCommand::SetTreatBooleanSymbolsAsBool { value, res_tx })?;
+
+        res_rx.recv().map_err(|_| CLIPSError::ThreadExited)
+    }
+
+    pub fn set_conflict_resolution_strategy(
+        &self,
+        value: ConflictResolutionStrategy,
+    ) -> CLIPSResult<()> {
+        let (res_tx, res_rx) = oneshot::channel();
+
+        self.send_command(CLIPSEnvironmentCommand::SetConflictResolutionStrategy {
+            value,
+            res_tx,
+        })?;
+
+        Ok(res_rx.recv().map_err(|_| CLIPSError::ThreadExited)?)
+    }
+
+    // The rule names of the current module's activations, in the order they would fire under `strategy`. Nothing is fired, and the current strategy and agenda order are left as they were.
+    pub fn agenda_order_preview(
+        &self,
+        strategy: ConflictResolutionStrategy,
+    ) -> CLIPSResult<Vec<String>> {
+        let (res_tx, res_rx) = oneshot::channel();
+
+        self.send_command(CLIPSEnvironmentCommand::AgendaOrderPreview { strategy, res_tx })?;
+
+        res_rx.recv().map_err(|_| CLIPSError::ThreadExited)?
+    }
+
+    // Forgets the file name and line number left behind by earlier loads. `load_from_str` and `load_from_str_atomic` already do this before they start, so it's only needed before loading through CLIPS code, e.g. with `eval`.
+    pub fn reset_parser_state(&self) -> CLIPSResult<()> {
+        let (res_tx, res_rx) = oneshot::channel();
+
+        self.send_command(CLIPSEnvironmentCommand::ResetParserState { res_tx })?;
+
+        res_rx.recv().map_err(|_| CLIPSError::ThreadExited)
+    }
+
+    // CLIPS can only `bsave` the whole environment, so this writes the constructs of `module` as text instead, which can be loaded back with `batch_star` or `load_from_str`. Any modules `module` imports from must already exist where it's loaded. Returns how many constructs were written.
+    pub fn save_module(&self, module: &str, path: PathBuf) -> CLIPSResult<usize> {
+        let (res_tx, res_rx) = oneshot::channel();
+
+        self.send_command(CLIPSEnvironmentCommand::SaveModule {
+            module: module.to_string(),
+            path,
+            res_tx,
+        })?;
+
+        res_rx.recv().map_err(|_| CLIPSError::ThreadExited)?
+    }
+
+    pub fn binary_save_facts(&self, path: PathBuf) -> CLIPSResult<usize> {
+        let (res_tx, res_rx) = oneshot::channel();
+
+        self.send_command(CLIPSEnvironmentCommand::BinarySaveFacts { path, res_tx })?;
+
+        res_rx.recv().map_err(|_| CLIPSError::ThreadExited)?
+    }
+
+    pub fn binary_load_facts(&self, path: PathBuf) -> CLIPSResult<usize> {
+        let (res_tx, res_rx) = oneshot::channel();
+
+        self.send_command(CLIPSEnvironmentCommand::BinaryLoadFacts { path, res_tx })?;
+
+        res_rx.recv().map_err(|_| CLIPSError::ThreadExited)?
+    }
+
+    // Like `binary_load_facts`, but from the bytes `binary_save_facts` wrote. Passing an `Arc<[u8]>` lets the same facts be loaded into many environments without copying them for each one.
+    pub fn binary_load_facts_from_bytes(&self, data: impl Into<Arc<[u8]>>) -> CLIPSResult<usize> {
+        let (res_tx, res_rx) = oneshot::channel();
+
+        self.send_command(CLIPSEnvironmentCommand::BinaryLoadFactsFromBytes {
+            data: data.into(),
+            res_tx,
+        })?;
+
+        res_rx.recv().map_err(|_| CLIPSError::ThreadExited)?
+    }
+
+    pub fn binary_save_instances(&self, path: PathBuf) -> CLIPSResult<usize> {
+        let (res_tx, res_rx) = oneshot::channel();
+
+        self.send_command(CLIPSEnvironmentCommand::BinarySaveInstances { path, res_tx })?;
+
+        res_rx.recv().map_err(|_| CLIPSError::ThreadExited)?
+    }
+
+    pub fn binary_load_instances(&self, path: PathBuf) -> CLIPSResult<usize> {
+        let (res_tx, res_rx) = oneshot::channel();
+
+        self.send_command(CLIPSEnvironmentCommand::BinaryLoadInstances { path, res_tx })?;
+
+        res_rx.recv().map_err(|_| CLIPSError::ThreadExited)?
+    }
+
+    // Like `binary_load_instances`, but from the bytes `binary_save_instances` wrote.
+    pub fn binary_load_instances_from_bytes(
+        &self,
+        data: impl Into<Arc<[u8]>>,
+    ) -> CLIPSResult<usize> {
+        let (res_tx, res_rx) = oneshot::channel();
+
+        self.send_command(CLIPSEnvironmentCommand::BinaryLoadInstancesFromBytes {
+            data: data.into(),
+            res_tx,
+        })?;
+
+        res_rx.recv().map_err(|_| CLIPSError::ThreadExited)?
+    }
+
+    // Saves the facts, instances and global values under `name`, replacing any savepoint with the same name, so they can be brought back with `rollback_to`.
+    pub fn savepoint(&self, name: &str) -> CLIPSResult<()> {
+        let (res_tx, res_rx) = oneshot::channel();
+
+        self.send_command(CLIPSEnvironmentCommand::Savepoint {
+            name: name.to_string(),
+            res_tx,
+        })?;
+
+        res_rx.recv().map_err(|_| CLIPSError::ThreadExited)?
+    }
+
+    // Replaces every fact and instance with the ones saved under `name`, and sets the globals back to their saved values. The savepoint is kept, so it can be rolled back to again. The constructs must be the same ones, with the same bodies, as when the savepoint was made. If the saved facts or instances can't be loaded back in, the ones from before the rollback are put back and the error is returned. Restored facts get new fact indices, and rules that already fired on them can fire again.
+    pub fn rollback_to(&self, name: &str) -> CLIPSResult<()> {
+        let (res_tx, res_rx) = oneshot::channel();
+
+        self.send_command(CLIPSEnvironmentCommand::RollbackTo {
+            name: name.to_string(),
+            res_tx,
+        })?;
+
+        res_rx.recv().map_err(|_| CLIPSError::ThreadExited)?
+    }
+
+    // Returns whether there was a savepoint with that name.
+    pub fn release_savepoint(&self, name: &str) -> CLIPSResult<bool> {
+        let (res_tx, res_rx) = oneshot::channel();
+
+        self.send_command(CLIPSEnvironmentCommand::ReleaseSavepoint {
+            name: name.to_string(),
+            res_tx,
+        })?;
+
+        res_rx.recv().map_err(|_| CLIPSError::ThreadExited)
+    }
+
+    pub fn retrieve_globals_values(&self) -> CLIPSResult<CLIPSGlobalsHierarchy> {
+        let (res_tx, res_rx) = oneshot::channel();
+
+        self.send_command(CLIPSEnvironmentCommand::RetrieveGlobalsValues { res_tx })?;
+
+        res_rx.recv().map_err(|_| CLIPSError::ThreadExited)?
+    }
+
+    // Use this instead of `retrieve_globals_values()` when the globals are compared or saved as text, e.g. in golden tests.
+    pub fn retrieve_ordered_globals_values(&self) -> CLIPSResult<OrderedGlobalsHierarchy> {
+        self.retrieve_globals_values().map(Into::into)
+    }
+
+    // Useful to move values retrieved from another environment into this one (see `CLIPSValue::clone_detached`).
+    pub fn reinsert_value(&self, target: ValueTarget, value: CLIPSValue) -> CLIPSResult<()> {
+        let (res_tx, res_rx) = oneshot::channel();
+
+        self.send_command(CLIPSEnvironmentCommand::ReinsertValue {
+            target,
+            value,
+            res_tx,
+        })?;
+
+        res_rx.recv().map_err(|_| CLIPSError::ThreadExited)?
+    }
+
+    // Takes either a `CLIPSGlobalsHierarchy` or an `OrderedGlobalsHierarchy`.
+    pub fn restore_globals(&self, globals: impl Into<CLIPSGlobalsHierarchy>) -> CLIPSResult<()> {
+        let (res_tx, res_rx) = oneshot::channel();
+
+        self.send_command(CLIPSEnvironmentCommand::RestoreGlobals {
+            globals: globals.into(),
+            res_tx,
+        })?;
+
+        res_rx.recv().map_err(|_| CLIPSError::ThreadExited)?
+    }
+
+    pub fn check_constraints(&self) -> CLIPSResult<Vec<ConstraintViolation>> {
+        let (res_tx, res_rx) = oneshot::channel();
+
+        self.send_command(CLIPSEnvironmentCommand::CheckConstraints { res_tx })?;
+
+        res_rx.recv().map_err(|_| CLIPSError::ThreadExited)?
+    }
+
+    pub fn class_info(&self, class: &str) -> CLIPSResult<ClassInfo> {
+        let (res_tx, res_rx) = oneshot::channel();
+
+        self.send_command(CLIPSEnvironmentCommand::GetClassInfo {
+            class: class.to_string(),
+            res_tx,
+        })?;
+
+        res_rx.recv().map_err(|_| CLIPSError::ThreadExited)?
+    }
+
+    // Writes every instance as a JSON object with its name, class and slots, one per line, and returns how many were written. The instances are sent over from the CLIPS thread a few at a time as they're written, so the whole store is never held in memory at once. They're all read in a single command, so no changes happen in the middle of the export.
+    #[cfg(feature = "json")]
+    pub fn export_instances_jsonl<W: std::io::Write>(&self, mut writer: W) -> CLIPSResult<usize> {
+        let (tx, rx) = mpsc::sync_channel(EXPORT_CHANNEL_CAPACITY);
+
+        self.send_command(CLIPSEnvironmentCommand::StreamInstances { tx })?;
+
+        let mut written = 0;
+        // Returning early drops the receiver, which makes the CLIPS thread stop sending.
+        for instance in rx {
+            json_export::write_instance_jsonl(&mut writer, &instance?)?;
+            written += 1;
+        }
+
+        writer.flush()?;
+        Ok(written)
+    }
+
+    // Writes every fact and instance as an `(assert ...)` or `(make-instance ...)` command, one per line. The output can be written to a file and replayed with `batch_star()` into an environment with the same constructs, as long as they're visible from its current module. Facts get new indexes when they're replayed. Like `export_instances_jsonl()`, everything is read in a single command and sent over a few entries at a time.
+    pub fn dump_working_memory<W: std::io::Write>(&self, mut writer: W) -> CLIPSResult<DumpStats> {
+        let (tx, rx) = mpsc::sync_channel(EXPORT_CHANNEL_CAPACITY);
+
+        self.send_command(CLIPSEnvironmentCommand::StreamWorkingMemory { tx })?;
+
+        let mut stats = DumpStats::default();
+        // Returning early drops the receiver, which makes the CLIPS thread stop sending.
+        for entry in rx {
+            match entry? {
+                WorkingMemoryEntry::Fact(fact) => {
+                    write_assert_command(&mut writer, &fact)?;
+                    stats.facts += 1;
+                }
+                WorkingMemoryEntry::Instance(instance) => {
+                    write_make_instance_command(&mut writer, &instance)?;
+                    stats.instances += 1;
+                }
+            }
+        }
+
+        writer.flush()?;
+        Ok(stats)
+    }
+
+    // Reads instances written by `export_instances_jsonl()`, making one instance per line, and returns how many were made. Blank lines are skipped. Stops at the first line that can't be read or made into an instance, and reports its line number (starting at 1) with the error. The instances made before that line are kept.
+    #[cfg(feature = "json")]
+    pub fn import_instances_jsonl<R: std::io::Read>(&self, reader: R) -> CLIPSResult<usize> {
+        use std::io::BufRead;
+
+        let mut made = 0;
+        for (index, line) in std::io::BufReader::new(reader).lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            json_export::read_instance_jsonl(&line)
+                .and_then(|(name, instance)| self.make_instance(instance, Some(name), None))
+                .map_err(|err| CLIPSError::JsonLineImport {
+                    line: index + 1,
+                    source: Box::new(err),
+                })?;
+            made += 1;
+        }
+
+        Ok(made)
+    }
+
+    pub fn construct_summary(&self) -> CLIPSResult<ConstructSummary> {
+        let (res_tx, res_rx) = oneshot::channel();
+
+        self.send_command(CLIPSEnvironmentCommand::ConstructSummary { res_tx })?;
+
+        res_rx.recv().map_err(|_| CLIPSError::ThreadExited)?
+    }
+
+    pub fn symbol_stats(&self) -> CLIPSResult<SymbolStats> {
+        self.symbol_stats_with_top(0)
+    }
+
+    pub fn constructs_fingerprint(&self) -> CLIPSResult<u64> {
+        let (res_tx, res_rx) = oneshot::channel();
+
+        self.send_command(CLIPSEnvironmentCommand::ConstructsFingerprint { res_tx })?;
+
+        res_rx.recv().map_err(|_| CLIPSError::ThreadExited)
+    }
+
+    // Also lists the `top` longest and the `top` most referenced lexemes.
+    pub fn symbol_stats_with_top(&self, top: usize) -> CLIPSResult<SymbolStats> {
+        let (res_tx, res_rx) = oneshot::channel();
+
+        self.send_command(CLIPSEnvironmentCommand::SymbolStats { top, res_tx })?;
+
+        res_rx.recv().map_err(|_| CLIPSError::ThreadExited)?
+    }
+
+    pub fn template_info(&self, template: &str) -> CLIPSResult<TemplateInfo> {
+        let (res_tx, res_rx) = oneshot::channel();
+
+        self.send_command(CLIPSEnvironmentCommand::GetTemplateInfo {
+            template: template.to_string(),
+            res_tx,
+        })?;
+
+        res_rx.recv().map_err(|_| CLIPSError::ThreadExited)?
+    }
+
+    // A JSON Schema document describing the facts of the template, built from `template_info()`.
+    #[cfg(feature = "json")]
+    pub fn template_schema(&self, template: &str) -> CLIPSResult<serde_json::Value> {
+        self.template_info(template)
+            .map(|info| json_schema::template_schema(&info))
+    }
+
+    // The values a slot is restricted to by `allowed-values` or any of the `allowed-` attributes for a single type, e.g. `allowed-symbols`. `None` if the slot isn't restricted.
+    pub fn slot_allowed_values(
+        &self,
+        template: &str,
+        slot: &str,
+    ) -> CLIPSResult<Option<Vec<CLIPSValue>>> {
+        self.template_info(template)?
+            .slots
+            .into_iter()
+            .find(|slot_info| slot_info.name == slot)
+            .map(|slot_info| slot_info.allowed_values)
+            .ok_or(CLIPSError::SlotNotFound)
+    }
+
+    // Replaces every construct in `target` with the ones in this environment, which is quicker than parsing the same source again. Facts and instances in `target` are lost. If the binary image is used, `target` ends up like after a `bload`: its constructs can't be changed until it's cleared.
+    pub fn clone_constructs_into(&self, target: &Environment) -> CLIPSResult<ConstructTransfer> {
+        let (res_tx, res_rx) = oneshot::channel();
+
+        self.send_command(CLIPSEnvironmentCommand::ExportConstructs { res_tx })?;
+
+        let export = res_rx.recv().map_err(|_| CLIPSError::ThreadExited)?;
+        let (res_tx, res_rx) = oneshot::channel();
+
+        target.send_command(CLIPSEnvironmentCommand::ImportConstructs { export, res_tx })?;
+
+        res_rx.recv().map_err(|_| CLIPSError::ThreadExited)
+    }
+
+    pub fn rule_info(&self, rule: &str) -> CLIPSResult<RuleInfo> {
+        let (res_tx, res_rx) = oneshot::channel();
+
+        self.send_command(CLIPSEnvironmentCommand::RuleInfo {
+            rule: rule.to_string(),
+            res_tx,
+        })?;
+
+        res_rx.recv().map_err(|_| CLIPSError::ThreadExited)?
+    }
+
+    pub fn rule_complexity(&self, rule: &str) -> CLIPSResult<u32> {
+        self.rule_info(rule).map(|info| info.complexity)
+    }
+
+    // How many partial matches each join of the rule holds, the same counts `(matches <rule> succinct)` prints for its CEs, in the same order. The first CE is left out, since its matches are the pattern's own. A rule with `or` is made of several rules, whose counts come one after the other.
+    pub fn rule_partial_matches(&self, rule: &str) -> CLIPSResult<Vec<usize>> {
+        let (res_tx, res_rx) = oneshot::channel();
+
+        self.send_command(CLIPSEnvironmentCommand::RulePartialMatches {
+            rule: rule.to_string(),
+            res_tx,
+        })?;
+
+        res_rx.recv().map_err(|_| CLIPSError::ThreadExited)?
+    }
+
+    pub fn list_handlers(&self, class: &str) -> CLIPSResult<Vec<MessageHandlerInfo>> {
+        let (res_tx, res_rx) = oneshot::channel();
+
+        self.send_command(CLIPSEnvironmentCommand::ListHandlers {
+            class: class.to_string(),
+            res_tx,
+        })?;
+
+        res_rx.recv().map_err(|_| CLIPSError::ThreadExited)?
+    }
+}
+
+enum CLIPSEnvironmentCommand {
+    LoadFromStr {
+        data: Arc<str>,
+        res_tx: oneshot::Sender<CLIPSResult<()>>,
+    },
+    LoadFromStrAtomic {
+        data: Arc<str>,
+        res_tx: oneshot::Sender<CLIPSResult<()>>,
+    },
+    LoadIntoModule {
+        module: String,
+        data: Arc<str>,
+        res_tx: oneshot::Sender<CLIPSResult<()>>,
+    },
+    UnloadModule {
+        module: String,
+        res_tx: oneshot::Sender<CLIPSResult<usize>>,
+    },
+    BatchStar {
+        file_path: PathBuf,
+        res_tx: oneshot::Sender<CLIPSResult<()>>,
+    },
+    LoadFromStrWithReport {
+        data: Arc<str>,
+        res_tx: oneshot::Sender<CLIPSResult<LoadReport>>,
+    },
+    BatchStarWithReport {
+        file_path: PathBuf,
+        res_tx: oneshot::Sender<CLIPSResult<LoadReport>>,
+    },
+    Run {
+        res_tx: oneshot::Sender<CLIPSResult<usize>>,
+    },
+    RunDetailed {
+        res_tx: oneshot::Sender<CLIPSResult<RunOutcome>>,
+    },
+    GetLastFiredRule {
+        res_tx: oneshot::Sender<Option<String>>,
+    },
+    RefreshAgenda {
+        res_tx: oneshot::Sender<CLIPSResult<()>>,
+    },
+    Agenda {
+        res_tx: oneshot::Sender<CLIPSResult<Vec<ActivationInfo>>>,
+    },
+    AgendaPP {
+        res_tx: oneshot::Sender<CLIPSResult<String>>,
+    },
+    RunLimit {
+        limit: usize,
+        res_tx: oneshot::Sender<CLIPSResult<usize>>,
+    },
+    SetAgendaChangedCallback {
+        callback: Option<AgendaChangedCallback>,
+        res_tx: oneshot::Sender<CLIPSResult<()>>,
+    },
+    SetWatchdog {
+        config: Option<WatchdogConfig>,
+        res_tx: oneshot::Sender<()>,
+    },
+    GetLastWatchdogReport {
+        res_tx: oneshot::Sender<Option<WatchdogReport>>,
+    },
+    RunCapped {
+        max_firings: usize,
+        res_tx: oneshot::Sender<CLIPSResult<usize>>,
+    },
+    Stats {
+        res_tx: oneshot::Sender<EnvStats>,
+    },
+    LastErrorText {
+        res_tx: oneshot::Sender<Option<String>>,
+    },
+    RecentErrorTexts {
+        res_tx: oneshot::Sender<Vec<String>>,
+    },
+    RuleFireCounts {
+        res_tx: oneshot::Sender<CLIPSResult<HashMap<String, u64>>>,
+    },
+    ResetFireCounts {
+        res_tx: oneshot::Sender<()>,
+    },
+    RunFor {
+        budget: Duration,
+        res_tx: oneshot::Sender<CLIPSResult<usize>>,
+    },
+    RunWatchingWm {
+        tx: mpsc::Sender<WmChange>,
+        res_tx: oneshot::Sender<CLIPSResult<usize>>,
+    },
+    RunRecordingFiredRules {
+        res_tx: oneshot::Sender<CLIPSResult<Vec<String>>>,
+    },
+    RunConsume {
+        result_template: String,
+        res_tx: oneshot::Sender<CLIPSResult<Vec<RetrievedFact>>>,
+    },
+    ChDir {
+        new_dir: PathBuf,
+        res_tx: oneshot::Sender<CLIPSResult<()>>,
+    },
+    AddUDF {
+        signature: UDFSignature,
+        function: Box<dyn FnMut(UDFData) + Send + Sync>,
+        res_tx: oneshot::Sender<CLIPSResult<()>>,
+    },
+    ListUDFs {
+        res_tx: oneshot::Sender<Vec<UDFSignature>>,
+    },
+    AddGenericMethod {
+        generic: String,
+        restrictions: Vec<String>,
+        function: Box<dyn FnMut(UDFData) + Send + Sync>,
+        res_tx: oneshot::Sender<CLIPSResult<()>>,
+    },
+    AddRouter {
+        name: String,
+        priority: i32,
+        router: RegisterableRouter,
+        res_tx: oneshot::Sender<CLIPSResult<()>>,
+    },
+    #[cfg(feature = "tracing")]
+    InstallTracingBridge {
+        res_tx: oneshot::Sender<CLIPSResult<()>>,
+    },
+    RemoveUDF {
+        name: String,
+        res_tx: oneshot::Sender<bool>,
+    },
+    AssertFact {
+        value: Box<dyn IntoFactOrInstance<FactBuilderData> + Send + Sync>,
+        module: Option<String>,
+        res_tx: oneshot::Sender<CLIPSResult<()>>,
+    },
+    AssertFactAllowDuplicate {
+        value: Box<dyn IntoFactOrInstance<FactBuilderData> + Send + Sync>,
+        module: Option<String>,
+        allow: bool,
+        res_tx: oneshot::Sender<CLIPSResult<()>>,
+    },
+    ValidateFact {
+        value: Box<dyn IntoFactOrInstance<FactBuilderData> + Send + Sync>,
+        module: Option<String>,
+        res_tx: oneshot::Sender<CLIPSResult<()>>,
+    },
+    ValidateSlotMap {
+        template_or_class: String,
+        slots: HashMap<String, CLIPSValue>,
+        res_tx: oneshot::Sender<CLIPSResult<Vec<SlotMismatch>>>,
+    },
+    AssertOrdered {
+        head: String,
+        values: Vec<CLIPSValue>,
+        res_tx: oneshot::Sender<CLIPSResult<i64>>,
+    },
+    AssertString {
+        fact: String,
+        res_tx: oneshot::Sender<CLIPSResult<i64>>,
+    },
+    DuplicateFact {
+        index: i64,
+        overrides: HashMap<String, CLIPSValue>,
+        res_tx: oneshot::Sender<CLIPSResult<i64>>,
+    },
+    EditMultislot {
+        index: i64,
+        slot: String,
+        edit: MultislotEdit,
+        res_tx: oneshot::Sender<CLIPSResult<i64>>,
+    },
+    AssertFacts {
+        values: Vec<Box<dyn IntoFactOrInstance<FactBuilderData> + Send + Sync>>,
+        module: Option<String>,
+        res_tx: oneshot::Sender<Vec<CLIPSResult<()>>>,
+    },
+    AssertFactWithProvenance {
+        value: Box<dyn IntoFactOrInstance<FactBuilderData> + Send + Sync>,
+        module: Option<String>,
+        provenance: String,
+        res_tx: oneshot::Sender<CLIPSResult<()>>,
+    },
+    AssertFactsWithProvenance {
+        values: Vec<Box<dyn IntoFactOrInstance<FactBuilderData> + Send + Sync>>,
+        module: Option<String>,
+        provenance: String,
+        res_tx: oneshot::Sender<Vec<CLIPSResult<()>>>,
+    },
+    FactProvenance {
+        index: i64,
+        res_tx: oneshot::Sender<Option<String>>,
+    },
+    FactsWithProvenance {
+        provenance: String,
+        res_tx: oneshot::Sender<CLIPSResult<Vec<RetrievedFact>>>,
+    },
+    AssertFactGraph {
+        graph: FactGraph,
+        module: Option<String>,
+        rollback: bool,
+        res_tx: oneshot::Sender<FactGraphReport>,
+    },
+    AssertFactWithSupport {
+        value: SupportedPayload,
+        module: Option<String>,
+        tag: String,
+        res_tx: oneshot::Sender<CLIPSResult<()>>,
+    },
+    WithdrawSupport {
+        tag: String,
+        res_tx: oneshot::Sender<CLIPSResult<usize>>,
+    },
+    MakeInstance {
+        value: Box<dyn IntoFactOrInstance<InstanceBuilderData> + Send + Sync>,
+        instance_name: Option<String>,
+        module: Option<String>,
+        res_tx: oneshot::Sender<CLIPSResult<()>>,
+    },
+    MakeInstanceFromMap {
+        class: String,
+        instance_name: Option<String>,
+        slots: HashMap<String, CLIPSValue>,
+        res_tx: oneshot::Sender<CLIPSResult<String>>,
+    },
+    RetractWhere {
+        template: String,
+        query: String,
+        res_tx: oneshot::Sender<CLIPSResult<usize>>,
+    },
+    FindAllFacts {
+        template: String,
+        query: String,
+        res_tx: oneshot::Sender<CLIPSResult<Vec<RetrievedFact>>>,
+    },
+    VisitFacts {
+        template: String,
+        visitor: FactVisitor,
+        res_tx: oneshot::Sender<CLIPSResult<()>>,
+    },
+    FindAllInstances {
+        class: String,
+        query: String,
+        res_tx: oneshot::Sender<CLIPSResult<Vec<RetrievedInstance>>>,
+    },
+    SetInstanceNamePrefix {
+        prefix: String,
+        res_tx: oneshot::Sender<()>,
+    },
+    Gensym {
+        res_tx: oneshot::Sender<CLIPSResult<String>>,
+    },
+    GensymStar {
+        res_tx: oneshot::Sender<CLIPSResult<String>>,
+    },
+    Format {
+        fmt: String,
+        args: Vec<CLIPSValue>,
+        res_tx: oneshot::Sender<CLIPSResult<String>>,
+    },
+    SetDynamicConstraintChecking {
+        value: bool,
+        res_tx: oneshot::Sender<()>,
+    },
+    SetSequenceOperatorRecognition {
+        value: bool,
+        res_tx: oneshot::Sender<()>,
+    },
+    GetSequenceOperatorRecognition {
+        res_tx: oneshot::Sender<bool>,
+    },
+    SetUserData {
+        data: Box<dyn Any + Send>,
+        res_tx: oneshot::Sender<()>,
+    },
+    SetValuePolicy {
+        policy: ValueExtractionPolicy,
+        res_tx: oneshot::Sender<()>,
+    },
+    GetValuePolicy {
+        res_tx: oneshot::Sender<ValueExtractionPolicy>,
+    },
+    SetTreatBooleanSymbolsAsBool {
+        value: bool,
+        res_tx: oneshot::Sender<()>,
+    },
+    AgendaOrderPreview {
+        strategy: ConflictResolutionStrategy,
+        res_tx: oneshot::Sender<CLIPSResult<Vec<String>>>,
+    },
+    SetConflictResolutionStrategy {
+        value: ConflictResolutionStrategy,
+        res_tx: oneshot::Sender<()>,
+    },
+    ResetParserState {
+        res_tx: oneshot::Sender<()>,
+    },
+    WatchSlot {
+        template: String,
+        slot: String,
+        tx: mpsc::Sender<SlotChange>,
+        res_tx: oneshot::Sender<CLIPSResult<SlotWatchId>>,
+    },
+    UnwatchSlot {
+        id: SlotWatchId,
+        res_tx: oneshot::Sender<bool>,
+    },
+    OnConstructDefined {
+        callback: ConstructCallback,
+        res_tx: oneshot::Sender<()>,
+    },
+    SaveModule {
+        module: String,
+        path: PathBuf,
+        res_tx: oneshot::Sender<CLIPSResult<usize>>,
+    },
+    BinarySaveFacts {
+        path: PathBuf,
+        res_tx: oneshot::Sender<CLIPSResult<usize>>,
+    },
+    BinaryLoadFacts {
+        path: PathBuf,
+        res_tx: oneshot::Sender<CLIPSResult<usize>>,
+    },
+    BinaryLoadFactsFromBytes {
+        data: Arc<[u8]>,
+        res_tx: oneshot::Sender<CLIPSResult<usize>>,
+    },
+    BinarySaveInstances {
+        path: PathBuf,
+        res_tx: oneshot::Sender<CLIPSResult<usize>>,
+    },
+    BinaryLoadInstances {
+        path: PathBuf,
+        res_tx: oneshot::Sender<CLIPSResult<usize>>,
+    },
+    BinaryLoadInstancesFromBytes {
+        data: Arc<[u8]>,
+        res_tx: oneshot::Sender<CLIPSResult<usize>>,
+    },
+    Savepoint {
+        name: String,
+        res_tx: oneshot::Sender<CLIPSResult<()>>,
+    },
+    RollbackTo {
+        name: String,
+        res_tx: oneshot::Sender<CLIPSResult<()>>,
+    },
+    ReleaseSavepoint {
+        name: String,
+        res_tx: oneshot::Sender<bool>,
+    },
+    RetrieveGlobalsValues {
+        res_tx: oneshot::Sender<CLIPSResult<CLIPSGlobalsHierarchy>>,
+    },
+    ReinsertValue {
+        target: ValueTarget,
+        value: CLIPSValue,
+        res_tx: oneshot::Sender<CLIPSResult<()>>,
+    },
+    RestoreGlobals {
+        globals: CLIPSGlobalsHierarchy,
+        res_tx: oneshot::Sender<CLIPSResult<()>>,
+    },
+    CheckConstraints {
+        res_tx: oneshot::Sender<CLIPSResult<Vec<ConstraintViolation>>>,
+    },
+    // The channel is closed once every instance was sent.
+    #[cfg(feature = "json")]
+    StreamInstances {
+        tx: mpsc::SyncSender<CLIPSResult<RetrievedInstance>>,
+    },
+    StreamWorkingMemory {
+        tx: mpsc::SyncSender<CLIPSResult<WorkingMemoryEntry>>,
+    },
+    ExportConstructs {
+        res_tx: oneshot::Sender<ConstructExport>,
+    },
+    ImportConstructs {
+        export: ConstructExport,
+        res_tx: oneshot::Sender<ConstructTransfer>,
+    },
+    ConstructSummary {
+        res_tx: oneshot::Sender<CLIPSResult<ConstructSummary>>,
+    },
+    ConstructsFingerprint {
+        res_tx: oneshot::Sender<u64>,
+    },
+    SymbolStats {
+        top: usize,
+        res_tx: oneshot::Sender<CLIPSResult<SymbolStats>>,
+    },
+    GetTemplateInfo {
+        template: String,
+        res_tx: oneshot::Sender<CLIPSResult<TemplateInfo>>,
+    },
+    GetClassInfo {
+        class: String,
+        res_tx: oneshot::Sender<CLIPSResult<ClassInfo>>,
+    },
+    RuleInfo {
+        rule: String,
+        res_tx: oneshot::Sender<CLIPSResult<RuleInfo>>,
+    },
+    RulePartialMatches {
+        rule: String,
+        res_tx: oneshot::Sender<CLIPSResult<Vec<usize>>>,
+    },
+    ListHandlers {
+        class: String,
+        res_tx: oneshot::Sender<CLIPSResult<Vec<MessageHandlerInfo>>>,
+    },
+    Close,
+    #[cfg(feature = "test-util")]
+    Poison,
+    Ping {
+        res_tx: oneshot::Sender<()>,
+    },
+}
+
+type BinarySaveFn = unsafe extern "C" fn(
+    *mut clips_sys::Environment,
+    *const c_char,
+    clips_sys::SaveScope,
+) -> c_long;
+type BinaryLoadFn = unsafe extern "C" fn(*mut clips_sys::Environment, *const c_char) -> c_long;
+
+fn binary_save_to_memory(
+    env: *mut clips_sys::Environment,
+    path: &Path,
+    save: BinarySaveFn,
+    error: CLIPSError,
+) -> CLIPSResult<Vec<u8>> {
+    let path_cstr = CString::new(path.as_os_str().as_encoded_bytes()).unwrap();
+    let res = unsafe { save(env, path_cstr.as_ptr(), clips_sys::SaveScope_LOCAL_SAVE) };
+
+    let data = if res == -1 {
+        Err(error)
+    } else {
+        fs::read(path).map_err(CLIPSError::from)
+    };
+    let _ = fs::remove_file(path);
+    data
+}
+
+fn binary_load_from_memory(
+    env: *mut clips_sys::Environment,
+    path: &Path,
+    data: &[u8],
+    load: BinaryLoadFn,
+    error: CLIPSError,
+) -> CLIPSResult<()> {
+    fs::write(path, data)?;
+
+    let path_cstr = CString::new(path.as_os_str().as_encoded_bytes()).unwrap();
+    let res = unsafe { load(env, path_cstr.as_ptr()) };
+    let _ = fs::remove_file(path);
+
+    if res == -1 {
+        Err(error)
+    } else {
+        Ok(())
+    }
+}
+
+// Shared by the handle and the CLIPS thread. The flag is set while the environment is paused, and the condvar wakes the thread up when it's resumed.
+type PauseState = Arc<(Mutex<bool>, Condvar)>;
+
+// How often a paused CLIPS thread checks whether its handle is gone.
+const PAUSE_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+// Once the handle is dropped, nobody can resume the environment anymore, so the thread carries on and processes what's left until the channel closes.
+fn wait_while_paused(paused: &PauseState) {
+    let (lock, condvar) = &**paused;
+    let mut is_paused = lock.lock().unwrap();
+
+    while *is_paused && Arc::strong_count(paused) > 1 {
+        is_paused = condvar
+            .wait_timeout(is_paused, PAUSE_CHECK_INTERVAL)
+            .unwrap()
+            .0;
+    }
+}
+
+// The flag is cleared before the result is sent back, so a caller never sees it set after its load returned.
+fn while_parsing<T>(parsing: &AtomicBool, load: impl FnOnce() -> T) -> T {
+    parsing.store(true, Ordering::Release);
+    let res = load();
+    parsing.store(false, Ordering::Release);
+    res
+}
+
+thread_local! {
+    // A panic caught in a callback, kept until the CLIPS thread is back in Rust code it can unwind through.
+    static CALLBACK_PANIC: RefCell<Option<Box<dyn Any + Send>>> = const { RefCell::new(None) };
+}
+
+// Unwinding into CLIPS would abort the process, so callbacks CLIPS calls run the user's code through this. After a panic, CLIPS gets `default` back and stops what it's running, and the CLIPS thread carries the panic on once the current command is answered (see `resume_callback_panic()`). Only the first panic is kept.
+pub(crate) fn catch_callback_panic<T>(
+    env: *mut clips_sys::Environment,
+    default: T,
+    callback: impl FnOnce() -> T,
+) -> T {
+    match panic::catch_unwind(AssertUnwindSafe(callback)) {
+        Ok(res) => res,
+        Err(payload) => {
+            CALLBACK_PANIC.with(|callback_panic| {
+                callback_panic.borrow_mut().get_or_insert(payload);
+            });
+
+            unsafe {
+                clips_sys::SetEvaluationError(env, true);
+                clips_sys::SetHaltExecution(env, true);
+                clips_sys::SetHaltRules(env, true);
+            }
+            default
+        }
+    }
+}
+
+// The thread's own `catch_unwind()` then records the panic, so the environment's handles see it in `failure_reason()`.
+fn resume_callback_panic() {
+    if let Some(payload) = CALLBACK_PANIC.with(|callback_panic| callback_panic.borrow_mut().take())
+    {
+        panic::resume_unwind(payload);
+    }
+}
+
+fn clips_environment_task(
+    input_rx: mpsc::Receiver<CLIPSEnvironmentCommand>,
+    pending_commands: Arc<AtomicUsize>,
+    parsing: Arc<AtomicBool>,
+    exit_code: Arc<Mutex<Option<i32>>>,
+    paused: PauseState,
+) {
+    // We use `unshare()` to allow this thread setting a different `chdir` than other threads in the process. This library expects to be used in multi-threaded programs, and by default `chdir()` applies to the entire process.
+    unshare(CloneFlags::CLONE_FS).unwrap();
+
+    // `exit_code` is dropped after `env`, so it lives as long as CLIPS can call the exit guard.
+    let mut env = CLIPSEnvironment::new().unwrap();
+    env.install_exit_guard(&exit_code);
+    env.install_rule_fire_counter();
+    env.install_stats_counters();
+    env.install_error_log();
+    env.install_construct_hooks();
+
+    // In the loop below, we'll ignore any `SendError`s that happen when sending the result of doing the work that was requested. To do this with some concise code, we must get rid of the `SendError`s  returned by each channel's `send()` call, because those errors all have different types (and thus can't be assigned to the same variable). The `StubError` below exists so we can map all `SendError`s to a `StubError` to allow the code to be concise.
+    struct StubError {}
+    fn create_stub_error<T>(_prev: SendError<T>) -> StubError {
+        StubError {}
+    }
+
+    loop {
+        let command = input_rx.recv();
+        if command.is_ok() {
+            // Checked after a command arrives, since the environment may have been paused while we waited for it.
+            wait_while_paused(&paused);
+            pending_commands.fetch_sub(1, Ordering::AcqRel);
+        }
+
+        let result_res = match command {
+            Err(_) => {
+                log::info!("The input channel for the CLIPS environment is closed, so will stop the CLIPS environment task.");
+                break;
+            }
+            Ok(CLIPSEnvironmentCommand::Close) => {
+                log::info!("Got asked to close the CLIPS environment. Stopping the CLIPS environment task.");
+                break;
+            }
+            #[cfg(feature = "test-util")]
+            Ok(CLIPSEnvironmentCommand::Poison) => panic!("the environment was poisoned"),
+            Ok(CLIPSEnvironmentCommand::Ping { res_tx }) => {
+                res_tx.send(()).map_err(create_stub_error)
+            }
+            Ok(CLIPSEnvironmentCommand::LoadFromStr { data, res_tx }) => res_tx
+                .send(while_parsing(&parsing, || env.load_from_str(&data)))
+                .map_err(create_stub_error),
+            Ok(CLIPSEnvironmentCommand::LoadFromStrAtomic { data, res_tx }) => res_tx
+                .send(while_parsing(&parsing, || env.load_from_str_atomic(&data)))
+                .map_err(create_stub_error),
+            Ok(CLIPSEnvironmentCommand::Run { res_tx }) => {
                 res_tx.send(env.run()).map_err(create_stub_error)
             }
-            Ok(CLIPSEnvironmentCommand::RunLimit { limit, res_tx }) => {
-                res_tx.send(env.run_limit(limit)).map_err(create_stub_error)
+            Ok(CLIPSEnvironmentCommand::RunDetailed { res_tx }) => {
+                res_tx.send(env.run_detailed()).map_err(create_stub_error)
+            }
+            Ok(CLIPSEnvironmentCommand::GetLastFiredRule { res_tx }) => res_tx
+                .send(env.last_fired_rule().map(str::to_string))
+                .map_err(create_stub_error),
+            Ok(CLIPSEnvironmentCommand::Agenda { res_tx }) => {
+                res_tx.send(env.agenda()).map_err(create_stub_error)
+            }
+            Ok(CLIPSEnvironmentCommand::AgendaPP { res_tx }) => {
+                res_tx.send(env.agenda_pp()).map_err(create_stub_error)
+            }
+            Ok(CLIPSEnvironmentCommand::RefreshAgenda { res_tx }) => {
+                res_tx.send(env.refresh_agenda()).map_err(create_stub_error)
+            }
+            Ok(CLIPSEnvironmentCommand::RunLimit { limit, res_tx }) => {
+                res_tx.send(env.run_limit(limit)).map_err(create_stub_error)
+            }
+            Ok(CLIPSEnvironmentCommand::SetAgendaChangedCallback { callback, res_tx }) => res_tx
+                .send(env.set_agenda_changed_callback(callback))
+                .map_err(create_stub_error),
+            Ok(CLIPSEnvironmentCommand::SetWatchdog { config, res_tx }) => {
+                env.set_watchdog(config);
+                res_tx.send(()).map_err(create_stub_error)
+            }
+            Ok(CLIPSEnvironmentCommand::GetLastWatchdogReport { res_tx }) => res_tx
+                .send(env.last_watchdog_report().cloned())
+                .map_err(create_stub_error),
+            Ok(CLIPSEnvironmentCommand::RunCapped {
+                max_firings,
+                res_tx,
+            }) => res_tx
+                .send(env.run_capped(max_firings))
+                .map_err(create_stub_error),
+            Ok(CLIPSEnvironmentCommand::Stats { res_tx }) => {
+                res_tx.send(env.stats()).map_err(create_stub_error)
+            }
+            Ok(CLIPSEnvironmentCommand::LastErrorText { res_tx }) => res_tx
+                .send(env.last_error_text())
+                .map_err(create_stub_error),
+            Ok(CLIPSEnvironmentCommand::RecentErrorTexts { res_tx }) => res_tx
+                .send(env.recent_error_texts())
+                .map_err(create_stub_error),
+            Ok(CLIPSEnvironmentCommand::RuleFireCounts { res_tx }) => res_tx
+                .send(env.rule_fire_counts())
+                .map_err(create_stub_error),
+            Ok(CLIPSEnvironmentCommand::ResetFireCounts { res_tx }) => {
+                env.reset_fire_counts();
+                res_tx.send(()).map_err(create_stub_error)
+            }
+            Ok(CLIPSEnvironmentCommand::RunFor { budget, res_tx }) => {
+                res_tx.send(env.run_for(budget)).map_err(create_stub_error)
+            }
+            Ok(CLIPSEnvironmentCommand::RunWatchingWm { tx, res_tx }) => res_tx
+                .send(env.run_watching_wm(tx))
+                .map_err(create_stub_error),
+            Ok(CLIPSEnvironmentCommand::RunRecordingFiredRules { res_tx }) => res_tx
+                .send(env.run_recording_fired_rules())
+                .map_err(create_stub_error),
+            Ok(CLIPSEnvironmentCommand::RunConsume {
+                result_template,
+                res_tx,
+            }) => res_tx
+                .send(env.run_consume(&result_template))
+                .map_err(create_stub_error),
+            Ok(CLIPSEnvironmentCommand::ChDir { new_dir, res_tx }) => {
+                res_tx.send(env.chdir(new_dir)).map_err(create_stub_error)
+            }
+            Ok(CLIPSEnvironmentCommand::LoadIntoModule {
+                module,
+                data,
+                res_tx,
+            }) => res_tx
+                .send(while_parsing(&parsing, || {
+                    env.load_into_module(&module, &data)
+                }))
+                .map_err(create_stub_error),
+            Ok(CLIPSEnvironmentCommand::UnloadModule { module, res_tx }) => res_tx
+                .send(env.unload_module(&module))
+                .map_err(create_stub_error),
+            Ok(CLIPSEnvironmentCommand::BatchStar { file_path, res_tx }) => res_tx
+                .send(while_parsing(&parsing, || env.batch_star(file_path)))
+                .map_err(create_stub_error),
+            Ok(CLIPSEnvironmentCommand::LoadFromStrWithReport { data, res_tx }) => res_tx
+                .send(while_parsing(&parsing, || {
+                    env.load_from_str_with_report(&data)
+                }))
+                .map_err(create_stub_error),
+            Ok(CLIPSEnvironmentCommand::BatchStarWithReport { file_path, res_tx }) => res_tx
+                .send(while_parsing(&parsing, || {
+                    env.batch_star_with_report(file_path)
+                }))
+                .map_err(create_stub_error),
+            Ok(CLIPSEnvironmentCommand::AddUDF {
+                signature,
+                function,
+                res_tx,
+            }) => res_tx
+                .send(env.add_udf_with_signature(signature, function))
+                .map_err(create_stub_error),
+            Ok(CLIPSEnvironmentCommand::ListUDFs { res_tx }) => {
+                res_tx.send(env.list_udfs()).map_err(create_stub_error)
+            }
+            Ok(CLIPSEnvironmentCommand::AddGenericMethod {
+                generic,
+                restrictions,
+                function,
+                res_tx,
+            }) => res_tx
+                .send(while_parsing(&parsing, || {
+                    env.add_generic_method(&generic, &restrictions, function)
+                }))
+                .map_err(create_stub_error),
+            Ok(CLIPSEnvironmentCommand::AddRouter {
+                name,
+                priority,
+                router,
+                res_tx,
+            }) => res_tx
+                .send(env.add_router(&name, priority, router))
+                .map_err(create_stub_error),
+            #[cfg(feature = "tracing")]
+            Ok(CLIPSEnvironmentCommand::InstallTracingBridge { res_tx }) => res_tx
+                .send(env.install_tracing_bridge())
+                .map_err(create_stub_error),
+            Ok(CLIPSEnvironmentCommand::RemoveUDF { name, res_tx }) => res_tx
+                .send(env.remove_udf(&name))
+                .map_err(create_stub_error),
+            Ok(CLIPSEnvironmentCommand::AssertFact {
+                value,
+                module,
+                res_tx,
+            }) => res_tx
+                .send(env.assert_fact(value, module.as_deref()))
+                .map_err(create_stub_error),
+            Ok(CLIPSEnvironmentCommand::AssertFactWithSupport {
+                value,
+                module,
+                tag,
+                res_tx,
+            }) => res_tx
+                .send(env.assert_fact_with_support(value, module.as_deref(), &tag))
+                .map_err(create_stub_error),
+            Ok(CLIPSEnvironmentCommand::AssertFactAllowDuplicate {
+                value,
+                module,
+                allow,
+                res_tx,
+            }) => res_tx
+                .send(env.assert_fact_allow_duplicate(value, module.as_deref(), allow))
+                .map_err(create_stub_error),
+            Ok(CLIPSEnvironmentCommand::ValidateFact {
+                value,
+                module,
+                res_tx,
+            }) => res_tx
+                .send(env.validate_fact(value, module.as_deref()))
+                .map_err(create_stub_error),
+            Ok(CLIPSEnvironmentCommand::ValidateSlotMap {
+                template_or_class,
+                slots,
+                res_tx,
+            }) => res_tx
+                .send(env.validate_slot_map(&template_or_class, &slots))
+                .map_err(create_stub_error),
+            Ok(CLIPSEnvironmentCommand::AssertOrdered {
+                head,
+                values,
+                res_tx,
+            }) => res_tx
+                .send(env.assert_ordered(&head, values))
+                .map_err(create_stub_error),
+            Ok(CLIPSEnvironmentCommand::AssertString { fact, res_tx }) => res_tx
+                .send(env.assert_string(&fact))
+                .map_err(create_stub_error),
+            Ok(CLIPSEnvironmentCommand::DuplicateFact {
+                index,
+                overrides,
+                res_tx,
+            }) => res_tx
+                .send(env.duplicate_fact(index, overrides))
+                .map_err(create_stub_error),
+            Ok(CLIPSEnvironmentCommand::EditMultislot {
+                index,
+                slot,
+                edit,
+                res_tx,
+            }) => res_tx
+                .send(env.edit_multislot(index, &slot, edit))
+                .map_err(create_stub_error),
+            Ok(CLIPSEnvironmentCommand::AssertFacts {
+                values,
+                module,
+                res_tx,
+            }) => res_tx
+                .send(
+                    values
+                        .into_iter()
+                        .map(|value| env.assert_fact(value, module.as_deref()))
+                        .collect(),
+                )
+                .map_err(create_stub_error),
+            Ok(CLIPSEnvironmentCommand::AssertFactWithProvenance {
+                value,
+                module,
+                provenance,
+                res_tx,
+            }) => res_tx
+                .send(env.assert_fact_with_provenance(value, module.as_deref(), &provenance))
+                .map_err(create_stub_error),
+            Ok(CLIPSEnvironmentCommand::AssertFactsWithProvenance {
+                values,
+                module,
+                provenance,
+                res_tx,
+            }) => res_tx
+                .send(
+                    values
+                        .into_iter()
+                        .map(|value| {
+                            env.assert_fact_with_provenance(value, module.as_deref(), &provenance)
+                        })
+                        .collect(),
+                )
+                .map_err(create_stub_error),
+            Ok(CLIPSEnvironmentCommand::FactProvenance { index, res_tx }) => res_tx
+                .send(env.fact_provenance(index))
+                .map_err(create_stub_error),
+            Ok(CLIPSEnvironmentCommand::FactsWithProvenance { provenance, res_tx }) => res_tx
+                .send(env.facts_with_provenance(&provenance))
+                .map_err(create_stub_error),
+            Ok(CLIPSEnvironmentCommand::AssertFactGraph {
+                graph,
+                module,
+                rollback,
+                res_tx,
+            }) => res_tx
+                .send(env.assert_fact_graph(graph, module.as_deref(), rollback))
+                .map_err(create_stub_error),
+            Ok(CLIPSEnvironmentCommand::WithdrawSupport { tag, res_tx }) => res_tx
+                .send(env.withdraw_support(&tag))
+                .map_err(create_stub_error),
+            Ok(CLIPSEnvironmentCommand::MakeInstance {
+                value,
+                instance_name,
+                module,
+                res_tx,
+            }) => res_tx
+                .send(env.make_instance(value, instance_name.as_deref(), module.as_deref()))
+                .map_err(create_stub_error),
+            Ok(CLIPSEnvironmentCommand::MakeInstanceFromMap {
+                class,
+                instance_name,
+                slots,
+                res_tx,
+            }) => res_tx
+                .send(env.make_instance_from_map(&class, instance_name.as_deref(), slots))
+                .map_err(create_stub_error),
+            Ok(CLIPSEnvironmentCommand::RetractWhere {
+                template,
+                query,
+                res_tx,
+            }) => res_tx
+                .send(env.retract_where(&template, &query))
+                .map_err(create_stub_error),
+            Ok(CLIPSEnvironmentCommand::FindAllFacts {
+                template,
+                query,
+                res_tx,
+            }) => res_tx
+                .send(env.find_all_facts(&template, &query))
+                .map_err(create_stub_error),
+            Ok(CLIPSEnvironmentCommand::VisitFacts {
+                template,
+                visitor,
+                res_tx,
+            }) => res_tx
+                .send(env.visit_facts(&template, visitor))
+                .map_err(create_stub_error),
+            Ok(CLIPSEnvironmentCommand::FindAllInstances {
+                class,
+                query,
+                res_tx,
+            }) => res_tx
+                .send(env.find_all_instances(&class, &query))
+                .map_err(create_stub_error),
+            Ok(CLIPSEnvironmentCommand::SetInstanceNamePrefix { prefix, res_tx }) => {
+                env.set_instance_name_prefix(prefix);
+                res_tx.send(()).map_err(create_stub_error)
+            }
+            Ok(CLIPSEnvironmentCommand::Gensym { res_tx }) => {
+                res_tx.send(env.gensym()).map_err(create_stub_error)
+            }
+            Ok(CLIPSEnvironmentCommand::GensymStar { res_tx }) => {
+                res_tx.send(env.gensym_star()).map_err(create_stub_error)
+            }
+            Ok(CLIPSEnvironmentCommand::Format { fmt, args, res_tx }) => res_tx
+                .send(env.format(&fmt, args))
+                .map_err(create_stub_error),
+            Ok(CLIPSEnvironmentCommand::SetDynamicConstraintChecking { value, res_tx }) => res_tx
+                .send(env.set_dynamic_constraint_checking(value))
+                .map_err(create_stub_error),
+            Ok(CLIPSEnvironmentCommand::SetSequenceOperatorRecognition { value, res_tx }) => {
+                env.set_sequence_operator_recognition(value);
+                res_tx.send(()).map_err(create_stub_error)
+            }
+            Ok(CLIPSEnvironmentCommand::GetSequenceOperatorRecognition { res_tx }) => res_tx
+                .send(env.get_sequence_operator_recognition())
+                .map_err(create_stub_error),
+            Ok(CLIPSEnvironmentCommand::SetUserData { data, res_tx }) => {
+                env.set_user_data(data);
+                res_tx.send(()).map_err(create_stub_error)
+            }
+            Ok(CLIPSEnvironmentCommand::SetValuePolicy { policy, res_tx }) => {
+                env.set_value_policy(policy);
+                res_tx.send(()).map_err(create_stub_error)
+            }
+            Ok(CLIPSEnvironmentCommand::GetValuePolicy { res_tx }) => {
+                res_tx.send(env.value_policy()).map_err(create_stub_error)
+            }
+            Ok(CLIPSEnvironmentCommand::SetTreatBooleanSymbolsAsBool { value, res_tx }) => {
+                env.set_treat_boolean_symbols_as_bool(value);
+                res_tx.send(()).map_err(create_stub_error)
+            }
+            Ok(CLIPSEnvironmentCommand::AgendaOrderPreview { strategy, res_tx }) => res_tx
+                .send(env.agenda_order_preview(strategy))
+                .map_err(create_stub_error),
+            Ok(CLIPSEnvironmentCommand::SetConflictResolutionStrategy { value, res_tx }) => res_tx
+                .send(env.set_conflict_resolution_strategy(value))
+                .map_err(create_stub_error),
+            Ok(CLIPSEnvironmentCommand::WatchSlot {
+                template,
+                slot,
+                tx,
+                res_tx,
+            }) => res_tx
+                .send(env.watch_slot(&template, &slot, tx))
+                .map_err(create_stub_error),
+            Ok(CLIPSEnvironmentCommand::UnwatchSlot { id, res_tx }) => {
+                res_tx.send(env.unwatch_slot(id)).map_err(create_stub_error)
+            }
+            Ok(CLIPSEnvironmentCommand::OnConstructDefined { callback, res_tx }) => {
+                env.on_construct_defined(callback);
+                res_tx.send(()).map_err(create_stub_error)
+            }
+            Ok(CLIPSEnvironmentCommand::ResetParserState { res_tx }) => {
+                env.reset_parser_state();
+                res_tx.send(()).map_err(create_stub_error)
+            }
+            Ok(CLIPSEnvironmentCommand::SaveModule {
+                module,
+                path,
+                res_tx,
+            }) => res_tx
+                .send(env.save_module(&module, path))
+                .map_err(create_stub_error),
+            Ok(CLIPSEnvironmentCommand::BinarySaveFacts { path, res_tx }) => res_tx
+                .send(env.binary_save_facts(path))
+                .map_err(create_stub_error),
+            Ok(CLIPSEnvironmentCommand::BinaryLoadFacts { path, res_tx }) => res_tx
+                .send(env.binary_load_facts(path))
+                .map_err(create_stub_error),
+            Ok(CLIPSEnvironmentCommand::BinaryLoadFactsFromBytes { data, res_tx }) => res_tx
+                .send(env.binary_load_facts_from_bytes(&data))
+                .map_err(create_stub_error),
+            Ok(CLIPSEnvironmentCommand::BinarySaveInstances { path, res_tx }) => res_tx
+                .send(env.binary_save_instances(path))
+                .map_err(create_stub_error),
+            Ok(CLIPSEnvironmentCommand::BinaryLoadInstances { path, res_tx }) => res_tx
+                .send(env.binary_load_instances(path))
+                .map_err(create_stub_error),
+            Ok(CLIPSEnvironmentCommand::BinaryLoadInstancesFromBytes { data, res_tx }) => res_tx
+                .send(env.binary_load_instances_from_bytes(&data))
+                .map_err(create_stub_error),
+            Ok(CLIPSEnvironmentCommand::Savepoint { name, res_tx }) => {
+                res_tx.send(env.savepoint(&name)).map_err(create_stub_error)
+            }
+            Ok(CLIPSEnvironmentCommand::RollbackTo { name, res_tx }) => res_tx
+                .send(env.rollback_to(&name))
+                .map_err(create_stub_error),
+            Ok(CLIPSEnvironmentCommand::ReleaseSavepoint { name, res_tx }) => res_tx
+                .send(env.release_savepoint(&name))
+                .map_err(create_stub_error),
+            Ok(CLIPSEnvironmentCommand::RetrieveGlobalsValues { res_tx }) => res_tx
+                .send(env.retrieve_globals_values())
+                .map_err(create_stub_error),
+            Ok(CLIPSEnvironmentCommand::ReinsertValue {
+                target,
+                value,
+                res_tx,
+            }) => res_tx
+                .send(env.reinsert_value(target, value))
+                .map_err(create_stub_error),
+            Ok(CLIPSEnvironmentCommand::RestoreGlobals { globals, res_tx }) => res_tx
+                .send(env.restore_globals(globals))
+                .map_err(create_stub_error),
+            Ok(CLIPSEnvironmentCommand::CheckConstraints { res_tx }) => res_tx
+                .send(env.check_constraints())
+                .map_err(create_stub_error),
+            #[cfg(feature = "json")]
+            Ok(CLIPSEnvironmentCommand::StreamInstances { tx }) => {
+                env.stream_instances(&tx);
+                Ok(())
+            }
+            Ok(CLIPSEnvironmentCommand::StreamWorkingMemory { tx }) => {
+                env.stream_working_memory(&tx);
+                Ok(())
+            }
+            Ok(CLIPSEnvironmentCommand::ExportConstructs { res_tx }) => res_tx
+                .send(env.export_constructs())
+                .map_err(create_stub_error),
+            Ok(CLIPSEnvironmentCommand::ImportConstructs { export, res_tx }) => res_tx
+                .send(env.import_constructs(export))
+                .map_err(create_stub_error),
+            Ok(CLIPSEnvironmentCommand::ConstructSummary { res_tx }) => res_tx
+                .send(env.construct_summary())
+                .map_err(create_stub_error),
+            Ok(CLIPSEnvironmentCommand::ConstructsFingerprint { res_tx }) => res_tx
+                .send(env.constructs_fingerprint())
+                .map_err(create_stub_error),
+            Ok(CLIPSEnvironmentCommand::SymbolStats { top, res_tx }) => res_tx
+                .send(env.symbol_stats(top))
+                .map_err(create_stub_error),
+            Ok(CLIPSEnvironmentCommand::GetTemplateInfo { template, res_tx }) => res_tx
+                .send(env.template_info(&template))
+                .map_err(create_stub_error),
+            Ok(CLIPSEnvironmentCommand::GetClassInfo { class, res_tx }) => res_tx
+                .send(env.class_info(&class))
+                .map_err(create_stub_error),
+            Ok(CLIPSEnvironmentCommand::RuleInfo { rule, res_tx }) => {
+                res_tx.send(env.rule_info(&rule)).map_err(create_stub_error)
+            }
+            Ok(CLIPSEnvironmentCommand::RulePartialMatches { rule, res_tx }) => res_tx
+                .send(env.rule_partial_matches(&rule))
+                .map_err(create_stub_error),
+            Ok(CLIPSEnvironmentCommand::ListHandlers { class, res_tx }) => res_tx
+                .send(env.list_handlers(&class))
+                .map_err(create_stub_error),
+        };
+
+        env.notify_agenda_changed();
+        resume_callback_panic();
+
+        if let Err(_) = result_res {
+            break;
+        }
+    }
+}
+
+const UDF_MAP_ENVIRONMENT_DATA_INDEX: u32 = clips_sys::USER_ENVIRONMENT_DATA + 0;
+const ROUTER_MAP_ENVIRONMENT_DATA_INDEX: u32 = clips_sys::USER_ENVIRONMENT_DATA + 1;
+const STRINGS_TO_DROP_ENVIRONMENT_DATA_INDEX: u32 = clips_sys::USER_ENVIRONMENT_DATA + 2;
+const UDF_SIGNATURE_MAP_ENVIRONMENT_DATA_INDEX: u32 = clips_sys::USER_ENVIRONMENT_DATA + 3;
+pub(crate) const VALUE_POLICY_ENVIRONMENT_DATA_INDEX: u32 = clips_sys::USER_ENVIRONMENT_DATA + 4;
+const USER_DATA_ENVIRONMENT_DATA_INDEX: u32 = clips_sys::USER_ENVIRONMENT_DATA + 5;
+const CONSTRUCT_HOOKS_ENVIRONMENT_DATA_INDEX: u32 = clips_sys::USER_ENVIRONMENT_DATA + 6;
+
+// `UNBOUNDED` in CLIPS, which isn't exported. A UDF with this as its maximum takes any number of arguments.
+const UDF_UNBOUNDED_ARGS: u16 = u16::MAX;
+
+// The sizes of the hash tables in symbol.h, as CLIPS was built with them.
+const SYMBOL_HASH_SIZE: usize = clips_sys::SYMBOL_HASH_SIZE as usize;
+const FLOAT_HASH_SIZE: usize = clips_sys::FLOAT_HASH_SIZE as usize;
+const INTEGER_HASH_SIZE: usize = clips_sys::INTEGER_HASH_SIZE as usize;
+const BITMAP_HASH_SIZE: usize = clips_sys::BITMAP_HASH_SIZE as usize;
+
+type CLIPSEnvironmentRouterMap = HashMap<String, RegisterableRouter>;
+type CLIPSEnvironmentUDFSignatureMap = HashMap<String, UDFSignature>;
+type CLIPSEnvironmentUserData = Option<Box<dyn Any + Send>>;
+type FactVisitor = Box<dyn FnMut(FactView<'_>) + Send>;
+
+type UDFFunction = Box<dyn FnMut(UDFData) + Sync + Send>;
+
+// UDFs are called by their index in `functions`, which CLIPS passes back as the UDF's context, so calling one doesn't need to look up its name. Indexes are never reused: a removed UDF leaves an empty entry behind, so a stale index can't call some other function.
+#[derive(Default)]
+pub(crate) struct CLIPSEnvironmentUDFMap {
+    functions: Vec<Option<UDFFunction>>,
+    // Only used to add, remove and list UDFs.
+    indexes: HashMap<String, usize>,
+}
+
+impl CLIPSEnvironmentUDFMap {
+    // The function only becomes the one called by `name` after `commit()`, since CLIPS may refuse the UDF.
+    fn push(&mut self, function: UDFFunction) -> usize {
+        self.functions.push(Some(function));
+        self.functions.len() - 1
+    }
+
+    fn commit(&mut self, name: &str, index: usize) {
+        if let Some(previous) = self.indexes.insert(name.to_string(), index) {
+            self.functions[previous] = None;
+        }
+    }
+
+    fn discard(&mut self, index: usize) {
+        self.functions[index] = None;
+    }
+
+    fn remove(&mut self, name: &str) {
+        if let Some(index) = self.indexes.remove(name) {
+            self.functions[index] = None;
+        }
+    }
+
+    pub(crate) fn get_mut(&mut self, index: usize) -> Option<&mut UDFFunction> {
+        self.functions.get_mut(index).and_then(Option::as_mut)
+    }
+}
+
+// Every string is its own allocation, so a pointer is only ever registered once, and unregistering a pointer that isn't registered (e.g. twice in a row) does nothing instead of freeing it again.
+#[derive(Default)]
+struct CLIPSEnvironmentStringsToDrop {
+    strings: HashSet<*const i8>,
+}
+
+impl CLIPSEnvironmentStringsToDrop {
+    fn register(&mut self, string: CString) -> *const i8 {
+        let ptr = string.into_raw() as *const i8;
+        self.strings.insert(ptr);
+        ptr
+    }
+
+    fn unregister(&mut self, ptr: *const i8) -> bool {
+        let registered = self.strings.remove(&ptr);
+
+        if registered {
+            drop(unsafe { CString::from_raw(ptr as *mut i8) });
+        }
+
+        registered
+    }
+}
+
+impl Drop for CLIPSEnvironmentStringsToDrop {
+    fn drop(&mut self) {
+        for ptr in self.strings.drain() {
+            drop(unsafe { CString::from_raw(ptr as *mut i8) });
+        }
+    }
+}
+
+pub struct CLIPSEnvironment {
+    raw: *mut clips_sys::Environment,
+    destroy_on_drop: bool,
+    fact_builders: HashMap<String, CLIPSFactBuilder>,
+    instance_builders: HashMap<String, CLIPSInstanceBuilder>,
+    instance_name_prefix: Option<String>,
+    instance_name_counter: usize,
+    last_fired_rule: Option<String>,
+    supported_assert: Option<SharedSupportedAssert>,
+    support_id_counter: i64,
+    savepoints: HashMap<String, Savepoint>,
+    fact_provenance: Option<SharedFactProvenance>,
+    slot_watchers: HashMap<SlotWatchId, Box<SlotWatcher>>,
+    slot_watch_counter: u64,
+    watchdog: Option<WatchdogConfig>,
+    last_watchdog_report: Option<WatchdogReport>,
+    agenda_watcher: Option<AgendaWatcher>,
+    // Boxed so CLIPS can keep a pointer to it while the environment moves around. `None` until the counter is installed.
+    rule_fire_counter: Option<Box<clips_sys::userDataRecord>>,
+    // Boxed for the same reason as `rule_fire_counter`.
+    stats: Box<EnvStats>,
+    // Boxed for the same reason as `rule_fire_counter`.
+    error_log: Box<ErrorLog>,
+}
+
+impl CLIPSEnvironment {
+    pub fn new() -> CLIPSResult<Self> {
+        let raw = unsafe { clips_sys::CreateEnvironment() };
+
+        let udf_map: Box<CLIPSEnvironmentUDFMap> = Box::default();
+        let router_map: Box<CLIPSEnvironmentRouterMap> = Box::new(HashMap::new());
+        // We unwrap some strings to give them to CLIPS so it can hold onto them while it runs. We also keep a copy of them here, so when we drop the environment we can take back ownership over those strings to properly drop them.
+        let strings_to_drop: Box<CLIPSEnvironmentStringsToDrop> = Box::default();
+        let udf_signature_map: Box<CLIPSEnvironmentUDFSignatureMap> = Box::new(HashMap::new());
+        let value_policy: Box<ValueExtractionPolicy> = Box::default();
+        let user_data: Box<CLIPSEnvironmentUserData> = Box::new(None);
+        let construct_hooks: Box<ConstructHooks> = Box::default();
+
+        unsafe {
+            let res = clips_sys::AllocateEnvironmentData(
+                raw,
+                UDF_MAP_ENVIRONMENT_DATA_INDEX,
+                size_of::<Box<CLIPSEnvironmentUDFMap>>(),
+                Some(cleanup_udf_map),
+            );
+
+            if !res {
+                return Err(CLIPSError::EnvironmentNotCreated);
+            }
+
+            let res = clips_sys::AllocateEnvironmentData(
+                raw,
+                ROUTER_MAP_ENVIRONMENT_DATA_INDEX,
+                size_of::<Box<CLIPSEnvironmentRouterMap>>(),
+                Some(cleanup_router_map),
+            );
+
+            if !res {
+                return Err(CLIPSError::EnvironmentNotCreated);
+            }
+
+            let res = clips_sys::AllocateEnvironmentData(
+                raw,
+                STRINGS_TO_DROP_ENVIRONMENT_DATA_INDEX,
+                size_of::<Box<CLIPSEnvironmentStringsToDrop>>(),
+                Some(cleanup_strings_to_drop),
+            );
+
+            if !res {
+                return Err(CLIPSError::EnvironmentNotCreated);
+            }
+
+            let res = clips_sys::AllocateEnvironmentData(
+                raw,
+                UDF_SIGNATURE_MAP_ENVIRONMENT_DATA_INDEX,
+                size_of::<Box<CLIPSEnvironmentUDFSignatureMap>>(),
+                Some(cleanup_udf_signature_map),
+            );
+
+            if !res {
+                return Err(CLIPSError::EnvironmentNotCreated);
+            }
+
+            let res = clips_sys::AllocateEnvironmentData(
+                raw,
+                VALUE_POLICY_ENVIRONMENT_DATA_INDEX,
+                size_of::<Box<ValueExtractionPolicy>>(),
+                Some(cleanup_value_policy),
+            );
+
+            if !res {
+                return Err(CLIPSError::EnvironmentNotCreated);
+            }
+
+            let res = clips_sys::AllocateEnvironmentData(
+                raw,
+                USER_DATA_ENVIRONMENT_DATA_INDEX,
+                size_of::<Box<CLIPSEnvironmentUserData>>(),
+                Some(cleanup_user_data),
+            );
+
+            if !res {
+                return Err(CLIPSError::EnvironmentNotCreated);
+            }
+
+            let res = clips_sys::AllocateEnvironmentData(
+                raw,
+                CONSTRUCT_HOOKS_ENVIRONMENT_DATA_INDEX,
+                size_of::<Box<ConstructHooks>>(),
+                Some(cleanup_construct_hooks),
+            );
+
+            if !res {
+                return Err(CLIPSError::EnvironmentNotCreated);
+            }
+
+            clips_sys::SetEnvironmentData(
+                raw,
+                UDF_MAP_ENVIRONMENT_DATA_INDEX,
+                Box::into_raw(udf_map) as *mut _,
+            );
+            clips_sys::SetEnvironmentData(
+                raw,
+                ROUTER_MAP_ENVIRONMENT_DATA_INDEX,
+                Box::into_raw(router_map) as *mut _,
+            );
+            clips_sys::SetEnvironmentData(
+                raw,
+                STRINGS_TO_DROP_ENVIRONMENT_DATA_INDEX,
+                Box::into_raw(strings_to_drop) as *mut _,
+            );
+            clips_sys::SetEnvironmentData(
+                raw,
+                UDF_SIGNATURE_MAP_ENVIRONMENT_DATA_INDEX,
+                Box::into_raw(udf_signature_map) as *mut _,
+            );
+            clips_sys::SetEnvironmentData(
+                raw,
+                VALUE_POLICY_ENVIRONMENT_DATA_INDEX,
+                Box::into_raw(value_policy) as *mut _,
+            );
+            clips_sys::SetEnvironmentData(
+                raw,
+                USER_DATA_ENVIRONMENT_DATA_INDEX,
+                Box::into_raw(user_data) as *mut _,
+            );
+            clips_sys::SetEnvironmentData(
+                raw,
+                CONSTRUCT_HOOKS_ENVIRONMENT_DATA_INDEX,
+                Box::into_raw(construct_hooks) as *mut _,
+            );
+        }
+
+        Ok(Self {
+            raw,
+            destroy_on_drop: true,
+            fact_builders: HashMap::new(),
+            instance_builders: HashMap::new(),
+            instance_name_prefix: None,
+            instance_name_counter: 0,
+            last_fired_rule: None,
+            supported_assert: None,
+            support_id_counter: 0,
+            savepoints: HashMap::new(),
+            fact_provenance: None,
+            slot_watchers: HashMap::new(),
+            slot_watch_counter: 0,
+            watchdog: None,
+            last_watchdog_report: None,
+            agenda_watcher: None,
+            rule_fire_counter: None,
+            stats: Box::default(),
+            error_log: Box::default(),
+        })
+    }
+
+    pub fn from_raw(raw: *mut clips_sys::Environment) -> Self {
+        Self {
+            raw,
+            destroy_on_drop: false,
+            fact_builders: HashMap::new(),
+            instance_builders: HashMap::new(),
+            instance_name_prefix: None,
+            instance_name_counter: 0,
+            last_fired_rule: None,
+            supported_assert: None,
+            support_id_counter: 0,
+            savepoints: HashMap::new(),
+            fact_provenance: None,
+            slot_watchers: HashMap::new(),
+            slot_watch_counter: 0,
+            watchdog: None,
+            last_watchdog_report: None,
+            agenda_watcher: None,
+            rule_fire_counter: None,
+            stats: Box::default(),
+            error_log: Box::default(),
+        }
+    }
+
+    pub(crate) fn retrieve_udf_map(&self) -> Box<CLIPSEnvironmentUDFMap> {
+        unsafe {
+            let udf_map_ptr =
+                clips_sys::GetEnvironmentData(self.raw, UDF_MAP_ENVIRONMENT_DATA_INDEX)
+                    as *mut CLIPSEnvironmentUDFMap;
+
+            Box::from_raw(udf_map_ptr)
+        }
+    }
+
+    pub(crate) fn store_udf_map(&self, map: Box<CLIPSEnvironmentUDFMap>) {
+        unsafe {
+            clips_sys::SetEnvironmentData(
+                self.raw,
+                UDF_MAP_ENVIRONMENT_DATA_INDEX,
+                Box::into_raw(map) as *mut _,
+            );
+        }
+    }
+
+    pub(crate) fn retrieve_router_map(&self) -> Box<CLIPSEnvironmentRouterMap> {
+        unsafe {
+            let router_map_ptr =
+                clips_sys::GetEnvironmentData(self.raw, ROUTER_MAP_ENVIRONMENT_DATA_INDEX)
+                    as *mut CLIPSEnvironmentRouterMap;
+
+            Box::from_raw(router_map_ptr)
+        }
+    }
+
+    pub(crate) fn store_router_map(&self, map: Box<CLIPSEnvironmentRouterMap>) {
+        unsafe {
+            clips_sys::SetEnvironmentData(
+                self.raw,
+                ROUTER_MAP_ENVIRONMENT_DATA_INDEX,
+                Box::into_raw(map) as *mut _,
+            );
+        }
+    }
+
+    pub(crate) fn retrieve_construct_hooks(&self) -> Box<ConstructHooks> {
+        unsafe {
+            let construct_hooks_ptr =
+                clips_sys::GetEnvironmentData(self.raw, CONSTRUCT_HOOKS_ENVIRONMENT_DATA_INDEX)
+                    as *mut ConstructHooks;
+
+            Box::from_raw(construct_hooks_ptr)
+        }
+    }
+
+    pub(crate) fn store_construct_hooks(&self, hooks: Box<ConstructHooks>) {
+        unsafe {
+            clips_sys::SetEnvironmentData(
+                self.raw,
+                CONSTRUCT_HOOKS_ENVIRONMENT_DATA_INDEX,
+                Box::into_raw(hooks) as *mut _,
+            );
+        }
+    }
+
+    fn retrieve_strings_to_drop(&self) -> Box<CLIPSEnvironmentStringsToDrop> {
+        unsafe {
+            let strings_to_drop_ptr =
+                clips_sys::GetEnvironmentData(self.raw, STRINGS_TO_DROP_ENVIRONMENT_DATA_INDEX)
+                    as *mut CLIPSEnvironmentStringsToDrop;
+
+            Box::from_raw(strings_to_drop_ptr)
+        }
+    }
+
+    fn store_strings_to_drop(&self, map: Box<CLIPSEnvironmentStringsToDrop>) {
+        unsafe {
+            clips_sys::SetEnvironmentData(
+                self.raw,
+                STRINGS_TO_DROP_ENVIRONMENT_DATA_INDEX,
+                Box::into_raw(map) as *mut _,
+            );
+        }
+    }
+
+    // The string is freed when it's unregistered or when the environment is destroyed, whichever comes first.
+    fn register_string(&self, string: CString) -> *const i8 {
+        let mut strings_to_drop = self.retrieve_strings_to_drop();
+        let ptr = strings_to_drop.register(string);
+        self.store_strings_to_drop(strings_to_drop);
+        ptr
+    }
+
+    // Only call this once CLIPS doesn't hold onto `ptr` anymore.
+    fn unregister_string(&self, ptr: *const i8) -> bool {
+        let mut strings_to_drop = self.retrieve_strings_to_drop();
+        let unregistered = strings_to_drop.unregister(ptr);
+        self.store_strings_to_drop(strings_to_drop);
+        unregistered
+    }
+
+    pub(crate) fn retrieve_udf_signature_map(&self) -> Box<CLIPSEnvironmentUDFSignatureMap> {
+        unsafe {
+            let udf_signature_map_ptr =
+                clips_sys::GetEnvironmentData(self.raw, UDF_SIGNATURE_MAP_ENVIRONMENT_DATA_INDEX)
+                    as *mut CLIPSEnvironmentUDFSignatureMap;
+
+            Box::from_raw(udf_signature_map_ptr)
+        }
+    }
+
+    pub(crate) fn store_udf_signature_map(&self, map: Box<CLIPSEnvironmentUDFSignatureMap>) {
+        unsafe {
+            clips_sys::SetEnvironmentData(
+                self.raw,
+                UDF_SIGNATURE_MAP_ENVIRONMENT_DATA_INDEX,
+                Box::into_raw(map) as *mut _,
+            );
+        }
+    }
+
+    fn retrieve_user_data(&self) -> Box<CLIPSEnvironmentUserData> {
+        unsafe {
+            let user_data_ptr =
+                clips_sys::GetEnvironmentData(self.raw, USER_DATA_ENVIRONMENT_DATA_INDEX)
+                    as *mut CLIPSEnvironmentUserData;
+
+            Box::from_raw(user_data_ptr)
+        }
+    }
+
+    fn store_user_data(&self, user_data: Box<CLIPSEnvironmentUserData>) {
+        unsafe {
+            clips_sys::SetEnvironmentData(
+                self.raw,
+                USER_DATA_ENVIRONMENT_DATA_INDEX,
+                Box::into_raw(user_data) as *mut _,
+            );
+        }
+    }
+
+    // Replaces (and drops) whatever user data was set before. It lives in the environment data, so every handle to the environment sees it, including the ones UDFs get.
+    pub fn set_user_data(&self, data: Box<dyn Any + Send>) {
+        let mut user_data = self.retrieve_user_data();
+        *user_data = Some(data);
+        self.store_user_data(user_data);
+    }
+
+    // The user data stays owned by the environment data, so this hands out the raw slot for `UDFData` to borrow from rather than taking it out like `retrieve_user_data()` does.
+    pub(crate) fn user_data_ptr(&self) -> *mut CLIPSEnvironmentUserData {
+        unsafe {
+            clips_sys::GetEnvironmentData(self.raw, USER_DATA_ENVIRONMENT_DATA_INDEX)
+                as *mut CLIPSEnvironmentUserData
+        }
+    }
+
+    fn send_routers_signal(&mut self, signal: CLIPSSignal) {
+        // TODO: optimise this by storing a list of routers that have SIGNAL support without having to check every time?
+        let mut router_map = self.retrieve_router_map();
+        for router in router_map.values_mut() {
+            if router.supports().contains(RouterSupport::SIGNAL) {
+                router.signal(signal.clone());
+            }
+        }
+        self.store_router_map(router_map);
+    }
+
+    pub fn load_from_str(&mut self, data: &str) -> CLIPSResult<()> {
+        self.reset_parser_state();
+
+        let res = self.while_loading(|raw| unsafe {
+            clips_sys::LoadFromString(raw, data.as_ptr() as *const i8, data.len())
+        });
+
+        if !res {
+            Err(CLIPSError::LoadFromString)
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn load_from_str_atomic(&mut self, data: &str) -> CLIPSResult<()> {
+        self.reset_parser_state();
+        let constructs_before = named_pp_forms(self.raw);
+        // Redefining a global evaluates its initial value, and building it again on a rollback would too, so the values are put back at the end. Values that can't be taken out of CLIPS, e.g. fact addresses, stay as they are.
+        let globals_before = self.retrieve_globals_values().ok();
+
+        let (loaded, diagnostics) = self.while_loading(|raw| {
+            collecting_parse_diagnostics(raw, || unsafe {
+                clips_sys::LoadFromString(raw, data.as_ptr() as *const i8, data.len())
+            })
+        });
+
+        if loaded {
+            return Ok(());
+        }
+
+        roll_back_load(self.raw, &constructs_before);
+        if let Some(globals) = globals_before {
+            // Globals the load defined are gone by now, and the rest are still there.
+            let _ = self.restore_globals(globals);
+        }
+
+        Err(CLIPSError::LoadFromStringRolledBack {
+            diagnostic: diagnostics.into_iter().next(),
+        })
+    }
+
+    pub fn load_into_module(&mut self, module: &str, data: &str) -> CLIPSResult<()> {
+        // The name ends up in a `defmodule` construct, so it must be a single symbol.
+        check_query_construct_name(module)?;
+        let module_cstr = CString::new(module).map_err(|_| CLIPSError::ModuleNotFound)?;
+        // Defining a module makes it the current one, so the current module is taken before that.
+        let current_module = unsafe { clips_sys::GetCurrentModule(self.raw) };
+        let mut defmodule = unsafe { clips_sys::FindDefmodule(self.raw, module_cstr.as_ptr()) };
+
+        if defmodule.is_null() {
+            self.load_from_str(&format!("(defmodule {})", module))?;
+            defmodule = unsafe { clips_sys::FindDefmodule(self.raw, module_cstr.as_ptr()) };
+        }
+
+        unsafe { clips_sys::SetCurrentModule(self.raw, defmodule) };
+        let res = self.load_from_str(data);
+        unsafe { clips_sys::SetCurrentModule(self.raw, current_module) };
+
+        res
+    }
+
+    pub fn unload_module(&mut self, module: &str) -> CLIPSResult<usize> {
+        let module_cstr = CString::new(module).map_err(|_| CLIPSError::ModuleNotFound)?;
+        let defmodule = unsafe { clips_sys::FindDefmodule(self.raw, module_cstr.as_ptr()) };
+
+        if defmodule.is_null() {
+            return Err(CLIPSError::ModuleNotFound);
+        }
+
+        let (undefined, failed) = undefine_module_constructs(self.raw, defmodule);
+        self.dispose_builders(Some(module));
+        if !failed.is_empty() {
+            return Err(CLIPSError::ModuleNotUnloaded { constructs: failed });
+        }
+
+        Ok(undefined)
+    }
+
+    // This is only ever called from the CLIPS thread, which has its own current directory (see `clips_environment_task()`), so relative paths given to CLIPS later on are resolved against the directory set here.
+    pub fn chdir<P: AsRef<Path>>(&mut self, new_dir: P) -> CLIPSResult<()> {
+        let new_dir = new_dir.as_ref();
+        log::debug!(
+            "Changing the CLIPS thread current directory from {:?} to {:?}.",
+            current_dir().ok(),
+            new_dir
+        );
+
+        set_current_dir(new_dir).map_err(CLIPSError::from)
+    }
+
+    pub fn batch_star<P: AsRef<Path>>(&mut self, file_path: P) -> CLIPSResult<()> {
+        let file_path = file_path.as_ref();
+        let resolved_path = if file_path.is_absolute() {
+            file_path.to_path_buf()
+        } else {
+            current_dir()?.join(file_path)
+        };
+        log::debug!(
+            "Resolved batch file path {:?} to {:?}.",
+            file_path,
+            resolved_path
+        );
+
+        // CLIPS only tells us that it failed, so we check the file ourselves first to tell IO problems apart from problems with the file contents. A directory can be opened but not read, so a byte is read too.
+        if let Err(err) = File::open(&resolved_path).and_then(|mut file| file.read(&mut [0; 1])) {
+            return Err(CLIPSError::BatchStar {
+                resolved_path,
+                io_kind: Some(err.kind()),
+            });
+        }
+
+        let path_str = resolved_path.to_str().ok_or(CLIPSError::PathNotUnicode)?;
+
+        let path_cstring = CString::new(path_str).unwrap();
+        // CLIPS only fails a batch file it can't open, and carries on past the commands and constructs that fail, so what it reported is checked too.
+        let (res, diagnostics) = self.while_loading(|raw| {
+            collecting_parse_diagnostics(raw, || unsafe {
+                clips_sys::BatchStar(raw, path_cstring.as_ptr())
+            })
+        });
+
+        if !res || !diagnostics.is_empty() {
+            Err(CLIPSError::BatchStar {
+                resolved_path,
+                io_kind: None,
+            })
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn load_from_str_with_report(&mut self, data: &str) -> CLIPSResult<LoadReport> {
+        let raw = self.raw;
+        measure_load(raw, || self.load_from_str(data))
+    }
+
+    pub fn batch_star_with_report<P: AsRef<Path>>(
+        &mut self,
+        file_path: P,
+    ) -> CLIPSResult<LoadReport> {
+        let raw = self.raw;
+        measure_load(raw, || self.batch_star(file_path))
+    }
+
+    pub fn run(&mut self) -> CLIPSResult<usize> {
+        self.send_routers_signal(CLIPSSignal::RunStarted { limit: None });
+        let (rules_ran, _) = self.run_tracking_fired_rule(-1);
+        self.send_routers_signal(CLIPSSignal::RunFinished { limit: None });
+
+        Ok(rules_ran as usize)
+    }
+
+    pub fn run_detailed(&mut self) -> CLIPSResult<RunOutcome> {
+        self.send_routers_signal(CLIPSSignal::RunStarted { limit: None });
+        let (rules_ran, evaluation_error) = self.run_tracking_fired_rule(-1);
+        self.send_routers_signal(CLIPSSignal::RunFinished { limit: None });
+
+        // `last_fired_rule` still holds the rule from a previous run if nothing fired in this one.
+        let last_fired_rule = if rules_ran > 0 {
+            self.last_fired_rule.clone()
+        } else {
+            None
+        };
+
+        Ok(RunOutcome {
+            rules_fired: rules_ran as usize,
+            last_fired_rule,
+            stop_reason: self.run_stop_reason(),
+            evaluation_error,
+        })
+    }
+
+    // An unlimited run only returns with activations left on the focused module's agenda if it was halted or hit a breakpoint.
+    fn run_stop_reason(&mut self) -> RunStopReason {
+        let activation = unsafe {
+            let focus = clips_sys::GetFocus(self.raw);
+            if focus.is_null() {
+                return RunStopReason::Completed;
+            }
+
+            // The agenda functions look at the current module, which isn't necessarily the one in focus.
+            let previous_module = clips_sys::SetCurrentModule(self.raw, focus);
+            let activation = clips_sys::GetNextActivation(self.raw, ptr::null_mut());
+            clips_sys::SetCurrentModule(self.raw, previous_module);
+            activation
+        };
+
+        if activation.is_null() {
+            RunStopReason::Completed
+        } else if unsafe { clips_sys::DefruleHasBreakpoint((*activation).theRule) } {
+            RunStopReason::Breakpoint
+        } else {
+            RunStopReason::Halted
+        }
+    }
+
+    pub fn run_limit(&mut self, limit: usize) -> CLIPSResult<usize> {
+        self.send_routers_signal(CLIPSSignal::RunStarted { limit: Some(limit) });
+        let (rules_ran, _) = self.run_tracking_fired_rule(limit as i64);
+        self.send_routers_signal(CLIPSSignal::RunFinished { limit: Some(limit) });
+
+        Ok(rules_ran as usize)
+    }
+
+    // Hitting the cap exactly as the agenda runs out isn't an error, since the rules stopped on their own.
+    pub fn run_capped(&mut self, max_firings: usize) -> CLIPSResult<usize> {
+        let rules_ran = self.run_limit(max_firings)?;
+
+        if rules_ran >= max_firings && self.run_stop_reason() != RunStopReason::Completed {
+            return Err(CLIPSError::FiringCapExceeded { max_firings });
+        }
+
+        Ok(rules_ran)
+    }
+
+    // The deadline is checked by a callback after every rule firing, which halts the rules once it's passed. Halting this way doesn't stick around after the run, since CLIPS clears the flag when `Run` returns.
+    pub fn run_for(&mut self, budget: Duration) -> CLIPSResult<usize> {
+        if budget.is_zero() {
+            return Ok(0);
+        }
+
+        let callback_name = CString::new("rust-run-deadline").unwrap();
+        let deadline = Instant::now() + budget;
+
+        let registered = unsafe {
+            clips_sys::AddAfterRuleFiresFunction(
+                self.raw,
+                callback_name.as_ptr(),
+                Some(halt_after_deadline),
+                0,
+                &deadline as *const Instant as *mut c_void,
+            )
+        };
+
+        if !registered {
+            return Err(CLIPSError::NameInUse);
+        }
+
+        let res = self.run();
+        unsafe { clips_sys::RemoveAfterRuleFiresFunction(self.raw, callback_name.as_ptr()) };
+
+        res
+    }
+
+    // Also returns whether the right-hand side of any of the rules fired had an evaluation error.
+    fn run_tracking_fired_rule(&mut self, limit: i64) -> (i64, bool) {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::info_span!(target: "clips", "clips_run", limit).entered();
+
+        let callback_name = CString::new("rust-last-fired-rule").unwrap();
+        let mut record = FiredRuleRecord::default();
+
+        // Taken out for the run, so the callbacks can point at it without borrowing `self`.
+        let mut watchdog = self.watchdog.take().map(WatchdogRun::new);
+        if let Some(watchdog) = watchdog.as_mut() {
+            watchdog.install(self.raw);
+        }
+
+        let rules_ran = unsafe {
+            clips_sys::AddAfterRuleFiresFunction(
+                self.raw,
+                callback_name.as_ptr(),
+                Some(record_fired_rule),
+                0,
+                &mut record as *mut FiredRuleRecord as *mut c_void,
+            );
+            let rules_ran = clips_sys::Run(self.raw, limit);
+            clips_sys::RemoveAfterRuleFiresFunction(self.raw, callback_name.as_ptr());
+            rules_ran
+        };
+
+        if record.rule.is_some() {
+            self.last_fired_rule = record.rule;
+        }
+
+        // The watchdog halts execution the same way an evaluation error does, so the flag can't be told apart from a real error.
+        let mut evaluation_error = record.evaluation_error;
+        if let Some(watchdog) = watchdog {
+            evaluation_error &= !watchdog.tripped();
+
+            let (config, report) = watchdog.uninstall(self.raw);
+            self.watchdog = Some(config);
+            self.last_watchdog_report = report;
+        }
+
+        (rules_ran, evaluation_error)
+    }
+
+    pub fn last_fired_rule(&self) -> Option<&str> {
+        self.last_fired_rule.as_deref()
+    }
+
+    // CLIPS has no hook for agenda changes, only a flag it sets whenever an activation is added or removed, so the flag is checked after every command instead.
+    pub fn set_agenda_changed_callback(
+        &mut self,
+        callback: Option<AgendaChangedCallback>,
+    ) -> CLIPSResult<()> {
+        self.agenda_watcher = match callback {
+            Some(callback) => Some(AgendaWatcher::new(callback, self.agenda()?)),
+            None => None,
+        };
+
+        unsafe { clips_sys::SetAgendaChanged(self.raw, false) };
+        Ok(())
+    }
+
+    pub(crate) fn notify_agenda_changed(&mut self) {
+        if self.agenda_watcher.is_none() || !unsafe { clips_sys::GetAgendaChanged(self.raw) } {
+            return;
+        }
+
+        unsafe { clips_sys::SetAgendaChanged(self.raw, false) };
+
+        match self.agenda() {
+            Ok(agenda) => self.agenda_watcher.as_mut().unwrap().notify(agenda),
+            Err(e) => log::warn!("Couldn't read the agenda to report its changes: {}", e),
+        }
+    }
+
+    pub fn set_watchdog(&mut self, config: Option<WatchdogConfig>) {
+        self.watchdog = config;
+    }
+
+    pub fn last_watchdog_report(&self) -> Option<&WatchdogReport> {
+        self.last_watchdog_report.as_ref()
+    }
+
+    pub fn run_watching_wm(&mut self, tx: mpsc::Sender<WmChange>) -> CLIPSResult<usize> {
+        let assert_name = CString::new("rust-wm-assert").unwrap();
+        let retract_name = CString::new("rust-wm-retract").unwrap();
+        // Both callbacks share the sender, and we take it back once the run is over and the callbacks are gone.
+        let tx = Box::into_raw(Box::new(tx)) as *mut c_void;
+
+        let registered = unsafe {
+            clips_sys::AddAssertFunction(
+                self.raw,
+                assert_name.as_ptr(),
+                Some(wm_assert_callback),
+                0,
+                tx,
+            ) && clips_sys::AddRetractFunction(
+                self.raw,
+                retract_name.as_ptr(),
+                Some(wm_retract_callback),
+                0,
+                tx,
+            )
+        };
+
+        let res = if registered {
+            self.run()
+        } else {
+            Err(CLIPSError::NameInUse)
+        };
+
+        unsafe {
+            clips_sys::RemoveAssertFunction(self.raw, assert_name.as_ptr());
+            clips_sys::RemoveRetractFunction(self.raw, retract_name.as_ptr());
+            drop(Box::from_raw(tx as *mut mpsc::Sender<WmChange>));
+        }
+
+        res
+    }
+
+    pub fn run_recording_fired_rules(&mut self) -> CLIPSResult<Vec<String>> {
+        let callback_name = CString::new("rust-fired-rules").unwrap();
+        let mut fired_rules: Vec<String> = Vec::new();
+
+        let registered = unsafe {
+            clips_sys::AddAfterRuleFiresFunction(
+                self.raw,
+                callback_name.as_ptr(),
+                Some(record_rule_sequence),
+                0,
+                &mut fired_rules as *mut Vec<String> as *mut c_void,
+            )
+        };
+
+        let res = if registered {
+            self.run()
+        } else {
+            Err(CLIPSError::NameInUse)
+        };
+
+        unsafe { clips_sys::RemoveAfterRuleFiresFunction(self.raw, callback_name.as_ptr()) };
+
+        res.map(|_| fired_rules)
+    }
+
+    // The template is looked up before running, so a misspelled name doesn't run the rules for nothing. Retracting the results can activate rules, but they don't fire until the next run.
+    pub fn run_consume(&mut self, result_template: &str) -> CLIPSResult<Vec<RetrievedFact>> {
+        let template_cstr =
+            CString::new(result_template).map_err(|_| CLIPSError::TemplateNotFound)?;
+        let deftemplate = unsafe { clips_sys::FindDeftemplate(self.raw, template_cstr.as_ptr()) };
+
+        if deftemplate.is_null() {
+            return Err(CLIPSError::TemplateNotFound);
+        }
+
+        self.run()?;
+
+        let mut results = Vec::new();
+        let mut fact = unsafe { clips_sys::GetNextFactInTemplate(deftemplate, ptr::null_mut()) };
+        while !fact.is_null() {
+            results.push(fact);
+            fact = unsafe { clips_sys::GetNextFactInTemplate(deftemplate, fact) };
+        }
+
+        let facts = results
+            .iter()
+            .map(|fact| retrieve_fact(self.raw, *fact))
+            .collect::<CLIPSResult<Vec<_>>>()?;
+
+        // A result can be retracted along with another one that gave it logical support, so all of them are kept around until we're done.
+        for fact in results.iter() {
+            unsafe { clips_sys::RetainFact(*fact) };
+        }
+
+        for fact in results.iter() {
+            unsafe {
+                if clips_sys::FactExistp(*fact) {
+                    clips_sys::Retract(*fact);
+                }
+            }
+        }
+
+        for fact in results.iter() {
+            unsafe { clips_sys::ReleaseFact(*fact) };
+        }
+
+        Ok(facts)
+    }
+
+    pub fn watch_slot(
+        &mut self,
+        template: &str,
+        slot: &str,
+        tx: mpsc::Sender<SlotChange>,
+    ) -> CLIPSResult<SlotWatchId> {
+        let template_cstr = CString::new(template).map_err(|_| CLIPSError::TemplateNotFound)?;
+        let deftemplate = unsafe { clips_sys::FindDeftemplate(self.raw, template_cstr.as_ptr()) };
+        if deftemplate.is_null() {
+            return Err(CLIPSError::TemplateNotFound);
+        }
+
+        let slot_cstr = CString::new(slot).map_err(|_| CLIPSError::SlotNotFound)?;
+        if !unsafe { clips_sys::DeftemplateSlotExistP(deftemplate, slot_cstr.as_ptr()) } {
+            return Err(CLIPSError::SlotNotFound);
+        }
+
+        self.slot_watch_counter += 1;
+        let id = SlotWatchId(self.slot_watch_counter);
+        // The watcher is boxed so it stays at the same address while CLIPS holds on to it.
+        let mut watcher = Box::new(SlotWatcher::new(deftemplate, slot_cstr, tx));
+
+        let registered = unsafe {
+            clips_sys::AddModifyFunction(
+                self.raw,
+                id.callback_name().as_ptr(),
+                Some(slot_watch_callback),
+                0,
+                &mut *watcher as *mut SlotWatcher as *mut c_void,
+            )
+        };
+
+        if !registered {
+            return Err(CLIPSError::NameInUse);
+        }
+
+        self.slot_watchers.insert(id, watcher);
+        Ok(id)
+    }
+
+    pub fn unwatch_slot(&mut self, id: SlotWatchId) -> bool {
+        let Some(watcher) = self.slot_watchers.remove(&id) else {
+            return false;
+        };
+
+        unsafe { clips_sys::RemoveModifyFunction(self.raw, id.callback_name().as_ptr()) };
+        drop(watcher);
+
+        true
+    }
+
+    pub fn agenda(&mut self) -> CLIPSResult<Vec<ActivationInfo>> {
+        let mut activations = Vec::new();
+
+        let mut activation = unsafe { clips_sys::GetNextActivation(self.raw, ptr::null_mut()) };
+        while !activation.is_null() {
+            let rule_name = unsafe { CStr::from_ptr(clips_sys::ActivationRuleName(activation)) };
+
+            activations.push(ActivationInfo {
+                rule: clips_cstr_to_string(self.raw, rule_name, "a rule name")?,
+                salience: unsafe { (*activation).salience },
+                timetag: unsafe { (*activation).timetag },
+            });
+
+            activation = unsafe { clips_sys::GetNextActivation(self.raw, activation) };
+        }
+
+        Ok(activations)
+    }
+
+    pub fn agenda_pp(&mut self) -> CLIPSResult<String> {
+        // CLIPS writes the agenda to a logical name, so we temporarily point one at a string builder to collect what it writes.
+        let logical_name = CString::new("rust-agenda-pp").unwrap();
+
+        let agenda = unsafe {
+            let sb = clips_sys::CreateStringBuilder(self.raw, 0);
+            clips_sys::OpenStringBuilderDestination(self.raw, logical_name.as_ptr(), sb);
+            clips_sys::Agenda(
+                self.raw,
+                logical_name.as_ptr(),
+                clips_sys::GetCurrentModule(self.raw),
+            );
+            clips_sys::CloseStringBuilderDestination(self.raw, logical_name.as_ptr());
+
+            let agenda =
+                clips_cstr_to_string(self.raw, CStr::from_ptr((*sb).contents), "the agenda");
+            clips_sys::SBDispose(sb);
+            agenda?
+        };
+
+        Ok(agenda)
+    }
+
+    pub fn refresh_agenda(&mut self) -> CLIPSResult<()> {
+        // `GetNextDefrule()` only goes through the rules in the current module, so we switch to every module and restore the current one at the end.
+        let current_module = unsafe { clips_sys::GetCurrentModule(self.raw) };
+
+        let mut defmodule = unsafe { clips_sys::GetNextDefmodule(self.raw, ptr::null_mut()) };
+        while !defmodule.is_null() {
+            unsafe { clips_sys::SetCurrentModule(self.raw, defmodule) };
+
+            let mut defrule = unsafe { clips_sys::GetNextDefrule(self.raw, ptr::null_mut()) };
+            while !defrule.is_null() {
+                unsafe { clips_sys::Refresh(defrule) };
+                defrule = unsafe { clips_sys::GetNextDefrule(self.raw, defrule) };
+            }
+
+            defmodule = unsafe { clips_sys::GetNextDefmodule(self.raw, defmodule) };
+        }
+
+        unsafe {
+            clips_sys::SetCurrentModule(self.raw, current_module);
+            clips_sys::RefreshAllAgendas(self.raw);
+        }
+
+        Ok(())
+    }
+
+    pub fn add_udf(
+        &mut self,
+        name: &str,
+        return_types: UDFType,
+        min_args: u16,
+        max_args: u16,
+        arg_types: Vec<UDFType>,
+        function: Box<dyn FnMut(UDFData) + Send + Sync>,
+    ) -> CLIPSResult<()> {
+        self.add_udf_with_signature(
+            UDFSignature::new(
+                name.to_string(),
+                min_args,
+                max_args,
+                return_types,
+                arg_types,
+            ),
+            function,
+        )
+    }
+
+    pub fn add_udf_with_signature(
+        &mut self,
+        signature: UDFSignature,
+        function: Box<dyn FnMut(UDFData) + Send + Sync>,
+    ) -> CLIPSResult<()> {
+        let name = signature.name.as_str();
+        // CLIPS takes the first type in the list as the default for every argument, and the ones after it for each argument in order. Arguments without types of their own can be anything.
+        let arg_types = if signature.arg_types.is_empty() {
+            String::new()
+        } else {
+            std::iter::once("*".to_string())
+                .chain(signature.arg_types.iter().map(|a| a.as_character_code()))
+                .collect::<Vec<_>>()
+                .join(";")
+        };
+        // CLIPS reads an empty list of return types as "anything", but an empty `UDFType` means the UDF returns nothing.
+        let return_types = if signature.return_types.is_empty() {
+            UDFType::Void.as_character_code()
+        } else {
+            signature.return_types.as_character_code()
+        };
+
+        // Types for arguments past the maximum would never be checked, so they're most likely a mistake in the signature.
+        if signature.max_args != UDF_UNBOUNDED_ARGS
+            && signature.arg_types.len() > signature.max_args as usize
+        {
+            return Err(CLIPSError::InvalidUDFArgumentTypes(arg_types));
+        }
+
+        let arg_types = CString::new(arg_types).unwrap();
+        let return_types = CString::new(return_types).unwrap();
+
+        let mut udf_map = self.retrieve_udf_map();
+        let index = udf_map.push(function);
+        self.store_udf_map(udf_map);
+
+        let name_str = self.register_string(CString::new(name).unwrap());
+
+        let res = unsafe {
+            clips_sys::AddUDF(
+                self.raw,
+                name_str,
+                return_types.as_ptr(),
+                signature.min_args,
+                signature.max_args,
+                arg_types.as_ptr(),
+                Some(call_udf),
+                name_str,
+                index as *mut c_void,
+            )
+        };
+
+        // If the name is already in use, CLIPS keeps the existing UDF, which still calls the function at its own index.
+        let mut udf_map = self.retrieve_udf_map();
+        if res == clips_sys::AddUDFError_AUE_NO_ERROR {
+            udf_map.commit(name, index);
+        } else {
+            udf_map.discard(index);
+        }
+        self.store_udf_map(udf_map);
+
+        if res != clips_sys::AddUDFError_AUE_NO_ERROR {
+            self.unregister_string(name_str);
+        }
+
+        match res {
+            clips_sys::AddUDFError_AUE_NO_ERROR => {
+                let mut udf_signature_map = self.retrieve_udf_signature_map();
+                udf_signature_map.insert(name.to_string(), signature);
+                self.store_udf_signature_map(udf_signature_map);
+
+                Ok(())
+            },
+            clips_sys::AddUDFError_AUE_MIN_EXCEEDS_MAX_ERROR => Err(CLIPSError::MinArgumentsExceedsMax),
+            clips_sys::AddUDFError_AUE_FUNCTION_NAME_IN_USE_ERROR => Err(CLIPSError::NameInUse),
+            clips_sys::AddUDFError_AUE_INVALID_ARGUMENT_TYPE_ERROR => Err(CLIPSError::InvalidUDFArgumentTypes(arg_types.into_string().unwrap())),
+            clips_sys::AddUDFError_AUE_INVALID_RETURN_TYPE_ERROR => Err(CLIPSError::InvalidUDFReturnTypes(return_types.into_string().unwrap())),
+            _ => unreachable!("a new error value for AddUDF was used by CLIPS, but this library doesn't handle it yet"),
+        }
+    }
+
+    pub fn remove_udf(&mut self, name: &str) -> bool {
+        let c_str = CString::new(name).unwrap();
+
+        // CLIPS holds onto the name we gave it when the UDF was added, so we can only free it after the UDF is gone.
+        let function = unsafe { clips_sys::FindFunction(self.raw, c_str.as_ptr()) };
+        if function.is_null() {
+            return false;
+        }
+        let name_str = unsafe { (*function).actualFunctionName };
+
+        let res = unsafe { clips_sys::RemoveUDF(self.raw, c_str.as_ptr()) };
+
+        if res {
+            let mut udf_map = self.retrieve_udf_map();
+            udf_map.remove(name);
+            self.store_udf_map(udf_map);
+
+            let mut udf_signature_map = self.retrieve_udf_signature_map();
+            udf_signature_map.remove(name);
+            self.store_udf_signature_map(udf_signature_map);
+
+            // Builtin functions and UDFs added outside of this crate were never registered, so they're left alone.
+            self.unregister_string(name_str);
+        }
+
+        res
+    }
+
+    // The method's body calls a UDF named `rust-generic-method-<n>`, which runs `function`. It shows up in `list_udfs()`, and is removed when a method with the same restrictions replaces it.
+    pub fn add_generic_method<S: AsRef<str>>(
+        &mut self,
+        generic: &str,
+        restrictions: &[S],
+        function: Box<dyn FnMut(UDFData) + Send + Sync>,
+    ) -> CLIPSResult<()> {
+        // Both end up in a `defmethod` construct, so each of them must only be symbols.
+        check_query_construct_name(generic)?;
+        for restriction in restrictions {
+            let restriction = restriction.as_ref();
+            if restriction
+                .split_whitespace()
+                .any(|name| check_query_construct_name(name).is_err())
+            {
+                return Err(CLIPSError::InvalidUDFArgumentTypes(restriction.to_string()));
+            }
+        }
+
+        let generic_cstr = CString::new(generic).unwrap();
+        let previous_udfs = generic_method_udfs(self.raw, &generic_cstr);
+        let udf_name = self.unused_generic_method_name();
+        // Past `u16::MAX - 1`, the count would be taken as unbounded or not fit at all.
+        let arg_count = u16::try_from(restrictions.len())
+            .ok()
+            .filter(|&count| count != UDF_UNBOUNDED_ARGS)
+            .ok_or_else(|| {
+                CLIPSError::InvalidUDFArgumentTypes(
+                    restrictions
+                        .iter()
+                        .map(|r| r.as_ref())
+                        .collect::<Vec<_>>()
+                        .join(";"),
+                )
+            })?;
+
+        self.add_udf_with_signature(
+            UDFSignature::new(
+                udf_name.clone(),
+                arg_count,
+                arg_count,
+                UDFType::Any,
+                Vec::new(),
+            )
+            .with_doc(format!(
+                "Runs a method of the generic function {}.",
+                generic
+            )),
+            function,
+        )?;
+
+        let params: Vec<_> = restrictions
+            .iter()
+            .enumerate()
+            .map(|(i, restriction)| match restriction.as_ref().trim() {
+                "" => format!("?arg{}", i),
+                restriction => format!("(?arg{} {})", i, restriction),
+            })
+            .collect();
+        let args: Vec<_> = (0..restrictions.len())
+            .map(|i| format!("?arg{}", i))
+            .collect();
+
+        let defmethod = format!(
+            "(defmethod {} ({}) ({} {}))",
+            generic,
+            params.join(" "),
+            udf_name,
+            args.join(" ")
+        );
+
+        let res = self.load_from_str(&defmethod);
+        if res.is_err() {
+            self.remove_udf(&udf_name);
+            return res;
+        }
+
+        // A method with the same restrictions replaces the old one, whose UDF nothing calls anymore.
+        for replaced in previous_udfs.difference(&generic_method_udfs(self.raw, &generic_cstr)) {
+            self.remove_udf(replaced);
+        }
+
+        Ok(())
+    }
+
+    fn unused_generic_method_name(&self) -> String {
+        (1..)
+            .map(|n| format!("rust-generic-method-{}", n))
+            .find(|name| {
+                let name_cstr = CString::new(name.as_str()).unwrap();
+                unsafe { clips_sys::FindFunction(self.raw, name_cstr.as_ptr()) }.is_null()
+            })
+            .unwrap()
+    }
+
+    pub fn list_udfs(&self) -> Vec<UDFSignature> {
+        let udf_signature_map = self.retrieve_udf_signature_map();
+        let mut signatures: Vec<_> = udf_signature_map.values().cloned().collect();
+        self.store_udf_signature_map(udf_signature_map);
+
+        signatures.sort_by(|a, b| a.name.cmp(&b.name));
+        signatures
+    }
+
+    #[cfg(feature = "tracing")]
+    pub fn install_tracing_bridge(&mut self) -> CLIPSResult<()> {
+        let router_map = self.retrieve_router_map();
+        let installed = router_map.contains_key(TRACING_ROUTER_NAME);
+        self.store_router_map(router_map);
+
+        if installed {
+            return Err(CLIPSError::NameInUse);
+        }
+
+        self.add_router(
+            TRACING_ROUTER_NAME,
+            TRACING_ROUTER_PRIORITY,
+            Box::new(TracingRouter::default()),
+        )?;
+
+        // These stay registered for as long as the environment lives, so they don't need a context.
+        let callback_name = CString::new(TRACING_ROUTER_NAME).unwrap();
+        unsafe {
+            clips_sys::AddAfterRuleFiresFunction(
+                self.raw,
+                callback_name.as_ptr(),
+                Some(trace_rule_fired),
+                0,
+                ptr::null_mut(),
+            );
+            clips_sys::AddAssertFunction(
+                self.raw,
+                callback_name.as_ptr(),
+                Some(trace_fact_asserted),
+                0,
+                ptr::null_mut(),
+            );
+            clips_sys::AddRetractFunction(
+                self.raw,
+                callback_name.as_ptr(),
+                Some(trace_fact_retracted),
+                0,
+                ptr::null_mut(),
+            );
+        }
+
+        Ok(())
+    }
+
+    // `exit_code` must outlive the environment, since CLIPS keeps a pointer to it.
+    pub(crate) fn install_exit_guard(&mut self, exit_code: &Mutex<Option<i32>>) {
+        let name = self.register_string(CString::new("rust-exit-guard").unwrap());
+
+        unsafe {
+            clips_sys::AddRouter(
+                self.raw,
+                name,
+                0,
+                Some(exit_guard_query),
+                None,
+                None,
+                None,
+                Some(exit_guard_exit),
+                exit_code as *const Mutex<Option<i32>> as *mut c_void,
+            )
+        };
+    }
+
+    // Each rule keeps its count in its own user data, like the profiler does, so only its first firing allocates. CLIPS frees the count along with the rule, so a rule that takes the place of a removed or redefined one never picks up its count.
+    pub(crate) fn install_rule_fire_counter(&mut self) {
+        let name = CString::new("rust-rule-fire-counter").unwrap();
+        let record = self
+            .rule_fire_counter
+            .insert(Box::new(clips_sys::userDataRecord {
+                dataID: 0,
+                createUserData: Some(create_rule_fire_count),
+                deleteUserData: Some(delete_rule_fire_count),
+            }))
+            .as_mut() as *mut clips_sys::userDataRecord;
+
+        unsafe {
+            clips_sys::InstallUserDataRecord(self.raw, record);
+            clips_sys::AddAfterRuleFiresFunction(
+                self.raw,
+                name.as_ptr(),
+                Some(count_rule_firing),
+                0,
+                record as *mut c_void,
+            );
+        }
+    }
+
+    pub(crate) fn install_stats_counters(&mut self) {
+        let name = CString::new("rust-stats").unwrap();
+        let context = self.stats.as_mut() as *mut EnvStats as *mut c_void;
+
+        unsafe {
+            clips_sys::AddAssertFunction(
+                self.raw,
+                name.as_ptr(),
+                Some(count_assertion),
+                0,
+                context,
+            );
+            clips_sys::AddRetractFunction(
+                self.raw,
+                name.as_ptr(),
+                Some(count_retraction),
+                0,
+                context,
+            );
+        }
+    }
+
+    pub fn stats(&self) -> EnvStats {
+        *self.stats
+    }
+
+    // Installed on every environment, so it stays registered through `clear`.
+    pub(crate) fn install_error_log(&mut self) {
+        let name = CString::new(ERROR_LOG_ROUTER_NAME).unwrap();
+
+        unsafe {
+            clips_sys::AddRouter(
+                self.raw,
+                name.as_ptr(),
+                ERROR_LOG_ROUTER_PRIORITY,
+                Some(error_log_query),
+                Some(error_log_write),
+                None,
+                None,
+                None,
+                self.error_log.as_mut() as *mut ErrorLog as *mut c_void,
+            )
+        };
+    }
+
+    // Installed on every environment, and only does any work once a callback is added.
+    pub(crate) fn install_construct_hooks(&mut self) {
+        let mut hooks = self.retrieve_construct_hooks();
+        hook_construct_parsers(self.raw, &mut hooks);
+        self.store_construct_hooks(hooks);
+    }
+
+    pub fn on_construct_defined(&mut self, callback: Box<dyn FnMut(ConstructEvent) + Send>) {
+        let mut hooks = self.retrieve_construct_hooks();
+        hooks.add_callback(callback);
+        self.store_construct_hooks(hooks);
+    }
+
+    pub fn last_error_text(&self) -> Option<String> {
+        self.error_log.last()
+    }
+
+    pub fn recent_error_texts(&self) -> Vec<String> {
+        self.error_log.messages()
+    }
+
+    pub fn rule_fire_counts(&mut self) -> CLIPSResult<HashMap<String, u64>> {
+        let mut counts = HashMap::new();
+        let Some(record) = self.rule_fire_counter.as_ref() else {
+            return Ok(counts);
+        };
+
+        for defrule in constructs_in_all_modules(self.raw, clips_sys::GetNextDefrule) {
+            let count: u64 = disjunct_fire_counts(record.dataID, defrule)
+                .map(|count| count.count)
+                .sum();
+            if count == 0 {
+                continue;
+            }
+
+            let (name, module) = unsafe {
+                (
+                    CStr::from_ptr(clips_sys::DefruleName(defrule)),
+                    CStr::from_ptr(clips_sys::DefruleModule(defrule)),
+                )
+            };
+
+            counts.insert(
+                qualify_name(&name.to_string_lossy(), Some(&module.to_string_lossy())),
+                count,
+            );
+        }
+
+        Ok(counts)
+    }
+
+    pub fn reset_fire_counts(&mut self) {
+        let Some(record) = self.rule_fire_counter.as_ref() else {
+            return;
+        };
+
+        for defrule in constructs_in_all_modules(self.raw, clips_sys::GetNextDefrule) {
+            for count in disjunct_fire_counts(record.dataID, defrule) {
+                count.count = 0;
+            }
+        }
+    }
+
+    pub fn add_router(
+        &mut self,
+        name: &str,
+        priority: i32,
+        router: RegisterableRouter,
+    ) -> CLIPSResult<()> {
+        let supports = router.supports();
+
+        let mut router_map = self.retrieve_router_map();
+        let previous_router = router_map.insert(name.to_string(), router);
+        self.store_router_map(router_map);
+
+        let name_str = self.register_string(CString::new(name).unwrap());
+
+        let res = unsafe {
+            clips_sys::AddRouter(
+                self.raw,
+                name_str,
+                priority,
+                Some(router_query),
+                if supports.contains(RouterSupport::WRITE) {
+                    Some(router_write)
+                } else {
+                    None
+                },
+                if supports.contains(RouterSupport::READ) {
+                    Some(router_read)
+                } else {
+                    None
+                },
+                if supports.contains(RouterSupport::READ) {
+                    Some(router_unread)
+                } else {
+                    None
+                },
+                Some(router_exit),
+                name_str as *mut _,
+            )
+        };
+
+        if res {
+            Ok(())
+        } else {
+            let mut router_map = self.retrieve_router_map();
+            match previous_router {
+                Some(previous_router) => router_map.insert(name.to_string(), previous_router),
+                None => router_map.remove(name),
+            };
+            self.store_router_map(router_map);
+
+            self.unregister_string(name_str);
+
+            Err(CLIPSError::AddRouter)
+        }
+    }
+
+    pub fn assert_fact(
+        &mut self,
+        data: Box<dyn IntoFactOrInstance<FactBuilderData>>,
+        module: Option<&str>,
+    ) -> CLIPSResult<()> {
+        self.assert_fact_returning_raw(data, module).map(|_| ())
+    }
+
+    fn assert_fact_returning_raw(
+        &mut self,
+        data: Box<dyn IntoFactOrInstance<FactBuilderData>>,
+        module: Option<&str>,
+    ) -> CLIPSResult<*mut clips_sys::Fact> {
+        let fb_data = self.fact_builder_data(data.definition_name(), module)?;
+
+        data.into_fact_or_instance(&fb_data)?;
+        fb_data.assert()
+    }
+
+    pub fn assert_fact_with_provenance(
+        &mut self,
+        data: Box<dyn IntoFactOrInstance<FactBuilderData>>,
+        module: Option<&str>,
+        provenance: &str,
+    ) -> CLIPSResult<()> {
+        let fact_provenance = self.install_fact_provenance()?;
+        let fact = self.assert_fact_returning_raw(data, module)?;
+        let index = unsafe { clips_sys::FactIndex(fact) };
+
+        fact_provenance
+            .lock()
+            .unwrap()
+            .insert(index, provenance.to_string());
+
+        Ok(())
+    }
+
+    pub fn fact_provenance(&self, index: i64) -> Option<String> {
+        self.fact_provenance
+            .as_ref()
+            .and_then(|fact_provenance| fact_provenance.lock().unwrap().get(&index).cloned())
+    }
+
+    pub fn facts_with_provenance(&self, provenance: &str) -> CLIPSResult<Vec<RetrievedFact>> {
+        let Some(fact_provenance) = &self.fact_provenance else {
+            return Ok(Vec::new());
+        };
+        let fact_provenance = fact_provenance.lock().unwrap();
+
+        let mut facts = Vec::new();
+        let mut fact = unsafe { clips_sys::GetNextFact(self.raw, ptr::null_mut()) };
+        while !fact.is_null() {
+            let index = unsafe { clips_sys::FactIndex(fact) };
+            if fact_provenance.get(&index).map(String::as_str) == Some(provenance) {
+                facts.push(retrieve_fact(self.raw, fact)?);
+            }
+
+            fact = unsafe { clips_sys::GetNextFact(self.raw, fact) };
+        }
+
+        Ok(facts)
+    }
+
+    // The retract callback stays registered for as long as the environment lives, so tags of facts retracted by anything (rules, `reset`, `clear`, ...) are forgotten.
+    fn install_fact_provenance(&mut self) -> CLIPSResult<SharedFactProvenance> {
+        if let Some(fact_provenance) = &self.fact_provenance {
+            return Ok(fact_provenance.clone());
+        }
+
+        let fact_provenance = SharedFactProvenance::default();
+        let callback_name = CString::new(FACT_PROVENANCE_CALLBACK).unwrap();
+        let registered = unsafe {
+            clips_sys::AddRetractFunction(
+                self.raw,
+                callback_name.as_ptr(),
+                Some(forget_fact_provenance),
+                0,
+                Arc::as_ptr(&fact_provenance) as *mut c_void,
+            )
+        };
+
+        if !registered {
+            return Err(CLIPSError::NameInUse);
+        }
+
+        self.fact_provenance = Some(fact_provenance.clone());
+        Ok(fact_provenance)
+    }
+
+    pub fn assert_fact_allow_duplicate(
+        &mut self,
+        data: Box<dyn IntoFactOrInstance<FactBuilderData>>,
+        module: Option<&str>,
+        allow: bool,
+    ) -> CLIPSResult<()> {
+        let previous = unsafe { clips_sys::SetFactDuplication(self.raw, allow) };
+        let res = self.assert_fact(data, module);
+        unsafe { clips_sys::SetFactDuplication(self.raw, previous) };
+
+        res
+    }
+
+    // CLIPS doesn't make fact builders for ordered facts, so the fact is created directly from the implied template, which holds all the fields in a single multislot.
+    pub fn assert_ordered(&mut self, head: &str, values: Vec<CLIPSValue>) -> CLIPSResult<i64> {
+        let deftemplate = self.implied_deftemplate(head)?;
+        self.assert_implied(deftemplate, values)
+    }
+
+    fn assert_implied(
+        &mut self,
+        deftemplate: *mut clips_sys::Deftemplate,
+        values: Vec<CLIPSValue>,
+    ) -> CLIPSResult<i64> {
+        let fact = unsafe { clips_sys::CreateFact(deftemplate) };
+        let mut fields: clips_sys::CLIPSValue = CLIPSInto::into(values, self.raw);
+
+        if !unsafe { clips_sys::PutFactSlot(fact, ptr::null(), &mut fields) } {
+            unsafe { clips_sys::ReturnFact(self.raw, fact) };
+            return Err(CLIPSError::UnableToAssertFact);
+        }
+
+        // `Assert` gets rid of the fact itself if it fails.
+        let fact = unsafe { clips_sys::Assert(fact) };
+        if fact.is_null() {
+            return Err(CLIPSError::UnableToAssertFact);
+        }
+
+        Ok(unsafe { clips_sys::FactIndex(fact) })
+    }
+
+    // CLIPS only creates implied templates when it parses an ordered fact, so if there isn't one yet, we parse an `assert` that never runs.
+    fn implied_deftemplate(&mut self, head: &str) -> CLIPSResult<*mut clips_sys::Deftemplate> {
+        check_query_construct_name(head)?;
+        let head_cstr = CString::new(head).unwrap();
+
+        let mut deftemplate = unsafe { clips_sys::FindDeftemplate(self.raw, head_cstr.as_ptr()) };
+        if deftemplate.is_null() {
+            let expression = CString::new(format!("(if FALSE then (assert ({})))", head)).unwrap();
+            let mut res = clips_sys::CLIPSValue::default();
+            unsafe { clips_sys::Eval(self.raw, expression.as_ptr(), &mut res) };
+
+            deftemplate = unsafe { clips_sys::FindDeftemplate(self.raw, head_cstr.as_ptr()) };
+        }
+
+        if deftemplate.is_null() || unsafe { (*deftemplate).implied() } == 0 {
+            return Err(CLIPSError::TemplateNotFound);
+        }
+
+        Ok(deftemplate)
+    }
+
+    pub fn assert_string(&mut self, fact: &str) -> CLIPSResult<i64> {
+        let fact_cstr = CString::new(fact).map_err(|_| CLIPSError::ParsingError)?;
+        let fact = unsafe { clips_sys::AssertString(self.raw, fact_cstr.as_ptr()) };
+
+        if fact.is_null() {
+            return match unsafe { clips_sys::GetAssertStringError(self.raw) } {
+                clips_sys::AssertStringError_ASE_PARSING_ERROR => Err(CLIPSError::ParsingError),
+                clips_sys::AssertStringError_ASE_RULE_NETWORK_ERROR => Err(CLIPSError::RuleNetwork),
+                _ => Err(CLIPSError::UnableToAssertFact),
+            };
+        }
+
+        Ok(unsafe { clips_sys::FactIndex(fact) })
+    }
+
+    pub fn duplicate_fact(
+        &mut self,
+        index: i64,
+        mut overrides: HashMap<String, CLIPSValue>,
+    ) -> CLIPSResult<i64> {
+        let fact = self.find_fact(index).ok_or(CLIPSError::FactNotFound)?;
+        let original = retrieve_fact(self.raw, fact)?;
+
+        if overrides
+            .keys()
+            .any(|slot| !original.slots.iter().any(|(name, _)| name == slot))
+        {
+            return Err(CLIPSError::SlotNotFound);
+        }
+
+        let deftemplate = unsafe { clips_sys::FactDeftemplate(fact) };
+
+        if unsafe { (*deftemplate).implied() } != 0 {
+            let fields = match overrides.remove("implied") {
+                Some(CLIPSValue::Multifield(values)) => values,
+                Some(value) => vec![value],
+                None => match original.slots.into_iter().next() {
+                    Some((_, CLIPSValue::Multifield(values))) => values,
+                    _ => Vec::new(),
+                },
+            };
+
+            return self.assert_implied(deftemplate, fields);
+        }
+
+        let module = unsafe { CStr::from_ptr(clips_sys::DeftemplateModule(deftemplate)) };
+        let fb_data = self.fact_builder_data(
+            &original.template,
+            Some(&clips_cstr_to_str(self.raw, module, "a module name")?),
+        )?;
+
+        for (slot, value) in original.slots {
+            let value = overrides.remove(&slot).unwrap_or(value);
+
+            if let Err(e) = fb_data.put_slot(&slot, value) {
+                fb_data.abort();
+                return Err(e);
+            }
+        }
+
+        let fact = fb_data.assert()?;
+        Ok(unsafe { clips_sys::FactIndex(fact) })
+    }
+
+    pub fn edit_multislot(
+        &mut self,
+        index: i64,
+        slot: &str,
+        edit: MultislotEdit,
+    ) -> CLIPSResult<i64> {
+        let fact = self.find_fact(index).ok_or(CLIPSError::FactNotFound)?;
+        let slot_cstr = CString::new(slot).map_err(|_| CLIPSError::SlotNotFound)?;
+
+        let mut slot_value = clips_sys::CLIPSValue::default();
+        match unsafe { clips_sys::GetFactSlot(fact, slot_cstr.as_ptr(), &mut slot_value) } {
+            clips_sys::GetSlotError_GSE_NO_ERROR => {}
+            clips_sys::GetSlotError_GSE_INVALID_TARGET_ERROR => {
+                return Err(CLIPSError::FactOrInstanceRemoved)
+            }
+            _ => return Err(CLIPSError::SlotNotFound),
+        }
+
+        if unsafe { (*slot_value.__bindgen_anon_1.header).type_ } as u32
+            != clips_sys::MULTIFIELD_TYPE
+        {
+            return Err(CLIPSError::SlotNotMultifield);
+        }
+
+        let multifield = unsafe { slot_value.__bindgen_anon_1.multifieldValue };
+        let len = unsafe { (*multifield).length };
+        // `contents` holds `length` values even though it's declared as a single-element array.
+        let contents = unsafe { (*multifield).contents.as_mut_ptr() };
+
+        let (index, value) = match edit {
+            MultislotEdit::Append(value) => (len, Some(value)),
+            MultislotEdit::Insert { index, value } if index <= len => (index, Some(value)),
+            MultislotEdit::Remove { index } if index < len => (index, None),
+            MultislotEdit::Insert { index, .. } | MultislotEdit::Remove { index } => {
+                return Err(CLIPSError::MultislotIndexOutOfRange { index, len })
+            }
+        };
+
+        // Everything from `index` on is kept after an insertion, and everything after it after a removal.
+        let (new_len, rest) = match value {
+            Some(_) => (len + 1, index),
+            None => (len - 1, index + 1),
+        };
+        let mb = unsafe { clips_sys::CreateMultifieldBuilder(self.raw, new_len) };
+
+        // The values are copied as they are, so only the edited one is converted.
+        unsafe {
+            for i in 0..index {
+                clips_sys::MBAppend(mb, contents.add(i));
+            }
+
+            if let Some(value) = value {
+                let mut value = CLIPSInto::into(value, self.raw);
+                clips_sys::MBAppend(mb, &mut value);
+            }
+
+            for i in rest..len {
+                clips_sys::MBAppend(mb, contents.add(i));
+            }
+        }
+
+        let mut new_value = clips_sys::CLIPSValue::default();
+        new_value.__bindgen_anon_1.multifieldValue = unsafe { clips_sys::MBCreate(mb) };
+        unsafe { clips_sys::MBDispose(mb) };
+
+        // The fact modifier only checks the new value against the slot. `FMModify()` frees the multifield after the modified fact took it over, so the fact is replaced the way the `modify` function does it instead.
+        let fm = unsafe { clips_sys::CreateFactModifier(self.raw, fact) };
+        if fm.is_null() {
+            return Err(translate_fact_modifier_error(unsafe {
+                clips_sys::FMError(self.raw)
+            }));
+        }
+
+        let checked = translate_put_slot_error(unsafe {
+            clips_sys::FMPutSlot(fm, slot_cstr.as_ptr(), &mut new_value)
+        });
+        unsafe { clips_sys::FMDispose(fm) };
+        checked?;
+
+        let deftemplate = unsafe { clips_sys::FactDeftemplate(fact) };
+        let slot_count = unsafe { (*deftemplate).numberOfSlots } as usize;
+
+        let mut position = 0;
+        let mut template_slot = unsafe { (*deftemplate).slotList };
+        while !template_slot.is_null()
+            && unsafe { CStr::from_ptr((*(*template_slot).slotName).contents) }
+                != slot_cstr.as_c_str()
+        {
+            position += 1;
+            template_slot = unsafe { (*template_slot).next };
+        }
+
+        // Slots that aren't changed hold the void constant, and the changed ones have their bit set in the change map.
+        let mut values = vec![clips_sys::CLIPSValue::default(); slot_count];
+        for value in values.iter_mut() {
+            value.__bindgen_anon_1.voidValue = unsafe { (*self.raw).VoidConstant };
+        }
+        values[position].__bindgen_anon_1.multifieldValue = unsafe {
+            clips_sys::CopyMultifield(self.raw, new_value.__bindgen_anon_1.multifieldValue)
+        };
+
+        let mut change_map = vec![0u8; slot_count.div_ceil(8)];
+        change_map[position / 8] |= 1 << (position % 8);
+
+        let modified = unsafe {
+            clips_sys::ReplaceFact(
+                self.raw,
+                fact,
+                values.as_mut_ptr(),
+                change_map.as_mut_ptr() as *mut c_char,
+            )
+        };
+
+        if modified.is_null() {
+            Err(CLIPSError::UnableToModifyFact)
+        } else {
+            Ok(unsafe { clips_sys::FactIndex(modified) })
+        }
+    }
+
+    fn find_fact(&self, index: i64) -> Option<*mut clips_sys::Fact> {
+        let mut fact = unsafe { clips_sys::GetNextFact(self.raw, ptr::null_mut()) };
+        while !fact.is_null() {
+            if unsafe { clips_sys::FactIndex(fact) } == index {
+                return Some(fact);
+            }
+
+            fact = unsafe { clips_sys::GetNextFact(self.raw, fact) };
+        }
+
+        None
+    }
+
+    pub fn validate_fact(
+        &mut self,
+        data: Box<dyn IntoFactOrInstance<FactBuilderData>>,
+        module: Option<&str>,
+    ) -> CLIPSResult<()> {
+        let fb_data = self.fact_builder_data(data.definition_name(), module)?;
+
+        let res = data.into_fact_or_instance(&fb_data);
+        fb_data.abort();
+        res
+    }
+
+    // Looks for a template first, and then for a class, which gives `ClassNotFound` if there's neither.
+    pub fn validate_slot_map(
+        &mut self,
+        template_or_class: &str,
+        slots: &HashMap<String, CLIPSValue>,
+    ) -> CLIPSResult<Vec<SlotMismatch>> {
+        let mut mismatches = Vec::new();
+
+        let template_cstr =
+            CString::new(template_or_class).map_err(|_| CLIPSError::TemplateNotFound)?;
+        let deftemplate = unsafe { clips_sys::FindDeftemplate(self.raw, template_cstr.as_ptr()) };
+
+        if deftemplate.is_null() {
+            let ib_data = self.instance_builder_data(template_or_class, None)?;
+            let res = collect_slot_mismatches(slots, &mut mismatches, |slot, value| {
+                ib_data.put_slot(slot, value.clone())
+            });
+            ib_data.abort();
+            res?;
+        } else {
+            let fb_data = self.fact_builder_data(template_or_class, None)?;
+            let res = collect_slot_mismatches(slots, &mut mismatches, |slot, value| {
+                fb_data.put_slot(slot, value.clone())
+            });
+            fb_data.abort();
+            res?;
+
+            let mut slot_names = clips_sys::CLIPSValue::default();
+            unsafe { clips_sys::DeftemplateSlotNames(deftemplate, &mut slot_names) };
+
+            for slot_name in extract_symbol_list(self.raw, slot_names)? {
+                let slot_name_cstr = CString::new(slot_name.as_str()).unwrap();
+                let required = unsafe {
+                    clips_sys::DeftemplateSlotDefaultP(deftemplate, slot_name_cstr.as_ptr())
+                        == clips_sys::DefaultType_NO_DEFAULT
+                };
+
+                if required && !slots.contains_key(&slot_name) {
+                    mismatches.push(SlotMismatch {
+                        slot: slot_name,
+                        kind: SlotMismatchKind::MissingValue,
+                    });
+                }
+            }
+        }
+
+        // The map has no order of its own, so the slots are sorted to always report them the same way.
+        mismatches.sort_by(|a, b| a.slot.cmp(&b.slot));
+
+        Ok(mismatches)
+    }
+
+    fn fact_builder_data(
+        &mut self,
+        template: &str,
+        module: Option<&str>,
+    ) -> CLIPSResult<FactBuilderData> {
+        // Builders are cached by the fully qualified name, so templates with the same name in different modules get different builders.
+        let template_name = self.qualified_template_name(template, module)?;
+
+        let fb = if let Some(fb) = self.fact_builders.get(&template_name) {
+            fb.fb
+        } else {
+            let template_name_cstr = CString::new(template_name.as_str()).unwrap();
+            let fb = unsafe { clips_sys::CreateFactBuilder(self.raw, template_name_cstr.as_ptr()) };
+            self.fact_builders
+                .insert(template_name, CLIPSFactBuilder { fb });
+            fb
+        };
+
+        Ok(FactBuilderData::new(fb, self.raw))
+    }
+
+    pub fn assert_fact_graph(
+        &mut self,
+        graph: FactGraph,
+        module: Option<&str>,
+        rollback: bool,
+    ) -> FactGraphReport {
+        // A fresh `gensym*` symbol isn't used anywhere yet, so every fact we assert is new and retracting it can't remove a fact that was already there. Gensym symbols are `gen` followed by digits, so the conversion never replaces anything.
+        let link = self.gensym_star_cstr().to_string_lossy().into_owned();
+
+        let mut asserted = Vec::new();
+        let results = graph
+            .into_linked_facts(&link)
+            .into_iter()
+            .map(|fact| {
+                let fact = self.assert_fact_returning_raw(Box::new(fact), module)?;
+                asserted.push(fact);
+                Ok(())
+            })
+            .collect::<Vec<_>>();
+
+        let rolled_back = rollback && asserted.len() < results.len();
+        if rolled_back {
+            for fact in asserted.iter() {
+                unsafe { clips_sys::Retract(*fact) };
+            }
+        }
+
+        FactGraphReport {
+            link,
+            results,
+            rolled_back,
+        }
+    }
+
+    pub fn assert_fact_with_support(
+        &mut self,
+        data: SupportedPayload,
+        module: Option<&str>,
+        tag: &str,
+    ) -> CLIPSResult<()> {
+        // CLIPS makes the rule's module the current one while its actions run, so the payload's template is looked up from the module that's current now.
+        let module = match module {
+            Some(module) => module.to_string(),
+            None => clips_cstr_to_string(
+                self.raw,
+                unsafe {
+                    CStr::from_ptr(clips_sys::DefmoduleName(clips_sys::GetCurrentModule(
+                        self.raw,
+                    )))
+                },
+                "a module name",
+            )?,
+        };
+
+        let supported_assert = self.install_logical_support()?;
+
+        // Every support fact needs its own id, otherwise CLIPS would see a duplicate fact and the rule wouldn't be activated again.
+        self.support_id_counter += 1;
+        let id = self.support_id_counter;
+
+        *supported_assert.lock().unwrap() = SupportedAssert::Pending {
+            id,
+            payload: data,
+            module,
+        };
+
+        let support_fact = SlotMap::new(SUPPORT_TEMPLATE)
+            .slot("tag", CLIPSValue::String(tag.to_string()))
+            .slot("id", CLIPSValue::Int(id));
+
+        let res = self
+            .assert_fact(Box::new(support_fact), None)
+            .and_then(|_| {
+                self.fire_support_rule(&supported_assert);
+
+                match std::mem::take(&mut *supported_assert.lock().unwrap()) {
+                    SupportedAssert::Done(res) => res,
+                    // The crate's rule never fired for this support fact, so the payload was never asserted.
+                    _ => Err(CLIPSError::UnableToAssertFact),
+                }
+            });
+
+        // Nothing is left pending if the support fact itself couldn't be asserted.
+        *supported_assert.lock().unwrap() = SupportedAssert::Empty;
+
+        if res.is_err() {
+            self.retract_support_facts(|_, fact_id| fact_id == id)?;
+        }
+
+        res
+    }
+
+    // `Run()` moves on to the next module on the focus stack once the focused one has no activations left, so it's only called while the support module still has some. Activations left over for older support facts fire without asserting anything.
+    fn fire_support_rule(&mut self, supported_assert: &SharedSupportedAssert) {
+        let module_cstr = CString::new(SUPPORT_MODULE).unwrap();
+        let defmodule = unsafe { clips_sys::FindDefmodule(self.raw, module_cstr.as_ptr()) };
+        let already_focused = unsafe { clips_sys::GetFocus(self.raw) } == defmodule;
+
+        if !already_focused {
+            unsafe { clips_sys::Focus(defmodule) };
+        }
+
+        while matches!(
+            *supported_assert.lock().unwrap(),
+            SupportedAssert::Pending { .. }
+        ) && self.module_has_activations(defmodule)
+        {
+            unsafe { clips_sys::Run(self.raw, 1) };
+        }
+
+        if !already_focused && unsafe { clips_sys::GetFocus(self.raw) } == defmodule {
+            unsafe { clips_sys::PopFocus(self.raw) };
+        }
+    }
+
+    // `GetNextActivation()` only goes through the agenda of the current module.
+    fn module_has_activations(&mut self, defmodule: *mut clips_sys::Defmodule) -> bool {
+        unsafe {
+            let current_module = clips_sys::SetCurrentModule(self.raw, defmodule);
+            let has_activations =
+                !clips_sys::GetNextActivation(self.raw, ptr::null_mut()).is_null();
+            clips_sys::SetCurrentModule(self.raw, current_module);
+            has_activations
+        }
+    }
+
+    pub fn withdraw_support(&mut self, tag: &str) -> CLIPSResult<usize> {
+        self.retract_support_facts(|fact_tag, _| fact_tag == tag)
+    }
+
+    // The UDF is added before the rule that calls it, and both are added back if a `clear` removed the rule and template.
+    fn install_logical_support(&mut self) -> CLIPSResult<SharedSupportedAssert> {
+        let supported_assert = match &self.supported_assert {
+            Some(supported_assert) => supported_assert.clone(),
+            None => {
+                let supported_assert = SharedSupportedAssert::default();
+                self.add_udf(
+                    SUPPORT_UDF,
+                    UDFType::Void,
+                    1,
+                    1,
+                    vec![UDFType::Integer],
+                    supported_assert_udf(supported_assert.clone()),
+                )?;
+                self.supported_assert = Some(supported_assert.clone());
+                supported_assert
+            }
+        };
+
+        // Loading a defmodule makes it the current module.
+        let template_cstr = CString::new(SUPPORT_TEMPLATE).unwrap();
+        if unsafe { clips_sys::FindDeftemplate(self.raw, template_cstr.as_ptr()) }.is_null() {
+            let current_module = unsafe { clips_sys::GetCurrentModule(self.raw) };
+            let res = self.load_from_str(SUPPORT_CONSTRUCTS);
+            unsafe { clips_sys::SetCurrentModule(self.raw, current_module) };
+            res?;
+        }
+
+        Ok(supported_assert)
+    }
+
+    fn retract_support_facts<F: Fn(&str, i64) -> bool>(
+        &mut self,
+        matches: F,
+    ) -> CLIPSResult<usize> {
+        let template_cstr = CString::new(SUPPORT_TEMPLATE).unwrap();
+        let template = unsafe { clips_sys::FindDeftemplate(self.raw, template_cstr.as_ptr()) };
+
+        if template.is_null() {
+            return Ok(0);
+        }
+
+        // Retracting while going through the facts would invalidate the iteration, so the facts are collected first.
+        let mut to_retract = Vec::new();
+        let mut fact = unsafe { clips_sys::GetNextFactInTemplate(template, ptr::null_mut()) };
+        while !fact.is_null() {
+            let support = retrieve_fact(self.raw, fact)?;
+
+            if let (Some(CLIPSValue::String(tag)), Some(CLIPSValue::Int(id))) =
+                (support.slot("tag"), support.slot("id"))
+            {
+                if matches(tag, *id) {
+                    to_retract.push(fact);
+                }
+            }
+
+            fact = unsafe { clips_sys::GetNextFactInTemplate(template, fact) };
+        }
+
+        for fact in to_retract.iter() {
+            unsafe { clips_sys::Retract(*fact) };
+        }
+
+        Ok(to_retract.len())
+    }
+
+    pub fn make_instance(
+        &mut self,
+        data: Box<dyn IntoFactOrInstance<InstanceBuilderData>>,
+        instance_name: Option<&str>,
+        module: Option<&str>,
+    ) -> CLIPSResult<()> {
+        self.build_instance(data, instance_name, module).map(|_| ())
+    }
+
+    pub fn make_instance_from_map(
+        &mut self,
+        class: &str,
+        instance_name: Option<&str>,
+        slots: HashMap<String, CLIPSValue>,
+    ) -> CLIPSResult<String> {
+        let instance = slots
+            .into_iter()
+            .fold(SlotMap::new(class), |instance, (slot, value)| {
+                instance.slot(slot, value)
+            });
+
+        self.build_instance(Box::new(instance), instance_name, None)
+    }
+
+    fn build_instance(
+        &mut self,
+        data: Box<dyn IntoFactOrInstance<InstanceBuilderData>>,
+        instance_name: Option<&str>,
+        module: Option<&str>,
+    ) -> CLIPSResult<String> {
+        let ib_data = self.instance_builder_data(data.definition_name(), module)?;
+
+        data.into_fact_or_instance(&ib_data)?;
+
+        let res = match (instance_name, self.instance_name_prefix.is_some()) {
+            (None, true) => {
+                let generated_name = self.next_instance_name();
+                ib_data.make(Some(&generated_name))
+            }
+            _ => ib_data.make(instance_name),
+        };
+
+        if res.is_ok() {
+            self.stats.instances_made += 1;
+        }
+
+        res
+    }
+
+    fn instance_builder_data(
+        &mut self,
+        class: &str,
+        module: Option<&str>,
+    ) -> CLIPSResult<InstanceBuilderData> {
+        let class_name = self.qualified_class_name(class, module)?;
+
+        let ib = if let Some(ib) = self.instance_builders.get(&class_name) {
+            ib.ib
+        } else {
+            let class_name_cstr = CString::new(class_name.as_str()).unwrap();
+            let ib =
+                unsafe { clips_sys::CreateInstanceBuilder(self.raw, class_name_cstr.as_ptr()) };
+            self.instance_builders
+                .insert(class_name, CLIPSInstanceBuilder { ib });
+            ib
+        };
+
+        Ok(InstanceBuilderData::new(ib, self.raw))
+    }
+
+    pub fn find_all_facts(
+        &mut self,
+        template: &str,
+        query: &str,
+    ) -> CLIPSResult<Vec<RetrievedFact>> {
+        self.find_all_fact_pointers(template, query)?
+            .into_iter()
+            .map(|fact| retrieve_fact(self.raw, fact))
+            .collect()
+    }
+
+    pub fn visit_facts(
+        &mut self,
+        template: &str,
+        mut visitor: impl FnMut(FactView<'_>),
+    ) -> CLIPSResult<()> {
+        let template_cstr = CString::new(template).map_err(|_| CLIPSError::TemplateNotFound)?;
+        let deftemplate = unsafe { clips_sys::FindDeftemplate(self.raw, template_cstr.as_ptr()) };
+
+        if deftemplate.is_null() {
+            return Err(CLIPSError::TemplateNotFound);
+        }
+
+        let template_name = clips_cstr_to_str(
+            self.raw,
+            unsafe { CStr::from_ptr(clips_sys::DeftemplateName(deftemplate)) },
+            "a template name",
+        )?;
+
+        // Every fact of the template has the same slots, so their names are only read once. The template holds on to them, so they stay valid for as long as we're going through its facts.
+        let mut slot_names_value = clips_sys::CLIPSValue::default();
+        unsafe { clips_sys::DeftemplateSlotNames(deftemplate, &mut slot_names_value) };
+
+        let slot_names_len = unsafe { (*slot_names_value.__bindgen_anon_1.multifieldValue).length };
+        let slot_names_contents = unsafe {
+            (*slot_names_value.__bindgen_anon_1.multifieldValue)
+                .contents
+                .as_ptr()
+        };
+
+        let mut slot_names = Vec::with_capacity(slot_names_len);
+        for i in 0..slot_names_len {
+            let name = unsafe {
+                CStr::from_ptr(
+                    (*(*slot_names_contents.add(i)).__bindgen_anon_1.lexemeValue).contents,
+                )
+            };
+            slot_names.push((clips_cstr_to_str(self.raw, name, "a slot name")?, name));
+        }
+
+        // `visitor` can't call into CLIPS, so nothing can retract the facts or collect their values while it runs.
+        let mut fact = unsafe { clips_sys::GetNextFactInTemplate(deftemplate, ptr::null_mut()) };
+        while !fact.is_null() {
+            let mut slots = Vec::with_capacity(slot_names.len());
+            for (name, name_cstr) in slot_names.iter() {
+                let mut slot_value = clips_sys::CLIPSValue::default();
+                unsafe { clips_sys::GetFactSlot(fact, name_cstr.as_ptr(), &mut slot_value) };
+
+                slots.push((name.clone(), extract_clipsvalue_ref(self.raw, slot_value)?));
+            }
+
+            visitor(FactView {
+                index: unsafe { clips_sys::FactIndex(fact) },
+                template: template_name.clone(),
+                slots,
+            });
+
+            fact = unsafe { clips_sys::GetNextFactInTemplate(deftemplate, fact) };
+        }
+
+        Ok(())
+    }
+
+    fn find_all_fact_pointers(
+        &mut self,
+        template: &str,
+        query: &str,
+    ) -> CLIPSResult<Vec<*mut clips_sys::Fact>> {
+        let res = self.eval_query("find-all-facts", "?f", template, query)?;
+
+        // `find-all-facts` gives back a multifield of fact addresses, which `extract_clipsvalue()` doesn't handle, so we go through it ourselves.
+        let facts_len = unsafe { (*res.__bindgen_anon_1.multifieldValue).length };
+        let facts = unsafe { (*res.__bindgen_anon_1.multifieldValue).contents.as_ptr() };
+
+        Ok((0..facts_len)
+            .map(|i| unsafe { (*facts.add(i)).__bindgen_anon_1.factValue })
+            .collect())
+    }
+
+    pub fn retract_where(&mut self, template: &str, query: &str) -> CLIPSResult<usize> {
+        let facts = self.capturing_errors(|env| env.find_all_fact_pointers(template, query))?;
+
+        // Retracting a fact can retract others that depended on it for logical support, so we keep all of them around until we're done and skip the ones that are already gone.
+        for fact in facts.iter() {
+            unsafe { clips_sys::RetainFact(*fact) };
+        }
+
+        let mut retracted = 0;
+        for fact in facts.iter() {
+            let removed = unsafe {
+                clips_sys::FactExistp(*fact)
+                    && clips_sys::Retract(*fact) == clips_sys::RetractError_RE_NO_ERROR
+            };
+
+            if removed {
+                retracted += 1;
+            }
+        }
+
+        for fact in facts.iter() {
+            unsafe { clips_sys::ReleaseFact(*fact) };
+        }
+
+        Ok(retracted)
+    }
+
+    // Turns parsing and processing errors into `QueryFailed` with whatever CLIPS wrote to stderr in the meantime. The text is written to stderr again afterwards, so the routers that would have seen it still do.
+    fn capturing_errors<T>(
+        &mut self,
+        f: impl FnOnce(&mut Self) -> CLIPSResult<T>,
+    ) -> CLIPSResult<T> {
+        let name = CString::new("rust-error-capture").unwrap();
+        let mut captured = String::new();
+
+        unsafe {
+            clips_sys::AddRouter(
+                self.raw,
+                name.as_ptr(),
+                i32::MAX,
+                Some(error_capture_query),
+                Some(error_capture_write),
+                None,
+                None,
+                None,
+                &mut captured as *mut String as *mut c_void,
+            )
+        };
+        let res = f(self);
+        unsafe { clips_sys::DeleteRouter(self.raw, name.as_ptr()) };
+
+        if !captured.is_empty() {
+            let logical_name = CString::new(STDERR).unwrap();
+            let text = CString::new(captured.as_str()).unwrap();
+            unsafe { clips_sys::WriteString(self.raw, logical_name.as_ptr(), text.as_ptr()) };
+        }
+
+        match res {
+            Err(CLIPSError::ParsingError | CLIPSError::ProcessingError) => {
+                Err(CLIPSError::QueryFailed { message: captured })
+            }
+            res => res,
+        }
+    }
+
+    pub fn find_all_instances(
+        &mut self,
+        class: &str,
+        query: &str,
+    ) -> CLIPSResult<Vec<RetrievedInstance>> {
+        let res = self.eval_query("find-all-instances", "?ins", class, query)?;
+
+        // `find-all-instances` gives back a multifield of instance names, which `extract_clipsvalue()` doesn't handle, so we go through it ourselves.
+        let names_len = unsafe { (*res.__bindgen_anon_1.multifieldValue).length };
+        let names = unsafe { (*res.__bindgen_anon_1.multifieldValue).contents.as_ptr() };
+
+        // `FindInstance()` can pick up a symbol or string with the same text as the name and then find nothing, so instances are matched by their name lexeme instead, which CLIPS keeps unique.
+        let mut by_name = HashMap::new();
+        let mut instance = unsafe { clips_sys::GetNextInstance(self.raw, ptr::null_mut()) };
+        while !instance.is_null() {
+            by_name.insert(unsafe { clips_sys::InstanceName(instance) }, instance);
+            instance = unsafe { clips_sys::GetNextInstance(self.raw, instance) };
+        }
+
+        let mut instances = Vec::with_capacity(names_len);
+        for i in 0..names_len {
+            let name = unsafe { (*(*names.add(i)).__bindgen_anon_1.lexemeValue).contents };
+            if let Some(&instance) = by_name.get(&name) {
+                instances.push(retrieve_instance(self.raw, instance)?);
+            }
+        }
+
+        Ok(instances)
+    }
+
+    // Evaluates `(<function> ((<variable> <construct>)) <query>)` for the fact-set and instance-set query functions.
+    fn eval_query(
+        &mut self,
+        function: &str,
+        variable: &str,
+        construct: &str,
+        query: &str,
+    ) -> CLIPSResult<clips_sys::CLIPSValue> {
+        // Both pieces end up inside a bigger expression, so we make sure neither of them can close it early and run something else.
+        check_query_construct_name(construct)?;
+        check_query_expression(query)?;
+
+        let expression = CString::new(format!(
+            "({} (({} {})) {})",
+            function, variable, construct, query
+        ))
+        .map_err(|_| CLIPSError::InvalidQuery("the query can't contain null bytes"))?;
+
+        let mut res = clips_sys::CLIPSValue::default();
+        let eval_res = unsafe { clips_sys::Eval(self.raw, expression.as_ptr(), &mut res) };
+
+        match eval_res {
+            clips_sys::EvalError_EE_NO_ERROR => Ok(res),
+            clips_sys::EvalError_EE_PARSING_ERROR => Err(CLIPSError::ParsingError),
+            _ => Err(CLIPSError::ProcessingError),
+        }
+    }
+
+    // Builders keep a pointer to their template or class, so they're disposed of when those may have been undefined. With a module, only the builders for its constructs are disposed of.
+    fn dispose_builders(&mut self, module: Option<&str>) {
+        let prefix = module.map(|module| format!("{}::", module));
+        let is_disposed = |name: &String| prefix.as_ref().is_none_or(|p| name.starts_with(p));
+
+        self.instance_builders.retain(|name, ib| {
+            if is_disposed(name) {
+                unsafe { clips_sys::IBDispose(ib.ib) };
+            }
+            !is_disposed(name)
+        });
+
+        self.fact_builders.retain(|name, fb| {
+            if is_disposed(name) {
+                unsafe { clips_sys::FBDispose(fb.fb) };
+            }
+            !is_disposed(name)
+        });
+    }
+
+    fn qualified_template_name(&self, name: &str, module: Option<&str>) -> CLIPSResult<String> {
+        let name_cstr = CString::new(qualify_name(name, module)).unwrap();
+        let deftemplate = unsafe { clips_sys::FindDeftemplate(self.raw, name_cstr.as_ptr()) };
+
+        if deftemplate.is_null() {
+            return Err(CLIPSError::TemplateNotFound);
+        }
+
+        let (module_name, template_name) = unsafe {
+            (
+                CStr::from_ptr(clips_sys::DeftemplateModule(deftemplate)),
+                CStr::from_ptr(clips_sys::DeftemplateName(deftemplate)),
+            )
+        };
+
+        Ok(format!(
+            "{}::{}",
+            clips_cstr_to_str(self.raw, module_name, "a module name")?,
+            clips_cstr_to_str(self.raw, template_name, "a template name")?
+        ))
+    }
+
+    fn qualified_class_name(&self, name: &str, module: Option<&str>) -> CLIPSResult<String> {
+        let name_cstr = CString::new(qualify_name(name, module)).unwrap();
+        let defclass = unsafe { clips_sys::FindDefclass(self.raw, name_cstr.as_ptr()) };
+
+        if defclass.is_null() {
+            return Err(CLIPSError::ClassNotFound);
+        }
+
+        let (module_name, class_name) = unsafe {
+            (
+                CStr::from_ptr(clips_sys::DefclassModule(defclass)),
+                CStr::from_ptr(clips_sys::DefclassName(defclass)),
+            )
+        };
+
+        Ok(format!(
+            "{}::{}",
+            clips_cstr_to_str(self.raw, module_name, "a module name")?,
+            clips_cstr_to_str(self.raw, class_name, "a class name")?
+        ))
+    }
+
+    pub fn set_instance_name_prefix(&mut self, prefix: String) {
+        self.instance_name_prefix = Some(prefix);
+        self.instance_name_counter = 0;
+    }
+
+    // Skips any name already taken by an existing instance, e.g. one that was restored with its original name.
+    fn next_instance_name(&mut self) -> String {
+        let prefix = self.instance_name_prefix.as_deref().unwrap_or_default();
+
+        loop {
+            self.instance_name_counter += 1;
+            let name = format!("{}{}", prefix, self.instance_name_counter);
+            let name_cstr = CString::new(name.as_str()).unwrap();
+
+            // `FindInstance()` can pick up a symbol with the same text as the name and then find nothing, so it's looked up by its instance name instead.
+            let existing = unsafe {
+                clips_sys::FindInstanceBySymbol(
+                    self.raw,
+                    clips_sys::CreateInstanceName(self.raw, name_cstr.as_ptr()),
+                )
+            };
+
+            if existing.is_null() {
+                return name;
+            }
+        }
+    }
+
+    // `gensym` is only reachable as a CLIPS function, unlike `gensym*`, so it's called like `format` is.
+    pub fn gensym(&mut self) -> CLIPSResult<String> {
+        let function_name = CString::new("gensym").unwrap();
+
+        let fcb = unsafe { clips_sys::CreateFunctionCallBuilder(self.raw, 0) };
+        let mut res = clips_sys::CLIPSValue::default();
+        let call_res = unsafe { clips_sys::FCBCall(fcb, function_name.as_ptr(), &mut res) };
+        unsafe { clips_sys::FCBDispose(fcb) };
+
+        if call_res != clips_sys::FunctionCallBuilderError_FCBE_NO_ERROR {
+            return Err(CLIPSError::ProcessingError);
+        }
+
+        match extract_clipsvalue(self.raw, res)? {
+            CLIPSValue::Symbol(symbol) => Ok(symbol),
+            _ => Err(CLIPSError::ProcessingError),
+        }
+    }
+
+    pub fn gensym_star(&mut self) -> CLIPSResult<String> {
+        clips_cstr_to_string(self.raw, self.gensym_star_cstr(), "a gensym symbol")
+    }
+
+    // CLIPS keeps the symbol for as long as something uses it, and nothing does yet, so it has to be copied before anything else runs.
+    fn gensym_star_cstr<'a>(&mut self) -> &'a CStr {
+        let mut value = clips_sys::UDFValue::default();
+        unsafe { clips_sys::GensymStar(self.raw, &mut value) };
+
+        unsafe { CStr::from_ptr((*value.__bindgen_anon_1.lexemeValue).contents) }
+    }
+
+    // The values are given to `format` as they are, so strings don't need any escaping. CLIPS writes what went wrong with the directives to stderr.
+    pub fn format(&mut self, fmt: &str, args: Vec<CLIPSValue>) -> CLIPSResult<String> {
+        let logical_name = CString::new("nil").unwrap();
+        let fmt_cstr = CString::new(fmt).map_err(|_| CLIPSError::ProcessingError)?;
+        let function_name = CString::new("format").unwrap();
+
+        let fcb = unsafe { clips_sys::CreateFunctionCallBuilder(self.raw, args.len() + 2) };
+        unsafe {
+            clips_sys::FCBAppendSymbol(fcb, logical_name.as_ptr());
+            clips_sys::FCBAppendString(fcb, fmt_cstr.as_ptr());
+        }
+
+        for arg in args {
+            let mut value: clips_sys::CLIPSValue = CLIPSInto::into(arg, self.raw);
+            unsafe { clips_sys::FCBAppend(fcb, &mut value) };
+        }
+
+        let mut res = clips_sys::CLIPSValue::default();
+        let call_res = unsafe { clips_sys::FCBCall(fcb, function_name.as_ptr(), &mut res) };
+        unsafe { clips_sys::FCBDispose(fcb) };
+
+        if call_res != clips_sys::FunctionCallBuilderError_FCBE_NO_ERROR {
+            return Err(CLIPSError::ProcessingError);
+        }
+
+        match extract_clipsvalue(self.raw, res)? {
+            CLIPSValue::String(text) => Ok(text),
+            _ => Err(CLIPSError::ProcessingError),
+        }
+    }
+
+    pub fn set_dynamic_constraint_checking(&mut self, value: bool) {
+        unsafe { clips_sys::SetDynamicConstraintChecking(self.raw, value) };
+    }
+
+    pub fn set_sequence_operator_recognition(&mut self, value: bool) {
+        unsafe { clips_sys::SetSequenceOperatorRecognition(self.raw, value) };
+    }
+
+    pub fn get_sequence_operator_recognition(&self) -> bool {
+        unsafe { clips_sys::GetSequenceOperatorRecognition(self.raw) }
+    }
+
+    pub fn set_value_policy(&mut self, policy: ValueExtractionPolicy) {
+        value::set_value_extraction_policy(self.raw, policy);
+    }
+
+    pub fn value_policy(&self) -> ValueExtractionPolicy {
+        value::value_extraction_policy(self.raw)
+    }
+
+    pub fn set_treat_boolean_symbols_as_bool(&mut self, value: bool) {
+        let mut policy = self.value_policy();
+        policy.booleans_as_symbols = !value;
+        self.set_value_policy(policy);
+    }
+
+    pub fn set_conflict_resolution_strategy(&mut self, strategy: ConflictResolutionStrategy) {
+        unsafe { clips_sys::SetStrategy(self.raw, strategy as u32) };
+    }
+
+    pub fn agenda_order_preview(
+        &mut self,
+        strategy: ConflictResolutionStrategy,
+    ) -> CLIPSResult<Vec<String>> {
+        // CLIPS reorders every agenda when the strategy changes. Ties are broken the same way every time, so switching back gives the original order again.
+        let previous_strategy = unsafe { clips_sys::SetStrategy(self.raw, strategy as u32) };
+
+        let order = self.agenda().map(|activations| {
+            activations
+                .into_iter()
+                .map(|activation| activation.rule)
+                .collect()
+        });
+
+        // The strategy is restored before anything is returned, so an error while reading the agenda can't leave it switched.
+        unsafe { clips_sys::SetStrategy(self.raw, previous_strategy) };
+
+        order
+    }
+
+    // CLIPS only marks loads from files as in progress, so string and batch loads are marked the same way for `get_current_parsing_location()` to report them. The previous state is put back, since a UDF can load constructs in the middle of another load.
+    fn while_loading<T>(&mut self, load: impl FnOnce(*mut clips_sys::Environment) -> T) -> T {
+        let was_loading = unsafe { clips_sys::GetLoadInProgress(self.raw) };
+        unsafe { clips_sys::SetLoadInProgress(self.raw, true) };
+        let res = load(self.raw);
+        unsafe { clips_sys::SetLoadInProgress(self.raw, was_loading) };
+
+        res
+    }
+
+    // A batch file leaves its name behind as the parsing file name, and string loads don't set one, so without this a string load would be reported as happening in the last batch file. Nothing is reset if this is called while something is being loaded (e.g. from a UDF), since that load is still using it.
+    pub fn reset_parser_state(&mut self) {
+        if unsafe { clips_sys::GetLoadInProgress(self.raw) } {
+            return;
+        }
+
+        unsafe {
+            clips_sys::SetParsingFileName(self.raw, ptr::null());
+            clips_sys::SetErrorFileName(self.raw, ptr::null());
+            clips_sys::SetWarningFileName(self.raw, ptr::null());
+            clips_sys::SetLineCount(self.raw, 0);
+        }
+    }
+
+    // `None` when CLIPS isn't loading constructs, since the file name and line count are left over from whatever was parsed last. The file name is empty when loading from a string.
+    pub fn get_current_parsing_location(&mut self) -> CLIPSResult<Option<(String, usize)>> {
+        if !unsafe { clips_sys::GetLoadInProgress(self.raw) } {
+            return Ok(None);
+        }
+
+        let file_name_ptr = unsafe { clips_sys::GetParsingFileName(self.raw) };
+        let file_name = if file_name_ptr.is_null() {
+            String::new()
+        } else {
+            clips_cstr_to_string(
+                self.raw,
+                unsafe { CStr::from_ptr(file_name_ptr) },
+                "the parsing file name",
+            )?
+        };
+
+        let line_number = unsafe { clips_sys::GetLineCount(self.raw) };
+
+        Ok(Some((file_name, line_number as usize)))
+    }
+
+    pub fn save_module(&self, module: &str, path: PathBuf) -> CLIPSResult<usize> {
+        let module_cstr = CString::new(module).map_err(|_| CLIPSError::ModuleNotFound)?;
+        let defmodule = unsafe { clips_sys::FindDefmodule(self.raw, module_cstr.as_ptr()) };
+
+        if defmodule.is_null() {
+            return Err(CLIPSError::ModuleNotFound);
+        }
+
+        let pp_forms = module_pp_forms(self.raw, defmodule);
+        fs::write(path, pp_forms.join("\n"))?;
+
+        Ok(pp_forms.len())
+    }
+
+    pub fn binary_save_facts(&self, path: PathBuf) -> CLIPSResult<usize> {
+        let res = unsafe {
+            let path_cstr = CString::new(path.into_os_string().as_encoded_bytes()).unwrap();
+
+            clips_sys::BinarySaveFacts(
+                self.raw,
+                path_cstr.as_ptr(),
+                clips_sys::SaveScope_VISIBLE_SAVE,
+            )
+        };
+
+        if res == -1 {
+            Err(CLIPSError::UnableToSaveFacts)
+        } else {
+            Ok(res as usize)
+        }
+    }
+
+    pub fn binary_load_facts(&self, path: PathBuf) -> CLIPSResult<usize> {
+        let res = unsafe {
+            let path_cstr = CString::new(path.into_os_string().as_encoded_bytes()).unwrap();
+
+            clips_sys::BinaryLoadFacts(self.raw, path_cstr.as_ptr())
+        };
+
+        if res == -1 {
+            Err(CLIPSError::UnableToLoadFacts)
+        } else {
+            Ok(res as usize)
+        }
+    }
+
+    pub fn binary_load_facts_from_bytes(&self, data: &[u8]) -> CLIPSResult<usize> {
+        let path = self.binary_load_path();
+        fs::write(&path, data).map_err(|_| CLIPSError::UnableToLoadFacts)?;
+
+        let res = self.binary_load_facts(path.clone());
+        let _ = fs::remove_file(&path);
+
+        res
+    }
+
+    pub fn binary_save_instances(&self, path: PathBuf) -> CLIPSResult<usize> {
+        let res = unsafe {
+            let path_cstr = CString::new(path.into_os_string().as_encoded_bytes()).unwrap();
+
+            clips_sys::BinarySaveInstances(
+                self.raw,
+                path_cstr.as_ptr(),
+                clips_sys::SaveScope_VISIBLE_SAVE,
+            )
+        };
+
+        if res == -1 {
+            Err(CLIPSError::UnableToSaveInstances)
+        } else {
+            Ok(res as usize)
+        }
+    }
+
+    pub fn binary_load_instances(&self, path: PathBuf) -> CLIPSResult<usize> {
+        let res = unsafe {
+            let path_cstr = CString::new(path.into_os_string().as_encoded_bytes()).unwrap();
+
+            clips_sys::BinaryLoadInstances(self.raw, path_cstr.as_ptr())
+        };
+
+        if res == -1 {
+            Err(CLIPSError::UnableToLoadInstances)
+        } else {
+            Ok(res as usize)
+        }
+    }
+
+    pub fn binary_load_instances_from_bytes(&self, data: &[u8]) -> CLIPSResult<usize> {
+        let path = self.binary_load_path();
+        fs::write(&path, data).map_err(|_| CLIPSError::UnableToLoadInstances)?;
+
+        let res = self.binary_load_instances(path.clone());
+        let _ = fs::remove_file(&path);
+
+        res
+    }
+
+    // CLIPS can only load binary facts and instances from files, so the bytes are written to one first.
+    fn binary_load_path(&self) -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "clips-rs-binary-load-{}-{:p}",
+            std::process::id(),
+            self.raw
+        ))
+    }
+
+    pub fn savepoint(&mut self, name: &str) -> CLIPSResult<()> {
+        let fingerprint = self.savepoint_fingerprint()?;
+        let globals = self.retrieve_globals_values()?;
+        let modules = self.save_modules()?;
+
+        self.savepoints.insert(
+            name.to_string(),
+            Savepoint {
+                fingerprint,
+                modules,
+                globals,
+            },
+        );
+
+        Ok(())
+    }
+
+    pub fn rollback_to(&mut self, name: &str) -> CLIPSResult<()> {
+        let savepoint = self
+            .savepoints
+            .get(name)
+            .ok_or(CLIPSError::SavepointNotFound)?;
+
+        if self.savepoint_fingerprint()? != savepoint.fingerprint {
+            return Err(CLIPSError::SavepointConstructsChanged);
+        }
+
+        let current = self.save_modules()?;
+
+        if let Err(err) = self.load_modules(&savepoint.modules) {
+            self.load_modules(&current)?;
+            return Err(err);
+        }
+
+        self.restore_globals(savepoint.globals.clone())
+    }
+
+    fn savepoint_fingerprint(&self) -> CLIPSResult<(u64, u64)> {
+        Ok((
+            self.construct_summary()?.fingerprint(),
+            self.constructs_fingerprint(),
+        ))
+    }
+
+    fn save_modules(&self) -> CLIPSResult<Vec<ModuleSavepoint>> {
+        let path = self.savepoint_path();
+        let current_module = unsafe { clips_sys::GetCurrentModule(self.raw) };
+        let mut modules = Vec::new();
+
+        // Saving with `LOCAL_SAVE` only saves the facts and instances whose template or class is in the current module.
+        let mut defmodule = unsafe { clips_sys::GetNextDefmodule(self.raw, ptr::null_mut()) };
+        let saved = loop {
+            if defmodule.is_null() {
+                break Ok(());
+            }
+
+            unsafe { clips_sys::SetCurrentModule(self.raw, defmodule) };
+
+            let module_savepoint = clips_cstr_to_string(
+                self.raw,
+                unsafe { CStr::from_ptr(clips_sys::DefmoduleName(defmodule)) },
+                "a module name",
+            )
+            .and_then(|module| {
+                let facts = binary_save_to_memory(
+                    self.raw,
+                    &path,
+                    clips_sys::BinarySaveFacts,
+                    CLIPSError::UnableToSaveFacts,
+                )?;
+                let instances = binary_save_to_memory(
+                    self.raw,
+                    &path,
+                    clips_sys::BinarySaveInstances,
+                    CLIPSError::UnableToSaveInstances,
+                )?;
+
+                Ok(ModuleSavepoint {
+                    module,
+                    facts,
+                    instances,
+                })
+            });
+
+            match module_savepoint {
+                Ok(module_savepoint) => modules.push(module_savepoint),
+                Err(err) => break Err(err),
             }
-            Ok(CLIPSEnvironmentCommand::ChDir { new_dir, res_tx }) => res_tx
-                .send(set_current_dir(new_dir).map_err(CLIPSError::from))
-                .map_err(create_stub_error),
-            Ok(CLIPSEnvironmentCommand::BatchStar { file_path, res_tx }) => res_tx
-                .send(env.batch_star(file_path))
-                .map_err(create_stub_error),
-            Ok(CLIPSEnvironmentCommand::AddUDF {
-                name,
-                min_args,
-                max_args,
-                return_types,
-                arg_types,
-                function,
-                res_tx,
-            }) => res_tx
-                .send(env.add_udf(&name, return_types, min_args, max_args, arg_types, function))
-                .map_err(create_stub_error),
-            Ok(CLIPSEnvironmentCommand::AddRouter {
-                name,
-                priority,
-                router,
-                res_tx,
-            }) => res_tx
-                .send(env.add_router(&name, priority, router))
-                .map_err(create_stub_error),
-            Ok(CLIPSEnvironmentCommand::RemoveUDF { name, res_tx }) => res_tx
-                .send(env.remove_udf(&name))
-                .map_err(create_stub_error),
-            Ok(CLIPSEnvironmentCommand::AssertFact { value, res_tx }) => res_tx
-                .send(env.assert_fact(value))
-                .map_err(create_stub_error),
-            Ok(CLIPSEnvironmentCommand::MakeInstance {
-                value,
-                instance_name,
-                res_tx,
-            }) => res_tx
-                .send(env.make_instance(value, instance_name.as_deref()))
-                .map_err(create_stub_error),
-            Ok(CLIPSEnvironmentCommand::SetDynamicConstraintChecking { value, res_tx }) => res_tx
-                .send(env.set_dynamic_constraint_checking(value))
-                .map_err(create_stub_error),
-            Ok(CLIPSEnvironmentCommand::SetConflictResolutionStrategy { value, res_tx }) => res_tx
-                .send(env.set_conflict_resolution_strategy(value))
-                .map_err(create_stub_error),
-            Ok(CLIPSEnvironmentCommand::GetCurrentParsingLocation { res_tx }) => res_tx
-                .send(env.get_current_parsing_location())
-                .map_err(create_stub_error),
-            Ok(CLIPSEnvironmentCommand::BinarySaveFacts { path, res_tx }) => res_tx
-                .send(env.binary_save_facts(path))
-                .map_err(create_stub_error),
-            Ok(CLIPSEnvironmentCommand::BinaryLoadFacts { path, res_tx }) => res_tx
-                .send(env.binary_load_facts(path))
-                .map_err(create_stub_error),
-            Ok(CLIPSEnvironmentCommand::BinarySaveInstances { path, res_tx }) => res_tx
-                .send(env.binary_save_instances(path))
-                .map_err(create_stub_error),
-            Ok(CLIPSEnvironmentCommand::BinaryLoadInstances { path, res_tx }) => res_tx
-                .send(env.binary_load_instances(path))
-                .map_err(create_stub_error),
-            Ok(CLIPSEnvironmentCommand::RetrieveGlobalsValues { res_tx }) => res_tx
-                .send(env.retrieve_globals_values())
-                .map_err(create_stub_error),
-            Ok(CLIPSEnvironmentCommand::RestoreGlobals { globals, res_tx }) => res_tx
-                .send(env.restore_globals(globals))
-                .map_err(create_stub_error),
+
+            defmodule = unsafe { clips_sys::GetNextDefmodule(self.raw, defmodule) };
         };
 
-        if let Err(_) = result_res {
-            break;
+        unsafe { clips_sys::SetCurrentModule(self.raw, current_module) };
+        saved.map(|_| modules)
+    }
+
+    // Replaces every fact and instance with the ones in `modules`.
+    fn load_modules(&self, modules: &[ModuleSavepoint]) -> CLIPSResult<()> {
+        let defmodules = modules
+            .iter()
+            .map(|module_savepoint| {
+                let module_cstr = CString::new(module_savepoint.module.as_str()).unwrap();
+                let defmodule = unsafe { clips_sys::FindDefmodule(self.raw, module_cstr.as_ptr()) };
+
+                if defmodule.is_null() {
+                    Err(CLIPSError::ModuleNotFound)
+                } else {
+                    Ok(defmodule)
+                }
+            })
+            .collect::<CLIPSResult<Vec<_>>>()?;
+
+        if unsafe { clips_sys::RetractAllFacts(self.raw) } != clips_sys::RetractError_RE_NO_ERROR {
+            return Err(CLIPSError::ProcessingError);
+        }
+
+        if unsafe { clips_sys::DeleteAllInstances(self.raw) }
+            != clips_sys::UnmakeInstanceError_UIE_NO_ERROR
+        {
+            return Err(CLIPSError::ProcessingError);
+        }
+
+        let path = self.savepoint_path();
+        let current_module = unsafe { clips_sys::GetCurrentModule(self.raw) };
+
+        // Facts go first, since instances can have facts in their slots.
+        let loaded = modules
+            .iter()
+            .zip(defmodules.iter())
+            .try_for_each(|(module_savepoint, defmodule)| {
+                unsafe { clips_sys::SetCurrentModule(self.raw, *defmodule) };
+                binary_load_from_memory(
+                    self.raw,
+                    &path,
+                    &module_savepoint.facts,
+                    clips_sys::BinaryLoadFacts,
+                    CLIPSError::UnableToLoadFacts,
+                )
+            })
+            .and_then(|_| {
+                modules.iter().zip(defmodules.iter()).try_for_each(
+                    |(module_savepoint, defmodule)| {
+                        unsafe { clips_sys::SetCurrentModule(self.raw, *defmodule) };
+                        binary_load_from_memory(
+                            self.raw,
+                            &path,
+                            &module_savepoint.instances,
+                            clips_sys::BinaryLoadInstances,
+                            CLIPSError::UnableToLoadInstances,
+                        )
+                    },
+                )
+            });
+
+        unsafe { clips_sys::SetCurrentModule(self.raw, current_module) };
+        loaded
+    }
+
+    pub fn release_savepoint(&mut self, name: &str) -> bool {
+        self.savepoints.remove(name).is_some()
+    }
+
+    pub(crate) fn export_constructs(&self) -> ConstructExport {
+        let path = self.construct_image_path();
+        let path_cstr = CString::new(path.as_os_str().as_encoded_bytes()).unwrap();
+
+        let image = if unsafe { clips_sys::Bsave(self.raw, path_cstr.as_ptr()) } {
+            fs::read(&path).ok()
+        } else {
+            None
+        };
+        let _ = fs::remove_file(&path);
+
+        ConstructExport {
+            image,
+            pp_forms: all_pp_forms(self.raw),
+            names: user_construct_names(self.raw),
+        }
+    }
+
+    // `Bload` clears the environment before loading the image, so building from the pretty print forms clears it too, and the environment ends up with the same constructs either way.
+    pub(crate) fn import_constructs(&mut self, export: ConstructExport) -> ConstructTransfer {
+        let mut binary_image = false;
+        // Every template and class is undefined either way, so no cached builder would be valid afterwards.
+        self.dispose_builders(None);
+
+        if let Some(image) = export.image {
+            let path = self.construct_image_path();
+            let path_cstr = CString::new(path.as_os_str().as_encoded_bytes()).unwrap();
+
+            if fs::write(&path, image).is_ok() {
+                binary_image = unsafe { clips_sys::Bload(self.raw, path_cstr.as_ptr()) };
+            }
+            let _ = fs::remove_file(&path);
+        }
+
+        if !binary_image {
+            unsafe { clips_sys::Clear(self.raw) };
+            let current_module = unsafe { clips_sys::GetCurrentModule(self.raw) };
+
+            // Constructs that fail to build are found below by their names, so the error isn't needed here.
+            for pp_form in export.pp_forms {
+                if let Ok(pp_form) = CString::new(pp_form) {
+                    unsafe { clips_sys::Build(self.raw, pp_form.as_ptr()) };
+                }
+            }
+
+            unsafe { clips_sys::SetCurrentModule(self.raw, current_module) };
+        }
+
+        let names = construct_names(self.raw);
+        let mut not_transferred = export
+            .names
+            .into_iter()
+            .filter(|name| !names.contains(name))
+            .collect::<Vec<_>>();
+        not_transferred.sort();
+
+        ConstructTransfer {
+            binary_image,
+            not_transferred,
+        }
+    }
+
+    fn construct_image_path(&self) -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "clips-rs-constructs-{}-{:p}",
+            std::process::id(),
+            self.raw
+        ))
+    }
+
+    // CLIPS can only save to and load from files, so the saves go through a file that only lives for as long as a savepoint is being made or rolled back to.
+    fn savepoint_path(&self) -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "clips-rs-savepoint-{}-{:p}",
+            std::process::id(),
+            self.raw
+        ))
+    }
+
+    // Note: this is an implementation based on the C code for `ShowDefglobals()` (in the CLIPS source code). `ShowDefglobals()` prints to a router, but to avoid the indirection we'll directly iterate through every defglobal (if we decided to call `ShowDefglobals()`, we'd have to define a new router that would parse the printed data, so doing things directly saves us a lot of work).
+    pub fn retrieve_globals_values(&self) -> CLIPSResult<CLIPSGlobalsHierarchy> {
+        let mut defglobals_hierarchy = HashMap::new();
+
+        let mut defmodule = unsafe { clips_sys::GetNextDefmodule(self.raw, ptr::null_mut()) };
+        while !defmodule.is_null() {
+            let module_name = unsafe { CStr::from_ptr(clips_sys::DefmoduleName(defmodule)) };
+            let module_name_str = clips_cstr_to_str(self.raw, module_name, "a module name")?;
+            let module_name_str = module_name_str.as_ref();
+
+            if !defglobals_hierarchy.contains_key(module_name_str) {
+                defglobals_hierarchy.insert(module_name_str.to_string(), HashMap::new());
+            }
+
+            let mut curr_defglobal = unsafe {
+                (*clips_sys::GetDefglobalModuleItem(self.raw, defmodule))
+                    .header
+                    .firstItem as *mut clips_sys::defglobal
+            };
+
+            while !curr_defglobal.is_null() {
+                let construct_type = unsafe { (*curr_defglobal).header.constructType };
+                if construct_type != clips_sys::ConstructType_DEFGLOBAL {
+                    return Err(CLIPSError::UnexpectedConstructType(construct_type));
+                } else {
+                    let name = unsafe { CStr::from_ptr((*(*curr_defglobal).header.name).contents) };
+                    let name_str = clips_cstr_to_string(self.raw, name, "a global name")?;
+                    let value = unsafe { (*curr_defglobal).current };
+
+                    defglobals_hierarchy
+                        .get_mut(module_name_str)
+                        .unwrap()
+                        .insert(name_str, extract_clipsvalue(self.raw, value)?);
+                }
+
+                curr_defglobal =
+                    unsafe { (*curr_defglobal).header.next as *mut clips_sys::defglobal };
+            }
+
+            defmodule = unsafe { clips_sys::GetNextDefmodule(self.raw, defmodule) };
+        }
+
+        Ok(defglobals_hierarchy)
+    }
+
+    pub fn reinsert_value(&mut self, target: ValueTarget, value: CLIPSValue) -> CLIPSResult<()> {
+        let mut raw_value: clips_sys::CLIPSValue = CLIPSInto::into(value, self.raw);
+
+        match target {
+            ValueTarget::Global { name } => {
+                let name_cstring = CString::new(name).map_err(|_| CLIPSError::DefglobalNotFound)?;
+                let defglobal =
+                    unsafe { clips_sys::FindDefglobal(self.raw, name_cstring.as_ptr()) };
+
+                if defglobal.is_null() {
+                    return Err(CLIPSError::DefglobalNotFound);
+                }
+
+                unsafe { clips_sys::DefglobalSetValue(defglobal, &mut raw_value) };
+                Ok(())
+            }
+            ValueTarget::InstanceSlot { instance, slot } => {
+                let instance_cstring =
+                    CString::new(instance).map_err(|_| CLIPSError::InstanceNotFound)?;
+                // `FindInstance()` can pick up a symbol with the same text as the name and then find nothing, so it's looked up by its instance name instead.
+                let instance = unsafe {
+                    clips_sys::FindInstanceBySymbol(
+                        self.raw,
+                        clips_sys::CreateInstanceName(self.raw, instance_cstring.as_ptr()),
+                    )
+                };
+
+                if instance.is_null() {
+                    return Err(CLIPSError::InstanceNotFound);
+                }
+
+                let slot_cstring = CString::new(slot).map_err(|_| CLIPSError::SlotNotFound)?;
+                translate_put_slot_error(unsafe {
+                    clips_sys::DirectPutSlot(instance, slot_cstring.as_ptr(), &mut raw_value)
+                })
+            }
+        }
+    }
+
+    pub fn restore_globals(&self, globals: impl Into<CLIPSGlobalsHierarchy>) -> CLIPSResult<()> {
+        for (module_name, globals) in globals.into() {
+            for (global_name, global_value) in globals {
+                let full_global_name = format!("{}::{}", module_name, global_name);
+                let mut raw_value: clips_sys::CLIPSValue = CLIPSInto::into(global_value, self.raw);
+
+                unsafe {
+                    let full_name_cstring = CString::new(full_global_name).unwrap();
+                    let curr_defglobal =
+                        clips_sys::FindDefglobal(self.raw, full_name_cstring.as_ptr());
+
+                    if curr_defglobal.is_null() {
+                        return Err(CLIPSError::DefglobalNotFound);
+                    }
+
+                    clips_sys::DefglobalSetValue(curr_defglobal, &mut raw_value);
+                };
+            }
         }
-    }
-}
 
-const UDF_MAP_ENVIRONMENT_DATA_INDEX: u32 = clips_sys::USER_ENVIRONMENT_DATA + 0;
-const ROUTER_MAP_ENVIRONMENT_DATA_INDEX: u32 = clips_sys::USER_ENVIRONMENT_DATA + 1;
-const STRINGS_TO_DROP_ENVIRONMENT_DATA_INDEX: u32 = clips_sys::USER_ENVIRONMENT_DATA + 2;
+        Ok(())
+    }
 
-type CLIPSEnvironmentUDFMap = HashMap<String, Box<dyn FnMut(UDFData) + Sync + Send>>;
-type CLIPSEnvironmentRouterMap = HashMap<String, RegisterableRouter>;
-type CLIPSEnvironmentStringsToDrop = Vec<*const i8>;
+    // Facts and instances created while dynamic constraint checking is off never had their slot values checked. The fact and instance builders always check the values they're given against the slot constraints, so we validate everything by putting each slot value through a builder again and collecting the errors, without ever asserting or making anything with those builders.
+    pub fn check_constraints(&self) -> CLIPSResult<Vec<ConstraintViolation>> {
+        let mut violations = Vec::new();
 
-pub struct CLIPSEnvironment {
-    raw: *mut clips_sys::Environment,
-    destroy_on_drop: bool,
-    fact_builders: HashMap<String, CLIPSFactBuilder>,
-    instance_builders: HashMap<String, CLIPSInstanceBuilder>,
-}
+        let mut fact = unsafe { clips_sys::GetNextFact(self.raw, ptr::null_mut()) };
+        while !fact.is_null() {
+            let template = unsafe { clips_sys::FactDeftemplate(fact) };
+            let template_name = unsafe { CStr::from_ptr(clips_sys::DeftemplateName(template)) };
 
-impl CLIPSEnvironment {
-    pub fn new() -> CLIPSResult<Self> {
-        let raw = unsafe { clips_sys::CreateEnvironment() };
+            let template_module = unsafe { CStr::from_ptr(clips_sys::DeftemplateModule(template)) };
+            let qualified_name = qualified_cstr(template_module, template_name);
 
-        let udf_map: Box<CLIPSEnvironmentUDFMap> = Box::new(HashMap::new());
-        let router_map: Box<CLIPSEnvironmentRouterMap> = Box::new(HashMap::new());
-        // We unwrap some strings to give them to CLIPS so it can hold onto them while it runs. We also keep a copy of them here, so when we drop the environment we can take back ownership over those strings to properly drop them.
-        let strings_to_drop: Box<CLIPSEnvironmentStringsToDrop> = Box::new(Vec::new());
+            // CLIPS doesn't create fact builders for ordered facts, but they don't have constraints to check anyway.
+            let fb = unsafe { clips_sys::CreateFactBuilder(self.raw, qualified_name.as_ptr()) };
 
-        unsafe {
-            let res = clips_sys::AllocateEnvironmentData(
-                raw,
-                UDF_MAP_ENVIRONMENT_DATA_INDEX,
-                size_of::<Box<CLIPSEnvironmentUDFMap>>(),
-                Some(cleanup_udf_map),
-            );
+            if !fb.is_null() {
+                let source = ConstraintViolationSource::Fact {
+                    index: unsafe { clips_sys::FactIndex(fact) },
+                    template: clips_cstr_to_string(self.raw, template_name, "a template name")?,
+                };
 
-            if !res {
-                return Err(CLIPSError::EnvironmentNotCreated);
+                let mut slot_names = clips_sys::CLIPSValue::default();
+                unsafe { clips_sys::FactSlotNames(fact, &mut slot_names) };
+
+                let res = extract_symbol_list(self.raw, slot_names).and_then(|slot_names| {
+                    check_slots_constraints(
+                        slot_names,
+                        source,
+                        &mut violations,
+                        |slot_name, slot_value| unsafe {
+                            clips_sys::GetFactSlot(fact, slot_name, slot_value)
+                        },
+                        |slot_name, slot_value| unsafe {
+                            clips_sys::FBPutSlot(fb, slot_name, slot_value)
+                        },
+                    )
+                });
+                unsafe { clips_sys::FBDispose(fb) };
+                res?;
             }
 
-            let res = clips_sys::AllocateEnvironmentData(
-                raw,
-                ROUTER_MAP_ENVIRONMENT_DATA_INDEX,
-                size_of::<Box<CLIPSEnvironmentRouterMap>>(),
-                Some(cleanup_router_map),
-            );
+            fact = unsafe { clips_sys::GetNextFact(self.raw, fact) };
+        }
 
-            if !res {
-                return Err(CLIPSError::EnvironmentNotCreated);
-            }
+        let mut instance = unsafe { clips_sys::GetNextInstance(self.raw, ptr::null_mut()) };
+        while !instance.is_null() {
+            let class = unsafe { clips_sys::InstanceClass(instance) };
+            let class_name = unsafe { CStr::from_ptr(clips_sys::DefclassName(class)) };
+            let instance_name = unsafe { CStr::from_ptr(clips_sys::InstanceName(instance)) };
 
-            let res = clips_sys::AllocateEnvironmentData(
-                raw,
-                STRINGS_TO_DROP_ENVIRONMENT_DATA_INDEX,
-                size_of::<Box<CLIPSEnvironmentStringsToDrop>>(),
-                Some(cleanup_strings_to_drop),
-            );
+            let class_module = unsafe { CStr::from_ptr(clips_sys::DefclassModule(class)) };
+            let qualified_name = qualified_cstr(class_module, class_name);
 
-            if !res {
-                return Err(CLIPSError::EnvironmentNotCreated);
-            }
+            let ib = unsafe { clips_sys::CreateInstanceBuilder(self.raw, qualified_name.as_ptr()) };
 
-            clips_sys::SetEnvironmentData(
-                raw,
-                UDF_MAP_ENVIRONMENT_DATA_INDEX,
-                Box::into_raw(udf_map) as *mut _,
-            );
-            clips_sys::SetEnvironmentData(
-                raw,
-                ROUTER_MAP_ENVIRONMENT_DATA_INDEX,
-                Box::into_raw(router_map) as *mut _,
-            );
-            clips_sys::SetEnvironmentData(
-                raw,
-                STRINGS_TO_DROP_ENVIRONMENT_DATA_INDEX,
-                Box::into_raw(strings_to_drop) as *mut _,
-            );
-        }
+            if !ib.is_null() {
+                let source = ConstraintViolationSource::Instance {
+                    name: clips_cstr_to_string(self.raw, instance_name, "an instance name")?,
+                    class: clips_cstr_to_string(self.raw, class_name, "a class name")?,
+                };
 
-        Ok(Self {
-            raw,
-            destroy_on_drop: true,
-            fact_builders: HashMap::new(),
-            instance_builders: HashMap::new(),
-        })
-    }
+                let mut slot_names = clips_sys::CLIPSValue::default();
+                unsafe { clips_sys::ClassSlots(class, &mut slot_names, true) };
+
+                let res = extract_symbol_list(self.raw, slot_names).and_then(|slot_names| {
+                    check_slots_constraints(
+                        slot_names,
+                        source,
+                        &mut violations,
+                        |slot_name, slot_value| unsafe {
+                            clips_sys::DirectGetSlot(instance, slot_name, slot_value)
+                        },
+                        |slot_name, slot_value| unsafe {
+                            clips_sys::IBPutSlot(ib, slot_name, slot_value)
+                        },
+                    )
+                });
+                unsafe { clips_sys::IBDispose(ib) };
+                res?;
+            }
 
-    pub fn from_raw(raw: *mut clips_sys::Environment) -> Self {
-        Self {
-            raw,
-            destroy_on_drop: false,
-            fact_builders: HashMap::new(),
-            instance_builders: HashMap::new(),
+            instance = unsafe { clips_sys::GetNextInstance(self.raw, instance) };
         }
-    }
-
-    pub(crate) fn retrieve_udf_map(&self) -> Box<CLIPSEnvironmentUDFMap> {
-        unsafe {
-            let udf_map_ptr =
-                clips_sys::GetEnvironmentData(self.raw, UDF_MAP_ENVIRONMENT_DATA_INDEX)
-                    as *mut CLIPSEnvironmentUDFMap;
 
-            Box::from_raw(udf_map_ptr)
-        }
+        Ok(violations)
     }
 
-    pub(crate) fn store_udf_map(&self, map: Box<CLIPSEnvironmentUDFMap>) {
-        unsafe {
-            clips_sys::SetEnvironmentData(
-                self.raw,
-                UDF_MAP_ENVIRONMENT_DATA_INDEX,
-                Box::into_raw(map) as *mut _,
-            );
+    // Stops early if the receiving end goes away.
+    pub fn stream_instances(&self, tx: &mpsc::SyncSender<CLIPSResult<RetrievedInstance>>) {
+        let mut instance = unsafe { clips_sys::GetNextInstance(self.raw, ptr::null_mut()) };
+        while !instance.is_null() {
+            if tx.send(retrieve_instance(self.raw, instance)).is_err() {
+                return;
+            }
+
+            instance = unsafe { clips_sys::GetNextInstance(self.raw, instance) };
         }
     }
 
-    pub(crate) fn retrieve_router_map(&self) -> Box<CLIPSEnvironmentRouterMap> {
-        unsafe {
-            let router_map_ptr =
-                clips_sys::GetEnvironmentData(self.raw, ROUTER_MAP_ENVIRONMENT_DATA_INDEX)
-                    as *mut CLIPSEnvironmentRouterMap;
+    // Stops early if the receiving end goes away.
+    pub(crate) fn stream_working_memory(
+        &self,
+        tx: &mpsc::SyncSender<CLIPSResult<WorkingMemoryEntry>>,
+    ) {
+        let mut fact = unsafe { clips_sys::GetNextFact(self.raw, ptr::null_mut()) };
+        while !fact.is_null() {
+            if tx
+                .send(retrieve_fact(self.raw, fact).map(WorkingMemoryEntry::Fact))
+                .is_err()
+            {
+                return;
+            }
 
-            Box::from_raw(router_map_ptr)
+            fact = unsafe { clips_sys::GetNextFact(self.raw, fact) };
         }
-    }
 
-    pub(crate) fn store_router_map(&self, map: Box<CLIPSEnvironmentRouterMap>) {
-        unsafe {
-            clips_sys::SetEnvironmentData(
-                self.raw,
-                ROUTER_MAP_ENVIRONMENT_DATA_INDEX,
-                Box::into_raw(map) as *mut _,
-            );
+        let mut instance = unsafe { clips_sys::GetNextInstance(self.raw, ptr::null_mut()) };
+        while !instance.is_null() {
+            let entry = retrieve_instance(self.raw, instance).map(WorkingMemoryEntry::Instance);
+            if tx.send(entry).is_err() {
+                return;
+            }
+
+            instance = unsafe { clips_sys::GetNextInstance(self.raw, instance) };
         }
     }
 
-    pub(crate) fn retrieve_strings_to_drop(&self) -> Box<CLIPSEnvironmentStringsToDrop> {
-        unsafe {
-            let strings_to_drop_ptr =
-                clips_sys::GetEnvironmentData(self.raw, STRINGS_TO_DROP_ENVIRONMENT_DATA_INDEX)
-                    as *mut CLIPSEnvironmentStringsToDrop;
+    pub fn construct_summary(&self) -> CLIPSResult<ConstructSummary> {
+        let mut summary = ConstructSummary::default();
 
-            Box::from_raw(strings_to_drop_ptr)
-        }
-    }
+        let mut construct_names = user_construct_names(self.raw)
+            .into_iter()
+            .collect::<Vec<_>>();
+        for name in construct_names.iter() {
+            let count = match name.split_once(' ').map(|(kind, _)| kind) {
+                Some("defrule") => &mut summary.defrules,
+                Some("deftemplate") => &mut summary.deftemplates,
+                Some("defclass") => &mut summary.defclasses,
+                Some("deffacts") => &mut summary.deffacts,
+                Some("definstances") => &mut summary.definstances,
+                Some("deffunction") => &mut summary.deffunctions,
+                Some("defgeneric") => &mut summary.defgenerics,
+                Some("defglobal") => &mut summary.defglobals,
+                _ => continue,
+            };
 
-    pub(crate) fn store_strings_to_drop(&self, map: Box<CLIPSEnvironmentStringsToDrop>) {
-        unsafe {
-            clips_sys::SetEnvironmentData(
-                self.raw,
-                STRINGS_TO_DROP_ENVIRONMENT_DATA_INDEX,
-                Box::into_raw(map) as *mut _,
-            );
+            *count += 1;
         }
-    }
 
-    fn send_routers_signal(&mut self, signal: CLIPSSignal) {
-        // TODO: optimise this by storing a list of routers that have SIGNAL support without having to check every time?
-        let mut router_map = self.retrieve_router_map();
-        for router in router_map.values_mut() {
-            if router.supports().contains(RouterSupport::SIGNAL) {
-                router.signal(signal.clone());
+        let mut defmodule = unsafe { clips_sys::GetNextDefmodule(self.raw, ptr::null_mut()) };
+        while !defmodule.is_null() {
+            if is_support_module(defmodule) {
+                defmodule = unsafe { clips_sys::GetNextDefmodule(self.raw, defmodule) };
+                continue;
             }
-        }
-        self.store_router_map(router_map);
-    }
 
-    pub fn load_from_str(&mut self, data: &str) -> CLIPSResult<()> {
-        let res =
-            unsafe { clips_sys::LoadFromString(self.raw, data.as_ptr() as *const i8, data.len()) };
+            let name = unsafe { CStr::from_ptr(clips_sys::DefmoduleName(defmodule)) };
+            construct_names.push(format!(
+                "defmodule {}",
+                clips_cstr_to_string(self.raw, name, "a module name")?
+            ));
+            summary.defmodules += 1;
 
-        if !res {
-            Err(CLIPSError::LoadFromString)
-        } else {
-            Ok(())
+            defmodule = unsafe { clips_sys::GetNextDefmodule(self.raw, defmodule) };
         }
-    }
 
-    pub fn batch_star<P: AsRef<Path>>(&mut self, file_path: P) -> CLIPSResult<()> {
-        let path_str = file_path
-            .as_ref()
-            .to_str()
-            .ok_or_else(|| CLIPSError::PathNotUnicode)?;
-
-        let path_cstring = CString::new(path_str).unwrap();
-        let res = unsafe { clips_sys::BatchStar(self.raw, path_cstring.as_ptr()) };
+        let mut fact = unsafe { clips_sys::GetNextFact(self.raw, ptr::null_mut()) };
+        while !fact.is_null() {
+            summary.facts += 1;
+            fact = unsafe { clips_sys::GetNextFact(self.raw, fact) };
+        }
 
-        if !res {
-            Err(CLIPSError::BatchStar)
-        } else {
-            Ok(())
+        let mut instance = unsafe { clips_sys::GetNextInstance(self.raw, ptr::null_mut()) };
+        while !instance.is_null() {
+            summary.instances += 1;
+            instance = unsafe { clips_sys::GetNextInstance(self.raw, instance) };
         }
-    }
 
-    pub fn run(&mut self) -> CLIPSResult<usize> {
-        self.send_routers_signal(CLIPSSignal::RunStarted { limit: None });
-        let rules_ran = unsafe { clips_sys::Run(self.raw, -1) };
-        self.send_routers_signal(CLIPSSignal::RunFinished { limit: None });
+        construct_names.sort();
+        summary.construct_names = construct_names;
 
-        Ok(rules_ran as usize)
+        Ok(summary)
     }
 
-    pub fn run_limit(&mut self, limit: usize) -> CLIPSResult<usize> {
-        self.send_routers_signal(CLIPSSignal::RunStarted { limit: Some(limit) });
-        let rules_ran = unsafe { clips_sys::Run(self.raw, limit as i64) };
-        self.send_routers_signal(CLIPSSignal::RunFinished { limit: Some(limit) });
-
-        Ok(rules_ran as usize)
+    // Unlike `ConstructSummary::fingerprint()`, this hashes the pretty print forms, so it changes when a construct is redefined with a different body. The forms are sorted, so the order the constructs were loaded in doesn't matter. Constructs without a pretty print form, e.g. the ones loaded from a binary image, aren't hashed.
+    pub fn constructs_fingerprint(&self) -> u64 {
+        let mut pp_forms = all_pp_forms(self.raw);
+        pp_forms.sort();
+        fnv1a_hash(pp_forms.iter().map(String::as_str))
     }
 
-    pub fn add_udf(
-        &mut self,
-        name: &str,
-        return_types: UDFType,
-        min_args: u16,
-        max_args: u16,
-        arg_types: Vec<UDFType>,
-        function: Box<dyn FnMut(UDFData) + Send + Sync>,
-    ) -> CLIPSResult<()> {
-        let arg_types: String = arg_types
-            .into_iter()
-            .map(|a| a.as_character_code())
-            .collect::<Vec<_>>()
-            .join(";");
-        let arg_types = CString::new(arg_types).unwrap();
-        let return_types = CString::new(return_types.as_character_code()).unwrap();
-
-        let mut udf_map = self.retrieve_udf_map();
-        udf_map.insert(name.to_string(), function);
-        self.store_udf_map(udf_map);
-
-        let name_str = CString::new(name).unwrap().into_raw();
-        let mut strings_to_drop = self.retrieve_strings_to_drop();
-        strings_to_drop.push(name_str);
-        self.store_strings_to_drop(strings_to_drop);
-
-        let res = unsafe {
-            clips_sys::AddUDF(
-                self.raw,
-                name_str as *const i8,
-                return_types.as_ptr(),
-                min_args,
-                max_args,
-                arg_types.as_ptr(),
-                Some(call_udf),
-                name_str as *const i8,
-                name_str as *mut _,
-            )
-        };
+    // Walks the hash tables the same way CLIPS does when it looks for lexemes and numbers to free.
+    pub fn symbol_stats(&self, top: usize) -> CLIPSResult<SymbolStats> {
+        let mut stats = SymbolStats::default();
+        let mut lexemes = Vec::new();
+
+        let symbol_table = unsafe { clips_sys::GetSymbolTable(self.raw) };
+        stats.symbols.bytes = SYMBOL_HASH_SIZE * size_of::<*mut clips_sys::CLIPSLexeme>();
+        for bucket in 0..SYMBOL_HASH_SIZE {
+            let mut lexeme = unsafe { *symbol_table.add(bucket) };
+            while !lexeme.is_null() {
+                let contents = unsafe { CStr::from_ptr((*lexeme).contents) };
+                stats.symbols.entries += 1;
+                stats.symbols.bytes +=
+                    size_of::<clips_sys::CLIPSLexeme>() + contents.to_bytes_with_nul().len();
+
+                if top > 0 {
+                    lexemes.push(LexemeUsage {
+                        contents: contents.to_string_lossy().into_owned(),
+                        references: unsafe { (*lexeme).count },
+                    });
+                }
 
-        match res {
-            clips_sys::AddUDFError_AUE_NO_ERROR => Ok(()),
-            clips_sys::AddUDFError_AUE_MIN_EXCEEDS_MAX_ERROR => Err(CLIPSError::MinArgumentsExceedsMax),
-            clips_sys::AddUDFError_AUE_FUNCTION_NAME_IN_USE_ERROR => Err(CLIPSError::NameInUse),
-            clips_sys::AddUDFError_AUE_INVALID_ARGUMENT_TYPE_ERROR => unreachable!("the library should've generated valid argument types"),
-            clips_sys::AddUDFError_AUE_INVALID_RETURN_TYPE_ERROR => unreachable!("the library should've generated valid return types"),
-            _ => unreachable!("a new error value for AddUDF was used by CLIPS, but this library doesn't handle it yet"),
+                lexeme = unsafe { (*lexeme).next };
+            }
         }
-    }
 
-    pub fn remove_udf(&mut self, name: &str) -> bool {
-        let mut udf_map = self.retrieve_udf_map();
-        udf_map.remove(name);
-        self.store_udf_map(udf_map);
+        let float_table = unsafe { clips_sys::GetFloatTable(self.raw) };
+        stats.floats.bytes = FLOAT_HASH_SIZE * size_of::<*mut clips_sys::CLIPSFloat>();
+        for bucket in 0..FLOAT_HASH_SIZE {
+            let mut float = unsafe { *float_table.add(bucket) };
+            while !float.is_null() {
+                stats.floats.entries += 1;
+                stats.floats.bytes += size_of::<clips_sys::CLIPSFloat>();
+                float = unsafe { (*float).next };
+            }
+        }
 
-        let c_str = CString::new(name).unwrap();
-        let res = unsafe { clips_sys::RemoveUDF(self.raw, c_str.as_ptr()) };
-        res
-    }
+        let integer_table = unsafe { clips_sys::GetIntegerTable(self.raw) };
+        stats.integers.bytes = INTEGER_HASH_SIZE * size_of::<*mut clips_sys::CLIPSInteger>();
+        for bucket in 0..INTEGER_HASH_SIZE {
+            let mut integer = unsafe { *integer_table.add(bucket) };
+            while !integer.is_null() {
+                stats.integers.entries += 1;
+                stats.integers.bytes += size_of::<clips_sys::CLIPSInteger>();
+                integer = unsafe { (*integer).next };
+            }
+        }
 
-    pub fn add_router(
-        &mut self,
-        name: &str,
-        priority: i32,
-        router: RegisterableRouter,
-    ) -> CLIPSResult<()> {
-        let supports = router.supports();
+        let bitmap_table = unsafe { clips_sys::GetBitMapTable(self.raw) };
+        stats.bitmaps.bytes = BITMAP_HASH_SIZE * size_of::<*mut clips_sys::CLIPSBitMap>();
+        for bucket in 0..BITMAP_HASH_SIZE {
+            let mut bitmap = unsafe { *bitmap_table.add(bucket) };
+            while !bitmap.is_null() {
+                stats.bitmaps.entries += 1;
+                stats.bitmaps.bytes +=
+                    size_of::<clips_sys::CLIPSBitMap>() + unsafe { (*bitmap).size } as usize;
+                bitmap = unsafe { (*bitmap).next };
+            }
+        }
 
-        let mut router_map = self.retrieve_router_map();
-        router_map.insert(name.to_string(), router);
-        self.store_router_map(router_map);
+        if top > 0 {
+            lexemes.sort_by_key(|lexeme| std::cmp::Reverse(lexeme.contents.len()));
+            stats.longest_lexemes = lexemes.iter().take(top).cloned().collect();
 
-        let name_str = CString::new(name).unwrap().into_raw();
-        let mut strings_to_drop = self.retrieve_strings_to_drop();
-        strings_to_drop.push(name_str);
-        self.store_strings_to_drop(strings_to_drop);
+            lexemes.sort_by_key(|lexeme| std::cmp::Reverse(lexeme.references));
+            lexemes.truncate(top);
+            stats.most_referenced_lexemes = lexemes;
+        }
 
-        let res = unsafe {
-            clips_sys::AddRouter(
-                self.raw,
-                name_str as *const i8,
-                priority,
-                Some(router_query),
-                if supports.contains(RouterSupport::WRITE) {
-                    Some(router_write)
-                } else {
-                    None
-                },
-                if supports.contains(RouterSupport::READ) {
-                    Some(router_read)
-                } else {
-                    None
-                },
-                if supports.contains(RouterSupport::READ) {
-                    Some(router_unread)
-                } else {
-                    None
-                },
-                Some(router_exit),
-                name_str as *mut _,
-            )
-        };
+        Ok(stats)
+    }
 
-        if res {
-            Ok(())
-        } else {
-            Err(CLIPSError::AddRouter)
+    pub fn template_info(&self, template: &str) -> CLIPSResult<TemplateInfo> {
+        let template_cstr = CString::new(template).map_err(|_| CLIPSError::TemplateNotFound)?;
+        let deftemplate = unsafe { clips_sys::FindDeftemplate(self.raw, template_cstr.as_ptr()) };
+
+        if deftemplate.is_null() {
+            return Err(CLIPSError::TemplateNotFound);
         }
+
+        let name = unsafe { CStr::from_ptr(clips_sys::DeftemplateName(deftemplate)) };
+        let module = unsafe { CStr::from_ptr(clips_sys::DeftemplateModule(deftemplate)) };
+
+        let mut slot_names = clips_sys::CLIPSValue::default();
+        unsafe { clips_sys::DeftemplateSlotNames(deftemplate, &mut slot_names) };
+
+        let slots = extract_symbol_list(self.raw, slot_names)?
+            .into_iter()
+            .map(|slot_name| template_slot_info(self.raw, deftemplate, slot_name))
+            .collect::<CLIPSResult<_>>()?;
+
+        Ok(TemplateInfo {
+            name: clips_cstr_to_string(self.raw, name, "a template name")?,
+            module: clips_cstr_to_string(self.raw, module, "a module name")?,
+            slots,
+        })
     }
 
-    pub fn assert_fact(
-        &mut self,
-        data: Box<dyn IntoFactOrInstance<FactBuilderData>>,
-    ) -> CLIPSResult<()> {
-        let template_name = data.definition_name();
+    pub fn class_info(&self, class: &str) -> CLIPSResult<ClassInfo> {
+        let class_cstr = CString::new(class).unwrap();
+        let defclass = unsafe { clips_sys::FindDefclass(self.raw, class_cstr.as_ptr()) };
 
-        let fb = if let Some(fb) = self.fact_builders.get(template_name) {
-            fb.fb
-        } else {
-            let template_name_cstr = CString::new(template_name).unwrap();
-            let fb = unsafe { clips_sys::CreateFactBuilder(self.raw, template_name_cstr.as_ptr()) };
-            self.fact_builders
-                .insert(template_name.to_string(), CLIPSFactBuilder { fb });
-            fb
-        };
+        if defclass.is_null() {
+            return Err(CLIPSError::ClassNotFound);
+        }
 
-        let fb_data = FactBuilderData::new(fb, self.raw);
+        let name = unsafe { CStr::from_ptr(clips_sys::DefclassName(defclass)) };
+        let module = unsafe { CStr::from_ptr(clips_sys::DefclassModule(defclass)) };
 
-        data.into_fact_or_instance(&fb_data)?;
-        fb_data.assert()
+        let mut direct_superclasses = clips_sys::CLIPSValue::default();
+        let mut superclasses = clips_sys::CLIPSValue::default();
+        let mut slot_names = clips_sys::CLIPSValue::default();
+        unsafe {
+            clips_sys::ClassSuperclasses(defclass, &mut direct_superclasses, false);
+            clips_sys::ClassSuperclasses(defclass, &mut superclasses, true);
+            clips_sys::ClassSlots(defclass, &mut slot_names, true);
+        }
+
+        let slots = extract_symbol_list(self.raw, slot_names)?
+            .into_iter()
+            .map(|slot_name| class_slot_info(self.raw, defclass, slot_name))
+            .collect::<CLIPSResult<_>>()?;
+
+        Ok(ClassInfo {
+            name: clips_cstr_to_string(self.raw, name, "a class name")?,
+            module: clips_cstr_to_string(self.raw, module, "a module name")?,
+            is_abstract: unsafe { clips_sys::ClassAbstractP(defclass) },
+            is_reactive: unsafe { clips_sys::ClassReactiveP(defclass) },
+            direct_superclasses: extract_symbol_list(self.raw, direct_superclasses)?,
+            superclasses: extract_symbol_list(self.raw, superclasses)?,
+            slots,
+            message_handlers: message_handlers_info(defclass),
+        })
     }
 
-    pub fn make_instance(
-        &mut self,
-        data: Box<dyn IntoFactOrInstance<InstanceBuilderData>>,
-        instance_name: Option<&str>,
-    ) -> CLIPSResult<()> {
-        let template_name = data.definition_name();
+    pub fn rule_info(&self, rule: &str) -> CLIPSResult<RuleInfo> {
+        let rule_cstr = CString::new(rule).map_err(|_| CLIPSError::RuleNotFound)?;
+        let defrule = unsafe { clips_sys::FindDefrule(self.raw, rule_cstr.as_ptr()) };
 
-        let ib = if let Some(ib) = self.instance_builders.get(template_name) {
-            ib.ib
-        } else {
-            let template_name_cstr = CString::new(template_name).unwrap();
-            let ib =
-                unsafe { clips_sys::CreateInstanceBuilder(self.raw, template_name_cstr.as_ptr()) };
-            self.instance_builders
-                .insert(template_name.to_string(), CLIPSInstanceBuilder { ib });
-            ib
-        };
+        if defrule.is_null() {
+            return Err(CLIPSError::RuleNotFound);
+        }
 
-        let ib_data = InstanceBuilderData::new(ib, self.raw);
+        let (name, module) = unsafe {
+            (
+                CStr::from_ptr(clips_sys::DefruleName(defrule)),
+                CStr::from_ptr(clips_sys::DefruleModule(defrule)),
+            )
+        };
 
-        data.into_fact_or_instance(&ib_data)?;
-        ib_data.make(instance_name)
+        Ok(RuleInfo {
+            name: clips_cstr_to_string(self.raw, name, "a rule name")?,
+            module: clips_cstr_to_string(self.raw, module, "a module name")?,
+            salience: unsafe { (*defrule).salience },
+            complexity: unsafe { (*defrule).complexity() },
+        })
     }
 
-    pub fn set_dynamic_constraint_checking(&mut self, value: bool) {
-        unsafe { clips_sys::SetDynamicConstraintChecking(self.raw, value) };
+    pub fn rule_partial_matches(&self, rule: &str) -> CLIPSResult<Vec<usize>> {
+        let rule_cstr = CString::new(rule).map_err(|_| CLIPSError::RuleNotFound)?;
+        let defrule = unsafe { clips_sys::FindDefrule(self.raw, rule_cstr.as_ptr()) };
+
+        if defrule.is_null() {
+            return Err(CLIPSError::RuleNotFound);
+        }
+
+        let mut counts = Vec::new();
+        let mut disjunct = defrule;
+        while !disjunct.is_null() {
+            counts.extend(disjunct_partial_matches(disjunct));
+            disjunct = unsafe { (*disjunct).disjunct };
+        }
+
+        Ok(counts)
     }
 
-    pub fn set_conflict_resolution_strategy(&mut self, strategy: ConflictResolutionStrategy) {
-        unsafe { clips_sys::SetStrategy(self.raw, strategy as u32) };
+    pub fn list_handlers(&self, class: &str) -> CLIPSResult<Vec<MessageHandlerInfo>> {
+        let class_cstr = CString::new(class).unwrap();
+        let defclass = unsafe { clips_sys::FindDefclass(self.raw, class_cstr.as_ptr()) };
+
+        if defclass.is_null() {
+            return Err(CLIPSError::ClassNotFound);
+        }
+
+        Ok(message_handlers_info(defclass))
     }
+}
 
-    pub fn get_current_parsing_location(&mut self) -> (String, usize) {
-        let file_name_ptr = unsafe { clips_sys::GetParsingFileName(self.raw) };
-        let file_name = unsafe { CStr::from_ptr(file_name_ptr) };
+// Goes back from the rule's last join like `(matches)` does. A join's partial matches are kept in the memory of the join that takes them in next, from the left, or from the right for the joins of a `not` or `exists` over several patterns.
+fn disjunct_partial_matches(defrule: *mut clips_sys::Defrule) -> Vec<usize> {
+    let mut counts = Vec::new();
 
-        let line_number = unsafe { clips_sys::GetLineCount(self.raw) };
+    let last_join = unsafe { (*defrule).lastJoin };
+    if last_join.is_null() {
+        return counts;
+    }
 
-        (
-            file_name.to_str().unwrap().to_string(),
-            line_number as usize,
-        )
+    let (mut join, mut memory) = unsafe { ((*last_join).lastLevel, (*last_join).leftMemory) };
+    while !join.is_null() {
+        counts.push(if memory.is_null() {
+            0
+        } else {
+            unsafe { (*memory).count as usize }
+        });
+
+        (join, memory) = unsafe {
+            if (*join).joinFromTheRight() != 0 {
+                (
+                    (*join).rightSideEntryStructure as *mut clips_sys::joinNode,
+                    (*join).rightMemory,
+                )
+            } else {
+                ((*join).lastLevel, (*join).leftMemory)
+            }
+        };
     }
 
-    pub fn binary_save_facts(&self, path: PathBuf) -> CLIPSResult<usize> {
-        let res = unsafe {
-            let path_cstr = CString::new(path.into_os_string().as_encoded_bytes()).unwrap();
+    // The first join was reached last, and it only passes on what its pattern matched.
+    counts.pop();
+    counts.reverse();
+    counts
+}
 
-            clips_sys::BinarySaveFacts(
-                self.raw,
-                path_cstr.as_ptr(),
-                clips_sys::SaveScope_VISIBLE_SAVE,
+fn message_handlers_info(defclass: *mut clips_sys::Defclass) -> Vec<MessageHandlerInfo> {
+    let mut message_handlers = Vec::new();
+
+    let mut handler_index = unsafe { clips_sys::GetNextDefmessageHandler(defclass, 0) };
+    while handler_index != 0 {
+        let (handler_name, handler_type, handler) = unsafe {
+            (
+                CStr::from_ptr(clips_sys::DefmessageHandlerName(defclass, handler_index)),
+                CStr::from_ptr(clips_sys::DefmessageHandlerType(defclass, handler_index)),
+                clips_sys::GetDefmessageHandlerPointer(defclass, handler_index),
             )
         };
 
-        if res == -1 {
-            Err(CLIPSError::UnableToSaveFacts)
-        } else {
-            Ok(res as usize)
-        }
+        // CLIPS counts the implicit `self` parameter in the handler's parameters.
+        let (min_params, max_params) = unsafe { ((*handler).minParams, (*handler).maxParams) };
+
+        message_handlers.push(MessageHandlerInfo {
+            name: handler_name.to_string_lossy().into_owned(),
+            handler_type: handler_type.to_string_lossy().into_owned(),
+            min_args: min_params.saturating_sub(1),
+            max_args: if max_params == u16::MAX {
+                None
+            } else {
+                Some(max_params.saturating_sub(1))
+            },
+        });
+
+        handler_index = unsafe { clips_sys::GetNextDefmessageHandler(defclass, handler_index) };
     }
 
-    pub fn binary_load_facts(&self, path: PathBuf) -> CLIPSResult<usize> {
-        let res = unsafe {
-            let path_cstr = CString::new(path.into_os_string().as_encoded_bytes()).unwrap();
+    message_handlers
+}
 
-            clips_sys::BinaryLoadFacts(self.raw, path_cstr.as_ptr())
-        };
+fn template_slot_info(
+    env: *mut clips_sys::Environment,
+    deftemplate: *mut clips_sys::Deftemplate,
+    slot_name: String,
+) -> CLIPSResult<TemplateSlotInfo> {
+    let slot_name_cstr = CString::new(slot_name.as_str()).unwrap();
+
+    let mut types = clips_sys::CLIPSValue::default();
+    let mut cardinality = clips_sys::CLIPSValue::default();
+    let mut allowed_values = clips_sys::CLIPSValue::default();
+    let mut range = clips_sys::CLIPSValue::default();
+    let mut default = clips_sys::CLIPSValue::default();
+
+    let (multislot, has_default) = unsafe {
+        clips_sys::DeftemplateSlotTypes(deftemplate, slot_name_cstr.as_ptr(), &mut types);
+        clips_sys::DeftemplateSlotCardinality(
+            deftemplate,
+            slot_name_cstr.as_ptr(),
+            &mut cardinality,
+        );
+        clips_sys::DeftemplateSlotAllowedValues(
+            deftemplate,
+            slot_name_cstr.as_ptr(),
+            &mut allowed_values,
+        );
+        clips_sys::DeftemplateSlotRange(deftemplate, slot_name_cstr.as_ptr(), &mut range);
 
-        if res == -1 {
-            Err(CLIPSError::UnableToSaveFacts)
+        (
+            clips_sys::DeftemplateSlotMultiP(deftemplate, slot_name_cstr.as_ptr()),
+            clips_sys::DeftemplateSlotDefaultP(deftemplate, slot_name_cstr.as_ptr())
+                != clips_sys::DefaultType_NO_DEFAULT
+                && clips_sys::DeftemplateSlotDefaultValue(
+                    deftemplate,
+                    slot_name_cstr.as_ptr(),
+                    &mut default,
+                ),
+        )
+    };
+
+    // Same shape as for class slots: an empty multifield for single-field slots, and `+oo` when there's no maximum.
+    let cardinality = match extract_clipsvalue(env, cardinality)? {
+        CLIPSValue::Multifield(vals) => match vals.as_slice() {
+            [CLIPSValue::Int(min), CLIPSValue::Int(max)] => Some((*min, Some(*max))),
+            [CLIPSValue::Int(min), _] => Some((*min, None)),
+            _ => None,
+        },
+        _ => None,
+    };
+
+    // CLIPS gives back the symbol `FALSE` instead of a multifield when the slot has no allowed values.
+    let allowed_values = match extract_clipsvalue(env, allowed_values)? {
+        CLIPSValue::Multifield(vals) => Some(vals),
+        _ => None,
+    };
+
+    // The bounds are the symbols `-oo` and `+oo` when the range is unbounded.
+    let bound = |val: &CLIPSValue| match val {
+        CLIPSValue::Int(val) => Some(*val as f64),
+        CLIPSValue::Float(val) => Some(*val),
+        _ => None,
+    };
+    let range = match extract_clipsvalue(env, range)? {
+        CLIPSValue::Multifield(vals) => match vals.as_slice() {
+            [min, max] => (bound(min), bound(max)),
+            _ => (None, None),
+        },
+        _ => (None, None),
+    };
+
+    Ok(TemplateSlotInfo {
+        types: extract_symbol_list(env, types)?,
+        multislot,
+        cardinality,
+        allowed_values,
+        range,
+        default: if has_default {
+            Some(extract_clipsvalue(env, default)?)
         } else {
-            Ok(res as usize)
+            None
+        },
+        name: slot_name,
+    })
+}
+
+fn class_slot_info(
+    env: *mut clips_sys::Environment,
+    defclass: *mut clips_sys::Defclass,
+    slot_name: String,
+) -> CLIPSResult<ClassSlotInfo> {
+    let slot_name_cstr = CString::new(slot_name.as_str()).unwrap();
+
+    let mut default = clips_sys::CLIPSValue::default();
+    let mut types = clips_sys::CLIPSValue::default();
+    let mut cardinality = clips_sys::CLIPSValue::default();
+    let mut facets = clips_sys::CLIPSValue::default();
+
+    let (has_default, has_cardinality) = unsafe {
+        clips_sys::SlotTypes(defclass, slot_name_cstr.as_ptr(), &mut types);
+        clips_sys::SlotFacets(defclass, slot_name_cstr.as_ptr(), &mut facets);
+
+        (
+            clips_sys::SlotDefaultValue(defclass, slot_name_cstr.as_ptr(), &mut default),
+            clips_sys::SlotCardinality(defclass, slot_name_cstr.as_ptr(), &mut cardinality),
+        )
+    };
+
+    let default = if has_default {
+        match extract_clipsvalue(env, default)? {
+            CLIPSValue::Symbol(symbol) if symbol == "?NONE" => None,
+            value => Some(value),
         }
-    }
+    } else {
+        None
+    };
+
+    // CLIPS gives back an empty multifield for single-field slots, and the maximum is the symbol `+oo` when there's no upper bound.
+    let cardinality = if has_cardinality {
+        match extract_clipsvalue(env, cardinality)? {
+            CLIPSValue::Multifield(vals) => match vals.as_slice() {
+                [CLIPSValue::Int(min), CLIPSValue::Int(max)] => Some((*min, Some(*max))),
+                [CLIPSValue::Int(min), _] => Some((*min, None)),
+                _ => None,
+            },
+            _ => None,
+        }
+    } else {
+        None
+    };
+
+    let facets = extract_symbol_list(env, facets)?;
+
+    Ok(ClassSlotInfo {
+        default,
+        types: extract_symbol_list(env, types)?,
+        multislot: facets.first().is_some_and(|facet| facet == "MLT"),
+        cardinality,
+        writable: unsafe { clips_sys::SlotWritableP(defclass, slot_name_cstr.as_ptr()) },
+        initable: unsafe { clips_sys::SlotInitableP(defclass, slot_name_cstr.as_ptr()) },
+        public: unsafe { clips_sys::SlotPublicP(defclass, slot_name_cstr.as_ptr()) },
+        facets,
+        name: slot_name,
+    })
+}
 
-    pub fn binary_save_instances(&self, path: PathBuf) -> CLIPSResult<usize> {
-        let res = unsafe {
-            let path_cstr = CString::new(path.into_os_string().as_encoded_bytes()).unwrap();
+fn extract_symbol_list(
+    env: *mut clips_sys::Environment,
+    value: clips_sys::CLIPSValue,
+) -> CLIPSResult<Vec<String>> {
+    let symbols = match extract_clipsvalue(env, value)? {
+        CLIPSValue::Multifield(vals) => vals
+            .into_iter()
+            .filter_map(|val| match val {
+                CLIPSValue::Symbol(name) => Some(name),
+                // Names don't keep their bytes with `StringConversion::Bytes`.
+                CLIPSValue::Lexeme(name) => Some(name.to_string_lossy().into_owned()),
+                _ => None,
+            })
+            .collect(),
+        _ => Vec::new(),
+    };
 
-            clips_sys::BinarySaveInstances(
-                self.raw,
-                path_cstr.as_ptr(),
-                clips_sys::SaveScope_VISIBLE_SAVE,
-            )
+    Ok(symbols)
+}
+
+fn collect_slot_mismatches(
+    slots: &HashMap<String, CLIPSValue>,
+    mismatches: &mut Vec<SlotMismatch>,
+    put_slot: impl Fn(&str, &CLIPSValue) -> CLIPSResult<()>,
+) -> CLIPSResult<()> {
+    for (slot_name, value) in slots {
+        let kind = match put_slot(slot_name, value) {
+            Ok(()) => continue,
+            Err(CLIPSError::SlotNotFound) => SlotMismatchKind::UnknownSlot,
+            Err(err) => match ConstraintViolationKind::from_error(&err) {
+                Some(kind) => SlotMismatchKind::Constraint(kind),
+                None => return Err(err),
+            },
         };
 
-        if res == -1 {
-            Err(CLIPSError::UnableToSaveInstances)
-        } else {
-            Ok(res as usize)
+        mismatches.push(SlotMismatch {
+            slot: slot_name.clone(),
+            kind,
+        });
+    }
+
+    Ok(())
+}
+
+fn check_slots_constraints(
+    slot_names: Vec<String>,
+    source: ConstraintViolationSource,
+    violations: &mut Vec<ConstraintViolation>,
+    get_slot: impl Fn(*const i8, *mut clips_sys::CLIPSValue) -> u32,
+    put_slot: impl Fn(*const i8, *mut clips_sys::CLIPSValue) -> u32,
+) -> CLIPSResult<()> {
+    for slot_name in slot_names {
+        let slot_name_cstr = CString::new(slot_name.as_str()).unwrap();
+        let mut slot_value = clips_sys::CLIPSValue::default();
+
+        if get_slot(slot_name_cstr.as_ptr(), &mut slot_value)
+            != clips_sys::GetSlotError_GSE_NO_ERROR
+        {
+            continue;
+        }
+
+        match translate_put_slot_error(put_slot(slot_name_cstr.as_ptr(), &mut slot_value)) {
+            Ok(()) => {}
+            Err(err) => match ConstraintViolationKind::from_error(&err) {
+                Some(kind) => violations.push(ConstraintViolation {
+                    source: source.clone(),
+                    slot: slot_name,
+                    kind,
+                }),
+                None => return Err(err),
+            },
         }
     }
 
-    pub fn binary_load_instances(&self, path: PathBuf) -> CLIPSResult<usize> {
-        let res = unsafe {
-            let path_cstr = CString::new(path.into_os_string().as_encoded_bytes()).unwrap();
+    Ok(())
+}
 
-            clips_sys::BinaryLoadInstances(self.raw, path_cstr.as_ptr())
-        };
+impl Drop for CLIPSEnvironment {
+    fn drop(&mut self) {
+        // Builders belong to the handle that created them, so handles made with `from_raw()` (e.g. in UDFs) dispose of theirs too.
+        self.dispose_builders(None);
 
-        if res == -1 {
-            Err(CLIPSError::UnableToSaveInstances)
-        } else {
-            Ok(res as usize)
+        if !self.destroy_on_drop {
+            return;
+        }
+
+        let res = unsafe { clips_sys::DestroyEnvironment(self.raw) };
+
+        if !res {
+            log::error!("Attempt at destroying CLIPS environment failed!");
         }
     }
+}
 
-    // Note: this is an implementation based on the C code for `ShowDefglobals()` (in the CLIPS source code). `ShowDefglobals()` prints to a router, but to avoid the indirection we'll directly iterate through every defglobal (if we decided to call `ShowDefglobals()`, we'd have to define a new router that would parse the printed data, so doing things directly saves us a lot of work).
-    pub fn retrieve_globals_values(&self) -> CLIPSResult<CLIPSGlobalsHierarchy> {
-        let mut defglobals_hierarchy = HashMap::new();
+fn retrieve_fact(
+    env: *mut clips_sys::Environment,
+    fact: *mut clips_sys::Fact,
+) -> CLIPSResult<RetrievedFact> {
+    let template_name =
+        unsafe { CStr::from_ptr(clips_sys::DeftemplateName(clips_sys::FactDeftemplate(fact))) };
 
-        let mut defmodule = unsafe { clips_sys::GetNextDefmodule(self.raw, ptr::null_mut()) };
-        while !defmodule.is_null() {
-            let module_name = unsafe { CStr::from_ptr(clips_sys::DefmoduleName(defmodule)) };
-            let module_name_str = module_name.to_str().unwrap();
+    let mut slot_names = clips_sys::CLIPSValue::default();
+    unsafe { clips_sys::FactSlotNames(fact, &mut slot_names) };
 
-            if !defglobals_hierarchy.contains_key(module_name_str) {
-                defglobals_hierarchy.insert(module_name_str.to_string(), HashMap::new());
-            }
+    let mut slots = Vec::new();
+    for slot_name in extract_symbol_list(env, slot_names)? {
+        let slot_name_cstr = CString::new(slot_name.as_str()).unwrap();
+        let mut slot_value = clips_sys::CLIPSValue::default();
+        unsafe { clips_sys::GetFactSlot(fact, slot_name_cstr.as_ptr(), &mut slot_value) };
 
-            let mut curr_defglobal = unsafe {
-                (*clips_sys::GetDefglobalModuleItem(self.raw, defmodule))
-                    .header
-                    .firstItem as *mut clips_sys::defglobal
-            };
+        slots.push((slot_name, extract_clipsvalue(env, slot_value)?));
+    }
 
-            while !curr_defglobal.is_null() {
-                let construct_type = unsafe { (*curr_defglobal).header.constructType };
-                if construct_type != clips_sys::ConstructType_DEFGLOBAL {
-                    return Err(CLIPSError::UnexpectedConstructType(construct_type));
-                } else {
-                    let name = unsafe { CStr::from_ptr((*(*curr_defglobal).header.name).contents) };
-                    let name_str = name.to_str().unwrap();
-                    let value = unsafe { (*curr_defglobal).current };
+    Ok(RetrievedFact {
+        index: unsafe { clips_sys::FactIndex(fact) },
+        template: clips_cstr_to_string(env, template_name, "a template name")?,
+        slots,
+    })
+}
 
-                    defglobals_hierarchy
-                        .get_mut(module_name_str)
-                        .unwrap()
-                        .insert(name_str.to_string(), extract_clipsvalue(value));
-                }
+fn retrieve_instance(
+    env: *mut clips_sys::Environment,
+    instance: *mut clips_sys::Instance,
+) -> CLIPSResult<RetrievedInstance> {
+    let class = unsafe { clips_sys::InstanceClass(instance) };
+    let (name, class_name) = unsafe {
+        (
+            CStr::from_ptr(clips_sys::InstanceName(instance)),
+            CStr::from_ptr(clips_sys::DefclassName(class)),
+        )
+    };
 
-                curr_defglobal =
-                    unsafe { (*curr_defglobal).header.next as *mut clips_sys::defglobal };
-            }
+    let mut slot_names = clips_sys::CLIPSValue::default();
+    unsafe { clips_sys::ClassSlots(class, &mut slot_names, true) };
 
-            defmodule = unsafe { clips_sys::GetNextDefmodule(self.raw, defmodule) };
+    let mut slots = Vec::new();
+    for slot_name in extract_symbol_list(env, slot_names)? {
+        let slot_name_cstr = CString::new(slot_name.as_str()).unwrap();
+        let mut slot_value = clips_sys::CLIPSValue::default();
+        unsafe { clips_sys::DirectGetSlot(instance, slot_name_cstr.as_ptr(), &mut slot_value) };
+
+        slots.push((slot_name, extract_clipsvalue(env, slot_value)?));
+    }
+
+    Ok(RetrievedInstance {
+        name: clips_cstr_to_string(env, name, "an instance name")?,
+        class: clips_cstr_to_string(env, class_name, "a class name")?,
+        slots,
+    })
+}
+
+// The `rust-generic-method-<n>` UDFs called by the methods of `generic`, found in their pretty print forms.
+fn generic_method_udfs(env: *mut clips_sys::Environment, generic: &CStr) -> HashSet<String> {
+    let defgeneric = unsafe { clips_sys::FindDefgeneric(env, generic.as_ptr()) };
+    let mut udfs = HashSet::new();
+    if defgeneric.is_null() {
+        return udfs;
+    }
+
+    let mut index = unsafe { clips_sys::GetNextDefmethod(defgeneric, 0) };
+    while index != 0 {
+        let pp_form = unsafe { clips_sys::DefmethodPPForm(defgeneric, index) };
+        if !pp_form.is_null() {
+            let pp_form = unsafe { CStr::from_ptr(pp_form) }.to_string_lossy();
+            udfs.extend(
+                pp_form
+                    .split(|c: char| c.is_whitespace() || c == '(' || c == ')')
+                    .filter(|token| token.starts_with("rust-generic-method-"))
+                    .map(str::to_string),
+            );
         }
 
-        Ok(defglobals_hierarchy)
+        index = unsafe { clips_sys::GetNextDefmethod(defgeneric, index) };
     }
 
-    pub fn restore_globals(&self, globals: CLIPSGlobalsHierarchy) -> CLIPSResult<()> {
-        for (module_name, globals) in globals {
-            for (global_name, global_value) in globals {
-                let full_global_name = format!("{}::{}", module_name, global_name);
-                let mut raw_value: clips_sys::CLIPSValue = CLIPSInto::into(global_value, self.raw);
+    udfs
+}
 
-                unsafe {
-                    let full_name_cstring = CString::new(full_global_name).unwrap();
-                    let curr_defglobal =
-                        clips_sys::FindDefglobal(self.raw, full_name_cstring.as_ptr());
+fn check_query_construct_name(name: &str) -> CLIPSResult<()> {
+    let is_valid = !name.is_empty()
+        && !name.starts_with('?')
+        && !name.starts_with("$?")
+        && !name
+            .chars()
+            .any(|c| c.is_whitespace() || "()\";&|~<".contains(c));
 
-                    if curr_defglobal.is_null() {
-                        return Err(CLIPSError::DefglobalNotFound);
-                    }
+    if is_valid {
+        Ok(())
+    } else {
+        Err(CLIPSError::InvalidQuery(
+            "the construct name isn't a valid symbol",
+        ))
+    }
+}
 
-                    clips_sys::DefglobalSetValue(curr_defglobal, &mut raw_value);
-                };
+// The query must be a single balanced expression: parentheses can't be closed before they're opened, and strings and comments can't swallow the closing parenthesis of the enclosing expression.
+fn check_query_expression(query: &str) -> CLIPSResult<()> {
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+
+    for c in query.chars() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+
+            continue;
+        }
+
+        match c {
+            '"' => in_string = true,
+            '(' => depth += 1,
+            ')' if depth == 0 => {
+                return Err(CLIPSError::InvalidQuery(
+                    "the query closes more parentheses than it opens",
+                ))
             }
+            ')' => depth -= 1,
+            ';' => return Err(CLIPSError::InvalidQuery("the query can't contain comments")),
+            _ => {}
         }
+    }
 
+    if in_string {
+        Err(CLIPSError::InvalidQuery(
+            "the query has an unterminated string",
+        ))
+    } else if depth != 0 {
+        Err(CLIPSError::InvalidQuery(
+            "the query has unbalanced parentheses",
+        ))
+    } else {
         Ok(())
     }
 }
 
-impl Drop for CLIPSEnvironment {
-    fn drop(&mut self) {
-        if !self.destroy_on_drop {
-            return;
-        }
+// Names that are already qualified are kept as they are, even if a module is given.
+fn qualify_name(name: &str, module: Option<&str>) -> String {
+    match module {
+        Some(module) if !name.contains("::") => format!("{}::{}", module, name),
+        _ => name.to_string(),
+    }
+}
 
-        for ib in self.instance_builders.values() {
-            unsafe { clips_sys::IBDispose(ib.ib) };
-        }
+// Works on the bytes, so names that aren't valid UTF-8 still find their construct.
+fn qualified_cstr(module: &CStr, name: &CStr) -> CString {
+    let mut qualified = module.to_bytes().to_vec();
+    qualified.extend_from_slice(b"::");
+    qualified.extend_from_slice(name.to_bytes());
+    CString::new(qualified).unwrap()
+}
 
-        for fb in self.fact_builders.values() {
-            unsafe { clips_sys::FBDispose(fb.fb) };
-        }
+#[derive(Default)]
+struct FiredRuleRecord {
+    rule: Option<String>,
+    evaluation_error: bool,
+}
 
-        let res = unsafe { clips_sys::DestroyEnvironment(self.raw) };
+// CLIPS also calls this once with a null activation when a run didn't fire any rules.
+extern "C" fn record_fired_rule(
+    environment: *mut clips_sys::Environment,
+    activation: *mut clips_sys::Activation,
+    context: *mut c_void,
+) {
+    if activation.is_null() {
+        return;
+    }
 
-        if !res {
-            log::error!("Attempt at destroying CLIPS environment failed!");
-        }
+    let record = unsafe { &mut *(context as *mut FiredRuleRecord) };
+    let rule_name = unsafe { CStr::from_ptr(clips_sys::ActivationRuleName(activation)) };
+
+    record.rule = Some(rule_name.to_string_lossy().into_owned());
+    // CLIPS clears the evaluation error flag before calling us, but an evaluation error also halts execution, and that flag is still set.
+    record.evaluation_error |= unsafe { clips_sys::GetHaltExecution(environment) };
+}
+
+extern "C" fn record_rule_sequence(
+    _environment: *mut clips_sys::Environment,
+    activation: *mut clips_sys::Activation,
+    context: *mut c_void,
+) {
+    if activation.is_null() {
+        return;
+    }
+
+    let fired_rules = unsafe { &mut *(context as *mut Vec<String>) };
+    let rule_name = unsafe { CStr::from_ptr(clips_sys::ActivationRuleName(activation)) };
+
+    fired_rules.push(rule_name.to_string_lossy().into_owned());
+}
+
+// Starts with the header CLIPS links the user data of a construct with.
+#[repr(C)]
+struct RuleFireCount {
+    header: clips_sys::userData,
+    count: u64,
+}
+
+extern "C" fn create_rule_fire_count(_environment: *mut clips_sys::Environment) -> *mut c_void {
+    Box::into_raw(Box::new(RuleFireCount {
+        header: clips_sys::userData::default(),
+        count: 0,
+    })) as *mut c_void
+}
+
+extern "C" fn delete_rule_fire_count(_environment: *mut clips_sys::Environment, data: *mut c_void) {
+    drop(unsafe { Box::from_raw(data as *mut RuleFireCount) });
+}
+
+// A rule with an `or` is made of one disjunct per alternative, and activations point to the disjunct that matched, so each disjunct has a count of its own.
+fn disjunct_fire_counts<'a>(
+    data_id: u8,
+    defrule: *mut clips_sys::Defrule,
+) -> impl Iterator<Item = &'a mut RuleFireCount> {
+    std::iter::successors((!defrule.is_null()).then_some(defrule), |&disjunct| {
+        let next = unsafe { (*disjunct).disjunct };
+        (!next.is_null()).then_some(next)
+    })
+    .filter_map(move |disjunct| unsafe {
+        (clips_sys::TestUserData(data_id, (*disjunct).header.usrData) as *mut RuleFireCount)
+            .as_mut()
+    })
+}
+
+extern "C" fn count_rule_firing(
+    environment: *mut clips_sys::Environment,
+    activation: *mut clips_sys::Activation,
+    context: *mut c_void,
+) {
+    if activation.is_null() {
+        return;
+    }
+
+    let record = unsafe { &*(context as *const clips_sys::userDataRecord) };
+    let count = unsafe {
+        let disjunct = (*activation).theRule;
+        &mut *(clips_sys::FetchUserData(environment, record.dataID, &mut (*disjunct).header.usrData)
+            as *mut RuleFireCount)
+    };
+    count.count += 1;
+}
+
+extern "C" fn halt_after_deadline(
+    environment: *mut clips_sys::Environment,
+    _activation: *mut clips_sys::Activation,
+    context: *mut c_void,
+) {
+    let deadline = unsafe { &*(context as *const Instant) };
+
+    if Instant::now() >= *deadline {
+        unsafe { clips_sys::SetHaltRules(environment, true) };
     }
 }
 
@@ -1057,11 +7037,32 @@ extern "C" fn cleanup_router_map(environment: *mut clips_sys::Environment) {
     drop(env.retrieve_router_map());
 }
 
+extern "C" fn cleanup_udf_signature_map(environment: *mut clips_sys::Environment) {
+    let env = CLIPSEnvironment::from_raw(environment);
+    drop(env.retrieve_udf_signature_map());
+}
+
+extern "C" fn cleanup_user_data(environment: *mut clips_sys::Environment) {
+    let env = CLIPSEnvironment::from_raw(environment);
+    drop(env.retrieve_user_data());
+}
+
+extern "C" fn cleanup_construct_hooks(environment: *mut clips_sys::Environment) {
+    let env = CLIPSEnvironment::from_raw(environment);
+    drop(env.retrieve_construct_hooks());
+}
+
 extern "C" fn cleanup_strings_to_drop(environment: *mut clips_sys::Environment) {
     let env = CLIPSEnvironment::from_raw(environment);
-    let mut strings_to_drop = env.retrieve_strings_to_drop();
+    drop(env.retrieve_strings_to_drop());
+}
 
-    for ptr in strings_to_drop.drain(..) {
-        drop(unsafe { CString::from_raw(ptr as *mut i8) });
-    }
+// The policy is only ever read and written in place (see `value::value_extraction_policy()`), so there are no `retrieve`/`store` methods for it.
+extern "C" fn cleanup_value_policy(environment: *mut clips_sys::Environment) {
+    drop(unsafe {
+        Box::from_raw(clips_sys::GetEnvironmentData(
+            environment,
+            VALUE_POLICY_ENVIRONMENT_DATA_INDEX,
+        ) as *mut ValueExtractionPolicy)
+    });
 }
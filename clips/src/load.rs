@@ -0,0 +1,541 @@
+use std::{
+    collections::{HashMap, HashSet},
+    ffi::{c_char, c_long, c_void, CStr, CString},
+    ptr,
+    time::{Duration, Instant},
+};
+
+use crate::{is_support_construct, is_support_module, CLIPSResult};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseDiagnostic {
+    pub line: usize,
+    pub message: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LoadReport {
+    // How long CLIPS took to parse the constructs and add them to the Rete network.
+    pub duration: Duration,
+    // Rules that redefined existing ones aren't counted.
+    pub rules_added: usize,
+    // Join nodes shared with rules that were already loaded aren't counted. If the load redefined rules, this is what's left after subtracting the join nodes of the rules that were replaced.
+    pub join_nodes_added: usize,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ConstructTransfer {
+    // Whether the constructs went over as a binary image. Otherwise they were built again from their pretty print forms.
+    pub binary_image: bool,
+    // The constructs the source has that the target doesn't, as "<kind> <module>::<name>". Sorted.
+    pub not_transferred: Vec<String>,
+}
+
+// What `Environment::clone_constructs_into()` takes from the source environment. The pretty print forms are only used if the binary image can't be made or loaded.
+pub(crate) struct ConstructExport {
+    pub(crate) image: Option<Vec<u8>>,
+    pub(crate) pp_forms: Vec<String>,
+    pub(crate) names: HashSet<String>,
+}
+
+pub(crate) fn measure_load<F: FnOnce() -> CLIPSResult<()>>(
+    env: *mut clips_sys::Environment,
+    load: F,
+) -> CLIPSResult<LoadReport> {
+    let rules_before = constructs_in_all_modules(env, clips_sys::GetNextDefrule).len();
+    let join_nodes_before = count_join_nodes(env);
+
+    let start = Instant::now();
+    load()?;
+    let duration = start.elapsed();
+
+    Ok(LoadReport {
+        duration,
+        rules_added: constructs_in_all_modules(env, clips_sys::GetNextDefrule)
+            .len()
+            .saturating_sub(rules_before),
+        join_nodes_added: count_join_nodes(env).saturating_sub(join_nodes_before),
+    })
+}
+
+// CLIPS has no counter for the join network, so we walk back from the last join of every rule (and of every disjunct of a rule with `or`), which reaches every join the rule uses. Joins are shared between rules with the same first patterns, so they're only counted once.
+fn count_join_nodes(env: *mut clips_sys::Environment) -> usize {
+    let mut joins = HashSet::new();
+    let mut to_visit = Vec::new();
+
+    for defrule in constructs_in_all_modules(env, clips_sys::GetNextDefrule) {
+        let mut disjunct = defrule;
+        while !disjunct.is_null() {
+            to_visit.push(unsafe { (*disjunct).lastJoin });
+            disjunct = unsafe { (*disjunct).disjunct };
+        }
+    }
+
+    while let Some(join) = to_visit.pop() {
+        if join.is_null() || !joins.insert(join) {
+            continue;
+        }
+
+        to_visit.push(unsafe { (*join).lastLevel });
+        // Joins for `not`/`exists` over several patterns get their right input from another chain of joins instead of from a pattern.
+        if unsafe { (*join).joinFromTheRight() } != 0 {
+            to_visit.push(unsafe { (*join).rightSideEntryStructure } as *mut clips_sys::joinNode);
+        }
+    }
+
+    joins.len()
+}
+
+// The context given to CLIPS is the list the diagnostics are collected into. Warnings don't make loading fail, so they're not collected.
+pub(crate) extern "C" fn collect_parse_diagnostic(
+    _environment: *mut clips_sys::Environment,
+    _file_name: *const c_char,
+    _warning: *const c_char,
+    error: *const c_char,
+    line: c_long,
+    context: *mut c_void,
+) {
+    if error.is_null() {
+        return;
+    }
+
+    let diagnostics = unsafe { &mut *(context as *mut Vec<ParseDiagnostic>) };
+    let message = unsafe { CStr::from_ptr(error) };
+
+    diagnostics.push(ParseDiagnostic {
+        line: line as usize,
+        message: message.to_string_lossy().trim().to_string(),
+    });
+}
+
+// Collects the errors CLIPS reports while `load` runs. A UDF called during a load can load more constructs itself, so the callback and context that were set before are put back afterwards.
+pub(crate) fn collecting_parse_diagnostics<T>(
+    env: *mut clips_sys::Environment,
+    load: impl FnOnce() -> T,
+) -> (T, Vec<ParseDiagnostic>) {
+    let mut diagnostics: Vec<ParseDiagnostic> = Vec::new();
+    let previous_context = unsafe { (*construct_data(env)).ParserErrorContext };
+
+    let previous_callback = unsafe {
+        clips_sys::SetParserErrorCallback(
+            env,
+            Some(collect_parse_diagnostic),
+            &mut diagnostics as *mut Vec<ParseDiagnostic> as *mut c_void,
+        )
+    };
+    let res = load();
+    unsafe { clips_sys::SetParserErrorCallback(env, previous_callback, previous_context) };
+
+    (res, diagnostics)
+}
+
+// `clips_sys::GetEnvironmentData()` reads the pointer our own environment data holds, but CLIPS' construct data is the data itself, so it's read straight from `theData`.
+pub(crate) fn construct_data(env: *mut clips_sys::Environment) -> *mut clips_sys::constructData {
+    unsafe {
+        *(*env).theData.add(clips_sys::CONSTRUCT_DATA as usize) as *mut clips_sys::constructData
+    }
+}
+
+pub(crate) type NextConstructFn<T> =
+    unsafe extern "C" fn(*mut clips_sys::Environment, *mut T) -> *mut T;
+pub(crate) type ConstructStrFn<T> = unsafe extern "C" fn(*mut T) -> *const c_char;
+type UndefConstructFn<T> = unsafe extern "C" fn(*mut T, *mut clips_sys::Environment) -> bool;
+
+// `GetNextDef*()` functions only go through the constructs in the current module, so we switch to every module and restore the current one at the end.
+pub(crate) fn constructs_in_all_modules<T>(
+    env: *mut clips_sys::Environment,
+    next: NextConstructFn<T>,
+) -> Vec<*mut T> {
+    let mut constructs = Vec::new();
+    let current_module = unsafe { clips_sys::GetCurrentModule(env) };
+
+    let mut defmodule = unsafe { clips_sys::GetNextDefmodule(env, ptr::null_mut()) };
+    while !defmodule.is_null() {
+        constructs.extend(constructs_in_module(env, defmodule, next));
+        defmodule = unsafe { clips_sys::GetNextDefmodule(env, defmodule) };
+    }
+
+    unsafe { clips_sys::SetCurrentModule(env, current_module) };
+
+    constructs
+}
+
+// Leaves `defmodule` as the current module.
+pub(crate) fn constructs_in_module<T>(
+    env: *mut clips_sys::Environment,
+    defmodule: *mut clips_sys::Defmodule,
+    next: NextConstructFn<T>,
+) -> Vec<*mut T> {
+    let mut constructs = Vec::new();
+    unsafe { clips_sys::SetCurrentModule(env, defmodule) };
+
+    let mut construct = unsafe { next(env, ptr::null_mut()) };
+    while !construct.is_null() {
+        constructs.push(construct);
+        construct = unsafe { next(env, construct) };
+    }
+
+    constructs
+}
+
+fn pp_form_to_string(pp_form: *const c_char) -> Option<String> {
+    if pp_form.is_null() {
+        return None;
+    }
+
+    Some(
+        unsafe { CStr::from_ptr(pp_form) }
+            .to_string_lossy()
+            .into_owned(),
+    )
+}
+
+fn construct_key<T>(
+    kind: &str,
+    construct: *mut T,
+    name: ConstructStrFn<T>,
+    module: ConstructStrFn<T>,
+) -> String {
+    let (module_name, construct_name) = unsafe {
+        (
+            CStr::from_ptr(module(construct)),
+            CStr::from_ptr(name(construct)),
+        )
+    };
+
+    format!(
+        "{} {}::{}",
+        kind,
+        module_name.to_string_lossy(),
+        construct_name.to_string_lossy()
+    )
+}
+
+struct ConstructKind<T> {
+    kind: &'static str,
+    next: NextConstructFn<T>,
+    name: ConstructStrFn<T>,
+    module: ConstructStrFn<T>,
+    undef: UndefConstructFn<T>,
+    pp_form: ConstructStrFn<T>,
+}
+
+impl<T> ConstructKind<T> {
+    fn add_names(&self, env: *mut clips_sys::Environment, names: &mut HashSet<String>) {
+        for construct in constructs_in_all_modules(env, self.next) {
+            names.insert(construct_key(self.kind, construct, self.name, self.module));
+        }
+    }
+
+    fn add_named_pp_forms(
+        &self,
+        env: *mut clips_sys::Environment,
+        pp_forms: &mut HashMap<String, Option<String>>,
+    ) {
+        for construct in constructs_in_all_modules(env, self.next) {
+            pp_forms.insert(
+                construct_key(self.kind, construct, self.name, self.module),
+                pp_form_to_string(unsafe { (self.pp_form)(construct) }),
+            );
+        }
+    }
+
+    // Redefined constructs are undefined too, even though building them again would replace them, because a redefined rule can use a redefined template and keep it from being replaced. The ones that can't be undefined, e.g. a redefined deffunction that an old rule calls, are replaced in place when they're built again.
+    fn undefine_changed(
+        &self,
+        env: *mut clips_sys::Environment,
+        before: &HashMap<String, Option<String>>,
+        to_rebuild: &mut Vec<Vec<String>>,
+    ) {
+        let mut rebuild = Vec::new();
+
+        for construct in constructs_in_all_modules(env, self.next) {
+            let key = construct_key(self.kind, construct, self.name, self.module);
+            let pp_form = pp_form_to_string(unsafe { (self.pp_form)(construct) });
+
+            match before.get(&key) {
+                Some(old_pp_form) if *old_pp_form == pp_form => {}
+                Some(Some(old_pp_form)) => {
+                    unsafe { (self.undef)(construct, env) };
+                    rebuild.push(old_pp_form.clone());
+                }
+                Some(None) => log::warn!(
+                    "Couldn't put {} back while rolling back a load, since it had no pretty print form.",
+                    key
+                ),
+                None => {
+                    if !unsafe { (self.undef)(construct, env) } {
+                        log::warn!("Couldn't undefine {} while rolling back a load.", key);
+                    }
+                }
+            }
+        }
+
+        to_rebuild.push(rebuild);
+    }
+
+    fn undefine_in_module(
+        &self,
+        env: *mut clips_sys::Environment,
+        defmodule: *mut clips_sys::Defmodule,
+        undefined: &mut usize,
+        failed: &mut Vec<String>,
+    ) {
+        for construct in constructs_in_module(env, defmodule, self.next) {
+            let key = construct_key(self.kind, construct, self.name, self.module);
+
+            if unsafe { (self.undef)(construct, env) } {
+                *undefined += 1;
+            } else {
+                failed.push(key);
+            }
+        }
+    }
+
+    fn add_pp_forms(
+        &self,
+        env: *mut clips_sys::Environment,
+        defmodule: *mut clips_sys::Defmodule,
+        pp_forms: &mut Vec<Vec<String>>,
+    ) {
+        pp_forms.push(
+            constructs_in_module(env, defmodule, self.next)
+                .into_iter()
+                .filter_map(|construct| pp_form_to_string(unsafe { (self.pp_form)(construct) }))
+                .collect(),
+        );
+    }
+}
+
+// Every kind of construct that can be undefined, in an order where constructs are undefined before the ones they may depend on.
+macro_rules! for_each_construct_kind {
+    ($action:ident, $($arg:expr),*) => {
+        ConstructKind {
+            kind: "defrule",
+            next: clips_sys::GetNextDefrule,
+            name: clips_sys::DefruleName,
+            module: clips_sys::DefruleModule,
+            undef: clips_sys::Undefrule,
+            pp_form: clips_sys::DefrulePPForm,
+        }
+        .$action($($arg),*);
+        ConstructKind {
+            kind: "deffacts",
+            next: clips_sys::GetNextDeffacts,
+            name: clips_sys::DeffactsName,
+            module: clips_sys::DeffactsModule,
+            undef: clips_sys::Undeffacts,
+            pp_form: clips_sys::DeffactsPPForm,
+        }
+        .$action($($arg),*);
+        ConstructKind {
+            kind: "definstances",
+            next: clips_sys::GetNextDefinstances,
+            name: clips_sys::DefinstancesName,
+            module: clips_sys::DefinstancesModule,
+            undef: clips_sys::Undefinstances,
+            pp_form: clips_sys::DefinstancesPPForm,
+        }
+        .$action($($arg),*);
+        ConstructKind {
+            kind: "deffunction",
+            next: clips_sys::GetNextDeffunction,
+            name: clips_sys::DeffunctionName,
+            module: clips_sys::DeffunctionModule,
+            undef: clips_sys::Undeffunction,
+            pp_form: clips_sys::DeffunctionPPForm,
+        }
+        .$action($($arg),*);
+        ConstructKind {
+            kind: "defgeneric",
+            next: clips_sys::GetNextDefgeneric,
+            name: clips_sys::DefgenericName,
+            module: clips_sys::DefgenericModule,
+            undef: clips_sys::Undefgeneric,
+            pp_form: clips_sys::DefgenericPPForm,
+        }
+        .$action($($arg),*);
+        ConstructKind {
+            kind: "defclass",
+            next: clips_sys::GetNextDefclass,
+            name: clips_sys::DefclassName,
+            module: clips_sys::DefclassModule,
+            undef: clips_sys::Undefclass,
+            pp_form: clips_sys::DefclassPPForm,
+        }
+        .$action($($arg),*);
+        ConstructKind {
+            kind: "deftemplate",
+            next: clips_sys::GetNextDeftemplate,
+            name: clips_sys::DeftemplateName,
+            module: clips_sys::DeftemplateModule,
+            undef: clips_sys::Undeftemplate,
+            pp_form: clips_sys::DeftemplatePPForm,
+        }
+        .$action($($arg),*);
+        ConstructKind {
+            kind: "defglobal",
+            next: clips_sys::GetNextDefglobal,
+            name: clips_sys::DefglobalName,
+            module: clips_sys::DefglobalModule,
+            undef: clips_sys::Undefglobal,
+            pp_form: clips_sys::DefglobalPPForm,
+        }
+        .$action($($arg),*);
+    };
+}
+
+// What `roll_back_load()` needs to put every construct back the way it was.
+pub(crate) fn named_pp_forms(env: *mut clips_sys::Environment) -> HashMap<String, Option<String>> {
+    let mut pp_forms = HashMap::new();
+    for_each_construct_kind!(add_named_pp_forms, env, &mut pp_forms);
+    pp_forms
+}
+
+pub(crate) fn construct_names(env: *mut clips_sys::Environment) -> HashSet<String> {
+    let mut names = HashSet::new();
+    for_each_construct_kind!(add_names, env, &mut names);
+    names
+}
+
+// Loads keep every construct in `construct_names()` when they roll back, so the crate's own constructs are only left out here.
+pub(crate) fn user_construct_names(env: *mut clips_sys::Environment) -> HashSet<String> {
+    let mut names = construct_names(env);
+    names.retain(|name| !is_support_construct(name));
+    names
+}
+
+// The pretty print forms of the constructs in every module, in an order they can be loaded back in. The crate's own module for `assert_fact_with_support()` is added back when it's needed, so it's left out.
+pub(crate) fn all_pp_forms(env: *mut clips_sys::Environment) -> Vec<String> {
+    let mut pp_forms = Vec::new();
+
+    let mut defmodule = unsafe { clips_sys::GetNextDefmodule(env, ptr::null_mut()) };
+    while !defmodule.is_null() {
+        if !is_support_module(defmodule) {
+            pp_forms.extend(module_pp_forms(env, defmodule));
+        }
+        defmodule = unsafe { clips_sys::GetNextDefmodule(env, defmodule) };
+    }
+
+    pp_forms
+}
+
+// The pretty print forms of the constructs in `defmodule`, in an order they can be loaded back in. Constructs without a pretty print form, e.g. the ones loaded from a binary image, are skipped.
+pub(crate) fn module_pp_forms(
+    env: *mut clips_sys::Environment,
+    defmodule: *mut clips_sys::Defmodule,
+) -> Vec<String> {
+    let current_module = unsafe { clips_sys::GetCurrentModule(env) };
+
+    let mut pp_forms = Vec::new();
+    pp_forms.extend(pp_form_to_string(unsafe {
+        clips_sys::DefmodulePPForm(defmodule)
+    }));
+
+    // The kinds are listed in undefining order, so defining them goes the other way around.
+    let mut kinds_pp_forms = Vec::new();
+    for_each_construct_kind!(add_pp_forms, env, defmodule, &mut kinds_pp_forms);
+    pp_forms.extend(kinds_pp_forms.into_iter().rev().flatten());
+
+    // Message handlers and methods are defined separately from their class or generic function, so they go after everything else.
+    for defclass in constructs_in_module(env, defmodule, clips_sys::GetNextDefclass) {
+        let mut handler = unsafe { clips_sys::GetNextDefmessageHandler(defclass, 0) };
+        while handler != 0 {
+            pp_forms.extend(pp_form_to_string(unsafe {
+                clips_sys::DefmessageHandlerPPForm(defclass, handler)
+            }));
+            handler = unsafe { clips_sys::GetNextDefmessageHandler(defclass, handler) };
+        }
+    }
+
+    for defgeneric in constructs_in_module(env, defmodule, clips_sys::GetNextDefgeneric) {
+        let mut method = unsafe { clips_sys::GetNextDefmethod(defgeneric, 0) };
+        while method != 0 {
+            pp_forms.extend(pp_form_to_string(unsafe {
+                clips_sys::DefmethodPPForm(defgeneric, method)
+            }));
+            method = unsafe { clips_sys::GetNextDefmethod(defgeneric, method) };
+        }
+    }
+
+    unsafe { clips_sys::SetCurrentModule(env, current_module) };
+
+    pp_forms
+}
+
+// Undefines the constructs that weren't there before the load, and builds the ones the load redefined again from the pretty print forms they had before.
+pub(crate) fn roll_back_load(
+    env: *mut clips_sys::Environment,
+    before: &HashMap<String, Option<String>>,
+) {
+    let current_module = unsafe { clips_sys::GetCurrentModule(env) };
+
+    let mut to_rebuild = Vec::new();
+    for_each_construct_kind!(undefine_changed, env, before, &mut to_rebuild);
+
+    // The kinds are listed in undefining order, so defining them goes the other way around.
+    for pp_form in to_rebuild.into_iter().rev().flatten() {
+        let built = CString::new(pp_form.as_str())
+            .is_ok_and(|pp_form| unsafe { clips_sys::Build(env, pp_form.as_ptr()) }
+                == clips_sys::BuildError_BE_NO_ERROR);
+
+        if !built {
+            log::warn!(
+                "Couldn't build {} again while rolling back a load.",
+                pp_form
+            );
+        }
+    }
+
+    unsafe { clips_sys::SetCurrentModule(env, current_module) };
+}
+
+// Facts and instances keep their template or class from being undefined, so the ones from `defmodule` are removed first. Message handlers and methods go away with their class or generic function. CLIPS can't undefine defmodules, so the module itself stays. Returns how many constructs were undefined, and the ones that couldn't be, e.g. because constructs in other modules use them.
+pub(crate) fn undefine_module_constructs(
+    env: *mut clips_sys::Environment,
+    defmodule: *mut clips_sys::Defmodule,
+) -> (usize, Vec<String>) {
+    let current_module = unsafe { clips_sys::GetCurrentModule(env) };
+
+    let deftemplates: HashSet<_> =
+        constructs_in_module(env, defmodule, clips_sys::GetNextDeftemplate)
+            .into_iter()
+            .collect();
+
+    let mut facts = Vec::new();
+    let mut fact = unsafe { clips_sys::GetNextFact(env, ptr::null_mut()) };
+    while !fact.is_null() {
+        if deftemplates.contains(&unsafe { clips_sys::FactDeftemplate(fact) }) {
+            facts.push(fact);
+        }
+
+        fact = unsafe { clips_sys::GetNextFact(env, fact) };
+    }
+
+    for fact in facts {
+        unsafe { clips_sys::Retract(fact) };
+    }
+
+    for defclass in constructs_in_module(env, defmodule, clips_sys::GetNextDefclass) {
+        let mut instance = unsafe { clips_sys::GetNextInstanceInClass(defclass, ptr::null_mut()) };
+        while !instance.is_null() {
+            let next = unsafe { clips_sys::GetNextInstanceInClass(defclass, instance) };
+            unsafe { clips_sys::DeleteInstance(instance) };
+            instance = next;
+        }
+    }
+
+    let mut undefined = 0;
+    let mut failed = Vec::new();
+    for_each_construct_kind!(
+        undefine_in_module,
+        env,
+        defmodule,
+        &mut undefined,
+        &mut failed
+    );
+
+    unsafe { clips_sys::SetCurrentModule(env, current_module) };
+
+    (undefined, failed)
+}
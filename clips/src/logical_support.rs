@@ -0,0 +1,80 @@
+use std::{
+    ffi::CStr,
+    sync::{Arc, Mutex},
+};
+
+use crate::{CLIPSResult, FactBuilderData, IntoFactOrInstance, UDFData};
+
+pub(crate) const SUPPORT_MODULE: &str = "rust-external-support";
+pub(crate) const SUPPORT_TEMPLATE: &str = "rust-external-support::support";
+pub(crate) const SUPPORT_UDF: &str = "rust-assert-supported";
+
+// Facts asserted while a rule's actions run depend on the facts matched by the rule's `logical` patterns, so the payload is asserted from this rule's actions. The module exports nothing, so user rules never match the support facts, and focusing it lets the rule fire without anything else on the agenda firing.
+pub(crate) const SUPPORT_CONSTRUCTS: &str = r#"
+(defmodule rust-external-support)
+
+(deftemplate rust-external-support::support
+   (slot tag (type STRING))
+   (slot id (type INTEGER)))
+
+(defrule rust-external-support::support
+   (logical (support (id ?id)))
+   =>
+   (rust-assert-supported ?id))
+"#;
+
+pub(crate) type SupportedPayload = Box<dyn IntoFactOrInstance<FactBuilderData> + Send + Sync>;
+
+// Only one supported fact is asserted at a time, since the CLIPS thread handles one command at a time.
+#[derive(Default)]
+pub(crate) enum SupportedAssert {
+    #[default]
+    Empty,
+    Pending {
+        id: i64,
+        payload: SupportedPayload,
+        module: String,
+    },
+    Done(CLIPSResult<()>),
+}
+
+pub(crate) type SharedSupportedAssert = Arc<Mutex<SupportedAssert>>;
+
+pub(crate) fn supported_assert_udf(
+    shared: SharedSupportedAssert,
+) -> Box<dyn FnMut(UDFData) + Send + Sync> {
+    Box::new(move |mut data: UDFData| {
+        data.set_void();
+        let Ok(id) = data.first_arg::<u64>() else {
+            return;
+        };
+        let mut supported_assert = shared.lock().unwrap();
+
+        // The rule can also fire for a support fact whose payload was already asserted, e.g. after a `refresh`. There's nothing to assert then.
+        if !matches!(&*supported_assert, SupportedAssert::Pending { id: pending_id, .. } if *pending_id as u64 == id)
+        {
+            return;
+        }
+        let SupportedAssert::Pending {
+            payload, module, ..
+        } = std::mem::take(&mut *supported_assert)
+        else {
+            return;
+        };
+
+        let res = data.env().assert_fact(payload, Some(&module));
+        *supported_assert = SupportedAssert::Done(res);
+    })
+}
+
+pub(crate) fn is_support_module(defmodule: *mut clips_sys::Defmodule) -> bool {
+    unsafe { CStr::from_ptr(clips_sys::DefmoduleName(defmodule)) }.to_bytes()
+        == SUPPORT_MODULE.as_bytes()
+}
+
+// Construct names are given as "<kind> <module>::<name>".
+pub(crate) fn is_support_construct(name: &str) -> bool {
+    name.split_once(' ')
+        .and_then(|(_, name)| name.split_once("::"))
+        .is_some_and(|(module, _)| module == SUPPORT_MODULE)
+}
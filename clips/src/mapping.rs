@@ -0,0 +1,602 @@
+use std::fmt::Display;
+
+use serde::{
+    de::{self, DeserializeOwned, DeserializeSeed, IntoDeserializer, Visitor},
+    forward_to_deserialize_any, ser, Serialize,
+};
+
+use crate::{visit_clipsvalue, CLIPSError, CLIPSResult, CLIPSValue};
+
+// Maps Rust types to and from `CLIPSValue`s through serde, so rule outputs can be read straight into Rust types.
+//
+// CLIPS multifields can't be nested, so every compound value maps to a single flat multifield:
+// - Structs, tuples and sequences become a multifield with their values in declaration order. Nested compound values are flattened into their parent.
+// - Enum variants are externally tagged: a unit variant is just its name as a symbol, and any other variant is a multifield whose first element is the variant name as a symbol, followed by the variant's values. For example, `Decision::Approve { limit: 5 }` maps to `(Approve 5)`.
+// - `None` and `()` map to the `nil` symbol.
+// - Maps become a multifield with alternating keys and values.
+//
+// Since the multifield is flat, a sequence or map of unknown length takes every value left, so it must be the last field of whatever contains it.
+impl CLIPSValue {
+    pub fn deserialize_into<T: DeserializeOwned>(&self) -> CLIPSResult<T> {
+        let mut values = Vec::new();
+        push_flattened(&mut values, self.clone());
+
+        let mut deserializer = FlatDeserializer { values, pos: 0 };
+        let res = T::deserialize(&mut deserializer)?;
+
+        if deserializer.pos < deserializer.values.len() {
+            return Err(CLIPSError::ValueMapping(format!(
+                "{} values were left over after deserializing",
+                deserializer.values.len() - deserializer.pos
+            )));
+        }
+
+        Ok(res)
+    }
+
+    pub fn from_serialize<T: Serialize + ?Sized>(value: &T) -> CLIPSResult<CLIPSValue> {
+        value.serialize(ValueSerializer)
+    }
+}
+
+impl de::Error for CLIPSError {
+    fn custom<T: Display>(msg: T) -> Self {
+        CLIPSError::ValueMapping(msg.to_string())
+    }
+}
+
+impl ser::Error for CLIPSError {
+    fn custom<T: Display>(msg: T) -> Self {
+        CLIPSError::ValueMapping(msg.to_string())
+    }
+}
+
+fn push_flattened(values: &mut Vec<CLIPSValue>, value: CLIPSValue) {
+    match value {
+        CLIPSValue::Multifield(inner) => {
+            for value in inner {
+                push_flattened(values, value);
+            }
+        }
+        value => values.push(value),
+    }
+}
+
+fn nil() -> CLIPSValue {
+    CLIPSValue::Symbol("nil".to_string())
+}
+
+struct ValueSerializer;
+
+impl ser::Serializer for ValueSerializer {
+    type Ok = CLIPSValue;
+    type Error = CLIPSError;
+
+    type SerializeSeq = MultifieldSerializer;
+    type SerializeTuple = MultifieldSerializer;
+    type SerializeTupleStruct = MultifieldSerializer;
+    type SerializeTupleVariant = MultifieldSerializer;
+    type SerializeMap = MultifieldSerializer;
+    type SerializeStruct = MultifieldSerializer;
+    type SerializeStructVariant = MultifieldSerializer;
+
+    fn serialize_bool(self, v: bool) -> CLIPSResult<CLIPSValue> {
+        Ok(CLIPSValue::Bool(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> CLIPSResult<CLIPSValue> {
+        Ok(CLIPSValue::Int(v as i64))
+    }
+
+    fn serialize_i16(self, v: i16) -> CLIPSResult<CLIPSValue> {
+        Ok(CLIPSValue::Int(v as i64))
+    }
+
+    fn serialize_i32(self, v: i32) -> CLIPSResult<CLIPSValue> {
+        Ok(CLIPSValue::Int(v as i64))
+    }
+
+    fn serialize_i64(self, v: i64) -> CLIPSResult<CLIPSValue> {
+        Ok(CLIPSValue::Int(v))
+    }
+
+    fn serialize_i128(self, v: i128) -> CLIPSResult<CLIPSValue> {
+        CLIPSValue::try_from_i128(v)
+    }
+
+    fn serialize_u8(self, v: u8) -> CLIPSResult<CLIPSValue> {
+        Ok(CLIPSValue::Int(v as i64))
+    }
+
+    fn serialize_u16(self, v: u16) -> CLIPSResult<CLIPSValue> {
+        Ok(CLIPSValue::Int(v as i64))
+    }
+
+    fn serialize_u32(self, v: u32) -> CLIPSResult<CLIPSValue> {
+        Ok(CLIPSValue::Int(v as i64))
+    }
+
+    fn serialize_u64(self, v: u64) -> CLIPSResult<CLIPSValue> {
+        CLIPSValue::try_from_i128(v as i128)
+    }
+
+    fn serialize_u128(self, v: u128) -> CLIPSResult<CLIPSValue> {
+        match i128::try_from(v) {
+            Ok(v) => CLIPSValue::try_from_i128(v),
+            Err(_) => Err(CLIPSError::ValueMapping(format!(
+                "the integer {} doesn't fit in a CLIPS integer (64 bits)",
+                v
+            ))),
+        }
+    }
+
+    fn serialize_f32(self, v: f32) -> CLIPSResult<CLIPSValue> {
+        Ok(CLIPSValue::Float(v as f64))
+    }
+
+    fn serialize_f64(self, v: f64) -> CLIPSResult<CLIPSValue> {
+        Ok(CLIPSValue::Float(v))
+    }
+
+    fn serialize_char(self, v: char) -> CLIPSResult<CLIPSValue> {
+        Ok(CLIPSValue::String(v.to_string()))
+    }
+
+    fn serialize_str(self, v: &str) -> CLIPSResult<CLIPSValue> {
+        Ok(CLIPSValue::String(v.to_string()))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> CLIPSResult<CLIPSValue> {
+        Ok(CLIPSValue::Multifield(
+            v.iter().map(|b| CLIPSValue::Int(*b as i64)).collect(),
+        ))
+    }
+
+    fn serialize_none(self) -> CLIPSResult<CLIPSValue> {
+        Ok(nil())
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> CLIPSResult<CLIPSValue> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> CLIPSResult<CLIPSValue> {
+        Ok(nil())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> CLIPSResult<CLIPSValue> {
+        Ok(nil())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> CLIPSResult<CLIPSValue> {
+        Ok(CLIPSValue::Symbol(variant.to_string()))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> CLIPSResult<CLIPSValue> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> CLIPSResult<CLIPSValue> {
+        let mut values = vec![CLIPSValue::Symbol(variant.to_string())];
+        push_flattened(&mut values, value.serialize(ValueSerializer)?);
+
+        Ok(CLIPSValue::Multifield(values))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> CLIPSResult<MultifieldSerializer> {
+        Ok(MultifieldSerializer::new(None, len.unwrap_or(0)))
+    }
+
+    fn serialize_tuple(self, len: usize) -> CLIPSResult<MultifieldSerializer> {
+        Ok(MultifieldSerializer::new(None, len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> CLIPSResult<MultifieldSerializer> {
+        Ok(MultifieldSerializer::new(None, len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> CLIPSResult<MultifieldSerializer> {
+        Ok(MultifieldSerializer::new(Some(variant), len))
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> CLIPSResult<MultifieldSerializer> {
+        Ok(MultifieldSerializer::new(None, len.unwrap_or(0) * 2))
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> CLIPSResult<MultifieldSerializer> {
+        Ok(MultifieldSerializer::new(None, len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> CLIPSResult<MultifieldSerializer> {
+        Ok(MultifieldSerializer::new(Some(variant), len))
+    }
+}
+
+struct MultifieldSerializer {
+    values: Vec<CLIPSValue>,
+}
+
+impl MultifieldSerializer {
+    fn new(variant: Option<&'static str>, len: usize) -> Self {
+        let mut values = Vec::with_capacity(len + 1);
+
+        if let Some(variant) = variant {
+            values.push(CLIPSValue::Symbol(variant.to_string()));
+        }
+
+        Self { values }
+    }
+
+    fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> CLIPSResult<()> {
+        push_flattened(&mut self.values, value.serialize(ValueSerializer)?);
+        Ok(())
+    }
+
+    fn finish(self) -> CLIPSResult<CLIPSValue> {
+        Ok(CLIPSValue::Multifield(self.values))
+    }
+}
+
+impl ser::SerializeSeq for MultifieldSerializer {
+    type Ok = CLIPSValue;
+    type Error = CLIPSError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> CLIPSResult<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> CLIPSResult<CLIPSValue> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTuple for MultifieldSerializer {
+    type Ok = CLIPSValue;
+    type Error = CLIPSError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> CLIPSResult<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> CLIPSResult<CLIPSValue> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleStruct for MultifieldSerializer {
+    type Ok = CLIPSValue;
+    type Error = CLIPSError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> CLIPSResult<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> CLIPSResult<CLIPSValue> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleVariant for MultifieldSerializer {
+    type Ok = CLIPSValue;
+    type Error = CLIPSError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> CLIPSResult<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> CLIPSResult<CLIPSValue> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeMap for MultifieldSerializer {
+    type Ok = CLIPSValue;
+    type Error = CLIPSError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> CLIPSResult<()> {
+        self.push(key)
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> CLIPSResult<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> CLIPSResult<CLIPSValue> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStruct for MultifieldSerializer {
+    type Ok = CLIPSValue;
+    type Error = CLIPSError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        _key: &'static str,
+        value: &T,
+    ) -> CLIPSResult<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> CLIPSResult<CLIPSValue> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStructVariant for MultifieldSerializer {
+    type Ok = CLIPSValue;
+    type Error = CLIPSError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        _key: &'static str,
+        value: &T,
+    ) -> CLIPSResult<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> CLIPSResult<CLIPSValue> {
+        self.finish()
+    }
+}
+
+// Goes through the flattened values one at a time, so compound types just take as many values as they need.
+struct FlatDeserializer {
+    values: Vec<CLIPSValue>,
+    pos: usize,
+}
+
+impl FlatDeserializer {
+    fn peek(&self) -> Option<&CLIPSValue> {
+        self.values.get(self.pos)
+    }
+
+    fn next(&mut self) -> CLIPSResult<CLIPSValue> {
+        let value = self.values.get(self.pos).cloned().ok_or_else(|| {
+            CLIPSError::ValueMapping("ran out of values while deserializing".to_string())
+        })?;
+        self.pos += 1;
+
+        Ok(value)
+    }
+
+    fn is_nil(&self) -> bool {
+        matches!(self.peek(), Some(CLIPSValue::Symbol(symbol)) if symbol == "nil")
+    }
+}
+
+impl<'de> de::Deserializer<'de> for &mut FlatDeserializer {
+    type Error = CLIPSError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> CLIPSResult<V::Value> {
+        visit_clipsvalue(self.next()?, visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> CLIPSResult<V::Value> {
+        if self.peek().is_none() {
+            visitor.visit_none()
+        } else if self.is_nil() {
+            self.pos += 1;
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> CLIPSResult<V::Value> {
+        if !self.is_nil() {
+            return Err(CLIPSError::ValueMapping(
+                "expected the nil symbol for a unit value".to_string(),
+            ));
+        }
+
+        self.pos += 1;
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> CLIPSResult<V::Value> {
+        self.deserialize_unit(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> CLIPSResult<V::Value> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> CLIPSResult<V::Value> {
+        visitor.visit_seq(FlatSeqAccess {
+            de: self,
+            remaining: None,
+        })
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, len: usize, visitor: V) -> CLIPSResult<V::Value> {
+        visitor.visit_seq(FlatSeqAccess {
+            de: self,
+            remaining: Some(len),
+        })
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> CLIPSResult<V::Value> {
+        self.deserialize_tuple(len, visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> CLIPSResult<V::Value> {
+        visitor.visit_map(FlatMapAccess { de: self })
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> CLIPSResult<V::Value> {
+        self.deserialize_tuple(fields.len(), visitor)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> CLIPSResult<V::Value> {
+        match self.peek() {
+            Some(CLIPSValue::Symbol(_)) => visitor.visit_enum(FlatEnumAccess { de: self }),
+            Some(value) => Err(CLIPSError::ValueMapping(format!(
+                "expected a symbol with an enum variant name, found {}",
+                value
+            ))),
+            None => Err(CLIPSError::ValueMapping(
+                "ran out of values while deserializing".to_string(),
+            )),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf identifier ignored_any
+    }
+}
+
+struct FlatSeqAccess<'a> {
+    de: &'a mut FlatDeserializer,
+    // `None` means the sequence takes every value left.
+    remaining: Option<usize>,
+}
+
+impl<'de, 'a> de::SeqAccess<'de> for FlatSeqAccess<'a> {
+    type Error = CLIPSError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> CLIPSResult<Option<T::Value>> {
+        match self.remaining {
+            Some(0) => return Ok(None),
+            Some(ref mut remaining) => *remaining -= 1,
+            None if self.de.peek().is_none() => return Ok(None),
+            None => {}
+        }
+
+        seed.deserialize(&mut *self.de).map(Some)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        self.remaining
+    }
+}
+
+struct FlatMapAccess<'a> {
+    de: &'a mut FlatDeserializer,
+}
+
+impl<'de, 'a> de::MapAccess<'de> for FlatMapAccess<'a> {
+    type Error = CLIPSError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> CLIPSResult<Option<K::Value>> {
+        if self.de.peek().is_none() {
+            return Ok(None);
+        }
+
+        seed.deserialize(&mut *self.de).map(Some)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> CLIPSResult<V::Value> {
+        seed.deserialize(&mut *self.de)
+    }
+}
+
+struct FlatEnumAccess<'a> {
+    de: &'a mut FlatDeserializer,
+}
+
+impl<'de, 'a> de::EnumAccess<'de> for FlatEnumAccess<'a> {
+    type Error = CLIPSError;
+    type Variant = Self;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> CLIPSResult<(V::Value, Self)> {
+        let variant = match self.de.next()? {
+            CLIPSValue::Symbol(variant) => variant,
+            value => {
+                return Err(CLIPSError::ValueMapping(format!(
+                    "expected a symbol with an enum variant name, found {}",
+                    value
+                )))
+            }
+        };
+
+        let value = seed.deserialize(IntoDeserializer::<CLIPSError>::into_deserializer(variant))?;
+        Ok((value, self))
+    }
+}
+
+impl<'de, 'a> de::VariantAccess<'de> for FlatEnumAccess<'a> {
+    type Error = CLIPSError;
+
+    fn unit_variant(self) -> CLIPSResult<()> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> CLIPSResult<T::Value> {
+        seed.deserialize(self.de)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, len: usize, visitor: V) -> CLIPSResult<V::Value> {
+        de::Deserializer::deserialize_tuple(self.de, len, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> CLIPSResult<V::Value> {
+        de::Deserializer::deserialize_tuple(self.de, fields.len(), visitor)
+    }
+}
@@ -0,0 +1,104 @@
+use std::{
+    collections::VecDeque,
+    ops::Deref,
+    sync::{Arc, Condvar, Mutex},
+};
+
+use crate::{CLIPSResult, Environment};
+
+struct PoolInner {
+    available: Mutex<VecDeque<Environment>>,
+    returned: Condvar,
+}
+
+// A fixed set of environments, each running on its own CLIPS thread, that are handed out one caller at a time. Cloning the pool gives another handle to the same environments.
+#[derive(Clone)]
+pub struct EnvironmentPool {
+    inner: Arc<PoolInner>,
+    size: usize,
+}
+
+impl EnvironmentPool {
+    // `init` runs once for every environment before the pool is returned, e.g. to load the same ruleset into all of them.
+    pub fn new<F: Fn(&Environment) -> CLIPSResult<()>>(size: usize, init: F) -> CLIPSResult<Self> {
+        let mut environments = VecDeque::with_capacity(size);
+
+        for _ in 0..size {
+            let env = Environment::new();
+            init(&env)?;
+            environments.push_back(env);
+        }
+
+        Ok(Self {
+            inner: Arc::new(PoolInner {
+                available: Mutex::new(environments),
+                returned: Condvar::new(),
+            }),
+            size,
+        })
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    // Blocks until an environment is returned to the pool if all of them are checked out. Callers waiting at the same time aren't guaranteed to be served in the order they started waiting.
+    pub fn acquire(&self) -> PooledEnvironment {
+        let mut available = self.inner.available.lock().unwrap();
+
+        loop {
+            if let Some(env) = take_least_busy(&mut available) {
+                return PooledEnvironment {
+                    env: Some(env),
+                    pool: self.inner.clone(),
+                };
+            }
+
+            available = self.inner.returned.wait(available).unwrap();
+        }
+    }
+
+    // Like `acquire`, but returns `None` instead of blocking if every environment is checked out.
+    pub fn try_acquire(&self) -> Option<PooledEnvironment> {
+        let mut available = self.inner.available.lock().unwrap();
+
+        take_least_busy(&mut available).map(|env| PooledEnvironment {
+            env: Some(env),
+            pool: self.inner.clone(),
+        })
+    }
+}
+
+// Environments can still be working on commands sent before they were returned, so we prefer the one with the fewest of those. Ties go to the environment that was returned first, which makes an idle pool round-robin.
+fn take_least_busy(available: &mut VecDeque<Environment>) -> Option<Environment> {
+    let index = available
+        .iter()
+        .enumerate()
+        .min_by_key(|(_, env)| env.pending_commands())
+        .map(|(index, _)| index)?;
+
+    available.remove(index)
+}
+
+// Goes back to the pool it came from when dropped.
+pub struct PooledEnvironment {
+    env: Option<Environment>,
+    pool: Arc<PoolInner>,
+}
+
+impl Deref for PooledEnvironment {
+    type Target = Environment;
+
+    fn deref(&self) -> &Environment {
+        self.env.as_ref().unwrap()
+    }
+}
+
+impl Drop for PooledEnvironment {
+    fn drop(&mut self) {
+        if let Some(env) = self.env.take() {
+            self.pool.available.lock().unwrap().push_back(env);
+            self.pool.returned.notify_one();
+        }
+    }
+}
@@ -0,0 +1,183 @@
+use crate::{dump::write_fact, CLIPSError, CLIPSResult, SlotMap};
+
+// The order constructs come out in. Anything a construct can refer to comes before it, e.g. templates before the rules that match them and the deffacts that use them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ConstructKind {
+    Defmodule,
+    Deftemplate,
+    Defclass,
+    Defglobal,
+    Defgeneric,
+    Deffunction,
+    Defmethod,
+    DefmessageHandler,
+    Defrule,
+    Deffacts,
+    Definstances,
+}
+
+impl ConstructKind {
+    fn from_keyword(keyword: &str) -> Option<Self> {
+        match keyword {
+            "defmodule" => Some(Self::Defmodule),
+            "deftemplate" => Some(Self::Deftemplate),
+            "defclass" => Some(Self::Defclass),
+            "defglobal" => Some(Self::Defglobal),
+            "defgeneric" => Some(Self::Defgeneric),
+            "deffunction" => Some(Self::Deffunction),
+            "defmethod" => Some(Self::Defmethod),
+            "defmessage-handler" => Some(Self::DefmessageHandler),
+            "defrule" => Some(Self::Defrule),
+            "deffacts" => Some(Self::Deffacts),
+            "definstances" => Some(Self::Definstances),
+            _ => None,
+        }
+    }
+
+    pub fn keyword(&self) -> &'static str {
+        match self {
+            Self::Defmodule => "defmodule",
+            Self::Deftemplate => "deftemplate",
+            Self::Defclass => "defclass",
+            Self::Defglobal => "defglobal",
+            Self::Defgeneric => "defgeneric",
+            Self::Deffunction => "deffunction",
+            Self::Defmethod => "defmethod",
+            Self::DefmessageHandler => "defmessage-handler",
+            Self::Defrule => "defrule",
+            Self::Deffacts => "deffacts",
+            Self::Definstances => "definstances",
+        }
+    }
+}
+
+// Puts constructs together into a single program for `load_from_str`, ordered by kind so that nothing comes before what it refers to. Constructs of the same kind keep the order they were added in.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ProgramBuilder {
+    constructs: Vec<(ConstructKind, String)>,
+}
+
+impl ProgramBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // `construct` must be exactly one construct, e.g. `(deftemplate point (slot x))`. Only its shape is checked here, and CLIPS checks the rest when the program is loaded.
+    pub fn construct(mut self, construct: &str) -> CLIPSResult<Self> {
+        let kind = check_construct(construct)?;
+        self.constructs.push((kind, construct.trim().to_string()));
+        Ok(self)
+    }
+
+    pub fn deffacts<I: IntoIterator<Item = SlotMap>>(
+        mut self,
+        name: &str,
+        facts: I,
+    ) -> CLIPSResult<Self> {
+        let mut text = format!("(deffacts {}", name).into_bytes();
+        for fact in facts {
+            text.extend_from_slice(b"\n   ");
+            write_fact(&mut text, fact.definition_name(), fact.slots())?;
+        }
+        text.push(b')');
+
+        self.constructs.push((
+            ConstructKind::Deffacts,
+            String::from_utf8_lossy(&text).into_owned(),
+        ));
+        Ok(self)
+    }
+
+    pub fn build(&self) -> String {
+        let mut constructs: Vec<_> = self.constructs.iter().collect();
+        // A stable sort, so constructs of the same kind stay in the order they were added.
+        constructs.sort_by_key(|(kind, _)| *kind);
+
+        constructs
+            .into_iter()
+            .map(|(_, construct)| construct.as_str())
+            .collect::<Vec<_>>()
+            .join("\n\n")
+            + "\n"
+    }
+}
+
+// Finds out what kind of construct the text holds, and makes sure it's a single balanced expression with nothing but whitespace and comments around it.
+fn check_construct(construct: &str) -> CLIPSResult<ConstructKind> {
+    let mut depth = 0usize;
+    let mut expressions = 0;
+    let mut keyword = None;
+    let mut in_string = false;
+    let mut escaped = false;
+    let mut in_comment = false;
+
+    for (i, c) in construct.char_indices() {
+        if in_comment {
+            in_comment = c != '\n';
+            continue;
+        }
+
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+
+            continue;
+        }
+
+        match c {
+            ';' => in_comment = true,
+            '"' => in_string = true,
+            '(' => {
+                if depth == 0 {
+                    expressions += 1;
+                    if expressions > 1 {
+                        return Err(CLIPSError::InvalidConstruct(
+                            "only a single construct can be added at a time",
+                        ));
+                    }
+
+                    let rest = &construct[i + 1..];
+                    let end = rest
+                        .find(|c: char| c.is_whitespace() || "()\";".contains(c))
+                        .unwrap_or(rest.len());
+                    keyword = Some(&rest[..end]);
+                }
+                depth += 1;
+            }
+            ')' if depth == 0 => {
+                return Err(CLIPSError::InvalidConstruct(
+                    "the construct closes more parentheses than it opens",
+                ))
+            }
+            ')' => depth -= 1,
+            _ if depth == 0 && !c.is_whitespace() => {
+                return Err(CLIPSError::InvalidConstruct(
+                    "there's text outside of the construct",
+                ))
+            }
+            _ => {}
+        }
+    }
+
+    if in_string {
+        return Err(CLIPSError::InvalidConstruct(
+            "the construct has an unterminated string",
+        ));
+    }
+    if depth != 0 {
+        return Err(CLIPSError::InvalidConstruct(
+            "the construct has unbalanced parentheses",
+        ));
+    }
+
+    match keyword {
+        None => Err(CLIPSError::InvalidConstruct("there's no construct")),
+        Some(keyword) => ConstructKind::from_keyword(keyword).ok_or(CLIPSError::InvalidConstruct(
+            "the text doesn't start with a construct keyword, e.g. `deftemplate` or `defrule`",
+        )),
+    }
+}
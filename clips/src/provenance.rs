@@ -0,0 +1,22 @@
+use std::{
+    collections::HashMap,
+    ffi::c_void,
+    sync::{Arc, Mutex},
+};
+
+pub(crate) const FACT_PROVENANCE_CALLBACK: &str = "rust-fact-provenance";
+
+// Tags keyed by fact index. Facts derived by rules are never in here.
+pub(crate) type SharedFactProvenance = Arc<Mutex<HashMap<i64, String>>>;
+
+// The context given to CLIPS is the map behind the `SharedFactProvenance`, which the environment keeps alive for as long as the callback is registered. Indices are never reused, so forgetting the tag of a retracted fact is only about not growing forever.
+pub(crate) extern "C" fn forget_fact_provenance(
+    _environment: *mut clips_sys::Environment,
+    fact: *mut c_void,
+    context: *mut c_void,
+) {
+    let provenance = unsafe { &*(context as *const Mutex<HashMap<i64, String>>) };
+    let index = unsafe { clips_sys::FactIndex(fact as *mut clips_sys::Fact) };
+
+    provenance.lock().unwrap().remove(&index);
+}
@@ -1,6 +1,10 @@
-use std::ffi::{c_void, CStr};
+use std::{
+    collections::{HashMap, VecDeque},
+    ffi::{c_void, CStr},
+    sync::Mutex,
+};
 
-use crate::{CLIPSEnvironment, CLIPSSignal, UDFData};
+use crate::{catch_callback_panic, CLIPSEnvironment, CLIPSSignal, UDFData, STDERR};
 
 pub type RegisterableRouter = Box<dyn Router + Send + Sync>;
 
@@ -17,6 +21,11 @@ pub trait Router {
     fn supports(&self) -> RouterSupport;
     fn query(&mut self, logical_name: &str) -> bool;
     fn write(&mut self, _logical_name: &str, _data: &CStr) {}
+    // Routers that return a size here get their writes through `write_chunked()` instead of `write()`, in pieces of at most that many bytes, so text CLIPS writes in one go (e.g. `ppdefrule` of a huge rule) never has to be handled whole. A piece can end in the middle of a multi-byte character.
+    fn write_chunk_size(&self) -> Option<usize> {
+        None
+    }
+    fn write_chunked(&mut self, _logical_name: &str, _chunk: &[u8], _final_chunk: bool) {}
     fn read(&mut self, _logical_name: &str) -> Option<i32> {
         None
     }
@@ -28,6 +37,187 @@ pub trait Router {
     fn signal(&mut self, _signal: CLIPSSignal) {}
 }
 
+// Combines several routers into one that CLIPS sees as a single router, so the same output can go to multiple destinations (e.g. the console and a capture buffer). Inner routers are consulted in order of their priority, highest first, mirroring how CLIPS itself orders routers.
+#[derive(Default)]
+pub struct TeeRouter {
+    routers: Vec<(i32, RegisterableRouter)>,
+}
+
+impl TeeRouter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_router(mut self, priority: i32, router: RegisterableRouter) -> Self {
+        self.add_router(priority, router);
+        self
+    }
+
+    pub fn add_router(&mut self, priority: i32, router: RegisterableRouter) {
+        let position = self
+            .routers
+            .iter()
+            .position(|(existing_priority, _)| *existing_priority < priority)
+            .unwrap_or(self.routers.len());
+        self.routers.insert(position, (priority, router));
+    }
+}
+
+impl Router for TeeRouter {
+    fn supports(&self) -> RouterSupport {
+        self.routers
+            .iter()
+            .fold(RouterSupport::empty(), |acc, (_, router)| {
+                acc | router.supports()
+            })
+    }
+
+    fn query(&mut self, logical_name: &str) -> bool {
+        // Not short-circuiting on purpose: every inner router gets to see the query, even after one of them claims the name.
+        self.routers.iter_mut().fold(false, |claimed, (_, router)| {
+            router.query(logical_name) || claimed
+        })
+    }
+
+    fn write(&mut self, logical_name: &str, data: &CStr) {
+        for (_, router) in self.routers.iter_mut() {
+            if router.supports().contains(RouterSupport::WRITE) && router.query(logical_name) {
+                dispatch_write(router.as_mut(), logical_name, data);
+            }
+        }
+    }
+
+    // Input can't be teed, so it comes from the first router able to provide it.
+    fn read(&mut self, logical_name: &str) -> Option<i32> {
+        self.routers
+            .iter_mut()
+            .filter(|(_, router)| router.supports().contains(RouterSupport::READ))
+            .find_map(|(_, router)| {
+                if router.query(logical_name) {
+                    router.read(logical_name)
+                } else {
+                    None
+                }
+            })
+    }
+
+    fn unread(&mut self, logical_name: &str, data: i32) -> Option<i32> {
+        self.routers
+            .iter_mut()
+            .filter(|(_, router)| router.supports().contains(RouterSupport::READ))
+            .find_map(|(_, router)| {
+                if router.query(logical_name) {
+                    router.unread(logical_name, data)
+                } else {
+                    None
+                }
+            })
+    }
+
+    fn exit(&mut self, exit_code: i32) {
+        for (_, router) in self.routers.iter_mut() {
+            router.exit(exit_code);
+        }
+    }
+
+    fn signal(&mut self, signal: CLIPSSignal) {
+        for (_, router) in self.routers.iter_mut() {
+            if router.supports().contains(RouterSupport::SIGNAL) {
+                router.signal(signal);
+            }
+        }
+    }
+}
+
+// Where a `BufferedReadRouter` gets its input from. `fill()` is only called once everything it gave before has been read, and returning `None` ends the input for `logical_name`. An empty chunk is skipped, and `fill()` is called again.
+pub trait ReadSource {
+    fn query(&mut self, logical_name: &str) -> bool;
+    fn fill(&mut self, logical_name: &str) -> Option<Vec<u8>>;
+}
+
+// Gives CLIPS the input from a `ReadSource` one character at a time, the way it reads it, and takes back the characters it unreads. CLIPS uses -1 for the end of the input, so bytes are handed over as unsigned values to keep bytes above 127 from looking like it.
+pub struct BufferedReadRouter<S> {
+    source: S,
+    buffers: HashMap<String, ReadBuffer>,
+}
+
+#[derive(Default)]
+struct ReadBuffer {
+    pending: VecDeque<u8>,
+    // Unread characters come back out last in, first out. The end of the input can be unread too, and is kept as `None`.
+    unread: Vec<Option<u8>>,
+}
+
+impl<S: ReadSource> BufferedReadRouter<S> {
+    pub fn new(source: S) -> Self {
+        Self {
+            source,
+            buffers: HashMap::new(),
+        }
+    }
+
+    pub fn source(&self) -> &S {
+        &self.source
+    }
+
+    pub fn source_mut(&mut self) -> &mut S {
+        &mut self.source
+    }
+
+    pub fn into_source(self) -> S {
+        self.source
+    }
+}
+
+impl<S: ReadSource> Router for BufferedReadRouter<S> {
+    fn supports(&self) -> RouterSupport {
+        RouterSupport::READ
+    }
+
+    fn query(&mut self, logical_name: &str) -> bool {
+        self.source.query(logical_name)
+    }
+
+    fn read(&mut self, logical_name: &str) -> Option<i32> {
+        let buffer = self.buffers.entry(logical_name.to_string()).or_default();
+
+        if let Some(ch) = buffer.unread.pop() {
+            return ch.map(i32::from);
+        }
+
+        while buffer.pending.is_empty() {
+            buffer.pending.extend(self.source.fill(logical_name)?);
+        }
+
+        buffer.pending.pop_front().map(i32::from)
+    }
+
+    fn unread(&mut self, logical_name: &str, data: i32) -> Option<i32> {
+        let buffer = self.buffers.entry(logical_name.to_string()).or_default();
+        buffer.unread.push(u8::try_from(data).ok());
+        Some(data)
+    }
+}
+
+// Hands a write to the router the way it asked for it. A write that fits in one chunk, even an empty one, still comes as a single final chunk.
+pub(crate) fn dispatch_write<R: Router + ?Sized>(router: &mut R, logical_name: &str, data: &CStr) {
+    let Some(chunk_size) = router.write_chunk_size() else {
+        router.write(logical_name, data);
+        return;
+    };
+
+    let mut rest = data.to_bytes();
+    loop {
+        let (chunk, remaining) = rest.split_at(chunk_size.max(1).min(rest.len()));
+        router.write_chunked(logical_name, chunk, remaining.is_empty());
+
+        if remaining.is_empty() {
+            break;
+        }
+        rest = remaining;
+    }
+}
+
 pub(crate) extern "C" fn router_query(
     environment: *mut clips_sys::Environment,
     logical_name: *const i8,
@@ -36,14 +226,15 @@ pub(crate) extern "C" fn router_query(
     let router_name = unsafe { CStr::from_ptr(router_name as *const i8) };
     let router_name_str = router_name.to_str().unwrap();
 
+    // Routers have no way of failing, so a logical name that isn't valid UTF-8 is converted lossily whatever the string conversion is.
     let logical_name = unsafe { CStr::from_ptr(logical_name) };
-    let logical_name = logical_name.to_str().unwrap();
+    let logical_name = logical_name.to_string_lossy();
 
     let env = CLIPSEnvironment::from_raw(environment);
     let mut router_map = env.retrieve_router_map();
     let router = router_map.get_mut(router_name_str).unwrap();
 
-    let res = router.query(logical_name);
+    let res = catch_callback_panic(environment, false, || router.query(&logical_name));
     env.store_router_map(router_map);
     res
 }
@@ -58,7 +249,7 @@ pub(crate) extern "C" fn router_write(
     let router_name_str = router_name.to_str().unwrap();
 
     let logical_name = unsafe { CStr::from_ptr(logical_name) };
-    let logical_name = logical_name.to_str().unwrap();
+    let logical_name = logical_name.to_string_lossy();
 
     let data = unsafe { CStr::from_ptr(data) };
 
@@ -66,9 +257,10 @@ pub(crate) extern "C" fn router_write(
     let mut router_map = env.retrieve_router_map();
     let router = router_map.get_mut(router_name_str).unwrap();
 
-    let res = router.write(logical_name, data);
+    catch_callback_panic(environment, (), || {
+        dispatch_write(router.as_mut(), &logical_name, data)
+    });
     env.store_router_map(router_map);
-    res
 }
 
 pub(crate) extern "C" fn router_read(
@@ -80,13 +272,13 @@ pub(crate) extern "C" fn router_read(
     let router_name_str = router_name.to_str().unwrap();
 
     let logical_name = unsafe { CStr::from_ptr(logical_name) };
-    let logical_name = logical_name.to_str().unwrap();
+    let logical_name = logical_name.to_string_lossy();
 
     let env = CLIPSEnvironment::from_raw(environment);
     let mut router_map = env.retrieve_router_map();
     let router = router_map.get_mut(router_name_str).unwrap();
 
-    let res = router.read(logical_name).unwrap_or(-1);
+    let res = catch_callback_panic(environment, -1, || router.read(&logical_name).unwrap_or(-1));
     env.store_router_map(router_map);
     res
 }
@@ -101,13 +293,15 @@ pub(crate) extern "C" fn router_unread(
     let router_name_str = router_name.to_str().unwrap();
 
     let logical_name = unsafe { CStr::from_ptr(logical_name) };
-    let logical_name = logical_name.to_str().unwrap();
+    let logical_name = logical_name.to_string_lossy();
 
     let env = CLIPSEnvironment::from_raw(environment);
     let mut router_map = env.retrieve_router_map();
     let router = router_map.get_mut(router_name_str).unwrap();
 
-    let res = router.unread(logical_name, data).unwrap_or(-1);
+    let res = catch_callback_panic(environment, -1, || {
+        router.unread(&logical_name, data).unwrap_or(-1)
+    });
     env.store_router_map(router_map);
     res
 }
@@ -124,23 +318,68 @@ pub(crate) extern "C" fn router_exit(
     let mut router_map = env.retrieve_router_map();
     let router = router_map.get_mut(router_name_str).unwrap();
 
-    router.exit(exit_code);
+    catch_callback_panic(environment, (), || router.exit(exit_code));
     env.store_router_map(router_map);
 }
 
+// The exit guard never claims a logical name, it only exists to get notified when CLIPS wants to exit.
+pub(crate) extern "C" fn exit_guard_query(
+    _environment: *mut clips_sys::Environment,
+    _logical_name: *const i8,
+    _context: *mut c_void,
+) -> bool {
+    false
+}
+
+// CLIPS calls every router's exit callback before terminating the process. Aborting the exit keeps the process alive, so instead we halt whatever is running and record the exit code for the environment handles. The context is the shared exit code.
+pub(crate) extern "C" fn exit_guard_exit(
+    environment: *mut clips_sys::Environment,
+    exit_code: i32,
+    context: *mut c_void,
+) {
+    let exited = unsafe { &*(context as *const Mutex<Option<i32>>) };
+    *exited.lock().unwrap() = Some(exit_code);
+
+    unsafe {
+        clips_sys::AbortExit(environment);
+        clips_sys::SetHaltExecution(environment, true);
+        clips_sys::SetHaltRules(environment, true);
+    }
+}
+
+// Claims stderr while the crate evaluates something on the user's behalf, so the error text can be reported with the error. The context is the `String` collecting the text.
+pub(crate) extern "C" fn error_capture_query(
+    _environment: *mut clips_sys::Environment,
+    logical_name: *const i8,
+    _context: *mut c_void,
+) -> bool {
+    unsafe { CStr::from_ptr(logical_name) }.to_bytes() == STDERR.as_bytes()
+}
+
+pub(crate) extern "C" fn error_capture_write(
+    _environment: *mut clips_sys::Environment,
+    _logical_name: *const i8,
+    data: *const i8,
+    context: *mut c_void,
+) {
+    let captured = unsafe { &mut *(context as *mut String) };
+    captured.push_str(&unsafe { CStr::from_ptr(data) }.to_string_lossy());
+}
+
 pub(crate) extern "C" fn call_udf(
     environment: *mut clips_sys::Environment,
     context: *mut clips_sys::UDFContext,
     udf_result: *mut clips_sys::UDFValue,
 ) {
-    let udf_name = unsafe { CStr::from_ptr(context.as_ref().unwrap().context as *const i8) };
-    let udf_name_str = udf_name.to_str().unwrap();
+    // The context is the UDF's index in the map, not a pointer.
+    let index = unsafe { context.as_ref().unwrap().context } as usize;
 
     let env = CLIPSEnvironment::from_raw(environment);
     let mut udf_map = env.retrieve_udf_map();
-    let function = udf_map.get_mut(udf_name_str).unwrap();
+    let function = udf_map.get_mut(index).unwrap();
 
     let data = UDFData::new(environment, context, udf_result);
-    function(data);
+    // The map is taken out of the environment while the UDF runs, so a panic must not skip putting it back.
+    catch_callback_panic(environment, (), || function(data));
     env.store_udf_map(udf_map);
 }
@@ -0,0 +1,94 @@
+use std::{
+    ffi::{c_void, CString},
+    sync::mpsc,
+};
+
+use crate::{extract_clipsvalue, CLIPSValue};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SlotChange {
+    pub index: i64,
+    pub old: CLIPSValue,
+    pub new: CLIPSValue,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SlotWatchId(pub(crate) u64);
+
+impl SlotWatchId {
+    // The name the modify callback is registered with.
+    pub(crate) fn callback_name(&self) -> CString {
+        CString::new(format!("rust-slot-watch-{}", self.0)).unwrap()
+    }
+}
+
+pub(crate) struct SlotWatcher {
+    // Only compared against, never dereferenced, so it doesn't matter if the template goes away.
+    pub(crate) deftemplate: *mut clips_sys::Deftemplate,
+    pub(crate) slot: CString,
+    pub(crate) tx: mpsc::Sender<SlotChange>,
+    old_value: Option<CLIPSValue>,
+}
+
+impl SlotWatcher {
+    pub(crate) fn new(
+        deftemplate: *mut clips_sys::Deftemplate,
+        slot: CString,
+        tx: mpsc::Sender<SlotChange>,
+    ) -> Self {
+        Self {
+            deftemplate,
+            slot,
+            tx,
+            old_value: None,
+        }
+    }
+
+    fn slot_value(
+        &self,
+        env: *mut clips_sys::Environment,
+        fact: *mut clips_sys::Fact,
+    ) -> Option<CLIPSValue> {
+        if unsafe { clips_sys::FactDeftemplate(fact) } != self.deftemplate {
+            return None;
+        }
+
+        let mut value = clips_sys::CLIPSValue::default();
+        let res = unsafe { clips_sys::GetFactSlot(fact, self.slot.as_ptr(), &mut value) };
+        if res != clips_sys::GetSlotError_GSE_NO_ERROR {
+            return None;
+        }
+
+        extract_clipsvalue(env, value).ok()
+    }
+}
+
+// CLIPS modifies facts in place, calling this once with only the old fact before the change and once with only the new fact after it. The context is the `SlotWatcher`, which the environment keeps alive for as long as the callback is registered. Facts of other templates are skipped before any value is read, and nothing is sent unless the slot actually changed. Sending errors are ignored, since it only means nobody is listening anymore.
+pub(crate) extern "C" fn slot_watch_callback(
+    environment: *mut clips_sys::Environment,
+    old_fact: *mut clips_sys::Fact,
+    new_fact: *mut clips_sys::Fact,
+    context: *mut c_void,
+) {
+    let watcher = unsafe { &mut *(context as *mut SlotWatcher) };
+
+    if !old_fact.is_null() {
+        watcher.old_value = watcher.slot_value(environment, old_fact);
+        return;
+    }
+
+    let (Some(old), Some(new)) = (
+        watcher.old_value.take(),
+        watcher.slot_value(environment, new_fact),
+    ) else {
+        return;
+    };
+
+    if old != new {
+        let _ = watcher.tx.send(SlotChange {
+            index: unsafe { clips_sys::FactIndex(new_fact) },
+            old,
+            new,
+        });
+    }
+}
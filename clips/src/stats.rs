@@ -0,0 +1,29 @@
+use std::ffi::c_void;
+
+// Counted over the whole life of the environment, and not reset by `clear` or `reset`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct EnvStats {
+    // A `modify` counts as a retraction and an assertion, since that's how CLIPS carries it out. Asserting a fact that already exists while fact duplication is off doesn't count.
+    pub assertions: u64,
+    pub retractions: u64,
+    // CLIPS has no hook for instance creation, so only the instances made through this library are counted, and not the ones made by `make-instance` in CLIPS code.
+    pub instances_made: u64,
+}
+
+pub(crate) extern "C" fn count_assertion(
+    _environment: *mut clips_sys::Environment,
+    _fact: *mut c_void,
+    context: *mut c_void,
+) {
+    let stats = unsafe { &mut *(context as *mut EnvStats) };
+    stats.assertions += 1;
+}
+
+pub(crate) extern "C" fn count_retraction(
+    _environment: *mut clips_sys::Environment,
+    _fact: *mut c_void,
+    context: *mut c_void,
+) {
+    let stats = unsafe { &mut *(context as *mut EnvStats) };
+    stats.retractions += 1;
+}
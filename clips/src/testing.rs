@@ -0,0 +1,321 @@
+use std::{
+    ffi::CStr,
+    fmt,
+    sync::{Arc, Mutex},
+};
+
+use crate::{
+    dump::write_fact, CLIPSError, CLIPSResult, CLIPSValue, Environment, FactBuilderData,
+    IntoFactOrInstance, LogicalName, RetrievedFact, Router, RouterSupport,
+};
+
+const OUTPUT_ROUTER_NAME: &str = "rust-rule-test-output";
+// Above the default console router, so the output is captured instead of being printed.
+const OUTPUT_ROUTER_PRIORITY: i32 = 30;
+
+type GivenStep = Box<dyn FnOnce(&Environment) -> CLIPSResult<()>>;
+
+// Describes the facts an expectation is about. Only the slots that were given are compared, so a pattern with no slots matches every fact of its template.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FactPattern {
+    pub template: String,
+    pub slots: Vec<(String, CLIPSValue)>,
+}
+
+impl FactPattern {
+    pub fn new(template: &str) -> Self {
+        Self {
+            template: template.to_string(),
+            slots: Vec::new(),
+        }
+    }
+
+    // Matches the ordered facts `(head values...)` with exactly these fields.
+    pub fn ordered(head: &str, values: Vec<CLIPSValue>) -> Self {
+        Self::new(head).slot("implied", CLIPSValue::Multifield(values))
+    }
+
+    pub fn slot<T: Into<CLIPSValue>>(mut self, slot: &str, value: T) -> Self {
+        self.slots.push((slot.to_string(), value.into()));
+        self
+    }
+
+    pub fn matches(&self, fact: &RetrievedFact) -> bool {
+        fact.template == self.template
+            && self
+                .slots
+                .iter()
+                .all(|(slot, value)| fact.slot(slot) == Some(value))
+    }
+}
+
+impl fmt::Display for FactPattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut text = Vec::new();
+        write_fact(&mut text, &self.template, &self.slots).map_err(|_| fmt::Error)?;
+
+        f.write_str(&String::from_utf8_lossy(&text))
+    }
+}
+
+// Runs rules against a fresh environment and checks what they did. Everything given is loaded or asserted in the order it was added to the test, and then the rules run once.
+#[derive(Default)]
+pub struct RuleTest {
+    given: Vec<GivenStep>,
+    expected_facts: Vec<FactPattern>,
+    expected_absent_facts: Vec<FactPattern>,
+    expected_fired_rules: Option<Vec<String>>,
+    expected_output: Vec<String>,
+}
+
+impl RuleTest {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn constructs(mut self, constructs: &str) -> Self {
+        let constructs = constructs.to_string();
+        self.given
+            .push(Box::new(move |env| env.load_from_str(constructs)));
+        self
+    }
+
+    pub fn given_fact<T: IntoFactOrInstance<FactBuilderData> + Send + Sync + 'static>(
+        mut self,
+        fact: T,
+    ) -> Self {
+        self.given
+            .push(Box::new(move |env| env.assert_fact(fact, None)));
+        self
+    }
+
+    // `fact` is written the way it would be in an `assert` command, e.g. `(point (x 1) (y 2))`.
+    pub fn given_fact_str(mut self, fact: &str) -> Self {
+        let fact = fact.to_string();
+        self.given
+            .push(Box::new(move |env| env.assert_string(&fact).map(|_| ())));
+        self
+    }
+
+    pub fn expect_fact(mut self, pattern: FactPattern) -> Self {
+        self.expected_facts.push(pattern);
+        self
+    }
+
+    pub fn expect_no_fact(mut self, pattern: FactPattern) -> Self {
+        self.expected_absent_facts.push(pattern);
+        self
+    }
+
+    // The whole sequence must match, so rules that aren't expected to fire can't fire in between.
+    pub fn expect_fired_rules<I, S>(mut self, rules: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.expected_fired_rules = Some(rules.into_iter().map(Into::into).collect());
+        self
+    }
+
+    // What CLIPS printed to stdout during the whole test must contain `text`.
+    pub fn expect_output(mut self, text: &str) -> Self {
+        self.expected_output.push(text.to_string());
+        self
+    }
+
+    // Errors only if the test couldn't be carried out, e.g. because the constructs didn't load. Expectations that weren't met are in the outcome.
+    pub fn run(self) -> CLIPSResult<RuleTestOutcome> {
+        let env = Environment::new();
+        let output = Arc::new(Mutex::new(String::new()));
+
+        env.add_router(
+            OUTPUT_ROUTER_NAME.to_string(),
+            OUTPUT_ROUTER_PRIORITY,
+            Box::new(OutputCapture {
+                output: output.clone(),
+            }),
+        )?;
+
+        for step in self.given {
+            step(&env)?;
+        }
+
+        let fired_rules = env.run_recording_fired_rules()?;
+        let mut failures = Vec::new();
+
+        for pattern in &self.expected_facts {
+            let facts = facts_of(&env, &pattern.template)?;
+
+            if !facts.iter().any(|fact| pattern.matches(fact)) {
+                failures.push(format!(
+                    "expected a fact matching {}, but {}",
+                    pattern,
+                    describe_facts(&pattern.template, &facts)
+                ));
+            }
+        }
+
+        for pattern in &self.expected_absent_facts {
+            let facts: Vec<RetrievedFact> = facts_of(&env, &pattern.template)?
+                .into_iter()
+                .filter(|fact| pattern.matches(fact))
+                .collect();
+
+            if !facts.is_empty() {
+                failures.push(format!(
+                    "expected no fact matching {}, but found:\n{}",
+                    pattern,
+                    list_facts(&facts)
+                ));
+            }
+        }
+
+        if let Some(expected) = &self.expected_fired_rules {
+            if *expected != fired_rules {
+                failures.push(format!(
+                    "the rules didn't fire as expected:\n{}",
+                    diff_rules(expected, &fired_rules)
+                ));
+            }
+        }
+
+        let output = std::mem::take(&mut *output.lock().unwrap());
+
+        for text in &self.expected_output {
+            if !output.contains(text.as_str()) {
+                failures.push(format!(
+                    "expected the output to contain {:?}, but it was:\n{}",
+                    text, output
+                ));
+            }
+        }
+
+        env.close()?;
+
+        Ok(RuleTestOutcome {
+            failures,
+            fired_rules,
+            output,
+        })
+    }
+
+    // Panics with a description of everything that went wrong, for use with the standard test harness.
+    pub fn assert_passes(self) {
+        match self.run() {
+            Err(e) => panic!("the rule test couldn't run: {}", e),
+            Ok(outcome) if !outcome.passed() => panic!("{}", outcome),
+            Ok(_) => {}
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RuleTestOutcome {
+    // One entry per expectation that wasn't met.
+    pub failures: Vec<String>,
+    pub fired_rules: Vec<String>,
+    pub output: String,
+}
+
+impl RuleTestOutcome {
+    pub fn passed(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+impl fmt::Display for RuleTestOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.passed() {
+            return write!(f, "the rule test passed");
+        }
+
+        writeln!(f, "the rule test failed:")?;
+        for failure in &self.failures {
+            writeln!(f, "\n{}", failure)?;
+        }
+
+        Ok(())
+    }
+}
+
+struct OutputCapture {
+    output: Arc<Mutex<String>>,
+}
+
+impl Router for OutputCapture {
+    fn supports(&self) -> RouterSupport {
+        RouterSupport::WRITE
+    }
+
+    fn query(&mut self, logical_name: &str) -> bool {
+        LogicalName::from_name(logical_name) == Some(LogicalName::Stdout)
+    }
+
+    fn write(&mut self, _logical_name: &str, data: &CStr) {
+        self.output
+            .lock()
+            .unwrap()
+            .push_str(&data.to_string_lossy());
+    }
+}
+
+// A template that was never defined or used has no facts, which is what an ordered fact nobody asserted looks like.
+fn facts_of(env: &Environment, template: &str) -> CLIPSResult<Vec<RetrievedFact>> {
+    match env.template_info(template) {
+        Err(CLIPSError::TemplateNotFound) => Ok(Vec::new()),
+        Err(e) => Err(e),
+        Ok(_) => env.find_all_facts(template, "TRUE"),
+    }
+}
+
+fn describe_facts(template: &str, facts: &[RetrievedFact]) -> String {
+    if facts.is_empty() {
+        format!("there were no {} facts", template)
+    } else {
+        format!("the {} facts were:\n{}", template, list_facts(facts))
+    }
+}
+
+fn list_facts(facts: &[RetrievedFact]) -> String {
+    facts
+        .iter()
+        .map(|fact| {
+            let mut text = Vec::new();
+            let _ = write_fact(&mut text, &fact.template, &fact.slots);
+            format!("  f-{} {}", fact.index, String::from_utf8_lossy(&text))
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// Lines where the two sequences differ are marked with `!`.
+fn diff_rules(expected: &[String], actual: &[String]) -> String {
+    let width = expected
+        .iter()
+        .map(String::len)
+        .max()
+        .unwrap_or(0)
+        .max("expected".len());
+
+    let mut lines = vec![format!("     {:width$}  actual", "expected", width = width)];
+    for i in 0..expected.len().max(actual.len()) {
+        let expected_rule = expected.get(i).map(String::as_str);
+        let actual_rule = actual.get(i).map(String::as_str);
+        let marker = if expected_rule == actual_rule {
+            ' '
+        } else {
+            '!'
+        };
+
+        lines.push(format!(
+            "{} {:>3} {:width$}  {}",
+            marker,
+            i + 1,
+            expected_rule.unwrap_or("-"),
+            actual_rule.unwrap_or("-"),
+            width = width
+        ));
+    }
+
+    lines.join("\n")
+}
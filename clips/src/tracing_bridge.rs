@@ -0,0 +1,108 @@
+use std::{
+    collections::HashMap,
+    ffi::{c_void, CStr},
+};
+
+use crate::{
+    working_memory::fact_index_and_template, CLIPSSignal, LogicalName, Router, RouterSupport,
+};
+
+pub(crate) const TRACING_ROUTER_NAME: &str = "rust-tracing-bridge";
+// Above the default console router, so the output goes to `tracing` instead of being printed.
+pub(crate) const TRACING_ROUTER_PRIORITY: i32 = 30;
+
+// Turns everything CLIPS writes to stdout, stdwrn and stderr into `tracing` events, one event per line. The logical name decides the level: stderr is `ERROR`, stdwrn is `WARN` and stdout (where watch output goes) is `INFO`.
+#[derive(Default)]
+pub struct TracingRouter {
+    pending_lines: HashMap<LogicalName, String>,
+}
+
+impl TracingRouter {
+    fn emit(logical_name: LogicalName, line: &str) {
+        let logical_name_str = logical_name.as_str();
+
+        match logical_name {
+            LogicalName::Stderr => {
+                tracing::error!(target: "clips", logical_name = logical_name_str, "{}", line)
+            }
+            LogicalName::Stdwrn => {
+                tracing::warn!(target: "clips", logical_name = logical_name_str, "{}", line)
+            }
+            _ => tracing::info!(target: "clips", logical_name = logical_name_str, "{}", line),
+        }
+    }
+
+    fn flush(&mut self) {
+        for (logical_name, line) in self.pending_lines.drain() {
+            if !line.is_empty() {
+                Self::emit(logical_name, &line);
+            }
+        }
+    }
+}
+
+impl Router for TracingRouter {
+    fn supports(&self) -> RouterSupport {
+        RouterSupport::WRITE | RouterSupport::SIGNAL
+    }
+
+    fn query(&mut self, logical_name: &str) -> bool {
+        !matches!(
+            LogicalName::from_name(logical_name),
+            None | Some(LogicalName::Stdin)
+        )
+    }
+
+    // CLIPS writes a line in several pieces, so we only emit an event once the line is complete.
+    fn write(&mut self, logical_name: &str, data: &CStr) {
+        let Some(logical_name) = LogicalName::from_name(logical_name) else {
+            return;
+        };
+
+        let pending = self.pending_lines.entry(logical_name).or_default();
+        pending.push_str(&data.to_string_lossy());
+
+        while let Some(newline) = pending.find('\n') {
+            let line: String = pending.drain(..=newline).collect();
+            Self::emit(logical_name, line.trim_end());
+        }
+    }
+
+    // Whatever is left over when a run finishes won't be completed by the run, so it's emitted as it is.
+    fn signal(&mut self, signal: CLIPSSignal) {
+        if let CLIPSSignal::RunFinished { .. } = signal {
+            self.flush();
+        }
+    }
+}
+
+pub(crate) extern "C" fn trace_rule_fired(
+    _environment: *mut clips_sys::Environment,
+    activation: *mut clips_sys::Activation,
+    _context: *mut c_void,
+) {
+    if activation.is_null() {
+        return;
+    }
+
+    let rule_name = unsafe { CStr::from_ptr(clips_sys::ActivationRuleName(activation)) };
+    tracing::info!(target: "clips", rule = %rule_name.to_string_lossy(), "rule fired");
+}
+
+pub(crate) extern "C" fn trace_fact_asserted(
+    _environment: *mut clips_sys::Environment,
+    fact: *mut c_void,
+    _context: *mut c_void,
+) {
+    let (fact_index, template) = fact_index_and_template(fact);
+    tracing::debug!(target: "clips", fact_index, template, "fact asserted");
+}
+
+pub(crate) extern "C" fn trace_fact_retracted(
+    _environment: *mut clips_sys::Environment,
+    fact: *mut c_void,
+    _context: *mut c_void,
+) {
+    let (fact_index, template) = fact_index_and_template(fact);
+    tracing::debug!(target: "clips", fact_index, template, "fact retracted");
+}
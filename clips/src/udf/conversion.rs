@@ -1,7 +1,7 @@
 use clips_sys::{CLIPSInstanceName, CLIPSSymbol};
 use std::ffi::CString;
 
-use crate::CLIPSFrom;
+use crate::{CLIPSFrom, CLIPSInto, CLIPSValue};
 
 impl CLIPSFrom<usize> for clips_sys::UDFValue {
     fn from(value: usize, env: *mut clips_sys::Environment) -> clips_sys::UDFValue {
@@ -61,3 +61,19 @@ impl CLIPSFrom<f64> for clips_sys::UDFValue {
         res
     }
 }
+
+impl CLIPSFrom<CLIPSValue> for clips_sys::UDFValue {
+    fn from(value: CLIPSValue, env: *mut clips_sys::Environment) -> clips_sys::UDFValue {
+        let converted_value: clips_sys::CLIPSValue = CLIPSInto::into(value, env);
+        let mut res = clips_sys::UDFValue::default();
+        // Both unions hold the same pointer types, but they're different Rust types, so we go through the untyped pointer.
+        res.__bindgen_anon_1.value = unsafe { converted_value.__bindgen_anon_1.value };
+        res
+    }
+}
+
+impl CLIPSFrom<Vec<CLIPSValue>> for clips_sys::UDFValue {
+    fn from(value: Vec<CLIPSValue>, env: *mut clips_sys::Environment) -> clips_sys::UDFValue {
+        CLIPSInto::into(CLIPSValue::Multifield(value), env)
+    }
+}
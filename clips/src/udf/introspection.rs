@@ -0,0 +1,72 @@
+use clips_sys::CLIPSSymbol;
+
+use crate::{CLIPSValue, UDFData, UDFSignature, UDFType};
+
+pub(crate) type IntrospectionUDF = (UDFSignature, Box<dyn FnMut(UDFData) + Send + Sync>);
+
+// UDFs that help debugging from the CLIPS side, e.g. checking which Rust functions are available to the rules that are running.
+pub(crate) fn introspection_udfs() -> Vec<IntrospectionUDF> {
+    vec![
+        (
+            UDFSignature::new("rust-udfs".to_string(), 0, 0, UDFType::Multifield, vec![])
+                .with_doc("Returns the names of all UDFs registered from Rust."),
+            Box::new(rust_udfs),
+        ),
+        (
+            UDFSignature::new("rust-version".to_string(), 0, 0, UDFType::String, vec![])
+                .with_doc("Returns the version of the Rust library driving this environment."),
+            Box::new(rust_version),
+        ),
+        (
+            UDFSignature::new(
+                "rust-log".to_string(),
+                2,
+                2,
+                UDFType::Void,
+                vec![UDFType::Symbol, UDFType::String],
+            )
+            .with_doc("Logs the message from the second argument through the Rust `log` crate, with the level given by the first argument (error, warn, info, debug or trace)."),
+            Box::new(rust_log),
+        ),
+    ]
+}
+
+fn rust_udfs(mut data: UDFData) {
+    let names: Vec<CLIPSValue> = data
+        .env()
+        .list_udfs()
+        .into_iter()
+        .map(|signature| CLIPSValue::Symbol(signature.name))
+        .collect();
+
+    data.set_result(names).unwrap();
+}
+
+fn rust_version(mut data: UDFData) {
+    data.set_result(env!("CARGO_PKG_VERSION").to_string())
+        .unwrap();
+}
+
+fn rust_log(data: UDFData) {
+    let (level, message) = match (data.first_arg::<CLIPSSymbol>(), data.next_arg::<String>()) {
+        (Ok(level), Ok(message)) => (level.0, message),
+        _ => {
+            data.throw_error().unwrap();
+            return;
+        }
+    };
+
+    let level = match level.as_str() {
+        "error" => log::Level::Error,
+        "warn" => log::Level::Warn,
+        "info" => log::Level::Info,
+        "debug" => log::Level::Debug,
+        "trace" => log::Level::Trace,
+        _ => {
+            data.throw_error().unwrap();
+            return;
+        }
+    };
+
+    log::log!(target: "clips", level, "{}", message);
+}
@@ -1,7 +1,13 @@
 pub mod conversion;
-use std::{collections::HashMap, sync::OnceLock};
+mod introspection;
+pub(crate) use introspection::*;
+use std::{any::Any, collections::HashMap, ffi::CString, marker::PhantomData, sync::OnceLock};
 
-use crate::{CLIPSEnvironment, CLIPSError, CLIPSInto, CLIPSResult};
+use crate::{
+    extract_clipsvalue,
+    value::{value_extraction_policy, with_argument_policy},
+    CLIPSEnvironment, CLIPSError, CLIPSInto, CLIPSResult, CLIPSValue, STDERR,
+};
 
 bitflags::bitflags! {
     #[repr(transparent)]
@@ -62,16 +68,188 @@ impl UDFType {
         res.shrink_to_fit();
         res
     }
+
+    // Describes the type using the terms from the CLIPS manual, e.g. "INTEGER or FLOAT" for `Number`. Combinations that don't have a name of their own are better described by `describe_parts()`.
+    pub fn describe(&self) -> &'static str {
+        if self.is_empty() {
+            return "no value";
+        }
+
+        all_udf_type_descriptions()
+            .find(|(udf_type, _)| udf_type == self)
+            .map(|(_, description)| description)
+            .unwrap_or("multiple types")
+    }
+
+    pub fn describe_parts(&self) -> Vec<&'static str> {
+        if self.is_empty() {
+            return vec!["no value"];
+        }
+
+        let mut remaining = *self;
+        let mut res = Vec::new();
+
+        for (udf_type, description) in all_udf_type_descriptions() {
+            if remaining.contains(udf_type) {
+                remaining.remove(udf_type);
+                res.push(description);
+            }
+        }
+
+        res
+    }
+
+    // The Rust type this library converts values of this type from/to, if there is one.
+    pub fn rust_type(&self) -> Option<&'static str> {
+        UDF_TYPE_TABLE
+            .iter()
+            .find(|info| info.udf_type == *self)
+            .and_then(|info| info.rust_type)
+    }
+}
+
+pub struct UDFTypeInfo {
+    pub udf_type: UDFType,
+    pub clips_name: &'static str,
+    pub rust_type: Option<&'static str>,
+}
+
+// The individual UDF types, what the CLIPS manual calls them and which Rust types this library maps them to. Exposed so tools (e.g. documentation generators) don't have to keep their own copy of it.
+pub static UDF_TYPE_TABLE: &[UDFTypeInfo] = &[
+    UDFTypeInfo {
+        udf_type: UDFType::Boolean,
+        clips_name: "BOOLEAN",
+        rust_type: Some("bool"),
+    },
+    UDFTypeInfo {
+        udf_type: UDFType::Float,
+        clips_name: "FLOAT",
+        rust_type: Some("f64"),
+    },
+    UDFTypeInfo {
+        udf_type: UDFType::ExternalAddress,
+        clips_name: "EXTERNAL-ADDRESS",
+        rust_type: None,
+    },
+    UDFTypeInfo {
+        udf_type: UDFType::FactAddress,
+        clips_name: "FACT-ADDRESS",
+        rust_type: None,
+    },
+    UDFTypeInfo {
+        udf_type: UDFType::InstanceAddress,
+        clips_name: "INSTANCE-ADDRESS",
+        rust_type: None,
+    },
+    UDFTypeInfo {
+        udf_type: UDFType::Integer,
+        clips_name: "INTEGER",
+        rust_type: Some("i64"),
+    },
+    UDFTypeInfo {
+        udf_type: UDFType::Multifield,
+        clips_name: "MULTIFIELD",
+        rust_type: Some("Vec<CLIPSValue>"),
+    },
+    UDFTypeInfo {
+        udf_type: UDFType::InstanceName,
+        clips_name: "INSTANCE-NAME",
+        rust_type: Some("CLIPSInstanceName"),
+    },
+    UDFTypeInfo {
+        udf_type: UDFType::String,
+        clips_name: "STRING",
+        rust_type: Some("String"),
+    },
+    UDFTypeInfo {
+        udf_type: UDFType::Symbol,
+        clips_name: "SYMBOL",
+        rust_type: Some("CLIPSSymbol"),
+    },
+    UDFTypeInfo {
+        udf_type: UDFType::Void,
+        clips_name: "VOID",
+        rust_type: Some("()"),
+    },
+];
+
+// Ordered from the largest to the smallest combination, which `describe_parts()` relies on.
+static UDF_TYPE_COMPOSITES: &[(UDFType, &str)] = &[
+    (UDFType::Any, "any value"),
+    (UDFType::Singlefield, "any single-field value"),
+    (
+        UDFType::Address,
+        "EXTERNAL-ADDRESS, FACT-ADDRESS or INSTANCE-ADDRESS",
+    ),
+    (UDFType::Instance, "INSTANCE-ADDRESS or INSTANCE-NAME"),
+    (UDFType::Number, "INTEGER or FLOAT"),
+    (UDFType::Lexeme, "SYMBOL or STRING"),
+];
+
+fn all_udf_type_descriptions() -> impl Iterator<Item = (UDFType, &'static str)> {
+    UDF_TYPE_COMPOSITES.iter().copied().chain(
+        UDF_TYPE_TABLE
+            .iter()
+            .map(|info| (info.udf_type, info.clips_name)),
+    )
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UDFSignature {
+    pub name: String,
+    pub min_args: u16,
+    pub max_args: u16,
+    pub return_types: UDFType,
+    pub arg_types: Vec<UDFType>,
+    pub doc: Option<String>,
 }
 
-pub struct UDFData {
+impl UDFSignature {
+    pub fn new(
+        name: String,
+        min_args: u16,
+        max_args: u16,
+        return_types: UDFType,
+        arg_types: Vec<UDFType>,
+    ) -> Self {
+        Self {
+            name,
+            min_args,
+            max_args,
+            return_types,
+            arg_types,
+            doc: None,
+        }
+    }
+
+    pub fn with_doc<S: Into<String>>(mut self, doc: S) -> Self {
+        self.doc = Some(doc.into());
+        self
+    }
+
+    pub fn arg_descriptions(&self) -> Vec<String> {
+        self.arg_types
+            .iter()
+            .map(|arg_type| arg_type.describe_parts().join(" or "))
+            .collect()
+    }
+
+    pub fn return_description(&self) -> String {
+        self.return_types.describe_parts().join(" or ")
+    }
+}
+
+// The pointers are only valid until the UDF returns. UDFs get a `UDFData` with a lifetime that ends with the call, so it can't be stored anywhere that outlives it, e.g. a `thread_local!`. The raw pointers already keep it from being `Send` or `Sync`.
+pub struct UDFData<'call> {
     env: *mut clips_sys::Environment,
     context: *mut clips_sys::UDFContext,
     result: *mut clips_sys::UDFValue,
+    call: PhantomData<&'call mut clips_sys::UDFContext>,
 }
 
-impl UDFData {
-    pub fn new(
+impl UDFData<'_> {
+    // Only the UDF trampoline makes these, since nothing else can promise the pointers belong to a call that's running.
+    pub(crate) fn new(
         env: *mut clips_sys::Environment,
         context: *mut clips_sys::UDFContext,
         result: *mut clips_sys::UDFValue,
@@ -80,23 +258,46 @@ impl UDFData {
             env,
             context,
             result,
+            call: PhantomData,
         }
     }
 
-    pub fn env(&self) -> CLIPSEnvironment {
+    // Takes `&mut self` so nothing borrowed from `user_data()` can still be around while the environment is used, since that can replace the data (`set_user_data()`) or run another UDF that borrows it mutably.
+    pub fn env(&mut self) -> CLIPSEnvironment {
         CLIPSEnvironment::from_raw(self.env)
     }
 
+    // The data set with `Environment::set_user_data()`. `None` if none was set or if it isn't a `T`.
+    pub fn user_data<T: Any>(&self) -> Option<&T> {
+        // The data can only be replaced by a command to the environment thread, which is busy running this UDF for as long as `self` lives, or through `env()`, which can't be called while the borrow lasts.
+        let user_data = unsafe { &*CLIPSEnvironment::from_raw(self.env).user_data_ptr() };
+        user_data
+            .as_deref()
+            .and_then(|data| data.downcast_ref::<T>())
+    }
+
+    pub fn user_data_mut<T: Any>(&mut self) -> Option<&mut T> {
+        let user_data = unsafe { &mut *CLIPSEnvironment::from_raw(self.env).user_data_ptr() };
+        user_data
+            .as_deref_mut()
+            .and_then(|data| data.downcast_mut::<T>())
+    }
+
     pub fn num_args(&self) -> usize {
         let res = unsafe { clips_sys::UDFArgumentCount(self.context) } as usize;
         res
     }
 
+    // The argument getters fail with `ArgumentMissing` when there's no argument in that position, `ArgumentNotRetrieved` when CLIPS couldn't get the argument's value, and a conversion error when the value doesn't fit `T`.
     pub fn first_arg<T>(&self) -> CLIPSResult<T>
     where
         T: std::convert::TryFrom<clips_sys::UDFValue>,
         CLIPSError: From<<T as TryFrom<clips_sys::UDFValue>>::Error>,
     {
+        if self.num_args() == 0 {
+            return Err(CLIPSError::ArgumentMissing);
+        }
+
         let mut arg = clips_sys::UDFValue::default();
 
         let res =
@@ -105,7 +306,7 @@ impl UDFData {
         if !res {
             Err(CLIPSError::ArgumentNotRetrieved)
         } else {
-            Ok(arg.try_into()?)
+            self.convert_arg(arg)
         }
     }
 
@@ -114,6 +315,10 @@ impl UDFData {
         T: std::convert::TryFrom<clips_sys::UDFValue>,
         CLIPSError: From<<T as TryFrom<clips_sys::UDFValue>>::Error>,
     {
+        if !self.has_next_arg() {
+            return Err(CLIPSError::ArgumentMissing);
+        }
+
         let mut arg = clips_sys::UDFValue::default();
 
         let res =
@@ -122,15 +327,38 @@ impl UDFData {
         if !res {
             Err(CLIPSError::ArgumentNotRetrieved)
         } else {
-            Ok(arg.try_into()?)
+            self.convert_arg(arg)
         }
     }
 
+    // For optional trailing arguments: `Ok(None)` if the UDF wasn't called with any more arguments.
+    pub fn opt_next_arg<T>(&self) -> CLIPSResult<Option<T>>
+    where
+        T: std::convert::TryFrom<clips_sys::UDFValue>,
+        CLIPSError: From<<T as TryFrom<clips_sys::UDFValue>>::Error>,
+    {
+        if !self.has_next_arg() {
+            return Ok(None);
+        }
+
+        self.next_arg().map(Some)
+    }
+
+    // What CLIPS' `UDFHasNextArgument` macro checks. Before any argument is retrieved, the next one is the first.
+    pub fn has_next_arg(&self) -> bool {
+        unsafe { !(*self.context).lastArg.is_null() }
+    }
+
+    // `n` starts at 1, like in CLIPS.
     pub fn nth_arg<T>(&self, n: u32) -> CLIPSResult<T>
     where
         T: std::convert::TryFrom<clips_sys::UDFValue>,
         CLIPSError: From<<T as TryFrom<clips_sys::UDFValue>>::Error>,
     {
+        if n == 0 || n as usize > self.num_args() {
+            return Err(CLIPSError::ArgumentMissing);
+        }
+
         let mut arg = clips_sys::UDFValue::default();
 
         let res =
@@ -139,10 +367,22 @@ impl UDFData {
         if !res {
             Err(CLIPSError::ArgumentNotRetrieved)
         } else {
-            Ok(arg.try_into()?)
+            self.convert_arg(arg)
         }
     }
 
+    // `TryFrom` can't be given the environment, so its `ValueExtractionPolicy` is passed along for the one conversion.
+    fn convert_arg<T>(&self, arg: clips_sys::UDFValue) -> CLIPSResult<T>
+    where
+        T: std::convert::TryFrom<clips_sys::UDFValue>,
+        CLIPSError: From<<T as TryFrom<clips_sys::UDFValue>>::Error>,
+    {
+        Ok(with_argument_policy(
+            value_extraction_policy(self.env),
+            || arg.try_into(),
+        )?)
+    }
+
     pub fn set_result<T>(&mut self, res: T) -> CLIPSResult<()>
     where
         T: CLIPSInto<clips_sys::UDFValue>,
@@ -156,6 +396,70 @@ impl UDFData {
         Ok(())
     }
 
+    // For predicates, e.g. ones called from a `test` CE. CLIPS treats every value other than the `FALSE` symbol as true, so a predicate must give back one of the boolean symbols rather than something like 0 or an empty string.
+    pub fn set_boolean_result(&mut self, res: bool) {
+        unsafe {
+            (*self.result).__bindgen_anon_1.lexemeValue = clips_sys::CreateBoolean(self.env, res);
+        }
+    }
+
+    // For UDFs declared with `UDFType::Void` return type, so CLIPS sees no value at all. Without this or `set_result`, the result is whatever CLIPS initialized it with.
+    pub fn set_void(&mut self) {
+        unsafe {
+            (*self.result).__bindgen_anon_1.voidValue = (*self.env).VoidConstant;
+        }
+    }
+
+    // The name can be module-qualified, otherwise the defglobal is looked up in the current module. These work on the environment the UDF is already running in, since going through an `Environment` handle from inside a UDF fails with `ReentrantCall`.
+    pub fn get_global(&self, name: &str) -> CLIPSResult<CLIPSValue> {
+        let defglobal = self.find_defglobal(name)?;
+
+        let mut value = clips_sys::CLIPSValue::default();
+        unsafe { clips_sys::DefglobalGetValue(defglobal, &mut value) };
+
+        extract_clipsvalue(self.env, value)
+    }
+
+    // Like `bind` from CLIPS code, the new value is seen right away by whatever runs next, including the rest of the rule that called the UDF, but it doesn't activate or reactivate any rules.
+    pub fn set_global<T: Into<CLIPSValue>>(&mut self, name: &str, value: T) -> CLIPSResult<()> {
+        let defglobal = self.find_defglobal(name)?;
+
+        let mut raw_value: clips_sys::CLIPSValue = CLIPSInto::into(value.into(), self.env);
+        unsafe { clips_sys::DefglobalSetValue(defglobal, &mut raw_value) };
+
+        Ok(())
+    }
+
+    fn find_defglobal(&self, name: &str) -> CLIPSResult<*mut clips_sys::Defglobal> {
+        let name = CString::new(name).map_err(|_| CLIPSError::DefglobalNotFound)?;
+        let defglobal = unsafe { clips_sys::FindDefglobal(self.env, name.as_ptr()) };
+
+        if defglobal.is_null() {
+            Err(CLIPSError::DefglobalNotFound)
+        } else {
+            Ok(defglobal)
+        }
+    }
+
+    // On `Err`, writes the error to stderr and throws a CLIPS error, so the UDF can just return when it gets `None`.
+    pub fn try_or_throw<T, E: std::fmt::Display>(&mut self, result: Result<T, E>) -> Option<T> {
+        match result {
+            Ok(value) => Some(value),
+            Err(err) => {
+                // Interior null bytes would end the message early, so we drop them instead of losing the whole message.
+                let message = CString::new(format!("{}\n", err).replace('\0', "")).unwrap();
+                let logical_name = CString::new(STDERR).unwrap();
+
+                unsafe {
+                    clips_sys::WriteString(self.env, logical_name.as_ptr(), message.as_ptr());
+                    clips_sys::UDFThrowError(self.context);
+                }
+
+                None
+            }
+        }
+    }
+
     pub fn throw_error(&self) -> CLIPSResult<()> {
         unsafe {
             clips_sys::UDFThrowError(self.context);
@@ -1,11 +1,14 @@
 use clips_sys::{CLIPSInstanceName, CLIPSSymbol};
 use serde::{de::Visitor, Deserialize, Serialize};
 use std::{
+    borrow::Cow,
+    cell::Cell,
     ffi::{CStr, CString},
     fmt::Display,
+    sync::atomic::{AtomicU8, Ordering},
 };
 
-use crate::{CLIPSFrom, CLIPSInto};
+use crate::{CLIPSError, CLIPSFrom, CLIPSInto, CLIPSResult, VALUE_POLICY_ENVIRONMENT_DATA_INDEX};
 
 impl CLIPSFrom<usize> for clips_sys::CLIPSValue {
     fn from(value: usize, env: *mut clips_sys::Environment) -> clips_sys::CLIPSValue {
@@ -87,6 +90,7 @@ impl CLIPSFrom<Vec<CLIPSValue>> for clips_sys::CLIPSValue {
         }
 
         res.__bindgen_anon_1.multifieldValue = unsafe { clips_sys::MBCreate(builder) };
+        unsafe { clips_sys::MBDispose(builder) };
         res
     }
 }
@@ -100,10 +104,18 @@ impl CLIPSFrom<CLIPSValue> for clips_sys::CLIPSValue {
             CLIPSValue::Float(v) => CLIPSInto::into(v, env),
             CLIPSValue::Bool(v) => CLIPSInto::into(v, env),
             CLIPSValue::Multifield(v) => CLIPSInto::into(v, env),
+            CLIPSValue::Lexeme(v) => lexeme_value(&v, env),
         }
     }
 }
 
+// Only `CLIPSValue::Lexeme` goes into CLIPS as a C string, so this is a helper rather than a public `CLIPSFrom<CString>` impl.
+fn lexeme_value(value: &CStr, env: *mut clips_sys::Environment) -> clips_sys::CLIPSValue {
+    let mut res = clips_sys::CLIPSValue::default();
+    res.__bindgen_anon_1.lexemeValue = unsafe { clips_sys::CreateString(env, value.as_ptr()) };
+    res
+}
+
 // The Serialize impl is derived because we only ever want to serialise `CLIPSValue`s to JSON. To convert a CLIPSValue to CLIPS, we use the `CLIPSFrom` trait.
 #[derive(Clone, Debug, Serialize, PartialEq)]
 pub enum CLIPSValue {
@@ -113,20 +125,32 @@ pub enum CLIPSValue {
     Float(f64),
     Bool(bool),
     Multifield(Vec<CLIPSValue>),
+    // A symbol or string that isn't valid UTF-8, only read with `StringConversion::Bytes`. It goes back into CLIPS as a string. It's a `CString` because CLIPS lexemes can't hold a NUL, so one with a NUL in it fails to be made instead of failing to go into CLIPS.
+    Lexeme(CString),
 }
 
+// Prints single-field values the way CLIPS reads them back. Multifields are printed the way CLIPS prints them, in parentheses.
 impl Display for CLIPSValue {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Self::Symbol(val) => f.write_str(&val),
+            Self::Symbol(val) => f.write_str(val),
             Self::Int(val) => f.write_str(&val.to_string()),
-            Self::String(val) => write!(f, "\"{}\"", val),
-            Self::Float(val) => f.write_str(&val.to_string()),
-            Self::Bool(val) => f.write_str(&val.to_string()),
+            Self::String(val) => {
+                write!(f, "\"{}\"", val.replace('\\', "\\\\").replace('"', "\\\""))
+            }
+            // `Debug` keeps the decimal point in e.g. `1.0`, which CLIPS would otherwise read back as an integer.
+            Self::Float(val) => write!(f, "{:?}", val),
+            Self::Bool(true) => f.write_str("TRUE"),
+            Self::Bool(false) => f.write_str("FALSE"),
+            Self::Lexeme(val) => write!(f, "{}", Self::String(val.to_string_lossy().into_owned())),
             Self::Multifield(vals) => {
                 f.write_str("(")?;
 
-                for val in vals.iter() {
+                for (i, val) in vals.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(" ")?;
+                    }
+
                     write!(f, "{}", val)?;
                 }
 
@@ -136,6 +160,161 @@ impl Display for CLIPSValue {
     }
 }
 
+// The same as `CLIPSValue`, but with symbols and strings borrowed from CLIPS. Only values that had to be converted lossily are copied.
+#[derive(Clone, Debug, PartialEq)]
+pub enum CLIPSValueRef<'a> {
+    Symbol(Cow<'a, str>),
+    Int(i64),
+    String(Cow<'a, str>),
+    Float(f64),
+    Bool(bool),
+    Multifield(Vec<CLIPSValueRef<'a>>),
+    Lexeme(&'a CStr),
+}
+
+impl CLIPSValueRef<'_> {
+    pub fn into_owned(self) -> CLIPSValue {
+        match self {
+            Self::Symbol(val) => CLIPSValue::Symbol(val.into_owned()),
+            Self::Int(val) => CLIPSValue::Int(val),
+            Self::String(val) => CLIPSValue::String(val.into_owned()),
+            Self::Float(val) => CLIPSValue::Float(val),
+            Self::Bool(val) => CLIPSValue::Bool(val),
+            Self::Multifield(vals) => {
+                CLIPSValue::Multifield(vals.into_iter().map(Self::into_owned).collect())
+            }
+            Self::Lexeme(val) => CLIPSValue::Lexeme(val.to_owned()),
+        }
+    }
+
+    // The text of a symbol or a string.
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::Symbol(val) | Self::String(val) => Some(val),
+            _ => None,
+        }
+    }
+}
+
+impl From<CLIPSValueRef<'_>> for CLIPSValue {
+    fn from(value: CLIPSValueRef<'_>) -> Self {
+        value.into_owned()
+    }
+}
+
+// Where `Environment::reinsert_value` puts a value. Names can be module-qualified (e.g. `MAIN::counter`), otherwise they're looked up from the current module.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ValueTarget {
+    // The name without the `?*` and `*` around it.
+    Global { name: String },
+    InstanceSlot { instance: String, slot: String },
+}
+
+impl CLIPSValue {
+    // A `CLIPSValue` never points into the environment it came from: retrieving a value copies everything out of CLIPS, and every environment creates its own copy when it's given a value. So any clone can be given to a different environment, or kept after the original environment is gone. This only exists to make that intent explicit at the call site.
+    pub fn clone_detached(&self) -> Self {
+        self.clone()
+    }
+
+    // CLIPS integers are 64-bit, so this fails instead of truncating values that don't fit in an i64. There's on purpose no `From<i128>`.
+    pub fn try_from_i128(value: i128) -> CLIPSResult<Self> {
+        i64::try_from(value)
+            .map(CLIPSValue::Int)
+            .map_err(|_| CLIPSError::IntOutOfRange(value))
+    }
+}
+
+impl TryFrom<i128> for CLIPSValue {
+    type Error = CLIPSError;
+
+    fn try_from(value: i128) -> Result<Self, Self::Error> {
+        CLIPSValue::try_from_i128(value)
+    }
+}
+
+// Lets UDFs take arguments of any type with `UDFData::next_arg::<CLIPSValue>()` and friends. A multifield argument can be a slice of a bigger multifield, so only the fields in its range are taken.
+impl TryFrom<clips_sys::UDFValue> for CLIPSValue {
+    type Error = CLIPSError;
+
+    fn try_from(value: clips_sys::UDFValue) -> Result<Self, Self::Error> {
+        let policy = argument_policy();
+        let value_type = unsafe { (*value.__bindgen_anon_1.header).type_ } as u32;
+
+        if value_type == clips_sys::MULTIFIELD_TYPE {
+            let contents = unsafe { (*value.__bindgen_anon_1.multifieldValue).contents.as_ptr() };
+
+            return (value.begin..value.begin + value.range)
+                .map(|i| extract_clipsvalue_with_policy(&policy, unsafe { *contents.add(i) }))
+                .collect::<CLIPSResult<_>>()
+                .map(CLIPSValue::Multifield);
+        }
+
+        let mut clips_value = clips_sys::CLIPSValue::default();
+        clips_value.__bindgen_anon_1.value = unsafe { value.__bindgen_anon_1.value };
+
+        extract_clipsvalue_with_policy(&policy, clips_value)
+    }
+}
+
+impl From<i64> for CLIPSValue {
+    fn from(value: i64) -> Self {
+        CLIPSValue::Int(value)
+    }
+}
+
+impl From<i32> for CLIPSValue {
+    fn from(value: i32) -> Self {
+        CLIPSValue::Int(value as i64)
+    }
+}
+
+impl From<f64> for CLIPSValue {
+    fn from(value: f64) -> Self {
+        CLIPSValue::Float(value)
+    }
+}
+
+impl From<bool> for CLIPSValue {
+    fn from(value: bool) -> Self {
+        CLIPSValue::Bool(value)
+    }
+}
+
+impl From<String> for CLIPSValue {
+    fn from(value: String) -> Self {
+        CLIPSValue::String(value)
+    }
+}
+
+impl From<&str> for CLIPSValue {
+    fn from(value: &str) -> Self {
+        CLIPSValue::String(value.to_string())
+    }
+}
+
+impl From<CLIPSSymbol> for CLIPSValue {
+    fn from(value: CLIPSSymbol) -> Self {
+        CLIPSValue::Symbol(value.0)
+    }
+}
+
+impl<T: Into<CLIPSValue>> From<Vec<T>> for CLIPSValue {
+    fn from(value: Vec<T>) -> Self {
+        CLIPSValue::Multifield(value.into_iter().map(Into::into).collect())
+    }
+}
+
+// Strings convert into CLIPS strings by default, so this is the short way to say a string should become a symbol instead, e.g. `"Bob".symbol()`.
+pub trait ToCLIPSSymbol {
+    fn symbol(&self) -> CLIPSValue;
+}
+
+impl<T: AsRef<str> + ?Sized> ToCLIPSSymbol for T {
+    fn symbol(&self) -> CLIPSValue {
+        CLIPSValue::Symbol(self.as_ref().to_string())
+    }
+}
+
 struct CLIPSValueVisitor {
     is_symbol: bool,
 }
@@ -155,6 +334,25 @@ impl<'de> Deserialize<'de> for CLIPSValue {
     }
 }
 
+// Gives a single value to `visitor` the way `CLIPSValueVisitor` reads it back. Symbols are given as plain strings, since the type being deserialized into decides what to do with them. `CLIPSValue::deserialize_into()` reads every value through this.
+pub(crate) fn visit_clipsvalue<'de, V: Visitor<'de>>(
+    value: CLIPSValue,
+    visitor: V,
+) -> CLIPSResult<V::Value> {
+    match value {
+        CLIPSValue::Symbol(v) => visitor.visit_string(v),
+        CLIPSValue::Int(v) => visitor.visit_i64(v),
+        CLIPSValue::String(v) => visitor.visit_string(v),
+        CLIPSValue::Float(v) => visitor.visit_f64(v),
+        CLIPSValue::Bool(v) => visitor.visit_bool(v),
+        CLIPSValue::Lexeme(v) => visitor.visit_byte_buf(v.into_bytes()),
+        // Multifields can't be nested, so there's no single value to give.
+        CLIPSValue::Multifield(_) => Err(CLIPSError::ValueMapping(
+            "a multifield can't be read as a single value".to_string(),
+        )),
+    }
+}
+
 // Beware: this impl is written to work with both deserialisation from JSON and from CLIPS. Read it carefully to understand the entry points of each.
 impl<'de> Visitor<'de> for CLIPSValueVisitor {
     type Value = CLIPSValue;
@@ -304,6 +502,9 @@ impl<'de> Visitor<'de> for CLIPSValueVisitor {
                 "Multifield" => {
                     res = Some(CLIPSValue::Multifield(map.next_value()?));
                 }
+                "Lexeme" => {
+                    res = Some(CLIPSValue::Lexeme(map.next_value::<CString>()?));
+                }
                 v => {
                     return Err(serde::de::Error::unknown_variant(
                         v,
@@ -315,6 +516,7 @@ impl<'de> Visitor<'de> for CLIPSValueVisitor {
                             "Float",
                             "Bool",
                             "Multifield",
+                            "Lexeme",
                         ],
                     ));
                 }
@@ -325,10 +527,172 @@ impl<'de> Visitor<'de> for CLIPSValueVisitor {
     }
 }
 
-pub(crate) fn extract_clipsvalue(val: clips_sys::CLIPSValue) -> CLIPSValue {
+// CLIPS strings and symbols are just bytes, so they aren't guaranteed to be valid UTF-8.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StringConversion {
+    // Fail with `CLIPSError::InvalidUtf8`.
+    Strict,
+    // Replace invalid sequences with U+FFFD.
+    Lossy,
+    // Read symbols and strings that aren't valid UTF-8 as `CLIPSValue::Lexeme`, with their bytes. Other text, like names, has nowhere to keep the bytes, so it's converted like with `Lossy`.
+    Bytes,
+}
+
+static STRING_CONVERSION: AtomicU8 = AtomicU8::new(StringConversion::Strict as u8);
+
+// The default for every environment, which `ValueExtractionPolicy::string_conversion` can override. It starts as `StringConversion::Strict`.
+pub fn set_string_conversion(conversion: StringConversion) {
+    STRING_CONVERSION.store(conversion as u8, Ordering::Release);
+}
+
+pub fn string_conversion() -> StringConversion {
+    match STRING_CONVERSION.load(Ordering::Acquire) {
+        0 => StringConversion::Strict,
+        1 => StringConversion::Lossy,
+        _ => StringConversion::Bytes,
+    }
+}
+
+// How values read from CLIPS are turned into `CLIPSValue`s. This applies to everything read from an environment: facts, instances, globals, `eval` results and UDF arguments taken as `CLIPSValue`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ValueExtractionPolicy {
+    // CLIPS has no boolean type, `TRUE` and `FALSE` are ordinary symbols. By default they're read as `CLIPSValue::Bool`, and with this set they stay `CLIPSValue::Symbol`.
+    pub booleans_as_symbols: bool,
+    // What to do with text that isn't valid UTF-8. Without one, the environment uses what `set_string_conversion()` set.
+    pub string_conversion: Option<StringConversion>,
+}
+
+// The policy is kept in the environment data, so every handle to an environment (including the ones UDFs get) reads values the same way.
+pub(crate) fn set_value_extraction_policy(
+    env: *mut clips_sys::Environment,
+    policy: ValueExtractionPolicy,
+) {
+    unsafe {
+        *(clips_sys::GetEnvironmentData(env, VALUE_POLICY_ENVIRONMENT_DATA_INDEX)
+            as *mut ValueExtractionPolicy) = policy;
+    }
+}
+
+pub(crate) fn value_extraction_policy(env: *mut clips_sys::Environment) -> ValueExtractionPolicy {
+    unsafe {
+        *(clips_sys::GetEnvironmentData(env, VALUE_POLICY_ENVIRONMENT_DATA_INDEX)
+            as *const ValueExtractionPolicy)
+    }
+}
+
+impl ValueExtractionPolicy {
+    fn treat_boolean_symbols_as_bool(&self) -> bool {
+        !self.booleans_as_symbols
+    }
+
+    fn string_conversion(&self) -> StringConversion {
+        self.string_conversion.unwrap_or_else(string_conversion)
+    }
+}
+
+thread_local! {
+    // `TryFrom<clips_sys::UDFValue>` can't take the environment the value came from, so `UDFData`'s argument getters put its policy here for as long as they convert one argument.
+    static ARGUMENT_POLICY: Cell<Option<ValueExtractionPolicy>> = const { Cell::new(None) };
+}
+
+struct ArgumentPolicyGuard(Option<ValueExtractionPolicy>);
+
+impl Drop for ArgumentPolicyGuard {
+    fn drop(&mut self) {
+        ARGUMENT_POLICY.with(|current| current.set(self.0));
+    }
+}
+
+pub(crate) fn with_argument_policy<R>(policy: ValueExtractionPolicy, f: impl FnOnce() -> R) -> R {
+    let _guard = ArgumentPolicyGuard(ARGUMENT_POLICY.with(|current| current.replace(Some(policy))));
+    // Arguments taken as `String`, `CLIPSSymbol` or `CLIPSInstanceName` are converted by clips-sys, which only knows whether to be lossy. They have nowhere to keep bytes, so `StringConversion::Bytes` is lossy there too.
+    clips_sys::with_lossy_text(policy.string_conversion() != StringConversion::Strict, f)
+}
+
+// Outside of a UDF there's no environment to take the policy from, so the defaults are used.
+fn argument_policy() -> ValueExtractionPolicy {
+    ARGUMENT_POLICY.with(Cell::get).unwrap_or_default()
+}
+
+// `context` says what the text is in the error, e.g. "a template name".
+pub(crate) fn clips_cstr_to_string(
+    env: *mut clips_sys::Environment,
+    cstr: &CStr,
+    context: &'static str,
+) -> CLIPSResult<String> {
+    clips_cstr_to_str(env, cstr, context).map(Cow::into_owned)
+}
+
+// Only allocates when lossy conversion has to replace something.
+pub(crate) fn clips_cstr_to_str<'a>(
+    env: *mut clips_sys::Environment,
+    cstr: &'a CStr,
+    context: &'static str,
+) -> CLIPSResult<Cow<'a, str>> {
+    cstr_to_str(&value_extraction_policy(env), cstr, context)
+}
+
+fn cstr_to_str<'a>(
+    policy: &ValueExtractionPolicy,
+    cstr: &'a CStr,
+    context: &'static str,
+) -> CLIPSResult<Cow<'a, str>> {
+    match policy.string_conversion() {
+        StringConversion::Strict => cstr
+            .to_str()
+            .map(Cow::Borrowed)
+            .map_err(|_| CLIPSError::InvalidUtf8 { context }),
+        StringConversion::Lossy | StringConversion::Bytes => Ok(cstr.to_string_lossy()),
+    }
+}
+
+// The text of a symbol or string, or its bytes if it isn't valid UTF-8 and the environment keeps those.
+enum Lexeme<'a> {
+    Text(Cow<'a, str>),
+    Bytes(&'a CStr),
+}
+
+fn cstr_to_lexeme<'a>(
+    policy: &ValueExtractionPolicy,
+    cstr: &'a CStr,
+    context: &'static str,
+) -> CLIPSResult<Lexeme<'a>> {
+    if policy.string_conversion() == StringConversion::Bytes {
+        return Ok(match cstr.to_str() {
+            Ok(text) => Lexeme::Text(Cow::Borrowed(text)),
+            Err(_) => Lexeme::Bytes(cstr),
+        });
+    }
+
+    cstr_to_str(policy, cstr, context).map(Lexeme::Text)
+}
+
+// What the CLIPS manual calls the types `extract_clipsvalue()` can't turn into a `CLIPSValue`.
+fn unsupported_value_type(value_type: u32) -> CLIPSError {
+    CLIPSError::UnsupportedValueType(match value_type {
+        clips_sys::FACT_ADDRESS_TYPE => "FACT-ADDRESS",
+        clips_sys::INSTANCE_ADDRESS_TYPE => "INSTANCE-ADDRESS",
+        clips_sys::INSTANCE_NAME_TYPE => "INSTANCE-NAME",
+        clips_sys::EXTERNAL_ADDRESS_TYPE => "EXTERNAL-ADDRESS",
+        clips_sys::VOID_TYPE => "VOID",
+        _ => "unknown",
+    })
+}
+
+pub(crate) fn extract_clipsvalue(
+    env: *mut clips_sys::Environment,
+    val: clips_sys::CLIPSValue,
+) -> CLIPSResult<CLIPSValue> {
+    extract_clipsvalue_with_policy(&value_extraction_policy(env), val)
+}
+
+fn extract_clipsvalue_with_policy(
+    policy: &ValueExtractionPolicy,
+    val: clips_sys::CLIPSValue,
+) -> CLIPSResult<CLIPSValue> {
     let value_type = unsafe { (*val.__bindgen_anon_1.header).type_ } as u32;
 
-    match value_type {
+    let value = match value_type {
         clips_sys::FLOAT_TYPE => {
             CLIPSValue::Float(unsafe { (*val.__bindgen_anon_1.floatValue).contents })
         }
@@ -338,33 +702,102 @@ pub(crate) fn extract_clipsvalue(val: clips_sys::CLIPSValue) -> CLIPSValue {
         clips_sys::SYMBOL_TYPE => {
             let symbol_val =
                 unsafe { CStr::from_ptr((*val.__bindgen_anon_1.lexemeValue).contents) };
-            let symbol_val = symbol_val.to_str().unwrap();
 
-            match symbol_val {
-                "TRUE" => CLIPSValue::Bool(true),
-                "FALSE" => CLIPSValue::Bool(true),
-                v => CLIPSValue::Symbol(v.to_string()),
+            match cstr_to_lexeme(policy, symbol_val, "a symbol")? {
+                Lexeme::Bytes(bytes) => CLIPSValue::Lexeme(bytes.to_owned()),
+                Lexeme::Text(text) => match text.as_ref() {
+                    "TRUE" if policy.treat_boolean_symbols_as_bool() => CLIPSValue::Bool(true),
+                    "FALSE" if policy.treat_boolean_symbols_as_bool() => CLIPSValue::Bool(false),
+                    _ => CLIPSValue::Symbol(text.into_owned()),
+                },
+            }
+        }
+        clips_sys::STRING_TYPE => {
+            let string_val =
+                unsafe { CStr::from_ptr((*val.__bindgen_anon_1.lexemeValue).contents) };
+
+            match cstr_to_lexeme(policy, string_val, "a string")? {
+                Lexeme::Bytes(bytes) => CLIPSValue::Lexeme(bytes.to_owned()),
+                Lexeme::Text(text) => CLIPSValue::String(text.into_owned()),
             }
         }
-        clips_sys::STRING_TYPE => CLIPSValue::String(unsafe {
-            let cstr = CStr::from_ptr((*val.__bindgen_anon_1.lexemeValue).contents);
-            cstr.to_str().unwrap().to_string()
-        }),
         clips_sys::MULTIFIELD_TYPE => {
             let vals_len = unsafe { (*val.__bindgen_anon_1.multifieldValue).length };
             let mut vals = Vec::with_capacity(vals_len);
 
+            // `contents` is declared as a single-element array in C, but it actually holds `length` values, so we can't index it directly in Rust.
+            let contents = unsafe { (*val.__bindgen_anon_1.multifieldValue).contents.as_ptr() };
+
             for i in 0..vals_len {
-                let curr_clipsvalue =
-                    unsafe { (*val.__bindgen_anon_1.multifieldValue).contents[i] };
-                vals.push(extract_clipsvalue(curr_clipsvalue));
+                let curr_clipsvalue = unsafe { *contents.add(i) };
+                vals.push(extract_clipsvalue_with_policy(policy, curr_clipsvalue)?);
             }
 
             CLIPSValue::Multifield(vals)
         }
-        _ => unimplemented!(
-            "Can't extract the value of a CLIPS value with type id '{}'.",
-            value_type
-        ),
-    }
+        _ => return Err(unsupported_value_type(value_type)),
+    };
+
+    Ok(value)
+}
+
+// Borrows the value's lexemes from CLIPS instead of copying them. The caller picks `'a` and has to make sure the value outlives it, which is why this is only used while the fact it came from can't go away.
+pub(crate) fn extract_clipsvalue_ref<'a>(
+    env: *mut clips_sys::Environment,
+    val: clips_sys::CLIPSValue,
+) -> CLIPSResult<CLIPSValueRef<'a>> {
+    extract_clipsvalue_ref_with_policy(&value_extraction_policy(env), val)
+}
+
+fn extract_clipsvalue_ref_with_policy<'a>(
+    policy: &ValueExtractionPolicy,
+    val: clips_sys::CLIPSValue,
+) -> CLIPSResult<CLIPSValueRef<'a>> {
+    let value_type = unsafe { (*val.__bindgen_anon_1.header).type_ } as u32;
+
+    let value = match value_type {
+        clips_sys::FLOAT_TYPE => {
+            CLIPSValueRef::Float(unsafe { (*val.__bindgen_anon_1.floatValue).contents })
+        }
+        clips_sys::INTEGER_TYPE => {
+            CLIPSValueRef::Int(unsafe { (*val.__bindgen_anon_1.integerValue).contents })
+        }
+        clips_sys::SYMBOL_TYPE => {
+            let symbol_val =
+                unsafe { CStr::from_ptr::<'a>((*val.__bindgen_anon_1.lexemeValue).contents) };
+
+            match cstr_to_lexeme(policy, symbol_val, "a symbol")? {
+                Lexeme::Bytes(bytes) => CLIPSValueRef::Lexeme(bytes),
+                Lexeme::Text(text) => match text.as_ref() {
+                    "TRUE" if policy.treat_boolean_symbols_as_bool() => CLIPSValueRef::Bool(true),
+                    "FALSE" if policy.treat_boolean_symbols_as_bool() => CLIPSValueRef::Bool(false),
+                    _ => CLIPSValueRef::Symbol(text),
+                },
+            }
+        }
+        clips_sys::STRING_TYPE => {
+            let string_val =
+                unsafe { CStr::from_ptr::<'a>((*val.__bindgen_anon_1.lexemeValue).contents) };
+
+            match cstr_to_lexeme(policy, string_val, "a string")? {
+                Lexeme::Bytes(bytes) => CLIPSValueRef::Lexeme(bytes),
+                Lexeme::Text(text) => CLIPSValueRef::String(text),
+            }
+        }
+        clips_sys::MULTIFIELD_TYPE => {
+            let vals_len = unsafe { (*val.__bindgen_anon_1.multifieldValue).length };
+            let mut vals = Vec::with_capacity(vals_len);
+            let contents = unsafe { (*val.__bindgen_anon_1.multifieldValue).contents.as_ptr() };
+
+            for i in 0..vals_len {
+                let curr_clipsvalue = unsafe { *contents.add(i) };
+                vals.push(extract_clipsvalue_ref_with_policy(policy, curr_clipsvalue)?);
+            }
+
+            CLIPSValueRef::Multifield(vals)
+        }
+        _ => return Err(unsupported_value_type(value_type)),
+    };
+
+    Ok(value)
 }
@@ -0,0 +1,190 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    ffi::{c_void, CStr, CString},
+    ptr,
+    time::{Duration, Instant},
+};
+
+use crate::{ActivationInfo, CLIPSEnvironment};
+
+pub enum WatchdogAction {
+    Halt,
+    // Halts the run and keeps a `WatchdogReport` of what it was doing.
+    DumpAndHalt,
+    // Called with a report of what the run was doing, and halts it if it returns `true`. The report is kept either way.
+    Callback(Box<dyn FnMut(&WatchdogReport) -> bool + Send>),
+}
+
+pub struct WatchdogConfig {
+    pub max_run_duration: Duration,
+    pub action: WatchdogAction,
+    // How many of the most recently fired rules go in the report.
+    pub recent_rules: usize,
+}
+
+impl WatchdogConfig {
+    pub fn new(max_run_duration: Duration, action: WatchdogAction) -> Self {
+        Self {
+            max_run_duration,
+            action,
+            recent_rules: 10,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct WatchdogReport {
+    // How long the run had been going when the watchdog went off.
+    pub elapsed: Duration,
+    // The current module's agenda, in the order the activations would fire.
+    pub agenda: Vec<ActivationInfo>,
+    // Oldest first.
+    pub recent_rules: Vec<String>,
+    pub facts: usize,
+    pub facts_by_template: HashMap<String, usize>,
+}
+
+// The state of a single watched run. CLIPS gets a pointer to it as the callbacks' context, so it can't move while they're registered.
+pub(crate) struct WatchdogRun {
+    config: WatchdogConfig,
+    started: Instant,
+    recent_rules: VecDeque<String>,
+    tripped: bool,
+    report: Option<WatchdogReport>,
+}
+
+const WATCHDOG_CALLBACK_NAME: &str = "rust-watchdog";
+
+impl WatchdogRun {
+    pub(crate) fn new(config: WatchdogConfig) -> Self {
+        Self {
+            config,
+            started: Instant::now(),
+            recent_rules: VecDeque::new(),
+            tripped: false,
+            report: None,
+        }
+    }
+
+    // The periodic function is what notices the run is stuck, since CLIPS also calls it from inside loops on the right-hand side of a rule, and not only between firings.
+    pub(crate) fn install(&mut self, env: *mut clips_sys::Environment) {
+        let callback_name = CString::new(WATCHDOG_CALLBACK_NAME).unwrap();
+        let context = self as *mut WatchdogRun as *mut c_void;
+
+        unsafe {
+            clips_sys::AddAfterRuleFiresFunction(
+                env,
+                callback_name.as_ptr(),
+                Some(watchdog_rule_fired),
+                0,
+                context,
+            );
+            clips_sys::AddPeriodicFunction(
+                env,
+                callback_name.as_ptr(),
+                Some(watchdog_periodic),
+                0,
+                context,
+            );
+        }
+    }
+
+    pub(crate) fn tripped(&self) -> bool {
+        self.tripped
+    }
+
+    // Gives back the config and the report, if the watchdog went off and made one. Halting execution would otherwise make every later evaluation fail, so it's cleared here.
+    pub(crate) fn uninstall(
+        self,
+        env: *mut clips_sys::Environment,
+    ) -> (WatchdogConfig, Option<WatchdogReport>) {
+        let callback_name = CString::new(WATCHDOG_CALLBACK_NAME).unwrap();
+
+        unsafe {
+            clips_sys::RemoveAfterRuleFiresFunction(env, callback_name.as_ptr());
+            clips_sys::RemovePeriodicFunction(env, callback_name.as_ptr());
+
+            if self.tripped {
+                clips_sys::SetHaltExecution(env, false);
+            }
+        }
+
+        (self.config, self.report)
+    }
+
+    fn snapshot(&self, env: *mut clips_sys::Environment) -> WatchdogReport {
+        let mut facts = 0;
+        let mut facts_by_template = HashMap::new();
+
+        let mut fact = unsafe { clips_sys::GetNextFact(env, ptr::null_mut()) };
+        while !fact.is_null() {
+            let template = unsafe {
+                CStr::from_ptr(clips_sys::DeftemplateName(clips_sys::FactDeftemplate(fact)))
+            };
+
+            facts += 1;
+            *facts_by_template
+                .entry(template.to_string_lossy().into_owned())
+                .or_insert(0) += 1;
+
+            fact = unsafe { clips_sys::GetNextFact(env, fact) };
+        }
+
+        WatchdogReport {
+            elapsed: self.started.elapsed(),
+            agenda: CLIPSEnvironment::from_raw(env).agenda().unwrap_or_default(),
+            recent_rules: self.recent_rules.iter().cloned().collect(),
+            facts,
+            facts_by_template,
+        }
+    }
+}
+
+extern "C" fn watchdog_rule_fired(
+    _environment: *mut clips_sys::Environment,
+    activation: *mut clips_sys::Activation,
+    context: *mut c_void,
+) {
+    if activation.is_null() {
+        return;
+    }
+
+    let run = unsafe { &mut *(context as *mut WatchdogRun) };
+    let rule_name = unsafe { CStr::from_ptr(clips_sys::ActivationRuleName(activation)) };
+
+    run.recent_rules
+        .push_back(rule_name.to_string_lossy().into_owned());
+    if run.recent_rules.len() > run.config.recent_rules {
+        run.recent_rules.pop_front();
+    }
+}
+
+// Only goes off once per run. Halting the rules alone wouldn't stop a rule that loops forever, so execution is halted too.
+extern "C" fn watchdog_periodic(environment: *mut clips_sys::Environment, context: *mut c_void) {
+    let run = unsafe { &mut *(context as *mut WatchdogRun) };
+
+    if run.tripped || run.started.elapsed() < run.config.max_run_duration {
+        return;
+    }
+
+    run.tripped = true;
+
+    let report = match run.config.action {
+        WatchdogAction::Halt => None,
+        _ => Some(run.snapshot(environment)),
+    };
+
+    let halt = match (&mut run.config.action, &report) {
+        (WatchdogAction::Callback(callback), Some(report)) => callback(report),
+        _ => true,
+    };
+
+    run.report = report;
+
+    if halt {
+        unsafe {
+            clips_sys::SetHaltRules(environment, true);
+            clips_sys::SetHaltExecution(environment, true);
+        }
+    }
+}
@@ -0,0 +1,44 @@
+use std::{
+    ffi::{c_void, CStr},
+    sync::mpsc,
+};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum WmChange {
+    Asserted { index: i64, template: String },
+    Retracted { index: i64, template: String },
+}
+
+// Also used by the tracing bridge, which reports the same changes as events.
+pub(crate) fn fact_index_and_template(fact: *mut c_void) -> (i64, String) {
+    let fact = fact as *mut clips_sys::Fact;
+
+    let index = unsafe { clips_sys::FactIndex(fact) };
+    let template =
+        unsafe { CStr::from_ptr(clips_sys::DeftemplateName(clips_sys::FactDeftemplate(fact))) };
+
+    (index, template.to_string_lossy().into_owned())
+}
+
+// The context given to CLIPS is the sender the changes should go to. Sending errors are ignored, since it only means nobody is listening anymore.
+pub(crate) extern "C" fn wm_assert_callback(
+    _environment: *mut clips_sys::Environment,
+    fact: *mut c_void,
+    context: *mut c_void,
+) {
+    let tx = unsafe { &*(context as *const mpsc::Sender<WmChange>) };
+    let (index, template) = fact_index_and_template(fact);
+
+    let _ = tx.send(WmChange::Asserted { index, template });
+}
+
+pub(crate) extern "C" fn wm_retract_callback(
+    _environment: *mut clips_sys::Environment,
+    fact: *mut c_void,
+    context: *mut c_void,
+) {
+    let tx = unsafe { &*(context as *const mpsc::Sender<WmChange>) };
+    let (index, template) = fact_index_and_template(fact);
+
+    let _ = tx.send(WmChange::Retracted { index, template });
+}
@@ -0,0 +1,92 @@
+use std::sync::mpsc::{self, Receiver};
+
+use clips::{ActivationInfo, AgendaChange, Environment};
+
+fn env() -> (Environment, Receiver<AgendaChange>) {
+    let env = Environment::new();
+    env.load_from_str(
+        "(defrule greet (person ?name) => (assert (greeted ?name)))
+         (defrule alarm (declare (salience 10)) (smoke) (person ?) => (assert (evacuate)))",
+    )
+    .unwrap();
+
+    let (tx, rx) = mpsc::channel();
+    env.set_agenda_changed_callback(move |change| tx.send(change.clone()).unwrap())
+        .unwrap();
+    (env, rx)
+}
+
+fn names(activations: &[ActivationInfo]) -> Vec<&str> {
+    activations
+        .iter()
+        .map(|activation| activation.rule.as_str())
+        .collect()
+}
+
+// The callback runs after a command's result is sent back, but before the next command starts, so another command is sent to wait for it.
+fn received(env: &Environment, changes: &Receiver<AgendaChange>) -> Vec<AgendaChange> {
+    env.agenda().unwrap();
+    changes.try_iter().collect()
+}
+
+fn next_change(env: &Environment, changes: &Receiver<AgendaChange>) -> AgendaChange {
+    let mut received = received(env, changes);
+    assert_eq!(received.len(), 1, "{received:?}");
+    received.pop().unwrap()
+}
+
+#[test]
+fn activations_coming_and_going_are_reported() {
+    let (env, changes) = env();
+
+    env.assert_string("(person alice)").unwrap();
+    let change = next_change(&env, &changes);
+    assert_eq!(names(&change.added), ["greet"]);
+    assert!(change.removed.is_empty());
+    assert_eq!(names(&change.agenda), ["greet"]);
+
+    env.assert_string("(smoke)").unwrap();
+    let change = next_change(&env, &changes);
+    assert_eq!(names(&change.added), ["alarm"]);
+    assert_eq!(names(&change.agenda), ["alarm", "greet"]);
+
+    env.retract_where("person", "TRUE").unwrap();
+    let change = next_change(&env, &changes);
+    assert!(change.added.is_empty());
+    assert_eq!(names(&change.removed), ["alarm", "greet"]);
+    assert!(change.agenda.is_empty());
+}
+
+#[test]
+fn a_run_is_reported_once() {
+    let (env, changes) = env();
+    env.assert_string("(person alice)").unwrap();
+    env.assert_string("(person bob)").unwrap();
+    received(&env, &changes);
+
+    env.run().unwrap();
+
+    let change = next_change(&env, &changes);
+    assert_eq!(names(&change.removed), ["greet", "greet"]);
+    assert!(change.agenda.is_empty());
+}
+
+#[test]
+fn commands_that_leave_the_agenda_alone_are_not_reported() {
+    let (env, changes) = env();
+
+    env.assert_string("(weather sunny)").unwrap();
+    env.load_from_str("(deftemplate unrelated (slot x))")
+        .unwrap();
+    assert!(received(&env, &changes).is_empty());
+}
+
+#[test]
+fn clearing_the_callback_stops_the_reports() {
+    let (env, changes) = env();
+    env.clear_agenda_changed_callback().unwrap();
+
+    env.assert_string("(person alice)").unwrap();
+    // The sender was dropped with the callback.
+    assert!(changes.recv().is_err());
+}
@@ -0,0 +1,32 @@
+use clips::Environment;
+
+#[test]
+fn activations_are_formatted_like_the_console() {
+    let env = Environment::new();
+    env.load_from_str(
+        "
+        (deftemplate item (slot name))
+        (defrule pair (declare (salience 10)) (item (name a)) (item (name b)) =>)
+        (defrule single (item) =>)
+        (defrule unconditional =>)
+        (defglobal ?*asserted* = (fact-index (assert (item (name a)) (item (name b)))))
+        ",
+    )
+    .unwrap();
+
+    assert_eq!(
+        env.agenda_pp().unwrap(),
+        "10     pair: f-1,f-2\n\
+         0      single: f-2\n\
+         0      single: f-1\n\
+         0      unconditional: *\n\
+         For a total of 4 activations.\n"
+    );
+}
+
+#[test]
+fn an_empty_agenda_prints_nothing() {
+    let env = Environment::new();
+
+    assert_eq!(env.agenda_pp().unwrap(), "");
+}
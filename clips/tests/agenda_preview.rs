@@ -0,0 +1,68 @@
+use clips::{ConflictResolutionStrategy, Environment};
+
+fn env() -> Environment {
+    let env = Environment::new();
+    env.load_from_str(
+        "
+        (defrule first (first) =>)
+        (defrule second (second) =>)
+        (defrule third (third) =>)",
+    )
+    .unwrap();
+    for fact in ["(first)", "(second)", "(third)"] {
+        env.assert_string(fact).unwrap();
+    }
+    env
+}
+
+fn rules(env: &Environment) -> Vec<String> {
+    env.agenda()
+        .unwrap()
+        .into_iter()
+        .map(|activation| activation.rule)
+        .collect()
+}
+
+#[test]
+fn the_preview_follows_the_strategy_without_changing_anything() {
+    let env = env();
+    let agenda_before = env.agenda().unwrap();
+    assert_eq!(rules(&env), ["third", "second", "first"]);
+
+    assert_eq!(
+        env.agenda_order_preview(ConflictResolutionStrategy::Breadth)
+            .unwrap(),
+        ["first", "second", "third"]
+    );
+    assert_eq!(
+        env.agenda_order_preview(ConflictResolutionStrategy::Depth)
+            .unwrap(),
+        ["third", "second", "first"]
+    );
+
+    assert_eq!(env.agenda().unwrap(), agenda_before);
+    // The environment is still on the default depth strategy.
+    assert_eq!(
+        env.run_recording_fired_rules().unwrap(),
+        ["third", "second", "first"]
+    );
+}
+
+#[test]
+fn a_strategy_set_before_the_preview_is_kept() {
+    let env = env();
+    env.set_conflict_resolution_strategy(ConflictResolutionStrategy::Breadth)
+        .unwrap();
+    assert_eq!(rules(&env), ["first", "second", "third"]);
+
+    assert_eq!(
+        env.agenda_order_preview(ConflictResolutionStrategy::Depth)
+            .unwrap(),
+        ["third", "second", "first"]
+    );
+    assert_eq!(rules(&env), ["first", "second", "third"]);
+    assert_eq!(
+        env.run_recording_fired_rules().unwrap(),
+        ["first", "second", "third"]
+    );
+}
@@ -0,0 +1,56 @@
+use std::{fs, io::ErrorKind, path::PathBuf};
+
+use clips::{CLIPSError, Environment};
+
+fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!(
+        "clips-rs-test-batch-star-{}-{}",
+        name,
+        std::process::id()
+    ))
+}
+
+#[test]
+fn missing_unreadable_and_invalid_files_fail_differently() {
+    let env = Environment::new();
+
+    let missing = temp_path("missing");
+    assert!(matches!(
+        env.batch_star(missing.clone()),
+        Err(CLIPSError::BatchStar { resolved_path, io_kind: Some(ErrorKind::NotFound) })
+            if resolved_path == missing
+    ));
+
+    // Running as root, permissions can't make a file unreadable, but nothing can read a directory.
+    let directory = temp_path("directory");
+    fs::create_dir_all(&directory).unwrap();
+    let res = env.batch_star(directory.clone());
+    fs::remove_dir(&directory).unwrap();
+    assert!(matches!(
+        res,
+        Err(CLIPSError::BatchStar { resolved_path, io_kind: Some(ErrorKind::IsADirectory) })
+            if resolved_path == directory
+    ));
+
+    let invalid = temp_path("invalid");
+    fs::write(&invalid, "(defrule broken (x) => (no-such-function))\n").unwrap();
+    let res = env.batch_star(invalid.clone());
+    fs::remove_file(&invalid).unwrap();
+    assert!(matches!(
+        res,
+        Err(CLIPSError::BatchStar { resolved_path, io_kind: None }) if resolved_path == invalid
+    ));
+}
+
+#[test]
+fn a_valid_file_loads() {
+    let env = Environment::new();
+
+    let valid = temp_path("valid");
+    fs::write(&valid, "(defglobal ?*loaded* = TRUE)\n").unwrap();
+    let res = env.batch_star(valid.clone());
+    fs::remove_file(&valid).unwrap();
+    res.unwrap();
+
+    assert!(env.retrieve_globals_values().unwrap()["MAIN"].contains_key("loaded"));
+}
@@ -0,0 +1,53 @@
+use std::{fs, sync::Arc};
+
+use clips::Environment;
+
+const CONSTRUCTS: &str = "(deftemplate point (slot x)) (defclass THING (is-a USER) (slot x))";
+
+#[test]
+fn saved_facts_and_instances_load_into_many_environments() {
+    let source = Environment::new();
+    source.load_from_str(CONSTRUCTS).unwrap();
+    source.assert_string("(point (x 1))").unwrap();
+    source.assert_string("(point (x 2))").unwrap();
+    source
+        .load_from_str("(defglobal ?*thing* = (make-instance a of THING (x 1)))")
+        .unwrap();
+
+    let dir = std::env::temp_dir();
+    let facts_path = dir.join(format!("clips-rs-test-facts-{}", std::process::id()));
+    let instances_path = dir.join(format!("clips-rs-test-instances-{}", std::process::id()));
+    source.binary_save_facts(facts_path.clone()).unwrap();
+    source
+        .binary_save_instances(instances_path.clone())
+        .unwrap();
+    let facts: Arc<[u8]> = fs::read(&facts_path).unwrap().into();
+    let instances: Arc<[u8]> = fs::read(&instances_path).unwrap().into();
+    fs::remove_file(facts_path).unwrap();
+    fs::remove_file(instances_path).unwrap();
+
+    for _ in 0..2 {
+        let target = Environment::new();
+        target.load_from_str(CONSTRUCTS).unwrap();
+
+        assert_eq!(
+            target.binary_load_facts_from_bytes(facts.clone()).unwrap(),
+            2
+        );
+        assert_eq!(
+            target
+                .binary_load_instances_from_bytes(instances.clone())
+                .unwrap(),
+            1
+        );
+        assert_eq!(target.find_all_facts("point", "TRUE").unwrap().len(), 2);
+    }
+}
+
+#[test]
+fn bytes_that_arent_a_binary_save_are_an_error() {
+    let env = Environment::new();
+    env.load_from_str(CONSTRUCTS).unwrap();
+
+    assert!(env.binary_load_facts_from_bytes(&b"not facts"[..]).is_err());
+}
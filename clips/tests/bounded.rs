@@ -0,0 +1,63 @@
+use std::{
+    sync::{mpsc, Mutex},
+    thread,
+    time::Duration,
+};
+
+use clips::{CLIPSError, Environment, SlotMap, UDFType};
+
+#[test]
+fn full_queue_pushes_back_on_producers() {
+    let env = Environment::new_bounded(2);
+    env.load_from_str("(deftemplate point (slot x))").unwrap();
+
+    // The UDF keeps the CLIPS thread busy until the gate opens, so everything sent meanwhile stays queued.
+    let (started_tx, started_rx) = mpsc::channel();
+    let (gate_tx, gate_rx) = mpsc::channel::<()>();
+    let started_tx = Mutex::new(started_tx);
+    let gate_rx = Mutex::new(gate_rx);
+    env.add_udf(
+        "slow".to_string(),
+        0,
+        0,
+        UDFType::Boolean,
+        vec![],
+        Box::new(move |mut data| {
+            started_tx.lock().unwrap().send(()).unwrap();
+            gate_rx.lock().unwrap().recv().unwrap();
+            data.set_boolean_result(true);
+        }),
+    )
+    .unwrap();
+
+    let consumer = {
+        let env = env.clone();
+        thread::spawn(move || env.load_from_str("(defglobal ?*done* = (slow))"))
+    };
+    started_rx.recv().unwrap();
+
+    let producers: Vec<_> = (0..2)
+        .map(|x| {
+            let env = env.clone();
+            thread::spawn(move || env.try_assert_fact(SlotMap::new("point").slot("x", x), None))
+        })
+        .collect();
+    while env.pending_commands() < 2 {
+        thread::sleep(Duration::from_millis(1));
+    }
+
+    assert!(matches!(
+        env.try_assert_fact(SlotMap::new("point").slot("x", 2), None),
+        Err(CLIPSError::Busy)
+    ));
+    assert_eq!(env.pending_commands(), 2);
+
+    gate_tx.send(()).unwrap();
+    consumer.join().unwrap().unwrap();
+    for producer in producers {
+        producer.join().unwrap().unwrap();
+    }
+
+    assert_eq!(env.pending_commands(), 0);
+    assert_eq!(env.find_all_facts("point", "TRUE").unwrap().len(), 2);
+}
@@ -0,0 +1,167 @@
+use std::collections::{HashMap, VecDeque};
+
+use clips::{BufferedReadRouter, CLIPSValue, Environment, ReadSource, Router};
+
+// Hands out the chunks given for each logical name, then ends the input.
+#[derive(Default)]
+struct Chunks {
+    chunks: HashMap<String, VecDeque<Vec<u8>>>,
+    fills: usize,
+}
+
+impl Chunks {
+    fn with(mut self, logical_name: &str, chunks: &[&[u8]]) -> Self {
+        self.chunks.insert(
+            logical_name.to_string(),
+            chunks.iter().map(|chunk| chunk.to_vec()).collect(),
+        );
+        self
+    }
+}
+
+impl ReadSource for Chunks {
+    fn query(&mut self, logical_name: &str) -> bool {
+        self.chunks.contains_key(logical_name)
+    }
+
+    fn fill(&mut self, logical_name: &str) -> Option<Vec<u8>> {
+        self.fills += 1;
+        self.chunks.get_mut(logical_name)?.pop_front()
+    }
+}
+
+fn env(source: Chunks) -> Environment {
+    let env = Environment::new();
+    env.add_router(
+        "input".to_string(),
+        30,
+        Box::new(BufferedReadRouter::new(source)),
+    )
+    .unwrap();
+    env
+}
+
+fn eval(env: &Environment, expression: &str) -> CLIPSValue {
+    env.load_from_str(format!("(defglobal ?*result* = {expression})"))
+        .unwrap();
+    env.retrieve_globals_values().unwrap()["MAIN"]["result"].clone()
+}
+
+fn symbol(value: &str) -> CLIPSValue {
+    CLIPSValue::Symbol(value.to_string())
+}
+
+fn string(value: &str) -> CLIPSValue {
+    CLIPSValue::String(value.to_string())
+}
+
+#[test]
+fn readline_joins_chunks_and_stops_at_each_line() {
+    let env = env(Chunks::default().with(
+        "input",
+        &[b"hel", b"", b"lo wo", b"rld\nsecond\nthi", b"rd"],
+    ));
+
+    assert_eq!(eval(&env, "(readline input)"), string("hello world"));
+    assert_eq!(eval(&env, "(readline input)"), string("second"));
+    // The last line has no newline, and is still read whole.
+    assert_eq!(eval(&env, "(readline input)"), string("third"));
+    assert_eq!(eval(&env, "(readline input)"), symbol("EOF"));
+    assert_eq!(eval(&env, "(readline input)"), symbol("EOF"));
+}
+
+#[test]
+fn read_gives_back_each_token_split_across_chunks() {
+    let env = env(Chunks::default().with("input", &[b"fo", b"o 4", b"2 \"a b", b"\" 2.5\n"]));
+
+    assert_eq!(eval(&env, "(read input)"), symbol("foo"));
+    assert_eq!(eval(&env, "(read input)"), CLIPSValue::Int(42));
+    assert_eq!(eval(&env, "(read input)"), string("a b"));
+    assert_eq!(eval(&env, "(read input)"), CLIPSValue::Float(2.5));
+    assert_eq!(eval(&env, "(read input)"), symbol("EOF"));
+}
+
+// `read-number` from `stdin` reads a whole line at a time, which is how the CLIPS command loop uses it.
+#[test]
+fn read_number_reads_integers_and_floats() {
+    let env = env(Chunks::default().with("stdin", &[b"12\n", b" -3.", b"75\n", b"7\n"]));
+
+    assert_eq!(eval(&env, "(read-number)"), CLIPSValue::Int(12));
+    assert_eq!(eval(&env, "(read-number)"), CLIPSValue::Float(-3.75));
+    assert_eq!(eval(&env, "(read-number)"), CLIPSValue::Int(7));
+    assert_eq!(eval(&env, "(read-number)"), symbol("EOF"));
+}
+
+#[test]
+fn reads_of_different_functions_pick_up_where_the_last_one_stopped() {
+    let env = env(Chunks::default().with("input", &[b"first 2\nthe rest of the line\n3\n"]));
+
+    assert_eq!(eval(&env, "(read input)"), symbol("first"));
+    // The newline after the number ends it, so the next line is read whole.
+    assert_eq!(eval(&env, "(read-number input)"), CLIPSValue::Int(2));
+    assert_eq!(
+        eval(&env, "(readline input)"),
+        string("the rest of the line")
+    );
+    assert_eq!(eval(&env, "(read input)"), CLIPSValue::Int(3));
+}
+
+#[test]
+fn bytes_above_127_are_not_taken_for_the_end_of_the_input() {
+    // The chunks split the two bytes of the "\u{e9}".
+    let text = "h\u{e9}llo\n".as_bytes();
+    let env = env(Chunks::default().with("input", &[&text[..2], &text[2..]]));
+
+    assert_eq!(eval(&env, "(readline input)"), string("h\u{e9}llo"));
+    assert_eq!(eval(&env, "(readline input)"), symbol("EOF"));
+}
+
+#[test]
+fn each_logical_name_has_its_own_input() {
+    let env = env(Chunks::default()
+        .with("left", &[b"l1\nl2\n"])
+        .with("right", &[b"r1\n"]));
+
+    assert_eq!(eval(&env, "(readline left)"), string("l1"));
+    assert_eq!(eval(&env, "(readline right)"), string("r1"));
+    assert_eq!(eval(&env, "(readline right)"), symbol("EOF"));
+    assert_eq!(eval(&env, "(readline left)"), string("l2"));
+}
+
+#[test]
+fn unread_characters_come_back_last_in_first_out() {
+    let mut router = BufferedReadRouter::new(Chunks::default().with("input", &[b"ab", b"c"]));
+
+    assert_eq!(router.read("input"), Some(i32::from(b'a')));
+    assert_eq!(router.read("input"), Some(i32::from(b'b')));
+    assert_eq!(
+        router.unread("input", i32::from(b'b')),
+        Some(i32::from(b'b'))
+    );
+    assert_eq!(
+        router.unread("input", i32::from(b'a')),
+        Some(i32::from(b'a'))
+    );
+    assert_eq!(router.read("input"), Some(i32::from(b'a')));
+    assert_eq!(router.read("input"), Some(i32::from(b'b')));
+    assert_eq!(router.read("input"), Some(i32::from(b'c')));
+    assert_eq!(router.read("input"), None);
+
+    // Unreading the end of the input makes the next read end it again, without asking the source for more.
+    let fills = router.source().fills;
+    assert_eq!(router.unread("input", -1), Some(-1));
+    assert_eq!(router.read("input"), None);
+    assert_eq!(router.source().fills, fills);
+}
+
+#[test]
+fn fill_is_only_called_once_the_buffer_is_empty() {
+    let mut router = BufferedReadRouter::new(Chunks::default().with("input", &[b"xyz"]));
+
+    for ch in b"xyz" {
+        assert_eq!(router.read("input"), Some(i32::from(*ch)));
+    }
+    assert_eq!(router.source().fills, 1);
+    assert_eq!(router.read("input"), None);
+    assert_eq!(router.into_source().fills, 2);
+}
@@ -0,0 +1,67 @@
+use std::{ffi::CStr, thread, time::Duration};
+
+use clips::{Environment, EnvironmentFailure, Router, RouterSupport, UDFType};
+
+fn wait_for_failure(env: &Environment) -> EnvironmentFailure {
+    loop {
+        if let Some(failure) = env.failure_reason() {
+            return failure;
+        }
+        thread::sleep(Duration::from_millis(1));
+    }
+}
+
+#[test]
+fn panicking_udf_is_reported_by_the_environment() {
+    let env = Environment::new();
+    env.add_udf(
+        "boom".to_string(),
+        0,
+        0,
+        UDFType::Boolean,
+        vec![],
+        Box::new(|_| panic!("the UDF panicked")),
+    )
+    .unwrap();
+
+    assert!(env.load_from_str("(defglobal ?*x* = (boom))").is_err());
+
+    assert_eq!(
+        wait_for_failure(&env),
+        EnvironmentFailure::Panicked {
+            message: Some("the UDF panicked".to_string())
+        }
+    );
+}
+
+struct PanickingRouter;
+
+impl Router for PanickingRouter {
+    fn supports(&self) -> RouterSupport {
+        RouterSupport::WRITE
+    }
+
+    fn query(&mut self, logical_name: &str) -> bool {
+        logical_name == "boom"
+    }
+
+    fn write(&mut self, _logical_name: &str, _data: &CStr) {
+        panic!("the router panicked");
+    }
+}
+
+#[test]
+fn panicking_router_is_reported_by_the_environment() {
+    let env = Environment::new();
+    env.add_router("panicking".to_string(), 100, Box::new(PanickingRouter))
+        .unwrap();
+
+    let _ = env.load_from_str("(defglobal ?*x* = (printout boom \"text\"))");
+
+    assert_eq!(
+        wait_for_failure(&env),
+        EnvironmentFailure::Panicked {
+            message: Some("the router panicked".to_string())
+        }
+    );
+}
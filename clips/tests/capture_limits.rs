@@ -0,0 +1,175 @@
+use std::fs;
+
+use clips::{
+    CaptureHandle, CaptureLimits, CaptureOverflow, CaptureRouter, Environment, LogicalName,
+    SpillFile,
+};
+
+const LIMIT: usize = 64 * 1024;
+const LINES: usize = 4096;
+const PADDING: usize = 1000;
+
+// Prints `LINES` lines of about 1 KB each, every one starting with its number, and returns how many bytes that was.
+fn print_lines(env: &Environment) -> u64 {
+    env.load_from_str(format!(
+        "(deffunction print-lines ()
+           (bind ?padding \"\")
+           (loop-for-count {PADDING} do (bind ?padding (str-cat ?padding \"x\")))
+           (loop-for-count (?i 1 {LINES}) do (printout t ?i \" \" ?padding crlf)))
+         (defglobal ?*printed* = (print-lines))"
+    ))
+    .unwrap();
+
+    (1..=LINES)
+        .map(|i| (i.to_string().len() + 1 + PADDING + 1) as u64)
+        .sum()
+}
+
+fn capture(router: CaptureRouter) -> (Environment, CaptureHandle) {
+    let env = Environment::new();
+    let handle = router.handle();
+    env.add_router("capture".to_string(), 30, Box::new(router))
+        .unwrap();
+    (env, handle)
+}
+
+fn spill_path(name: &str) -> std::path::PathBuf {
+    std::env::temp_dir().join(format!(
+        "clips-rs-test-capture-{}-{}",
+        name,
+        std::process::id()
+    ))
+}
+
+#[test]
+fn without_limits_everything_is_kept() {
+    let (env, handle) = capture(CaptureRouter::new(&[LogicalName::Stdout]));
+    let printed = print_lines(&env);
+
+    assert_eq!(handle.buffered_bytes() as u64, printed);
+    assert_eq!(handle.dropped_bytes(), 0);
+
+    let text = handle.take();
+    assert!(text.starts_with("1 x"));
+    assert_eq!(handle.buffered_bytes(), 0);
+    assert_eq!(handle.contents(), "");
+}
+
+#[test]
+fn dropping_the_oldest_keeps_the_end() {
+    let (env, handle) = capture(
+        CaptureRouter::new(&[LogicalName::Stdout])
+            .with_limits(CaptureLimits::new(LIMIT, CaptureOverflow::DropOldest)),
+    );
+    let printed = print_lines(&env);
+
+    assert_eq!(handle.buffered_bytes(), LIMIT);
+    assert_eq!(handle.dropped_bytes(), printed - LIMIT as u64);
+    assert_eq!(handle.spilled_bytes(), 0);
+    assert!(handle
+        .contents()
+        .lines()
+        .last()
+        .unwrap()
+        .starts_with(&format!("{LINES} ")));
+}
+
+#[test]
+fn dropping_the_newest_keeps_the_beginning() {
+    let (env, handle) = capture(
+        CaptureRouter::new(&[LogicalName::Stdout])
+            .with_limits(CaptureLimits::new(LIMIT, CaptureOverflow::DropNewest)),
+    );
+    let printed = print_lines(&env);
+
+    assert_eq!(handle.buffered_bytes(), LIMIT);
+    assert_eq!(handle.dropped_bytes(), printed - LIMIT as u64);
+    assert!(handle.contents().starts_with("1 x"));
+
+    // Once there's room again, new text is taken in.
+    handle.take();
+    env.load_from_str("(defglobal ?*more* = (printout t \"more\" crlf))")
+        .unwrap();
+    assert_eq!(handle.contents(), "more\n");
+    assert_eq!(handle.dropped_bytes(), printed - LIMIT as u64);
+}
+
+#[test]
+fn the_oldest_text_spills_into_rotated_files() {
+    let path = spill_path("spill");
+    let max_file_bytes = 1024 * 1024;
+    let (env, handle) = capture(CaptureRouter::new(&[LogicalName::Stdout]).with_limits(
+        CaptureLimits::new(LIMIT, CaptureOverflow::DropOldest).with_spill(SpillFile {
+            path: path.clone(),
+            max_file_bytes,
+            max_files: 2,
+        }),
+    ));
+    let printed = print_lines(&env);
+    let rotated = |number: usize| {
+        path.with_file_name(format!(
+            "{}.{number}",
+            path.file_name().unwrap().to_str().unwrap()
+        ))
+    };
+
+    let spilled = printed - LIMIT as u64;
+    assert_eq!(handle.buffered_bytes(), LIMIT);
+    assert_eq!(handle.spilled_bytes(), spilled);
+    assert_eq!(handle.dropped_bytes(), 0);
+    assert_eq!(handle.last_spill_error(), None);
+
+    // About 4 MB spilled into 1 MB files, of which only the newest two full ones are kept besides the current one.
+    let current = fs::read_to_string(&path).unwrap();
+    let first = fs::read_to_string(rotated(1)).unwrap();
+    let second = fs::read_to_string(rotated(2)).unwrap();
+    assert!(!rotated(3).exists());
+    assert_eq!(first.len() as u64, max_file_bytes);
+    assert_eq!(second.len() as u64, max_file_bytes);
+    assert_eq!(current.len() as u64, spilled % max_file_bytes);
+
+    // The spill files and the buffer join up without a gap.
+    let joined = format!("{second}{first}{current}{}", handle.contents());
+    assert!(joined.ends_with(&format!("{LINES} {}\n", "x".repeat(PADDING))));
+    assert_eq!(
+        joined.len() as u64,
+        2 * max_file_bytes + spilled % max_file_bytes + LIMIT as u64
+    );
+    for line in joined.lines().skip(1) {
+        assert!(line.ends_with(&"x".repeat(PADDING)), "{line}");
+    }
+
+    for file in [path.clone(), rotated(1), rotated(2)] {
+        fs::remove_file(file).unwrap();
+    }
+}
+
+#[test]
+fn text_that_cannot_be_spilled_is_dropped() {
+    let path = spill_path("missing-dir").join("spill");
+    let (env, handle) = capture(CaptureRouter::new(&[LogicalName::Stdout]).with_limits(
+        CaptureLimits::new(LIMIT, CaptureOverflow::DropOldest).with_spill(SpillFile {
+            path,
+            max_file_bytes: 1024 * 1024,
+            max_files: 1,
+        }),
+    ));
+    let printed = print_lines(&env);
+
+    assert_eq!(handle.spilled_bytes(), 0);
+    assert_eq!(handle.dropped_bytes(), printed - LIMIT as u64);
+    assert!(handle.last_spill_error().is_some());
+}
+
+#[test]
+fn other_logical_names_are_not_captured() {
+    let (env, handle) = capture(
+        CaptureRouter::new(&[LogicalName::Stdwrn])
+            .with_limits(CaptureLimits::new(LIMIT, CaptureOverflow::DropOldest)),
+    );
+    env.load_from_str("(defglobal ?*x* = (printout t \"hello\" crlf))")
+        .unwrap();
+
+    assert_eq!(handle.contents(), "");
+    assert_eq!(handle.dropped_bytes(), 0);
+}
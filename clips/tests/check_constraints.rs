@@ -0,0 +1,57 @@
+use clips::{ConstraintViolation, ConstraintViolationKind, ConstraintViolationSource, Environment};
+
+#[test]
+fn values_put_in_without_dynamic_checking_are_found() {
+    let env = Environment::new();
+    env.set_dynamic_constraint_checking(false).unwrap();
+    env.load_from_str(
+        "
+        (deftemplate reading (slot value (type INTEGER) (range 0 100)))
+        (defclass sensor (is-a USER) (slot unit (allowed-symbols celsius kelvin)))",
+    )
+    .unwrap();
+
+    env.assert_string("(reading (value 42))").unwrap();
+    // Constants are checked when they're parsed, so the values come from globals.
+    env.load_from_str(
+        "
+        (defglobal ?*value* = 500 ?*unit* = fahrenheit)
+        (defglobal ?*reading* = (assert (reading (value ?*value*))))
+        (defglobal ?*probe* = (make-instance probe of sensor (unit ?*unit*)))",
+    )
+    .unwrap();
+    let too_high = env.find_all_facts("reading", "(> ?f:value 100)").unwrap()[0].index;
+
+    assert_eq!(
+        env.check_constraints().unwrap(),
+        [
+            ConstraintViolation {
+                source: ConstraintViolationSource::Fact {
+                    index: too_high,
+                    template: "reading".to_string(),
+                },
+                slot: "value".to_string(),
+                kind: ConstraintViolationKind::Range,
+            },
+            ConstraintViolation {
+                source: ConstraintViolationSource::Instance {
+                    name: "probe".to_string(),
+                    class: "sensor".to_string(),
+                },
+                slot: "unit".to_string(),
+                kind: ConstraintViolationKind::AllowedValues,
+            },
+        ]
+    );
+}
+
+#[test]
+fn valid_values_have_no_violations() {
+    let env = Environment::new();
+    env.set_dynamic_constraint_checking(false).unwrap();
+    env.load_from_str("(deftemplate reading (slot value (type INTEGER) (range 0 100)))")
+        .unwrap();
+    env.assert_string("(reading (value 100))").unwrap();
+
+    assert!(env.check_constraints().unwrap().is_empty());
+}
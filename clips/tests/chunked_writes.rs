@@ -0,0 +1,180 @@
+use std::{
+    ffi::CStr,
+    sync::{Arc, Mutex},
+};
+
+use clips::{
+    CaptureLimits, CaptureOverflow, CaptureRouter, Environment, LogicalName, Router, RouterSupport,
+    TeeRouter,
+};
+
+const BIG_RULE_BYTES: usize = 50 * 1024 * 1024;
+const CHUNK_SIZE: usize = 1024 * 1024;
+
+#[derive(Default)]
+struct Writes {
+    // The length of every chunk or whole write, and whether it was the final chunk.
+    pieces: Vec<(usize, bool)>,
+    text: Vec<u8>,
+}
+
+// Writes to `big`, unless told otherwise, come here, and are kept whole or in chunks depending on `chunk_size`.
+struct Recording {
+    logical_name: String,
+    chunk_size: Option<usize>,
+    writes: Arc<Mutex<Writes>>,
+}
+
+impl Recording {
+    fn new(chunk_size: Option<usize>) -> (Self, Arc<Mutex<Writes>>) {
+        let writes = Arc::new(Mutex::new(Writes::default()));
+        let router = Self {
+            logical_name: "big".to_string(),
+            chunk_size,
+            writes: writes.clone(),
+        };
+        (router, writes)
+    }
+}
+
+impl Router for Recording {
+    fn supports(&self) -> RouterSupport {
+        RouterSupport::WRITE
+    }
+
+    fn query(&mut self, logical_name: &str) -> bool {
+        logical_name == self.logical_name
+    }
+
+    fn write(&mut self, _logical_name: &str, data: &CStr) {
+        let mut writes = self.writes.lock().unwrap();
+        writes.pieces.push((data.to_bytes().len(), true));
+        writes.text.extend_from_slice(data.to_bytes());
+    }
+
+    fn write_chunk_size(&self) -> Option<usize> {
+        self.chunk_size
+    }
+
+    fn write_chunked(&mut self, _logical_name: &str, chunk: &[u8], final_chunk: bool) {
+        let mut writes = self.writes.lock().unwrap();
+        writes.pieces.push((chunk.len(), final_chunk));
+        writes.text.extend_from_slice(chunk);
+    }
+}
+
+// CLIPS writes the pretty-printed form of a rule in a single write, so a rule with a lot of text in it makes a huge write. The text is split into many symbols because CLIPS grows its buffer for a single token a few bytes at a time, so one huge string would take ages to parse.
+fn load_big_rule(env: &Environment) {
+    let symbol = "x".repeat(1023);
+    let symbols = vec![symbol.as_str(); BIG_RULE_BYTES / 1024].join("\n");
+    env.load_from_str(format!(
+        "(defrule big => (bind ?symbols (create$\n{symbols})))"
+    ))
+    .unwrap();
+}
+
+fn ppdefrule(env: &Environment, logical_name: &str) {
+    env.load_from_str(format!(
+        "(defglobal ?*printed* = (progn (ppdefrule big {logical_name}) TRUE))"
+    ))
+    .unwrap();
+}
+
+fn recording_env(chunk_size: Option<usize>) -> (Environment, Arc<Mutex<Writes>>) {
+    let env = Environment::new();
+    let (router, writes) = Recording::new(chunk_size);
+    env.add_router("recording".to_string(), 30, Box::new(router))
+        .unwrap();
+    load_big_rule(&env);
+    (env, writes)
+}
+
+#[test]
+fn a_huge_write_comes_in_bounded_chunks() {
+    let (env, writes) = recording_env(Some(CHUNK_SIZE));
+
+    ppdefrule(&env, "big");
+
+    let writes = writes.lock().unwrap();
+    assert!(writes.text.starts_with(b"(defrule MAIN::big"));
+    assert!(writes.text.len() > BIG_RULE_BYTES);
+    assert!(writes.pieces.iter().all(|(len, _)| *len <= CHUNK_SIZE));
+
+    // The rule came in one write, so in a run of full chunks that only the last one of ends.
+    let start = writes
+        .pieces
+        .iter()
+        .position(|(len, _)| *len == CHUNK_SIZE)
+        .unwrap();
+    let end = start
+        + writes.pieces[start..]
+            .iter()
+            .position(|(_, final_chunk)| *final_chunk)
+            .unwrap();
+    let big_write: usize = writes.pieces[start..=end].iter().map(|(len, _)| len).sum();
+    assert!(big_write > BIG_RULE_BYTES);
+    assert!(writes.pieces[start..end]
+        .iter()
+        .all(|piece| *piece == (CHUNK_SIZE, false)));
+}
+
+#[test]
+fn routers_that_do_not_opt_in_get_whole_writes() {
+    let (env, writes) = recording_env(None);
+
+    ppdefrule(&env, "big");
+
+    let writes = writes.lock().unwrap();
+    assert!(writes.pieces.iter().any(|(len, _)| *len > BIG_RULE_BYTES));
+}
+
+#[test]
+fn routers_in_a_tee_get_chunks_too() {
+    let env = Environment::new();
+    let (chunked, chunked_writes) = Recording::new(Some(CHUNK_SIZE));
+    let (whole, whole_writes) = Recording::new(None);
+    let tee = TeeRouter::new()
+        .with_router(20, Box::new(chunked))
+        .with_router(10, Box::new(whole));
+    env.add_router("tee".to_string(), 30, Box::new(tee))
+        .unwrap();
+    load_big_rule(&env);
+
+    ppdefrule(&env, "big");
+
+    let chunked_writes = chunked_writes.lock().unwrap();
+    let whole_writes = whole_writes.lock().unwrap();
+    assert!(chunked_writes.text == whole_writes.text);
+    assert!(chunked_writes
+        .pieces
+        .iter()
+        .all(|(len, _)| *len <= CHUNK_SIZE));
+    assert!(chunked_writes.pieces.len() > whole_writes.pieces.len());
+}
+
+#[test]
+fn a_capture_takes_a_huge_write_within_its_limit() {
+    let env = Environment::new();
+    let limit = 64 * 1024;
+    let capture = CaptureRouter::new(&[LogicalName::Stdout])
+        .with_limits(CaptureLimits::new(limit, CaptureOverflow::DropOldest))
+        .with_write_chunk_size(4096);
+    let handle = capture.handle();
+    // The whole text, to compare against.
+    let (mut whole, whole_writes) = Recording::new(None);
+    whole.logical_name = LogicalName::Stdout.as_ref().to_string();
+    let tee = TeeRouter::new()
+        .with_router(20, Box::new(capture))
+        .with_router(10, Box::new(whole));
+    env.add_router("tee".to_string(), 30, Box::new(tee))
+        .unwrap();
+    load_big_rule(&env);
+
+    ppdefrule(&env, "t");
+
+    let text = &whole_writes.lock().unwrap().text;
+    assert!(text.len() > BIG_RULE_BYTES);
+    assert_eq!(handle.buffered_bytes(), limit);
+    assert_eq!(handle.dropped_bytes(), (text.len() - limit) as u64);
+    assert!(handle.contents().as_bytes() == &text[text.len() - limit..]);
+}
@@ -0,0 +1,105 @@
+use clips::{CLIPSError, CLIPSValue, ClassInfo, Environment};
+
+fn env_with_classes() -> Environment {
+    let env = Environment::new();
+    env.load_from_str(
+        "
+        (defclass shape (is-a USER)
+          (slot colour (type SYMBOL) (default red)))
+        (defclass polygon (is-a shape)
+          (multislot corners (type INTEGER) (cardinality 3 8) (default 0 0 0))
+          (slot label (access read-only) (default none)))
+        (defmessage-handler polygon describe primary ()
+          (printout t ?self:corners crlf))
+        ",
+    )
+    .unwrap();
+    env
+}
+
+#[test]
+fn superclasses_and_inherited_slots_are_listed() {
+    let env = env_with_classes();
+
+    let info = env.class_info("polygon").unwrap();
+
+    assert_eq!(info.name, "polygon");
+    assert_eq!(info.module, "MAIN");
+    assert!(!info.is_abstract);
+    assert_eq!(info.direct_superclasses, vec!["shape"]);
+    assert_eq!(info.superclasses, vec!["shape", "USER", "OBJECT"]);
+
+    let slot_names: Vec<_> = info.slots.iter().map(|slot| slot.name.as_str()).collect();
+    assert_eq!(slot_names, vec!["colour", "corners", "label"]);
+}
+
+#[test]
+fn slot_facets_and_defaults_are_read() {
+    let env = env_with_classes();
+
+    let info = env.class_info("polygon").unwrap();
+    let slot = |name: &str| info.slots.iter().find(|slot| slot.name == name).unwrap();
+
+    let colour = slot("colour");
+    assert_eq!(colour.default, Some(CLIPSValue::Symbol("red".to_string())));
+    assert_eq!(colour.types, vec!["SYMBOL"]);
+    assert!(!colour.multislot);
+    assert_eq!(colour.cardinality, None);
+    assert!(colour.writable);
+
+    let corners = slot("corners");
+    assert_eq!(
+        corners.default,
+        Some(CLIPSValue::Multifield(vec![
+            CLIPSValue::Int(0),
+            CLIPSValue::Int(0),
+            CLIPSValue::Int(0),
+        ]))
+    );
+    assert_eq!(corners.types, vec!["INTEGER"]);
+    assert!(corners.multislot);
+    assert_eq!(corners.cardinality, Some((3, Some(8))));
+
+    let label = slot("label");
+    assert!(!label.writable);
+    assert!(!label.initable);
+}
+
+#[test]
+fn only_handlers_defined_on_the_class_are_listed() {
+    let env = env_with_classes();
+
+    let polygon = env.class_info("polygon").unwrap();
+    let shape = env.class_info("shape").unwrap();
+
+    let handler_names = |info: &ClassInfo| -> Vec<String> {
+        info.message_handlers
+            .iter()
+            .map(|handler| format!("{} {}", handler.name, handler.handler_type))
+            .collect()
+    };
+    // CLIPS creates the slot accessors as handlers of the class that defines the slot.
+    assert_eq!(
+        handler_names(&polygon),
+        vec![
+            "get-corners primary",
+            "put-corners primary",
+            "get-label primary",
+            "describe primary",
+        ]
+    );
+    assert_eq!(
+        handler_names(&shape),
+        vec!["get-colour primary", "put-colour primary"]
+    );
+}
+
+#[test]
+fn unknown_classes_are_reported() {
+    let env = env_with_classes();
+
+    assert!(matches!(
+        env.class_info("circle"),
+        Err(CLIPSError::ClassNotFound)
+    ));
+}
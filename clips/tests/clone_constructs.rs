@@ -0,0 +1,24 @@
+use clips::{Environment, SlotMap};
+
+#[test]
+fn asserting_after_cloning_constructs_uses_the_new_template() {
+    let source = Environment::new();
+    source
+        .load_from_str("(deftemplate point (slot y) (slot x))")
+        .unwrap();
+
+    let target = Environment::new();
+    target
+        .load_from_str("(deftemplate point (slot x))")
+        .unwrap();
+    target
+        .assert_fact(SlotMap::new("point").slot("x", 1), None)
+        .unwrap();
+
+    source.clone_constructs_into(&target).unwrap();
+
+    // A builder kept from before the import would still point to the old template, which has no `y` slot.
+    target
+        .assert_fact(SlotMap::new("point").slot("y", 2).slot("x", 3), None)
+        .unwrap();
+}
@@ -0,0 +1,35 @@
+use clips::{CLIPSError, Environment, EnvironmentFailure};
+
+#[test]
+fn clones_fail_fast_after_close() {
+    let env = Environment::new();
+    let clone = env.clone();
+
+    env.close().unwrap();
+
+    assert!(matches!(
+        clone.load_from_str("(defglobal ?*x* = 1)"),
+        Err(CLIPSError::Closed)
+    ));
+    assert_eq!(clone.failure_reason(), Some(EnvironmentFailure::Closed));
+}
+
+#[test]
+fn closing_twice_does_nothing() {
+    let env = Environment::new();
+    let clone = env.clone();
+
+    env.close().unwrap();
+    clone.close().unwrap();
+    env.close().unwrap();
+}
+
+#[test]
+fn clones_share_the_environment() {
+    let env = Environment::new();
+    let clone = env.clone();
+
+    env.load_from_str("(defglobal ?*x* = 1)").unwrap();
+
+    assert!(clone.retrieve_globals_values().unwrap()["MAIN"].contains_key("x"));
+}
@@ -0,0 +1,128 @@
+use std::{
+    fs,
+    sync::{Arc, Mutex},
+};
+
+use clips::{ConstructEvent, ConstructKind, Environment};
+
+type Events = Arc<Mutex<Vec<ConstructEvent>>>;
+
+fn env() -> (Environment, Events) {
+    let env = Environment::new();
+    let events = Events::default();
+    let events_in_callback = events.clone();
+    env.on_construct_defined(Box::new(move |event| {
+        events_in_callback.lock().unwrap().push(event)
+    }))
+    .unwrap();
+    (env, events)
+}
+
+fn event(kind: ConstructKind, name: &str, module: &str, redefined: bool) -> ConstructEvent {
+    ConstructEvent {
+        kind,
+        name: name.to_string(),
+        module: module.to_string(),
+        redefined,
+    }
+}
+
+fn take(events: &Events) -> Vec<ConstructEvent> {
+    std::mem::take(&mut *events.lock().unwrap())
+}
+
+#[test]
+fn every_reported_kind_of_construct_is_reported() {
+    let (env, events) = env();
+    env.load_from_str(
+        "(deftemplate reading (slot value))
+         (defglobal ?*limit* = 10)
+         (deffunction high (?v) (> ?v ?*limit*))
+         (defgeneric describe)
+         (defclass sensor (is-a USER) (slot id))
+         (definstances sensors (s1 of sensor))
+         (deffacts readings (reading (value 1)))
+         (defrule alarm (reading (value ?v&:(high ?v))) =>)",
+    )
+    .unwrap();
+
+    assert_eq!(
+        take(&events),
+        [
+            event(ConstructKind::Deftemplate, "reading", "MAIN", false),
+            event(ConstructKind::Deffunction, "high", "MAIN", false),
+            event(ConstructKind::Defgeneric, "describe", "MAIN", false),
+            event(ConstructKind::Defclass, "sensor", "MAIN", false),
+            event(ConstructKind::Definstances, "sensors", "MAIN", false),
+            event(ConstructKind::Deffacts, "readings", "MAIN", false),
+            event(ConstructKind::Defrule, "alarm", "MAIN", false),
+        ]
+    );
+}
+
+#[test]
+fn a_batch_file_redefining_a_rule_is_reported() {
+    let (env, events) = env();
+    env.load_from_str("(defrule check => (printout t ok crlf))")
+        .unwrap();
+    take(&events);
+
+    let path = std::env::temp_dir().join(format!(
+        "clips-rs-test-construct-events-{}",
+        std::process::id()
+    ));
+    fs::write(
+        &path,
+        "(defrule check => (printout t changed crlf))\n(defrule other =>)\n",
+    )
+    .unwrap();
+    let res = env.batch_star(path.clone());
+    fs::remove_file(path).unwrap();
+    res.unwrap();
+
+    assert_eq!(
+        take(&events),
+        [
+            event(ConstructKind::Defrule, "check", "MAIN", true),
+            event(ConstructKind::Defrule, "other", "MAIN", false),
+        ]
+    );
+}
+
+#[test]
+fn constructs_with_the_same_name_in_another_module_are_not_redefinitions() {
+    let (env, events) = env();
+    env.load_from_str("(defrule check =>)").unwrap();
+    env.load_from_str("(defmodule OTHER) (defrule OTHER::check =>)")
+        .unwrap();
+
+    assert_eq!(
+        take(&events),
+        [
+            event(ConstructKind::Defrule, "check", "MAIN", false),
+            event(ConstructKind::Defrule, "check", "OTHER", false),
+        ]
+    );
+}
+
+#[test]
+fn constructs_defined_by_clips_code_are_reported() {
+    let (env, events) = env();
+    env.load_from_str("(defglobal ?*built* = (build \"(deftemplate built (slot x))\"))")
+        .unwrap();
+
+    assert_eq!(
+        take(&events),
+        [event(ConstructKind::Deftemplate, "built", "MAIN", false)]
+    );
+}
+
+#[test]
+fn failed_and_checked_constructs_are_not_reported() {
+    let (env, events) = env();
+    assert!(env.load_from_str("(defrule broken (x) => (").is_err());
+    env.load_from_str("(defglobal ?*ok* = (check-syntax \"(defrule checked =>)\"))")
+        .unwrap();
+
+    assert_eq!(take(&events), []);
+}
@@ -0,0 +1,67 @@
+use clips::Environment;
+
+const TEMPLATE: &str = "(deftemplate reading (slot value))";
+const RULE: &str = "(defrule high (reading (value ?v&:(> ?v 10))) => (assert (alarm)))";
+const GLOBAL: &str = "(defglobal ?*limit* = 10)";
+
+fn fingerprint(sources: &[&str]) -> u64 {
+    let env = Environment::new();
+    for source in sources {
+        env.load_from_str(*source).unwrap();
+    }
+    env.constructs_fingerprint().unwrap()
+}
+
+#[test]
+fn identical_sources_give_the_same_fingerprint() {
+    assert_eq!(
+        fingerprint(&[TEMPLATE, RULE, GLOBAL]),
+        fingerprint(&[TEMPLATE, RULE, GLOBAL])
+    );
+}
+
+#[test]
+fn the_load_order_does_not_matter() {
+    assert_eq!(
+        fingerprint(&[TEMPLATE, RULE, GLOBAL]),
+        fingerprint(&[GLOBAL, TEMPLATE, RULE])
+    );
+}
+
+#[test]
+fn changed_constructs_give_a_different_fingerprint() {
+    let original = fingerprint(&[TEMPLATE, RULE, GLOBAL]);
+
+    let changed_rule = "(defrule high (reading (value ?v&:(> ?v 20))) => (assert (alarm)))";
+    assert_ne!(original, fingerprint(&[TEMPLATE, changed_rule, GLOBAL]));
+    assert_ne!(original, fingerprint(&[TEMPLATE, RULE]));
+    assert_ne!(
+        original,
+        fingerprint(&[TEMPLATE, RULE, "(defglobal ?*limit* = 20)"])
+    );
+}
+
+#[test]
+fn redefining_a_rule_changes_the_fingerprint() {
+    let env = Environment::new();
+    env.load_from_str(TEMPLATE).unwrap();
+    env.load_from_str(RULE).unwrap();
+    let before = env.constructs_fingerprint().unwrap();
+
+    env.load_from_str("(defrule high (reading (value ?v&:(> ?v 20))) => (assert (alarm)))")
+        .unwrap();
+    assert_ne!(before, env.constructs_fingerprint().unwrap());
+
+    env.load_from_str(RULE).unwrap();
+    assert_eq!(before, env.constructs_fingerprint().unwrap());
+}
+
+#[test]
+fn working_memory_does_not_change_the_fingerprint() {
+    let env = Environment::new();
+    env.load_from_str(TEMPLATE).unwrap();
+    let before = env.constructs_fingerprint().unwrap();
+
+    env.assert_string("(reading (value 5))").unwrap();
+    assert_eq!(before, env.constructs_fingerprint().unwrap());
+}
@@ -0,0 +1,99 @@
+#![cfg(feature = "csv")]
+
+use clips::{CLIPSValue, CsvMapping, CsvValueKind, Environment};
+
+fn env() -> Environment {
+    let env = Environment::new();
+    env.load_from_str(
+        "(deftemplate product (slot sku) (slot name) (slot price) (slot stock) (slot active) (multislot tags))",
+    )
+    .unwrap();
+    env
+}
+
+fn mapping() -> CsvMapping {
+    CsvMapping::new()
+        .column("sku", "sku", CsvValueKind::Symbol)
+        .column("name", "name", CsvValueKind::String)
+        .column("price", "price", CsvValueKind::Float)
+        .column("stock", "stock", CsvValueKind::Int)
+        .column("active", "active", CsvValueKind::Bool)
+        .column(
+            "tags",
+            "tags",
+            CsvValueKind::Multifield {
+                delimiter: '|',
+                item: Box::new(CsvValueKind::Symbol),
+            },
+        )
+}
+
+#[test]
+fn cells_are_converted_to_their_slot_types() {
+    let env = env();
+    let data = "sku,name,price,stock,active,tags\n a-1 , Blue mug ,4.5,12,true,kitchen|gift\nb-2,Plate,3,0,0,\n";
+
+    let report = env
+        .assert_facts_from_csv("product", data.as_bytes(), mapping())
+        .unwrap();
+    assert_eq!(report.asserted, 2);
+    assert!(report.failures.is_empty());
+
+    let facts = env.find_all_facts("product", "TRUE").unwrap();
+    let mug = &facts[0];
+    assert_eq!(mug.slot("sku"), Some(&CLIPSValue::Symbol("a-1".into())));
+    assert_eq!(
+        mug.slot("name"),
+        Some(&CLIPSValue::String("Blue mug".into()))
+    );
+    assert_eq!(mug.slot("price"), Some(&CLIPSValue::Float(4.5)));
+    assert_eq!(mug.slot("stock"), Some(&CLIPSValue::Int(12)));
+    assert_eq!(mug.slot("active"), Some(&CLIPSValue::Bool(true)));
+    assert_eq!(
+        mug.slot("tags"),
+        Some(&CLIPSValue::Multifield(vec![
+            CLIPSValue::Symbol("kitchen".into()),
+            CLIPSValue::Symbol("gift".into()),
+        ]))
+    );
+
+    let plate = &facts[1];
+    assert_eq!(plate.slot("active"), Some(&CLIPSValue::Bool(false)));
+    assert_eq!(plate.slot("tags"), Some(&CLIPSValue::Multifield(vec![])));
+}
+
+#[test]
+fn rows_that_fail_are_reported_with_their_lines() {
+    let env = env();
+    let data = "sku,name,price,stock,active,tags
+a-1,Mug,4.5,12,true,kitchen
+b-2,Plate,cheap,1,false,
+c-3,Bowl,2.0,3
+d-4,Cup,1.0,5,maybe,
+e-5,Jug,7.25,2,false,kitchen
+";
+
+    let report = env
+        .assert_facts_from_csv("product", data.as_bytes(), mapping())
+        .unwrap();
+    assert_eq!(report.asserted, 2);
+    assert_eq!(
+        report
+            .failures
+            .iter()
+            .map(|failure| failure.line)
+            .collect::<Vec<_>>(),
+        vec![3, 4, 5]
+    );
+    assert_eq!(env.find_all_facts("product", "TRUE").unwrap().len(), 2);
+}
+
+#[test]
+fn a_missing_column_fails_the_whole_import() {
+    let env = env();
+    let mapping = CsvMapping::new().column("colour", "name", CsvValueKind::String);
+
+    assert!(env
+        .assert_facts_from_csv("product", "sku\na-1\n".as_bytes(), mapping)
+        .is_err());
+}
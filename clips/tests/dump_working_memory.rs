@@ -0,0 +1,102 @@
+use std::fs;
+
+use clips::{DumpStats, Environment, RetrievedFact, RetrievedInstance};
+
+const CONSTRUCTS: &str = "
+    (deftemplate note (slot text) (slot weight) (multislot tags))
+    (defclass point (is-a USER) (slot x) (slot label) (multislot path))";
+
+fn env() -> Environment {
+    let env = Environment::new();
+    env.load_from_str(CONSTRUCTS).unwrap();
+    env
+}
+
+// Fact indexes change when the dump is replayed, so only the contents are compared.
+fn facts(env: &Environment, template: &str) -> Vec<(String, Vec<(String, String)>)> {
+    let mut facts: Vec<_> = env
+        .find_all_facts(template, "TRUE")
+        .unwrap()
+        .into_iter()
+        .map(
+            |RetrievedFact {
+                 template, slots, ..
+             }| {
+                let slots = slots
+                    .into_iter()
+                    .map(|(slot, value)| (slot, format!("{value:?}")))
+                    .collect();
+                (template, slots)
+            },
+        )
+        .collect();
+    facts.sort();
+    facts
+}
+
+fn instances(env: &Environment) -> Vec<RetrievedInstance> {
+    let mut instances = env.find_all_instances("point", "TRUE").unwrap();
+    instances.sort_by(|a, b| a.name.cmp(&b.name));
+    instances
+}
+
+fn replay(dump: &[u8]) -> Environment {
+    let path = std::env::temp_dir().join(format!(
+        "clips-rs-test-dump-working-memory-{}",
+        std::process::id()
+    ));
+    fs::write(&path, dump).unwrap();
+
+    let env = env();
+    let res = env.batch_star(path.clone());
+    fs::remove_file(path).unwrap();
+    res.unwrap();
+
+    env
+}
+
+#[test]
+fn a_dump_replays_into_the_same_working_memory() {
+    let source = env();
+    source
+        .assert_string(r#"(note (text "say \"hi\" \\ bye") (weight 1.5) (tags a "b c" 3))"#)
+        .unwrap();
+    source
+        .assert_string("(note (text \"\") (weight -2))")
+        .unwrap();
+    source.assert_string("(reading 1 2.5 x \"y z\")").unwrap();
+    source.assert_string("(empty)").unwrap();
+    source
+        .load_from_str(
+            "(defglobal ?*made* = (progn
+               (make-instance a of point (x 1) (label \"first\") (path 1 2 3))
+               (make-instance b of point (x a) (label second))))",
+        )
+        .unwrap();
+
+    let mut dump = Vec::new();
+    let stats = source.dump_working_memory(&mut dump).unwrap();
+    assert_eq!(
+        stats,
+        DumpStats {
+            facts: 4,
+            instances: 2
+        }
+    );
+    assert_eq!(String::from_utf8(dump.clone()).unwrap().lines().count(), 6);
+
+    let target = replay(&dump);
+    for template in ["note", "reading", "empty"] {
+        assert_eq!(facts(&target, template), facts(&source, template));
+    }
+    assert_eq!(instances(&target), instances(&source));
+}
+
+#[test]
+fn an_empty_working_memory_dumps_nothing() {
+    let mut dump = Vec::new();
+    let stats = env().dump_working_memory(&mut dump).unwrap();
+
+    assert_eq!(stats, DumpStats::default());
+    assert!(dump.is_empty());
+}
@@ -0,0 +1,102 @@
+use std::collections::HashMap;
+
+use clips::{CLIPSError, CLIPSValue, Environment, RetrievedFact};
+
+fn env() -> Environment {
+    let env = Environment::new();
+    env.load_from_str(
+        "(deftemplate scenario (slot name) (slot speed (default 10)) (multislot tags))",
+    )
+    .unwrap();
+    env
+}
+
+fn fact(env: &Environment, template: &str, index: i64) -> RetrievedFact {
+    env.find_all_facts(template, &format!("(= (fact-index ?f) {index})"))
+        .unwrap()
+        .remove(0)
+}
+
+fn symbol(value: &str) -> CLIPSValue {
+    CLIPSValue::Symbol(value.to_string())
+}
+
+#[test]
+fn the_copy_has_the_overrides_and_keeps_the_rest() {
+    let env = env();
+    let base = env
+        .assert_string("(scenario (name base) (speed 20) (tags fast wet))")
+        .unwrap();
+
+    let copy = env
+        .duplicate_fact(
+            base,
+            HashMap::from([("name".to_string(), symbol("variant"))]),
+        )
+        .unwrap();
+
+    assert_ne!(copy, base);
+    let copy = fact(&env, "scenario", copy);
+    assert_eq!(copy.slot("name"), Some(&symbol("variant")));
+    assert_eq!(copy.slot("speed"), Some(&CLIPSValue::Int(20)));
+    assert_eq!(
+        copy.slot("tags"),
+        Some(&CLIPSValue::Multifield(vec![symbol("fast"), symbol("wet")]))
+    );
+
+    // The original is left alone.
+    assert_eq!(
+        fact(&env, "scenario", base).slot("name"),
+        Some(&symbol("base"))
+    );
+}
+
+#[test]
+fn ordered_facts_are_copied_through_their_implied_slot() {
+    let env = env();
+    let base = env.assert_string("(numbers 1 2 3)").unwrap();
+
+    let copy = env
+        .duplicate_fact(
+            base,
+            HashMap::from([(
+                "implied".to_string(),
+                CLIPSValue::Multifield(vec![CLIPSValue::Int(4), CLIPSValue::Int(5)]),
+            )]),
+        )
+        .unwrap();
+
+    assert_eq!(
+        fact(&env, "numbers", copy).slot("implied"),
+        Some(&CLIPSValue::Multifield(vec![
+            CLIPSValue::Int(4),
+            CLIPSValue::Int(5)
+        ]))
+    );
+    assert_eq!(env.find_all_facts("numbers", "TRUE").unwrap().len(), 2);
+}
+
+#[test]
+fn a_copy_without_changes_is_the_original_fact() {
+    let env = env();
+    let base = env.assert_string("(scenario (name base))").unwrap();
+
+    assert_eq!(env.duplicate_fact(base, HashMap::new()).unwrap(), base);
+    assert_eq!(env.find_all_facts("scenario", "TRUE").unwrap().len(), 1);
+}
+
+#[test]
+fn unknown_facts_and_slots_are_rejected() {
+    let env = env();
+    let base = env.assert_string("(scenario (name base))").unwrap();
+
+    assert!(matches!(
+        env.duplicate_fact(base, HashMap::from([("colour".to_string(), symbol("red"))])),
+        Err(CLIPSError::SlotNotFound)
+    ));
+    assert!(matches!(
+        env.duplicate_fact(base + 100, HashMap::new()),
+        Err(CLIPSError::FactNotFound)
+    ));
+    assert_eq!(env.find_all_facts("scenario", "TRUE").unwrap().len(), 1);
+}
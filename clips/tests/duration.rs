@@ -0,0 +1,49 @@
+#![cfg(feature = "duration")]
+
+use std::{sync::mpsc, time::Duration};
+
+use clips::{CLIPSEnvironment, CLIPSValue, UDFType};
+
+#[test]
+fn durations_are_milliseconds() {
+    let mut env = CLIPSEnvironment::new().unwrap();
+    let (tx, rx) = mpsc::channel();
+
+    env.add_udf(
+        "timeout",
+        UDFType::Integer,
+        0,
+        0,
+        vec![],
+        Box::new(|mut data| {
+            data.set_result(Duration::from_millis(1500)).unwrap();
+        }),
+    )
+    .unwrap();
+    env.add_udf(
+        "wait",
+        UDFType::Boolean,
+        1,
+        1,
+        vec![UDFType::Integer],
+        Box::new(move |mut data| {
+            tx.send(data.first_arg::<Duration>().ok()).unwrap();
+            data.set_boolean_result(true);
+        }),
+    )
+    .unwrap();
+
+    env.load_from_str(
+        "(defglobal ?*timeout* = (timeout))
+         (defglobal ?*a* = (wait 250))
+         (defglobal ?*b* = (wait -1))",
+    )
+    .unwrap();
+
+    assert_eq!(
+        env.retrieve_globals_values().unwrap()["MAIN"]["timeout"],
+        CLIPSValue::Int(1500)
+    );
+    assert_eq!(rx.recv().unwrap(), Some(Duration::from_millis(250)));
+    assert_eq!(rx.recv().unwrap(), None);
+}
@@ -0,0 +1,26 @@
+use clips::{CLIPSError, Environment, EnvironmentFailure};
+
+#[test]
+fn exit_from_a_rule_stops_the_environment_without_ending_the_process() {
+    let env = Environment::new();
+    env.load_from_str("(defrule leave (go) => (exit 3))")
+        .unwrap();
+    env.assert_string("(go)").unwrap();
+
+    // Whatever `run()` reports, it has to come back instead of taking the process down with it.
+    let _ = env.run();
+
+    assert!(matches!(
+        env.assert_string("(more)"),
+        Err(CLIPSError::EnvironmentExited { code: 3 })
+    ));
+    assert!(matches!(
+        env.run(),
+        Err(CLIPSError::EnvironmentExited { code: 3 })
+    ));
+    assert!(!env.is_alive());
+    assert_eq!(
+        env.failure_reason(),
+        Some(EnvironmentFailure::Exited { code: 3 })
+    );
+}
@@ -0,0 +1,65 @@
+use clips::{CLIPSValue, Environment, SlotMap};
+
+fn env() -> Environment {
+    let env = Environment::new();
+    env.load_from_str("(deftemplate item (slot id (type INTEGER)))")
+        .unwrap();
+    env
+}
+
+fn item(id: i64) -> SlotMap {
+    SlotMap::new("item").slot("id", id)
+}
+
+fn item_count(env: &Environment) -> usize {
+    env.find_all_facts("item", "TRUE").unwrap().len()
+}
+
+fn fact_duplication(env: &Environment) -> CLIPSValue {
+    env.load_from_str("(defglobal ?*duplication* = (get-fact-duplication))")
+        .unwrap();
+    env.retrieve_globals_values().unwrap()["MAIN"]["duplication"].clone()
+}
+
+#[test]
+fn duplicates_are_only_allowed_for_the_call_that_asks() {
+    let env = env();
+    env.assert_fact(item(1), None).unwrap();
+
+    env.assert_fact_allow_duplicate(item(1), None, false)
+        .unwrap();
+    assert_eq!(item_count(&env), 1);
+
+    env.assert_fact_allow_duplicate(item(1), None, true)
+        .unwrap();
+    assert_eq!(item_count(&env), 2);
+
+    // Plain asserts still follow the environment's setting.
+    env.assert_fact(item(1), None).unwrap();
+    assert_eq!(item_count(&env), 2);
+    assert_eq!(fact_duplication(&env), CLIPSValue::Bool(false));
+}
+
+#[test]
+fn the_setting_is_restored_when_the_assert_fails() {
+    let env = env();
+
+    assert!(env
+        .assert_fact_allow_duplicate(item(1).slot("name", 1), None, true)
+        .is_err());
+    assert_eq!(fact_duplication(&env), CLIPSValue::Bool(false));
+    assert_eq!(item_count(&env), 0);
+}
+
+#[test]
+fn duplicates_can_be_refused_when_duplication_is_on() {
+    let env = env();
+    env.load_from_str("(defglobal ?*previous* = (set-fact-duplication TRUE))")
+        .unwrap();
+    env.assert_fact(item(1), None).unwrap();
+
+    env.assert_fact_allow_duplicate(item(1), None, false)
+        .unwrap();
+    assert_eq!(item_count(&env), 1);
+    assert_eq!(fact_duplication(&env), CLIPSValue::Bool(true));
+}
@@ -0,0 +1,72 @@
+use clips::{CLIPSValue, Environment, FactGraph, SlotMap};
+
+fn env() -> Environment {
+    let env = Environment::new();
+    env.load_from_str(
+        "
+        (deftemplate order (slot id) (slot customer))
+        (deftemplate item (slot order-id) (slot sku))",
+    )
+    .unwrap();
+    env
+}
+
+// The second item has a slot its template doesn't have.
+fn graph() -> FactGraph {
+    FactGraph::new("id")
+        .fact(SlotMap::new("order").slot("customer", "alice"))
+        .fact_linked_by(SlotMap::new("item").slot("sku", "a-1"), "order-id")
+        .fact_linked_by(SlotMap::new("item").slot("colour", "red"), "order-id")
+}
+
+fn fact_count(env: &Environment) -> usize {
+    env.find_all_facts("order", "TRUE").unwrap().len()
+        + env.find_all_facts("item", "TRUE").unwrap().len()
+}
+
+#[test]
+fn failures_are_reported_per_fact() {
+    let env = env();
+
+    let report = env.assert_fact_graph(graph(), None, false).unwrap();
+    assert!(!report.is_complete());
+    assert!(!report.rolled_back);
+    assert!(report.results[0].is_ok());
+    assert!(report.results[1].is_ok());
+    assert!(report.results[2].is_err());
+
+    // The facts that could be asserted stay, linked by the generated symbol.
+    let link = CLIPSValue::Symbol(report.link.clone());
+    let orders = env.find_all_facts("order", "TRUE").unwrap();
+    let items = env.find_all_facts("item", "TRUE").unwrap();
+    assert_eq!(orders.len(), 1);
+    assert_eq!(items.len(), 1);
+    assert_eq!(orders[0].slot("id"), Some(&link));
+    assert_eq!(items[0].slot("order-id"), Some(&link));
+}
+
+#[test]
+fn a_failure_with_rollback_retracts_the_asserted_facts() {
+    let env = env();
+
+    let report = env.assert_fact_graph(graph(), None, true).unwrap();
+    assert!(report.rolled_back);
+    assert_eq!(
+        report.results.iter().map(Result::is_ok).collect::<Vec<_>>(),
+        vec![true, true, false]
+    );
+    assert_eq!(fact_count(&env), 0);
+}
+
+#[test]
+fn a_complete_graph_is_not_rolled_back() {
+    let env = env();
+    let graph = FactGraph::new("id")
+        .fact(SlotMap::new("order").slot("customer", "bob"))
+        .fact_linked_by(SlotMap::new("item").slot("sku", "b-2"), "order-id");
+
+    let report = env.assert_fact_graph(graph, None, true).unwrap();
+    assert!(report.is_complete());
+    assert!(!report.rolled_back);
+    assert_eq!(fact_count(&env), 2);
+}
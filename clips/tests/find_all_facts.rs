@@ -0,0 +1,64 @@
+use clips::{CLIPSError, CLIPSValue, Environment};
+
+fn env_with_people() -> Environment {
+    let env = Environment::new();
+    env.load_from_str(
+        "
+        (deftemplate person (slot name) (slot age))
+        (deftemplate pet (slot name) (slot age))
+        (defglobal ?*asserted* = (fact-index (assert
+          (person (name alice) (age 34))
+          (person (name bob) (age 19))
+          (pet (name rex) (age 40))
+          (person (name carol) (age 52)))))
+        ",
+    )
+    .unwrap();
+    env
+}
+
+#[test]
+fn only_matching_facts_are_returned() {
+    let env = env_with_people();
+
+    let facts = env.find_all_facts("person", "(> ?f:age 30)").unwrap();
+
+    let found: Vec<_> = facts
+        .iter()
+        .map(|fact| {
+            (
+                fact.index,
+                fact.template.as_str(),
+                fact.slot("name").cloned(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        found,
+        vec![
+            (1, "person", Some(CLIPSValue::Symbol("alice".into()))),
+            (4, "person", Some(CLIPSValue::Symbol("carol".into()))),
+        ]
+    );
+    assert_eq!(facts[0].slot("age"), Some(&CLIPSValue::Int(34)));
+}
+
+#[test]
+fn no_matches_give_no_facts() {
+    let env = env_with_people();
+
+    assert!(env
+        .find_all_facts("person", "(> ?f:age 100)")
+        .unwrap()
+        .is_empty());
+}
+
+#[test]
+fn template_names_must_be_symbols() {
+    let env = env_with_people();
+
+    assert!(matches!(
+        env.find_all_facts("?person", "TRUE"),
+        Err(CLIPSError::InvalidQuery(_))
+    ));
+}
@@ -0,0 +1,55 @@
+use clips::{CLIPSValue, Environment, SlotMap};
+
+fn env() -> Environment {
+    let env = Environment::new();
+    env.load_from_str("(defclass point (is-a USER) (slot x) (slot label))")
+        .unwrap();
+    env
+}
+
+fn names(env: &Environment, query: &str) -> Vec<String> {
+    let mut names: Vec<_> = env
+        .find_all_instances("point", query)
+        .unwrap()
+        .into_iter()
+        .map(|instance| instance.name)
+        .collect();
+    names.sort();
+    names
+}
+
+#[test]
+fn instances_matching_the_query_are_found() {
+    let env = env();
+    for (name, x) in [("a", 1i64), ("b", 2), ("c", 3)] {
+        env.make_instance(
+            SlotMap::new("point").slot("x", x),
+            Some(name.to_string()),
+            None,
+        )
+        .unwrap();
+    }
+
+    assert_eq!(names(&env, "TRUE"), ["a", "b", "c"]);
+    assert_eq!(names(&env, "(> ?ins:x 1)"), ["b", "c"]);
+    assert!(names(&env, "(> ?ins:x 3)").is_empty());
+
+    let found = env.find_all_instances("point", "(= ?ins:x 2)").unwrap();
+    assert_eq!(found[0].slot("x"), Some(&CLIPSValue::Int(2)));
+}
+
+#[test]
+fn instances_named_like_a_symbol_are_found() {
+    let env = env();
+    // `red` and `blue` exist as symbols before they name any instance.
+    env.make_instance(
+        SlotMap::new("point").slot("label", CLIPSValue::Symbol("red".to_string())),
+        Some("blue".to_string()),
+        None,
+    )
+    .unwrap();
+    env.load_from_str("(defglobal ?*made* = (make-instance red of point (label blue)))")
+        .unwrap();
+
+    assert_eq!(names(&env, "TRUE"), ["blue", "red"]);
+}
@@ -0,0 +1,65 @@
+use std::collections::HashMap;
+
+use clips::{flatten_globals, unflatten_globals, CLIPSError, CLIPSValue, Environment};
+
+fn env() -> Environment {
+    let env = Environment::new();
+    env.load_from_str(
+        "(defglobal ?*x* = 1 ?*name* = \"main\")
+         (defmodule OTHER)
+         (defglobal OTHER ?*x* = 2.5 ?*tags* = (create$ a b))",
+    )
+    .unwrap();
+    env
+}
+
+#[test]
+fn two_modules_round_trip_through_a_flat_map() {
+    let globals = env().retrieve_globals_values().unwrap();
+
+    let flat = flatten_globals(&globals);
+    assert_eq!(
+        flat,
+        HashMap::from([
+            ("MAIN::x".to_string(), CLIPSValue::Int(1)),
+            (
+                "MAIN::name".to_string(),
+                CLIPSValue::String("main".to_string())
+            ),
+            ("OTHER::x".to_string(), CLIPSValue::Float(2.5)),
+            (
+                "OTHER::tags".to_string(),
+                CLIPSValue::Multifield(vec![
+                    CLIPSValue::Symbol("a".to_string()),
+                    CLIPSValue::Symbol("b".to_string()),
+                ])
+            ),
+        ])
+    );
+
+    assert_eq!(unflatten_globals(flat).unwrap(), globals);
+}
+
+#[test]
+fn a_flat_map_can_be_restored() {
+    let env = env();
+    let mut flat = flatten_globals(&env.retrieve_globals_values().unwrap());
+    flat.insert("OTHER::x".to_string(), CLIPSValue::Int(7));
+
+    env.restore_globals(unflatten_globals(flat).unwrap())
+        .unwrap();
+    assert_eq!(
+        env.retrieve_globals_values().unwrap()["OTHER"]["x"],
+        CLIPSValue::Int(7)
+    );
+}
+
+#[test]
+fn keys_without_a_module_are_rejected() {
+    let flat = HashMap::from([("x".to_string(), CLIPSValue::Int(1))]);
+
+    assert!(matches!(
+        unflatten_globals(flat),
+        Err(CLIPSError::UnqualifiedGlobalName(name)) if name == "x"
+    ));
+}
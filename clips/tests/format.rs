@@ -0,0 +1,79 @@
+use clips::{CLIPSValue, Environment};
+
+// Each case is a format string and the arguments, written once for Rust and once for CLIPS.
+fn cases() -> Vec<(&'static str, Vec<CLIPSValue>, &'static str)> {
+    vec![
+        ("%f", vec![CLIPSValue::Float(1.23456)], "1.23456"),
+        ("%8.3f|", vec![CLIPSValue::Float(2.0 / 3.0)], "(/ 2.0 3.0)"),
+        (
+            "%e %g",
+            vec![CLIPSValue::Float(1e20), CLIPSValue::Float(1e20)],
+            "1e20 1e20",
+        ),
+        (
+            "[%5d] [%-5d] [%05d]",
+            vec![CLIPSValue::Int(42); 3],
+            "42 42 42",
+        ),
+        (
+            "%s and %s%n",
+            vec![
+                CLIPSValue::String("a \"quoted\" string".into()),
+                CLIPSValue::Symbol("sym".into()),
+            ],
+            "\"a \\\"quoted\\\" string\" sym",
+        ),
+        ("100%% of %d", vec![CLIPSValue::Int(-7)], "-7"),
+    ]
+}
+
+#[test]
+fn rust_and_rule_formatting_match() {
+    let env = Environment::new();
+    env.load_from_str("(deftemplate formatted (slot id) (slot text))")
+        .unwrap();
+
+    for (id, (fmt, args, clips_args)) in cases().into_iter().enumerate() {
+        let escaped_fmt = fmt.replace('\\', "\\\\").replace('"', "\\\"");
+        env.load_from_str(format!(
+            "(defrule format-{id} => (assert (formatted (id {id}) (text (format nil \"{escaped_fmt}\" {clips_args})))))"
+        ))
+        .unwrap();
+        env.run().unwrap();
+
+        let from_rule = env
+            .find_all_facts("formatted", &format!("(= ?f:id {id})"))
+            .unwrap()[0]
+            .slot("text")
+            .unwrap()
+            .clone();
+        let from_rust = env.format(fmt, args).unwrap();
+
+        assert_eq!(CLIPSValue::String(from_rust), from_rule, "{fmt}");
+    }
+}
+
+#[test]
+fn formatted_text_is_what_clips_prints() {
+    let env = Environment::new();
+
+    assert_eq!(
+        env.format(
+            "%d items at %.2f each",
+            vec![CLIPSValue::Int(3), CLIPSValue::Float(0.5)]
+        )
+        .unwrap(),
+        "3 items at 0.50 each"
+    );
+    assert_eq!(
+        env.format("%s", vec![CLIPSValue::String("no \"escaping\"".into())])
+            .unwrap(),
+        "no \"escaping\""
+    );
+}
+
+#[test]
+fn missing_arguments_are_an_error() {
+    let env = Environment::new();
+    assert!(env.format("%d and %d", vec![CLIPSValue::Int(1)]).is_err());
+}
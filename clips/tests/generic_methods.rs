@@ -0,0 +1,84 @@
+use clips::{CLIPSEnvironment, CLIPSError, CLIPSValue};
+
+fn rust_method_udfs(env: &CLIPSEnvironment) -> usize {
+    env.list_udfs()
+        .iter()
+        .filter(|signature| signature.name.starts_with("rust-generic-method-"))
+        .count()
+}
+
+#[test]
+fn clips_dispatches_on_the_restrictions() {
+    let mut env = CLIPSEnvironment::new().unwrap();
+    env.add_generic_method(
+        "describe",
+        &["INTEGER"],
+        Box::new(|mut data| data.set_result(CLIPSValue::Int(1)).unwrap()),
+    )
+    .unwrap();
+    env.add_generic_method(
+        "describe",
+        &["STRING"],
+        Box::new(|mut data| data.set_result(CLIPSValue::Int(2)).unwrap()),
+    )
+    .unwrap();
+
+    env.load_from_str("(defglobal ?*int* = (describe 5) ?*string* = (describe \"x\"))")
+        .unwrap();
+
+    let globals = env.retrieve_globals_values().unwrap();
+    assert_eq!(globals["MAIN"]["int"], CLIPSValue::Int(1));
+    assert_eq!(globals["MAIN"]["string"], CLIPSValue::Int(2));
+}
+
+#[test]
+fn generic_name_must_be_a_single_symbol() {
+    let mut env = CLIPSEnvironment::new().unwrap();
+
+    let res = env.add_generic_method::<&str>(
+        "describe () (+ 1 2)) (defglobal ?*injected* = 1) (defmethod other",
+        &[],
+        Box::new(|_| {}),
+    );
+
+    assert!(matches!(res, Err(CLIPSError::InvalidQuery(_))));
+    assert_eq!(rust_method_udfs(&env), 0);
+}
+
+#[test]
+fn restrictions_can_only_hold_types_or_classes() {
+    let mut env = CLIPSEnvironment::new().unwrap();
+
+    for restriction in ["INTEGER (> ?arg0 1)", "?x", "INTEGER&FLOAT"] {
+        let res = env.add_generic_method("describe", &[restriction], Box::new(|_| {}));
+
+        assert!(
+            matches!(res, Err(CLIPSError::InvalidUDFArgumentTypes(ref r)) if r == restriction),
+            "{:?} was accepted",
+            restriction
+        );
+    }
+    assert_eq!(rust_method_udfs(&env), 0);
+}
+
+#[test]
+fn replacing_a_method_removes_its_udf() {
+    let mut env = CLIPSEnvironment::new().unwrap();
+    for value in [1, 2] {
+        env.add_generic_method(
+            "describe",
+            &["INTEGER"],
+            Box::new(move |mut data| data.set_result(CLIPSValue::Int(value)).unwrap()),
+        )
+        .unwrap();
+    }
+
+    env.load_from_str("(defglobal ?*int* = (describe 5))")
+        .unwrap();
+
+    assert_eq!(
+        env.retrieve_globals_values().unwrap()["MAIN"]["int"],
+        CLIPSValue::Int(2)
+    );
+    assert_eq!(rust_method_udfs(&env), 1);
+}
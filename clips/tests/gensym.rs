@@ -0,0 +1,70 @@
+use std::collections::HashSet;
+
+use clips::{CLIPSValue, Environment};
+
+fn setgen(env: &Environment, n: i64) {
+    env.load_from_str(format!("(defglobal ?*setgen* = (setgen {n}))"))
+        .unwrap();
+}
+
+#[test]
+fn generated_symbols_are_unique() {
+    let env = Environment::new();
+
+    let symbols: HashSet<_> = (0..100).map(|_| env.gensym_star().unwrap()).collect();
+    assert_eq!(symbols.len(), 100);
+}
+
+#[test]
+fn symbols_from_rules_and_from_rust_do_not_clash() {
+    let env = Environment::new();
+    env.load_from_str(
+        "(deftemplate id (slot value))
+         (deftemplate request)
+         (defrule make-id
+           ?request <- (request)
+           =>
+           (retract ?request)
+           (assert (id (value (gensym*)))))",
+    )
+    .unwrap();
+
+    let mut symbols = Vec::new();
+    for _ in 0..10 {
+        symbols.push(env.gensym_star().unwrap());
+        env.assert_string("(request)").unwrap();
+        env.run().unwrap();
+        symbols.push(env.gensym().unwrap());
+    }
+    for fact in env.find_all_facts("id", "TRUE").unwrap() {
+        match fact.slot("value") {
+            Some(CLIPSValue::Symbol(symbol)) => symbols.push(symbol.clone()),
+            value => panic!("unexpected value {value:?}"),
+        }
+    }
+
+    assert_eq!(symbols.len(), 30);
+    assert_eq!(symbols.iter().collect::<HashSet<_>>().len(), 30);
+}
+
+#[test]
+fn gensym_and_gensym_star_share_the_sequence() {
+    let env = Environment::new();
+    setgen(&env, 10);
+
+    assert_eq!(env.gensym().unwrap(), "gen10");
+    assert_eq!(env.gensym_star().unwrap(), "gen11");
+    assert_eq!(env.gensym().unwrap(), "gen12");
+}
+
+#[test]
+fn only_gensym_star_skips_symbols_in_use() {
+    let env = Environment::new();
+    env.assert_string("(taken gen20 gen21)").unwrap();
+
+    setgen(&env, 20);
+    assert_eq!(env.gensym().unwrap(), "gen20");
+
+    setgen(&env, 20);
+    assert_eq!(env.gensym_star().unwrap(), "gen22");
+}
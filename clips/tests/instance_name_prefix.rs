@@ -0,0 +1,25 @@
+use clips::{CLIPSValue, Environment, SlotMap};
+
+// `instance-name-to-symbol` makes a symbol with the same text as the existing instance's name, which mustn't hide the instance.
+#[test]
+fn generated_names_skip_existing_instances() {
+    let env = Environment::new();
+    env.load_from_str(
+        "(defclass item (is-a USER) (slot size))
+         (defglobal ?*first* = (instance-name-to-symbol (make-instance item-1 of item (size 10))))",
+    )
+    .unwrap();
+    env.set_instance_name_prefix("item-").unwrap();
+
+    env.make_instance(SlotMap::new("item").slot("size", 20), None, None)
+        .unwrap();
+
+    env.load_from_str(
+        "(defglobal ?*sizes* = (create$ (send [item-1] get-size) (send [item-2] get-size)))",
+    )
+    .unwrap();
+    assert_eq!(
+        env.retrieve_globals_values().unwrap()["MAIN"]["sizes"],
+        CLIPSValue::Multifield(vec![CLIPSValue::Int(10), CLIPSValue::Int(20)])
+    );
+}
@@ -0,0 +1,182 @@
+#![cfg(feature = "json")]
+
+use clips::{CLIPSError, CLIPSValue, Environment, RetrievedInstance, SlotMap};
+use serde_json::{json, Value};
+
+const CLASSES: &str = "
+    (defclass point (is-a USER)
+      (slot x)
+      (slot label (type STRING))
+      (multislot tags))
+    (defclass named-point (is-a point)
+      (slot title (type SYMBOL)))";
+
+fn env() -> Environment {
+    let env = Environment::new();
+    env.load_from_str(CLASSES).unwrap();
+    env
+}
+
+fn fill(env: &Environment) {
+    env.make_instance(
+        SlotMap::new("point")
+            .slot("x", 1)
+            .slot("label", CLIPSValue::String("first".into()))
+            .slot(
+                "tags",
+                CLIPSValue::Multifield(vec![
+                    CLIPSValue::Symbol("red".into()),
+                    CLIPSValue::Float(2.5),
+                ]),
+            ),
+        Some("p1".into()),
+        None,
+    )
+    .unwrap();
+    env.make_instance(
+        SlotMap::new("named-point").slot("x", 2),
+        Some("p2".into()),
+        None,
+    )
+    .unwrap();
+}
+
+fn export(env: &Environment) -> (usize, String) {
+    let mut out = Vec::new();
+    let written = env.export_instances_jsonl(&mut out).unwrap();
+    (written, String::from_utf8(out).unwrap())
+}
+
+#[test]
+fn each_instance_is_a_json_line() {
+    let env = env();
+    fill(&env);
+
+    let (written, text) = export(&env);
+    assert_eq!(written, 2);
+
+    let mut lines: Vec<Value> = text
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    lines.sort_by_key(|line| line["name"].as_str().unwrap().to_string());
+
+    assert_eq!(
+        lines,
+        vec![
+            json!({
+                "name": "p1",
+                "class": "point",
+                "slots": {
+                    "x": { "Int": 1 },
+                    "label": { "String": "first" },
+                    "tags": { "Multifield": [{ "Symbol": "red" }, { "Float": 2.5 }] },
+                },
+            }),
+            // Inherited slots are included.
+            json!({
+                "name": "p2",
+                "class": "named-point",
+                "slots": {
+                    "x": { "Int": 2 },
+                    "label": { "String": "" },
+                    "tags": { "Multifield": [] },
+                    "title": { "Symbol": "nil" },
+                },
+            }),
+        ]
+    );
+}
+
+#[test]
+fn an_empty_store_writes_nothing() {
+    let (written, text) = export(&env());
+    assert_eq!(written, 0);
+    assert_eq!(text, "");
+}
+
+#[test]
+fn large_stores_are_exported_completely() {
+    let env = env();
+    for x in 0..2000 {
+        env.make_instance(
+            SlotMap::new("point").slot("x", x),
+            Some(format!("p{x}")),
+            None,
+        )
+        .unwrap();
+    }
+
+    let (written, text) = export(&env);
+    assert_eq!(written, 2000);
+    assert_eq!(text.lines().count(), 2000);
+}
+
+fn instances(env: &Environment) -> Vec<RetrievedInstance> {
+    let mut instances = env.find_all_instances("point", "TRUE").unwrap();
+    instances.sort_by(|a, b| a.name.cmp(&b.name));
+    instances
+}
+
+#[test]
+fn exported_instances_can_be_imported_again() {
+    let env = env();
+    for x in 0..20 {
+        env.make_instance(
+            SlotMap::new("point")
+                .slot("x", x)
+                .slot("label", CLIPSValue::String(format!("point {x}")))
+                .slot(
+                    "tags",
+                    CLIPSValue::Multifield(vec![CLIPSValue::Float(x as f64 / 2.0)]),
+                ),
+            Some(format!("p{x}")),
+            None,
+        )
+        .unwrap();
+    }
+    let (_, text) = export(&env);
+
+    let copy = self::env();
+    assert_eq!(copy.import_instances_jsonl(text.as_bytes()).unwrap(), 20);
+
+    assert_eq!(instances(&copy).len(), 20);
+    assert_eq!(instances(&copy), instances(&env));
+}
+
+#[test]
+fn malformed_lines_are_reported_with_their_number() {
+    let env = env();
+    let text = r#"{"name":"p1","class":"point","slots":{"x":{"Int":1}}}
+
+{"name":"p2","class":"point","slots":{"x":{"Int":2}}}
+{"name":"p3","class":"point"}
+{"name":"p4","class":"point","slots":{"x":{"Int":4}}}
+"#;
+
+    match env.import_instances_jsonl(text.as_bytes()) {
+        Err(CLIPSError::JsonLineImport { line, .. }) => assert_eq!(line, 4),
+        other => panic!("unexpected result {other:?}"),
+    }
+
+    // The instances before the bad line are kept.
+    let names: Vec<_> = instances(&env)
+        .into_iter()
+        .map(|instance| instance.name)
+        .collect();
+    assert_eq!(names, ["p1", "p2"]);
+}
+
+#[test]
+fn instances_of_unknown_classes_are_reported() {
+    let env = env();
+    let text = r#"{"name":"s1","class":"shape","slots":{}}"#;
+
+    match env.import_instances_jsonl(text.as_bytes()) {
+        Err(CLIPSError::JsonLineImport { line, source }) => {
+            assert_eq!(line, 1);
+            assert!(matches!(*source, CLIPSError::ClassNotFound), "{source:?}");
+        }
+        other => panic!("unexpected result {other:?}"),
+    }
+}
@@ -0,0 +1,91 @@
+use std::sync::Mutex;
+
+use clips::{CLIPSValue, Environment, UDFType};
+use log::{Level, Log, Metadata, Record};
+
+// Only the `clips` target is kept, so records from anything else in the process don't get in the way.
+struct Collector(Mutex<Vec<(Level, String)>>);
+
+impl Log for Collector {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.target() == "clips"
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            self.0
+                .lock()
+                .unwrap()
+                .push((record.level(), record.args().to_string()));
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: Collector = Collector(Mutex::new(Vec::new()));
+
+#[test]
+fn rules_can_call_the_introspection_udfs() {
+    log::set_logger(&LOGGER).unwrap();
+    log::set_max_level(log::LevelFilter::Trace);
+
+    let env = Environment::new();
+    env.install_introspection_udfs().unwrap();
+    env.add_udf(
+        "noop".to_string(),
+        0,
+        0,
+        UDFType::Void,
+        vec![],
+        Box::new(|mut data| data.set_void()),
+    )
+    .unwrap();
+
+    env.load_from_str(
+        r#"
+        (defglobal ?*udfs* = (create$) ?*version* = "")
+        (defrule introspect
+          (go)
+          =>
+          (bind ?*udfs* (rust-udfs))
+          (bind ?*version* (rust-version))
+          (rust-log warn "checked the udfs")
+          (rust-log debug (str-cat "found " (length$ ?*udfs*))))"#,
+    )
+    .unwrap();
+    env.assert_string("(go)").unwrap();
+    assert_eq!(env.run().unwrap(), 1);
+
+    let globals = env.retrieve_globals_values().unwrap();
+    let CLIPSValue::Multifield(udfs) = &globals["MAIN"]["udfs"] else {
+        panic!("expected a multifield, got {:?}", globals["MAIN"]["udfs"]);
+    };
+    let mut udfs: Vec<_> = udfs.iter().cloned().collect();
+    udfs.sort_by_key(|udf| format!("{:?}", udf));
+    assert_eq!(
+        udfs,
+        ["noop", "rust-log", "rust-udfs", "rust-version"]
+            .map(|name| CLIPSValue::Symbol(name.to_string()))
+    );
+    assert_eq!(
+        globals["MAIN"]["version"],
+        CLIPSValue::String(env!("CARGO_PKG_VERSION").to_string())
+    );
+
+    assert_eq!(
+        *LOGGER.0.lock().unwrap(),
+        vec![
+            (Level::Warn, "checked the udfs".to_string()),
+            (Level::Debug, "found 4".to_string()),
+        ]
+    );
+
+    // An unknown level is an error in the rule, and nothing is logged.
+    LOGGER.0.lock().unwrap().clear();
+    env.load_from_str(r#"(defrule bad-level (bad) => (rust-log loud "ignored"))"#)
+        .unwrap();
+    env.assert_string("(bad)").unwrap();
+    env.run().unwrap();
+    assert!(LOGGER.0.lock().unwrap().is_empty());
+}
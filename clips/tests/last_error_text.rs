@@ -0,0 +1,61 @@
+use clips::Environment;
+
+#[test]
+fn there_is_no_error_text_before_anything_goes_wrong() {
+    let env = Environment::new();
+    env.load_from_str("(defglobal ?*x* = 1)").unwrap();
+
+    assert_eq!(env.last_error_text().unwrap(), None);
+    assert!(env.recent_error_texts().unwrap().is_empty());
+}
+
+#[test]
+fn the_last_error_is_read_back() {
+    let env = Environment::new();
+    assert!(env
+        .load_from_str("(defglobal ?*x* = (frobnicate))")
+        .is_err());
+
+    let text = env.last_error_text().unwrap().unwrap();
+    assert!(text.starts_with("[EXPRNPSR3]"), "{text}");
+    assert!(text.contains("frobnicate"), "{text}");
+}
+
+#[test]
+fn runtime_errors_are_kept_too() {
+    let env = Environment::new();
+    let _ = env.load_from_str("(defglobal ?*x* = (/ 1 0))");
+
+    let text = env.last_error_text().unwrap().unwrap();
+    assert!(text.contains("divide by zero"), "{text}");
+}
+
+#[test]
+fn older_messages_roll_over() {
+    let env = Environment::new();
+    for i in 0..20 {
+        assert!(env
+            .load_from_str(format!("(defglobal ?*x* = (missing-{i}))"))
+            .is_err());
+    }
+
+    let texts = env.recent_error_texts().unwrap();
+    assert_eq!(texts.len(), 16);
+    assert!(texts[0].contains("missing-4"), "{}", texts[0]);
+    assert!(texts[15].contains("missing-19"), "{}", texts[15]);
+    assert_eq!(env.last_error_text().unwrap().as_ref(), texts.last());
+}
+
+#[test]
+fn long_messages_are_cut_off() {
+    let env = Environment::new();
+    let name = "f".repeat(10_000);
+    assert!(env
+        .load_from_str(format!("(defglobal ?*x* = ({name}))"))
+        .is_err());
+
+    let text = env.last_error_text().unwrap().unwrap();
+    assert!(text.starts_with("[EXPRNPSR3]"), "{text}");
+    assert!(text.len() <= 4096);
+    assert!(text.ends_with('f'));
+}
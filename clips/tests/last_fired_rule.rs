@@ -0,0 +1,41 @@
+use clips::{CLIPSEnvironment, Environment};
+
+static CHAIN: &str = "
+    (defrule first (start) => (assert (second)))
+    (defrule second (second) => (assert (third)))
+    (defrule third (third) =>)
+    (defglobal ?*start* = (fact-index (assert (start))))
+    ";
+
+#[test]
+fn nothing_is_reported_before_a_rule_fires() {
+    let env = Environment::new();
+
+    assert_eq!(env.last_fired_rule().unwrap(), None);
+
+    env.run().unwrap();
+
+    assert_eq!(env.last_fired_rule().unwrap(), None);
+}
+
+// A later run that doesn't fire anything keeps the rule reported.
+#[test]
+fn the_last_rule_of_the_run_is_reported() {
+    let env = Environment::new();
+    env.load_from_str(CHAIN).unwrap();
+
+    env.run().unwrap();
+    env.run().unwrap();
+
+    assert_eq!(env.last_fired_rule().unwrap(), Some("third".to_string()));
+}
+
+#[test]
+fn runs_with_a_limit_are_tracked() {
+    let mut env = CLIPSEnvironment::new().unwrap();
+    env.load_from_str(CHAIN).unwrap();
+
+    env.run_limit(2).unwrap();
+
+    assert_eq!(env.last_fired_rule(), Some("second"));
+}
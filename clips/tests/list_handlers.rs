@@ -0,0 +1,53 @@
+use clips::{CLIPSError, Environment, MessageHandlerInfo};
+
+fn handler(
+    name: &str,
+    handler_type: &str,
+    min_args: u16,
+    max_args: Option<u16>,
+) -> MessageHandlerInfo {
+    MessageHandlerInfo {
+        name: name.to_string(),
+        handler_type: handler_type.to_string(),
+        min_args,
+        max_args,
+    }
+}
+
+#[test]
+fn primary_and_after_handlers_are_listed_with_their_arguments() {
+    let env = Environment::new();
+    env.load_from_str(
+        "
+        (defclass counter (is-a USER))
+        (defmessage-handler counter add primary (?amount ?times)
+          (* ?amount ?times))
+        (defmessage-handler counter add after (?amount ?times)
+          (printout t \"added\" crlf))
+        (defmessage-handler counter log primary (?level $?rest)
+          (printout t ?level crlf))
+        ",
+    )
+    .unwrap();
+
+    let handlers = env.list_handlers("counter").unwrap();
+
+    assert_eq!(
+        handlers,
+        vec![
+            handler("add", "primary", 2, Some(2)),
+            handler("add", "after", 2, Some(2)),
+            handler("log", "primary", 1, None),
+        ]
+    );
+}
+
+#[test]
+fn unknown_classes_are_reported() {
+    let env = Environment::new();
+
+    assert!(matches!(
+        env.list_handlers("counter"),
+        Err(CLIPSError::ClassNotFound)
+    ));
+}
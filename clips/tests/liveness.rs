@@ -0,0 +1,50 @@
+#![cfg(feature = "test-util")]
+
+use std::{thread, time::Duration};
+
+use clips::{CLIPSError, Environment, EnvironmentFailure};
+
+fn wait_for_failure(env: &Environment) -> EnvironmentFailure {
+    loop {
+        if let Some(failure) = env.failure_reason() {
+            return failure;
+        }
+        thread::sleep(Duration::from_millis(1));
+    }
+}
+
+#[test]
+fn poisoned_environment_is_reported_as_panicked() {
+    let env = Environment::new();
+    assert!(env.is_alive());
+    assert_eq!(env.failure_reason(), None);
+    env.ping(Duration::from_secs(5)).unwrap();
+
+    env.poison().unwrap();
+
+    assert_eq!(
+        wait_for_failure(&env),
+        EnvironmentFailure::Panicked {
+            message: Some("the environment was poisoned".to_string())
+        }
+    );
+    assert!(!env.is_alive());
+    assert!(matches!(
+        env.ping(Duration::from_secs(5)),
+        Err(CLIPSError::ThreadExited)
+    ));
+}
+
+#[test]
+fn clones_see_the_poisoned_environment() {
+    let env = Environment::new();
+    let clone = env.clone();
+
+    env.poison().unwrap();
+
+    assert!(matches!(
+        wait_for_failure(&clone),
+        EnvironmentFailure::Panicked { .. }
+    ));
+    assert!(!clone.is_alive());
+}
@@ -0,0 +1,106 @@
+use clips::{CLIPSError, CLIPSValue, Environment};
+
+const BAD_RULE: &str = "(defrule bad (a) => (frobnicate))";
+
+fn slot_names(env: &Environment, template: &str) -> Vec<String> {
+    env.template_info(template)
+        .unwrap()
+        .slots
+        .into_iter()
+        .map(|slot| slot.name)
+        .collect()
+}
+
+fn construct_names(env: &Environment) -> Vec<String> {
+    env.construct_summary().unwrap().construct_names
+}
+
+fn global(env: &Environment, name: &str) -> CLIPSValue {
+    env.retrieve_globals_values().unwrap()["MAIN"][name].clone()
+}
+
+#[test]
+fn new_constructs_are_undefined_on_failure() {
+    let env = Environment::new();
+    env.load_from_str("(deftemplate a (slot x))").unwrap();
+    let before = construct_names(&env);
+
+    let res = env.load_from_str_atomic(format!(
+        "(deftemplate b (slot z))\n(defrule uses-b (b) =>)\n{BAD_RULE}"
+    ));
+
+    assert!(matches!(
+        res,
+        Err(CLIPSError::LoadFromStringRolledBack {
+            diagnostic: Some(_)
+        })
+    ));
+    assert_eq!(construct_names(&env), before);
+}
+
+#[test]
+fn redefined_constructs_are_put_back_on_failure() {
+    let env = Environment::new();
+    env.load_from_str("(deftemplate a (slot x))").unwrap();
+    let before = construct_names(&env);
+
+    assert!(env
+        .load_from_str_atomic(format!("(deftemplate a (slot y))\n{BAD_RULE}"))
+        .is_err());
+
+    assert_eq!(construct_names(&env), before);
+    assert_eq!(slot_names(&env, "a"), ["x"]);
+}
+
+#[test]
+fn a_redefined_rule_using_a_redefined_template_is_put_back_too() {
+    let env = Environment::new();
+    env.load_from_str(
+        "(deftemplate a (slot x))
+         (deftemplate trigger)
+         (deftemplate fired)
+         (defrule r (trigger) => (assert (fired)))",
+    )
+    .unwrap();
+    let before = construct_names(&env);
+
+    assert!(env
+        .load_from_str_atomic(format!(
+            "(deftemplate a (slot y))\n(defrule r (a (y 1)) =>)\n{BAD_RULE}"
+        ))
+        .is_err());
+
+    assert_eq!(construct_names(&env), before);
+    assert_eq!(slot_names(&env, "a"), ["x"]);
+    env.assert_string("(a (x 1))").unwrap();
+    env.assert_string("(trigger)").unwrap();
+    assert_eq!(env.run().unwrap(), 1);
+    assert_eq!(env.find_all_facts("fired", "TRUE").unwrap().len(), 1);
+}
+
+#[test]
+fn global_values_are_put_back_on_failure() {
+    let env = Environment::new();
+    env.load_from_str("(defglobal ?*g* = 1)").unwrap();
+    env.load_from_str("(defglobal ?*set* = (bind ?*g* 5))")
+        .unwrap();
+
+    assert!(env
+        .load_from_str_atomic(format!("(defglobal ?*g* = 2 ?*new* = 3)\n{BAD_RULE}"))
+        .is_err());
+
+    assert_eq!(global(&env, "g"), CLIPSValue::Int(5));
+    assert!(!env.retrieve_globals_values().unwrap()["MAIN"].contains_key("new"));
+}
+
+#[test]
+fn a_successful_load_keeps_everything() {
+    let env = Environment::new();
+    env.load_from_str("(deftemplate a (slot x))").unwrap();
+
+    env.load_from_str_atomic("(deftemplate a (slot y))\n(defrule uses-a (a (y 1)) =>)")
+        .unwrap();
+
+    assert_eq!(slot_names(&env, "a"), ["y"]);
+    assert!(construct_names(&env).contains(&"defrule MAIN::uses-a".to_string()));
+}
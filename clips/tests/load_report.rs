@@ -0,0 +1,79 @@
+use std::{fs, time::Duration};
+
+use clips::Environment;
+
+const TEMPLATES: &str = "(deftemplate reading (slot sensor) (slot value))
+(deftemplate limit (slot sensor) (slot max))
+(deftemplate alarm (slot sensor))
+";
+
+// One construct per line, since that's how batch files are read.
+fn rules(count: usize) -> String {
+    (0..count)
+        .map(|rule| {
+            format!(
+                "(defrule check-{rule} \
+                   (reading (sensor {rule}) (value ?value)) \
+                   (limit (sensor {rule}) (max ?max&:(> ?value ?max))) \
+                   (not (alarm (sensor {rule}))) \
+                   => \
+                   (assert (alarm (sensor {rule}))))\n"
+            )
+        })
+        .collect()
+}
+
+fn ruleset(count: usize) -> String {
+    format!("{TEMPLATES}{}", rules(count))
+}
+
+#[test]
+fn loading_a_ruleset_reports_what_it_added() {
+    let env = Environment::new();
+
+    let report = env.load_from_str_with_report(ruleset(40)).unwrap();
+    assert!(report.duration > Duration::ZERO);
+    assert_eq!(report.rules_added, 40);
+    // Each rule has its own join for every pattern.
+    assert!(report.join_nodes_added >= 3 * 40, "{report:?}");
+}
+
+#[test]
+fn redefined_rules_add_nothing() {
+    let env = Environment::new();
+    env.load_from_str_with_report(ruleset(10)).unwrap();
+
+    let report = env.load_from_str_with_report(rules(10)).unwrap();
+    assert_eq!(report.rules_added, 0);
+    assert_eq!(report.join_nodes_added, 0);
+
+    let report = env.load_from_str_with_report(rules(15)).unwrap();
+    assert_eq!(report.rules_added, 5);
+}
+
+#[test]
+fn loading_without_rules_adds_no_joins() {
+    let env = Environment::new();
+
+    let report = env
+        .load_from_str_with_report("(deftemplate point (slot x)) (defglobal ?*x* = 1)")
+        .unwrap();
+    assert_eq!(report.rules_added, 0);
+    assert_eq!(report.join_nodes_added, 0);
+}
+
+#[test]
+fn batch_files_are_reported_like_strings() {
+    let path =
+        std::env::temp_dir().join(format!("clips-rs-test-load-report-{}", std::process::id()));
+    fs::write(&path, ruleset(20)).unwrap();
+
+    let env = Environment::new();
+    let report = env.batch_star_with_report(path.clone());
+    fs::remove_file(path).unwrap();
+
+    let report = report.unwrap();
+    assert!(report.duration > Duration::ZERO);
+    assert_eq!(report.rules_added, 20);
+    assert!(report.join_nodes_added >= 3 * 20, "{report:?}");
+}
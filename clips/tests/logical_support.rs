@@ -0,0 +1,75 @@
+use clips::{CLIPSEnvironment, CLIPSValue, ConflictResolutionStrategy, SlotMap};
+
+const PROGRAM: &str = r#"
+(defglobal ?*fired* = 0)
+(deftemplate point (slot x))
+(defrule eager
+   (declare (salience 10000) (auto-focus TRUE))
+   (trigger)
+   =>
+   (bind ?*fired* (+ ?*fired* 1)))
+(defrule on-point
+   (declare (salience 10000))
+   (point)
+   =>
+   (bind ?*fired* (+ ?*fired* 1)))
+"#;
+
+fn points(env: &mut CLIPSEnvironment) -> usize {
+    env.find_all_facts("point", "TRUE").unwrap().len()
+}
+
+#[test]
+fn user_rules_dont_fire_while_asserting() {
+    let mut env = CLIPSEnvironment::new().unwrap();
+    env.load_from_str(PROGRAM).unwrap();
+    // With the breadth strategy, the older activation of `eager` would come before the crate's own one if they were on the same agenda.
+    env.set_conflict_resolution_strategy(ConflictResolutionStrategy::Breadth);
+    env.assert_string("(trigger)").unwrap();
+
+    env.assert_fact_with_support(Box::new(SlotMap::new("point").slot("x", 1)), None, "a")
+        .unwrap();
+
+    assert_eq!(points(&mut env), 1);
+    assert_eq!(
+        env.retrieve_globals_values().unwrap()["MAIN"]["fired"],
+        CLIPSValue::Int(0)
+    );
+
+    // Both activations are still there for the next run.
+    assert_eq!(env.run().unwrap(), 2);
+}
+
+#[test]
+fn withdrawing_support_retracts_the_facts() {
+    let mut env = CLIPSEnvironment::new().unwrap();
+    env.load_from_str(PROGRAM).unwrap();
+
+    env.assert_fact_with_support(Box::new(SlotMap::new("point").slot("x", 1)), None, "a")
+        .unwrap();
+    env.assert_fact_with_support(Box::new(SlotMap::new("point").slot("x", 2)), None, "a")
+        .unwrap();
+    env.assert_fact_with_support(Box::new(SlotMap::new("point").slot("x", 3)), None, "b")
+        .unwrap();
+    assert_eq!(points(&mut env), 3);
+
+    assert_eq!(env.withdraw_support("a").unwrap(), 2);
+    assert_eq!(points(&mut env), 1);
+}
+
+#[test]
+fn support_constructs_are_hidden() {
+    let mut env = CLIPSEnvironment::new().unwrap();
+    env.load_from_str(PROGRAM).unwrap();
+    let construct_names = env.construct_summary().unwrap().construct_names;
+    let fingerprint = env.constructs_fingerprint();
+
+    env.assert_fact_with_support(Box::new(SlotMap::new("point").slot("x", 1)), None, "a")
+        .unwrap();
+
+    assert_eq!(
+        env.construct_summary().unwrap().construct_names,
+        construct_names
+    );
+    assert_eq!(env.constructs_fingerprint(), fingerprint);
+}
@@ -0,0 +1,69 @@
+use clips::{fact, instance, multifield, CLIPSValue, Environment, SlotMap, ToCLIPSSymbol};
+
+#[test]
+fn the_fact_macro_builds_a_slot_map() {
+    let order = fact!("order", "id" => 7, "customer" => "alice".symbol(), "items" => multifield!["mug", 2, 1.5]);
+
+    assert_eq!(
+        order,
+        SlotMap::new("order")
+            .slot("id", 7)
+            .slot("customer", CLIPSValue::Symbol("alice".to_string()))
+            .slot(
+                "items",
+                CLIPSValue::Multifield(vec![
+                    CLIPSValue::String("mug".to_string()),
+                    CLIPSValue::Int(2),
+                    CLIPSValue::Float(1.5),
+                ])
+            )
+    );
+}
+
+#[test]
+fn facts_and_instances_from_the_macros_can_be_asserted() {
+    let env = Environment::new();
+    env.load_from_str(
+        "
+        (deftemplate order (slot id) (slot customer) (multislot items))
+        (defclass point (is-a USER) (slot x) (multislot tags))",
+    )
+    .unwrap();
+
+    env.assert_fact(
+        fact!("order", "id" => 7, "customer" => "alice".symbol(), "items" => multifield!["mug".symbol(), 2],),
+        None,
+    )
+    .unwrap();
+    env.make_instance(
+        instance!("point", "x" => 1, "tags" => multifield![]),
+        Some("p".to_string()),
+        None,
+    )
+    .unwrap();
+
+    let facts = env.find_all_facts("order", "TRUE").unwrap();
+    assert_eq!(facts.len(), 1);
+    assert_eq!(facts[0].slot("id"), Some(&CLIPSValue::Int(7)));
+    assert_eq!(
+        facts[0].slot("customer"),
+        Some(&CLIPSValue::Symbol("alice".to_string()))
+    );
+    assert_eq!(
+        facts[0].slot("items"),
+        Some(&CLIPSValue::Multifield(vec![
+            CLIPSValue::Symbol("mug".to_string()),
+            CLIPSValue::Int(2),
+        ]))
+    );
+
+    let instances = env.find_all_instances("point", "TRUE").unwrap();
+    assert_eq!(instances[0].name, "p");
+    assert_eq!(
+        instances[0].slots,
+        [
+            ("x".to_string(), CLIPSValue::Int(1)),
+            ("tags".to_string(), CLIPSValue::Multifield(vec![])),
+        ]
+    );
+}
@@ -0,0 +1,98 @@
+use std::collections::HashMap;
+
+use clips::{CLIPSError, CLIPSValue, Environment};
+
+const CLASS: &str = "
+    (defclass entry (is-a USER)
+      (slot title)
+      (slot count)
+      (slot ratio)
+      (slot kind)
+      (multislot tags))";
+
+fn env() -> Environment {
+    let env = Environment::new();
+    env.load_from_str(CLASS).unwrap();
+    env
+}
+
+fn form() -> HashMap<String, CLIPSValue> {
+    HashMap::from([
+        (
+            "title".to_string(),
+            CLIPSValue::String("A \"quoted\" title".to_string()),
+        ),
+        ("count".to_string(), CLIPSValue::Int(-3)),
+        ("ratio".to_string(), CLIPSValue::Float(0.25)),
+        ("kind".to_string(), CLIPSValue::Symbol("draft".to_string())),
+        (
+            "tags".to_string(),
+            CLIPSValue::Multifield(vec![
+                CLIPSValue::Symbol("a".to_string()),
+                CLIPSValue::Int(2),
+                CLIPSValue::String("c d".to_string()),
+            ]),
+        ),
+    ])
+}
+
+#[test]
+fn slots_of_every_type_are_read_back() {
+    let env = env();
+    let slots = form();
+
+    let name = env
+        .make_instance_from_map("entry", Some("first".to_string()), slots.clone())
+        .unwrap();
+    assert_eq!(name, "first");
+
+    let instances = env.find_all_instances("entry", "TRUE").unwrap();
+    assert_eq!(instances.len(), 1);
+    assert_eq!(instances[0].name, "first");
+    for (slot, value) in &slots {
+        assert_eq!(instances[0].slot(slot), Some(value), "{slot}");
+    }
+}
+
+#[test]
+fn a_name_is_generated_when_none_is_given() {
+    let env = env();
+
+    let first = env.make_instance_from_map("entry", None, form()).unwrap();
+    let second = env
+        .make_instance_from_map("entry", None, HashMap::new())
+        .unwrap();
+    assert_ne!(first, second);
+
+    let mut names: Vec<_> = env
+        .find_all_instances("entry", "TRUE")
+        .unwrap()
+        .into_iter()
+        .map(|instance| instance.name)
+        .collect();
+    names.sort();
+    let mut expected = vec![first, second];
+    expected.sort();
+    assert_eq!(names, expected);
+}
+
+#[test]
+fn the_name_prefix_is_used() {
+    let env = env();
+    env.set_instance_name_prefix("entry-").unwrap();
+
+    let name = env.make_instance_from_map("entry", None, form()).unwrap();
+    assert!(name.starts_with("entry-"), "{name}");
+}
+
+#[test]
+fn unknown_slots_are_rejected() {
+    let env = env();
+    let slots = HashMap::from([("colour".to_string(), CLIPSValue::Int(1))]);
+
+    assert!(matches!(
+        env.make_instance_from_map("entry", None, slots),
+        Err(CLIPSError::SlotNotFound)
+    ));
+    assert!(env.find_all_instances("entry", "TRUE").unwrap().is_empty());
+}
@@ -0,0 +1,95 @@
+use std::sync::{Arc, Mutex};
+
+use clips::{CLIPSEnvironment, CLIPSError, CLIPSValue, UDFType};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+enum Decision {
+    Approve { limit: i64 },
+    Decline { reason: String },
+    Escalate,
+}
+
+#[test]
+fn enums_round_trip_through_a_defglobal() {
+    let mut env = CLIPSEnvironment::new().unwrap();
+    let approve = CLIPSValue::from_serialize(&Decision::Approve { limit: 5 }).unwrap();
+    let escalate = CLIPSValue::from_serialize(&Decision::Escalate).unwrap();
+
+    env.load_from_str(&format!(
+        "(defglobal ?*approve* = (create$ {}) ?*escalate* = {})",
+        approve.to_string().trim_matches(['(', ')']),
+        escalate
+    ))
+    .unwrap();
+
+    let globals = env.retrieve_globals_values().unwrap();
+    assert_eq!(globals["MAIN"]["approve"], approve);
+    assert_eq!(
+        globals["MAIN"]["approve"]
+            .deserialize_into::<Decision>()
+            .unwrap(),
+        Decision::Approve { limit: 5 }
+    );
+    assert_eq!(
+        globals["MAIN"]["escalate"]
+            .deserialize_into::<Decision>()
+            .unwrap(),
+        Decision::Escalate
+    );
+}
+
+#[test]
+fn enums_are_read_from_udf_arguments() {
+    let mut env = CLIPSEnvironment::new().unwrap();
+
+    let seen = Arc::new(Mutex::new(None));
+    let seen_in_udf = seen.clone();
+    env.add_udf(
+        "decide",
+        UDFType::Boolean,
+        1,
+        1,
+        vec![],
+        Box::new(move |mut data| {
+            let decision = data.first_arg::<CLIPSValue>().unwrap();
+            *seen_in_udf.lock().unwrap() = Some(decision.deserialize_into::<Decision>());
+            data.set_boolean_result(true);
+        }),
+    )
+    .unwrap();
+
+    env.load_from_str("(defglobal ?*done* = (decide (create$ Decline \"too risky\")))")
+        .unwrap();
+
+    assert_eq!(
+        seen.lock().unwrap().take().unwrap().unwrap(),
+        Decision::Decline {
+            reason: "too risky".to_string()
+        }
+    );
+}
+
+#[test]
+fn a_variant_must_be_a_symbol() {
+    let value = CLIPSValue::Multifield(vec![CLIPSValue::Int(1), CLIPSValue::Int(5)]);
+
+    assert!(matches!(
+        value.deserialize_into::<Decision>(),
+        Err(CLIPSError::ValueMapping(_))
+    ));
+}
+
+#[test]
+fn leftover_values_are_an_error() {
+    let value = CLIPSValue::Multifield(vec![
+        CLIPSValue::Symbol("Approve".to_string()),
+        CLIPSValue::Int(5),
+        CLIPSValue::Int(6),
+    ]);
+
+    assert!(matches!(
+        value.deserialize_into::<Decision>(),
+        Err(CLIPSError::ValueMapping(_))
+    ));
+}
@@ -0,0 +1,86 @@
+use clips::{CLIPSEnvironment, CLIPSError, CLIPSValue, SlotMap};
+
+#[test]
+fn module_name_must_be_a_single_symbol() {
+    let mut env = CLIPSEnvironment::new().unwrap();
+
+    let res = env.load_into_module("M) (defglobal ?*injected* = 1", "");
+
+    assert!(matches!(res, Err(CLIPSError::InvalidQuery(_))));
+    assert!(!env.retrieve_globals_values().unwrap()["MAIN"].contains_key("injected"));
+}
+
+#[test]
+fn asserting_after_reloading_a_module_uses_the_new_template() {
+    let mut env = CLIPSEnvironment::new().unwrap();
+    env.load_into_module("M", "(deftemplate point (slot x))")
+        .unwrap();
+    env.assert_fact(Box::new(SlotMap::new("point").slot("x", 1)), Some("M"))
+        .unwrap();
+
+    env.unload_module("M").unwrap();
+    env.load_into_module("M", "(deftemplate point (slot y) (slot x))")
+        .unwrap();
+
+    // A builder kept from before the unload would still point to the old template, which has no `y` slot.
+    env.assert_fact(
+        Box::new(SlotMap::new("point").slot("y", 2).slot("x", 3)),
+        Some("M"),
+    )
+    .unwrap();
+}
+
+const PLUGIN: &str = "
+    (deftemplate ping (slot n))
+    (defglobal ?*pings* = 0)
+    (defrule respond
+      (declare (auto-focus TRUE))
+      (ping)
+      =>
+      (bind ?*pings* (+ ?*pings* 1)))";
+
+fn rules(env: &CLIPSEnvironment) -> Vec<String> {
+    env.construct_summary()
+        .unwrap()
+        .construct_names
+        .into_iter()
+        .filter(|name| name.starts_with("defrule "))
+        .collect()
+}
+
+fn pings(env: &CLIPSEnvironment, module: &str) -> CLIPSValue {
+    env.retrieve_globals_values().unwrap()[module]["pings"].clone()
+}
+
+#[test]
+fn modules_with_the_same_rule_names_coexist_and_unload_independently() {
+    let mut env = CLIPSEnvironment::new().unwrap();
+    env.load_into_module("A", PLUGIN).unwrap();
+    env.load_into_module("B", PLUGIN).unwrap();
+    assert_eq!(rules(&env), ["defrule A::respond", "defrule B::respond"]);
+
+    for module in ["A", "B"] {
+        env.assert_fact(Box::new(SlotMap::new("ping").slot("n", 1)), Some(module))
+            .unwrap();
+    }
+    assert_eq!(env.run().unwrap(), 2);
+    assert_eq!(pings(&env, "A"), CLIPSValue::Int(1));
+    assert_eq!(pings(&env, "B"), CLIPSValue::Int(1));
+
+    // The template, the global and the rule go, along with the fact using the template.
+    assert_eq!(env.unload_module("A").unwrap(), 3);
+    assert_eq!(rules(&env), ["defrule B::respond"]);
+    assert!(matches!(
+        env.assert_fact(Box::new(SlotMap::new("ping").slot("n", 2)), Some("A")),
+        Err(CLIPSError::TemplateNotFound)
+    ));
+
+    env.assert_fact(Box::new(SlotMap::new("ping").slot("n", 2)), Some("B"))
+        .unwrap();
+    assert_eq!(env.run().unwrap(), 1);
+    assert_eq!(pings(&env, "B"), CLIPSValue::Int(2));
+
+    // The emptied module can take the plugin again.
+    env.load_into_module("A", PLUGIN).unwrap();
+    assert_eq!(rules(&env), ["defrule A::respond", "defrule B::respond"]);
+}
@@ -0,0 +1,123 @@
+use clips::{CLIPSError, CLIPSValue, Environment, MultislotEdit};
+
+fn env_with_list(values: &str) -> (Environment, i64) {
+    let env = Environment::new();
+    env.load_from_str("(deftemplate list (slot name) (multislot items))")
+        .unwrap();
+    let index = env
+        .assert_string(&format!("(list (name a) (items {}))", values))
+        .unwrap();
+
+    (env, index)
+}
+
+fn items(env: &Environment) -> Vec<CLIPSValue> {
+    let facts = env.find_all_facts("list", "TRUE").unwrap();
+    assert_eq!(facts.len(), 1);
+
+    match facts[0].slot("items") {
+        Some(CLIPSValue::Multifield(items)) => items.clone(),
+        other => panic!("expected a multifield, got {:?}", other),
+    }
+}
+
+fn ints(values: &[i64]) -> Vec<CLIPSValue> {
+    values.iter().copied().map(CLIPSValue::Int).collect()
+}
+
+#[test]
+fn append_insert_and_remove() {
+    let (env, index) = env_with_list("1 2 3");
+
+    let index = env
+        .append_to_multislot(index, "items", CLIPSValue::Int(4))
+        .unwrap();
+    assert_eq!(items(&env), ints(&[1, 2, 3, 4]));
+
+    let index = env
+        .insert_into_multislot(index, "items", 0, CLIPSValue::Int(0))
+        .unwrap();
+    assert_eq!(items(&env), ints(&[0, 1, 2, 3, 4]));
+
+    let index = env
+        .insert_into_multislot(index, "items", 2, CLIPSValue::Int(9))
+        .unwrap();
+    assert_eq!(items(&env), ints(&[0, 1, 9, 2, 3, 4]));
+
+    // Inserting at the length appends.
+    let index = env
+        .insert_into_multislot(index, "items", 6, CLIPSValue::Int(5))
+        .unwrap();
+    assert_eq!(items(&env), ints(&[0, 1, 9, 2, 3, 4, 5]));
+
+    let index = env.remove_from_multislot(index, "items", 2).unwrap();
+    assert_eq!(items(&env), ints(&[0, 1, 2, 3, 4, 5]));
+
+    env.remove_from_multislot(index, "items", 5).unwrap();
+    assert_eq!(items(&env), ints(&[0, 1, 2, 3, 4]));
+}
+
+#[test]
+fn multifield_values_are_spliced_in() {
+    let (env, index) = env_with_list("1 4");
+
+    env.edit_multislot(
+        index,
+        "items",
+        MultislotEdit::Insert {
+            index: 1,
+            value: CLIPSValue::Multifield(ints(&[2, 3])),
+        },
+    )
+    .unwrap();
+
+    assert_eq!(items(&env), ints(&[1, 2, 3, 4]));
+}
+
+#[test]
+fn editing_an_empty_multislot() {
+    let (env, index) = env_with_list("");
+
+    assert!(matches!(
+        env.remove_from_multislot(index, "items", 0),
+        Err(CLIPSError::MultislotIndexOutOfRange { index: 0, len: 0 })
+    ));
+
+    env.insert_into_multislot(index, "items", 0, CLIPSValue::Int(1))
+        .unwrap();
+    assert_eq!(items(&env), ints(&[1]));
+}
+
+#[test]
+fn out_of_range_edits_leave_the_fact_alone() {
+    let (env, index) = env_with_list("1 2 3");
+
+    assert!(matches!(
+        env.insert_into_multislot(index, "items", 4, CLIPSValue::Int(0)),
+        Err(CLIPSError::MultislotIndexOutOfRange { index: 4, len: 3 })
+    ));
+    assert!(matches!(
+        env.remove_from_multislot(index, "items", 3),
+        Err(CLIPSError::MultislotIndexOutOfRange { index: 3, len: 3 })
+    ));
+
+    assert_eq!(items(&env), ints(&[1, 2, 3]));
+}
+
+#[test]
+fn only_multislots_of_existing_facts_can_be_edited() {
+    let (env, index) = env_with_list("1");
+
+    assert!(matches!(
+        env.append_to_multislot(index, "name", CLIPSValue::Int(1)),
+        Err(CLIPSError::SlotNotMultifield)
+    ));
+    assert!(matches!(
+        env.append_to_multislot(index, "missing", CLIPSValue::Int(1)),
+        Err(CLIPSError::SlotNotFound)
+    ));
+    assert!(matches!(
+        env.append_to_multislot(index + 100, "items", CLIPSValue::Int(1)),
+        Err(CLIPSError::FactNotFound)
+    ));
+}
@@ -0,0 +1,41 @@
+use std::sync::mpsc;
+
+use clips::{Environment, UDFType};
+
+#[test]
+fn a_missing_optional_argument_is_none_and_a_wrong_one_is_an_error() {
+    let env = Environment::new();
+    let (tx, rx) = mpsc::channel();
+
+    env.add_udf(
+        "greet".to_string(),
+        1,
+        2,
+        UDFType::Void,
+        vec![],
+        Box::new(move |mut data| {
+            let name = data.first_arg::<String>().unwrap();
+            tx.send((name, data.opt_next_arg::<String>())).unwrap();
+            data.set_void();
+        }),
+    )
+    .unwrap();
+
+    env.load_from_str(
+        r#"
+        (defglobal ?*one* = (greet "ada"))
+        (defglobal ?*two* = (greet "ada" "lovelace"))
+        (defglobal ?*wrong* = (greet "ada" 5))"#,
+    )
+    .unwrap();
+
+    let (name, title) = rx.recv().unwrap();
+    assert_eq!(name, "ada");
+    assert!(matches!(title, Ok(None)));
+
+    let (_, title) = rx.recv().unwrap();
+    assert!(matches!(title, Ok(Some(title)) if title == "lovelace"));
+
+    let (_, title) = rx.recv().unwrap();
+    assert!(title.is_err());
+}
@@ -0,0 +1,62 @@
+use clips::{CLIPSError, CLIPSValue, Environment};
+
+fn implied(env: &Environment, index: i64) -> CLIPSValue {
+    env.find_all_facts("numbers", &format!("(= (fact-index ?f) {index})"))
+        .unwrap()[0]
+        .slot("implied")
+        .unwrap()
+        .clone()
+}
+
+#[test]
+fn ordered_facts_are_asserted_without_formatting() {
+    let env = Environment::new();
+    let values = vec![
+        CLIPSValue::Int(1),
+        CLIPSValue::Float(2.5),
+        CLIPSValue::Symbol("three".into()),
+        CLIPSValue::String("a \"quoted\" (string)".into()),
+    ];
+
+    let index = env.assert_ordered("numbers", values.clone()).unwrap();
+
+    assert_eq!(implied(&env, index), CLIPSValue::Multifield(values));
+    assert_eq!(
+        env.find_all_facts(
+            "numbers",
+            "(eq (nth$ 4 ?f:implied) \"a \\\"quoted\\\" (string)\")"
+        )
+        .unwrap()
+        .len(),
+        1
+    );
+}
+
+#[test]
+fn ordered_facts_match_rules() {
+    let env = Environment::new();
+    env.load_from_str("(defrule sum (numbers ?a ?b) => (assert (sum (+ ?a ?b))))")
+        .unwrap();
+
+    env.assert_ordered("numbers", vec![CLIPSValue::Int(2), CLIPSValue::Int(3)])
+        .unwrap();
+    env.assert_ordered("numbers", vec![]).unwrap();
+    env.run().unwrap();
+
+    assert_eq!(
+        env.find_all_facts("sum", "TRUE").unwrap()[0].slot("implied"),
+        Some(&CLIPSValue::Multifield(vec![CLIPSValue::Int(5)]))
+    );
+}
+
+#[test]
+fn heads_naming_a_deftemplate_are_rejected() {
+    let env = Environment::new();
+    env.load_from_str("(deftemplate point (slot x))").unwrap();
+
+    assert!(matches!(
+        env.assert_ordered("point", vec![CLIPSValue::Int(1)]),
+        Err(CLIPSError::TemplateNotFound)
+    ));
+    assert!(env.assert_ordered("(bad", vec![]).is_err());
+}
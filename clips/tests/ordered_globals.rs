@@ -0,0 +1,120 @@
+use clips::{CLIPSGlobalsHierarchy, CLIPSValue, Environment, OrderedGlobalsHierarchy};
+
+fn env(globals: &[&str]) -> Environment {
+    let env = Environment::new();
+    for global in globals {
+        env.load_from_str(*global).unwrap();
+    }
+    env.load_from_str("(defmodule OTHER) (defglobal OTHER ?*b* = 2 ?*a* = 1)")
+        .unwrap();
+    env
+}
+
+const GLOBALS: [&str; 4] = [
+    "(defglobal ?*zeta* = 26)",
+    "(defglobal ?*alpha* = \"first\")",
+    "(defglobal ?*mid* = (create$ a b))",
+    "(defglobal ?*beta* = 2.5)",
+];
+
+#[test]
+fn globals_are_sorted_by_module_and_name() {
+    let globals = env(&GLOBALS).retrieve_ordered_globals_values().unwrap();
+
+    assert_eq!(globals.keys().collect::<Vec<_>>(), ["MAIN", "OTHER"]);
+    assert_eq!(
+        globals["MAIN"].keys().collect::<Vec<_>>(),
+        ["alpha", "beta", "mid", "zeta"]
+    );
+    assert_eq!(globals["OTHER"].keys().collect::<Vec<_>>(), ["a", "b"]);
+}
+
+#[test]
+fn the_same_globals_always_serialise_the_same_way() {
+    let mut reversed = GLOBALS;
+    reversed.reverse();
+
+    let json = |globals: &[&str]| {
+        serde_json::to_string(&env(globals).retrieve_ordered_globals_values().unwrap()).unwrap()
+    };
+    assert_eq!(json(&GLOBALS), json(&reversed));
+    assert!(json(&GLOBALS).starts_with("{\"MAIN\":{\"alpha\":"));
+}
+
+#[test]
+fn both_kinds_of_hierarchy_convert_into_each_other() {
+    let env = env(&GLOBALS);
+    let globals = env.retrieve_globals_values().unwrap();
+    let ordered = env.retrieve_ordered_globals_values().unwrap();
+
+    assert_eq!(OrderedGlobalsHierarchy::from(globals.clone()), ordered);
+    assert_eq!(CLIPSGlobalsHierarchy::from(ordered), globals);
+}
+
+#[test]
+fn either_kind_of_hierarchy_can_be_restored() {
+    let env = env(&GLOBALS);
+    let saved = env.retrieve_ordered_globals_values().unwrap();
+
+    env.load_from_str("(defglobal MAIN ?*zeta* = 0)").unwrap();
+    env.restore_globals(saved.clone()).unwrap();
+    assert_eq!(env.retrieve_ordered_globals_values().unwrap(), saved);
+
+    env.load_from_str("(defglobal MAIN ?*zeta* = 0)").unwrap();
+    env.restore_globals(CLIPSGlobalsHierarchy::from(saved.clone()))
+        .unwrap();
+    assert_eq!(
+        env.retrieve_ordered_globals_values().unwrap()["MAIN"]["zeta"],
+        CLIPSValue::Int(26)
+    );
+}
+
+#[test]
+fn facts_come_back_in_fact_index_order() {
+    let env = Environment::new();
+    env.load_from_str("(deftemplate item (slot n))").unwrap();
+    for n in [3, 1, 4, 7, 5, 9, 2, 6] {
+        env.assert_string(&format!("(item (n {n}))")).unwrap();
+    }
+    env.retract_where("item", "(= ?f:n 4)").unwrap();
+    env.assert_string("(item (n 0))").unwrap();
+
+    let facts = env.find_all_facts("item", "TRUE").unwrap();
+    let indexes: Vec<_> = facts.iter().map(|fact| fact.index).collect();
+    let mut sorted = indexes.clone();
+    sorted.sort();
+    assert_eq!(indexes, sorted);
+
+    let values: Vec<_> = facts.iter().map(|fact| fact.slot("n").cloned()).collect();
+    let expected: Vec<_> = [3, 1, 7, 5, 9, 2, 6, 0]
+        .into_iter()
+        .map(|n| Some(CLIPSValue::Int(n)))
+        .collect();
+    assert_eq!(values, expected);
+}
+
+#[test]
+fn instances_come_back_by_class_in_creation_order() {
+    let env = Environment::new();
+    env.load_from_str(
+        "(defclass animal (is-a USER))
+         (defclass dog (is-a animal))",
+    )
+    .unwrap();
+    env.load_from_str(
+        "(defglobal ?*made* = (progn
+           (make-instance rex of dog)
+           (make-instance zebra of animal)
+           (make-instance ant of animal)
+           (make-instance fido of dog)))",
+    )
+    .unwrap();
+
+    let names: Vec<_> = env
+        .find_all_instances("animal", "TRUE")
+        .unwrap()
+        .into_iter()
+        .map(|instance| instance.name)
+        .collect();
+    assert_eq!(names, ["zebra", "ant", "rex", "fido"]);
+}
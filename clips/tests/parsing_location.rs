@@ -0,0 +1,106 @@
+use std::{
+    fs,
+    sync::{Arc, Mutex},
+};
+
+use clips::{CLIPSValue, Environment, UDFType};
+
+type Locations = Arc<Mutex<Vec<Option<(String, usize)>>>>;
+
+// `(where)` records the parsing location at the time it's called, so a global initialized with it shows where the global was parsed.
+fn env_with_where_udf() -> (Environment, Locations) {
+    let env = Environment::new();
+    let locations = Locations::default();
+    let locations_in_udf = locations.clone();
+    env.add_udf(
+        "where".to_string(),
+        0,
+        0,
+        UDFType::Integer,
+        vec![],
+        Box::new(move |mut data| {
+            let location = data.env().get_current_parsing_location().unwrap();
+            locations_in_udf.lock().unwrap().push(location);
+            data.set_result(CLIPSValue::Int(0)).unwrap();
+        }),
+    )
+    .unwrap();
+
+    (env, locations)
+}
+
+fn last_location(locations: &Locations) -> Option<(String, usize)> {
+    locations.lock().unwrap().last().cloned().flatten()
+}
+
+#[test]
+fn a_failed_string_load_does_not_leak_into_the_next_one() {
+    let (env, locations) = env_with_where_udf();
+
+    let bad = format!("{}(defrule broken (x) => (", "\n".repeat(20));
+    assert!(env.load_from_str(bad).is_err());
+
+    env.load_from_str("\n(defglobal ?*here* = (where))")
+        .unwrap();
+    assert_eq!(last_location(&locations), Some((String::new(), 2)));
+}
+
+#[test]
+fn a_batch_file_name_does_not_stick_to_string_loads() {
+    let (env, locations) = env_with_where_udf();
+
+    let path = std::env::temp_dir().join(format!(
+        "clips-rs-test-parsing-location-{}",
+        std::process::id()
+    ));
+    fs::write(&path, "\n(defglobal ?*in-file* = (where))\n").unwrap();
+    let batch = env.batch_star(path.clone());
+    fs::remove_file(&path).unwrap();
+    batch.unwrap();
+
+    assert_eq!(
+        last_location(&locations),
+        Some((path.to_str().unwrap().to_string(), 2))
+    );
+
+    env.load_from_str("(defglobal ?*here* = (where))").unwrap();
+    assert_eq!(last_location(&locations), Some((String::new(), 1)));
+}
+
+#[test]
+fn there_is_no_location_outside_of_loads() {
+    let (env, locations) = env_with_where_udf();
+    assert!(env.load_from_str("(defrule broken (x) => (").is_err());
+    env.load_from_str("(defrule report (go) => (where))")
+        .unwrap();
+
+    env.reset_parser_state().unwrap();
+    env.assert_string("(go)").unwrap();
+    assert_eq!(env.run().unwrap(), 1);
+    assert_eq!(locations.lock().unwrap().last(), Some(&None));
+}
+
+#[test]
+fn the_parsing_flag_is_only_set_during_a_load() {
+    let env = Environment::new();
+    assert!(!env.is_parsing());
+
+    let source: String = (0..20_000)
+        .map(|i| format!("(defrule rule-{i} (value {i} ?x) => (printout t ?x crlf))\n"))
+        .collect();
+    let loading_env = env.clone();
+    let load = std::thread::spawn(move || loading_env.load_from_str(source));
+
+    let mut seen_parsing = false;
+    while !load.is_finished() {
+        if env.is_parsing() {
+            seen_parsing = true;
+            break;
+        }
+        std::thread::yield_now();
+    }
+
+    load.join().unwrap().unwrap();
+    assert!(seen_parsing);
+    assert!(!env.is_parsing());
+}
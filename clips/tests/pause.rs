@@ -0,0 +1,68 @@
+use std::{
+    sync::mpsc::{self, RecvTimeoutError},
+    thread,
+    time::Duration,
+};
+
+use clips::Environment;
+
+const WAIT: Duration = Duration::from_millis(200);
+
+// Asserts `fact` from another handle on a thread of its own, and sends back the result once the command finishes.
+fn assert_in_background(env: &Environment, fact: &'static str) -> mpsc::Receiver<i64> {
+    let env = env.clone();
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || tx.send(env.assert_string(fact).unwrap()).unwrap());
+    rx
+}
+
+#[test]
+fn queued_commands_wait_for_resume() {
+    let env = Environment::new();
+    env.pause();
+    assert!(env.is_paused());
+
+    let first = assert_in_background(&env, "(first)");
+    assert_eq!(first.recv_timeout(WAIT), Err(RecvTimeoutError::Timeout));
+    let second = assert_in_background(&env, "(second)");
+    assert_eq!(second.recv_timeout(WAIT), Err(RecvTimeoutError::Timeout));
+
+    env.resume();
+    assert!(!env.is_paused());
+
+    // Both commands ran, and neither was lost while the environment was paused.
+    let mut indexes = vec![first.recv().unwrap(), second.recv().unwrap()];
+    indexes.sort();
+    assert_eq!(indexes, [1, 2]);
+    assert_eq!(env.find_all_facts("first", "TRUE").unwrap().len(), 1);
+    assert_eq!(env.find_all_facts("second", "TRUE").unwrap().len(), 1);
+}
+
+#[test]
+fn pausing_doesnt_interrupt_a_running_command() {
+    let env = Environment::new();
+    env.load_from_str("(defrule slow (go) => (loop-for-count 3000000 do (+ 1 1)) (assert (done)))")
+        .unwrap();
+    env.assert_string("(go)").unwrap();
+
+    let runner = env.clone();
+    let run = thread::spawn(move || runner.run().unwrap());
+    thread::sleep(Duration::from_millis(50));
+    env.pause();
+
+    assert_eq!(run.join().unwrap(), 1);
+    env.resume();
+    assert_eq!(env.find_all_facts("done", "TRUE").unwrap().len(), 1);
+}
+
+#[test]
+fn closing_runs_the_queued_commands() {
+    let env = Environment::new();
+    env.pause();
+
+    let queued = assert_in_background(&env, "(queued)");
+    assert_eq!(queued.recv_timeout(WAIT), Err(RecvTimeoutError::Timeout));
+
+    env.close().unwrap();
+    assert_eq!(queued.recv_timeout(WAIT), Ok(1));
+}
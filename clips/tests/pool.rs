@@ -0,0 +1,40 @@
+use std::{sync::mpsc, thread, time::Duration};
+
+use clips::{CLIPSValue, Environment, EnvironmentPool};
+
+fn marker(env: &Environment) -> CLIPSValue {
+    env.retrieve_globals_values().unwrap()["MAIN"]["marker"].clone()
+}
+
+#[test]
+fn acquire_waits_for_an_environment_to_be_returned() {
+    let pool =
+        EnvironmentPool::new(2, |env| env.load_from_str("(defglobal ?*marker* = 0)")).unwrap();
+    assert_eq!(pool.size(), 2);
+
+    let first = pool.acquire();
+    let second = pool.acquire();
+    assert!(pool.try_acquire().is_none());
+
+    let waiting_pool = pool.clone();
+    let (tx, rx) = mpsc::channel();
+    let waiter = thread::spawn(move || {
+        let env = waiting_pool.acquire();
+        tx.send(marker(&env)).unwrap();
+    });
+
+    assert!(rx.recv_timeout(Duration::from_millis(200)).is_err());
+
+    first.load_from_str("(defglobal ?*marker* = 1)").unwrap();
+    drop(first);
+
+    // The waiter gets the environment that was just returned, since the other one is still checked out.
+    assert_eq!(
+        rx.recv_timeout(Duration::from_secs(5)).unwrap(),
+        CLIPSValue::Int(1)
+    );
+    waiter.join().unwrap();
+
+    drop(second);
+    assert!(pool.try_acquire().is_some());
+}
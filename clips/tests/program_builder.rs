@@ -0,0 +1,107 @@
+use std::fs;
+
+use clips::{CLIPSError, CLIPSValue, Environment, ProgramBuilder, SlotMap};
+
+// `Environment` has no `reset()`, so it's run from a batch file.
+fn reset(env: &Environment) {
+    let path = std::env::temp_dir().join(format!(
+        "clips-rs-test-program-builder-{}",
+        std::process::id()
+    ));
+    fs::write(&path, "(reset)\n").unwrap();
+    let res = env.batch_star(path.clone());
+    fs::remove_file(path).unwrap();
+    res.unwrap();
+}
+
+// Added in the opposite order of what CLIPS needs: everything here refers to something added after it.
+fn program() -> ProgramBuilder {
+    ProgramBuilder::new()
+        .deffacts(
+            "readings",
+            [
+                SlotMap::new("reading").slot("value", 5),
+                SlotMap::new("reading").slot("value", 15),
+            ],
+        )
+        .unwrap()
+        .construct(
+            "(defrule high
+               (reading (value ?v&:(too-high ?v)))
+               =>
+               (assert (alarm (value ?v))))",
+        )
+        .unwrap()
+        .construct("(deffunction too-high (?v) (> ?v ?*limit*))")
+        .unwrap()
+        .construct("; The threshold for alarms.\n(defglobal ?*limit* = 10)")
+        .unwrap()
+        .construct("(deftemplate alarm (slot value))")
+        .unwrap()
+        .construct("(deftemplate reading (slot value))")
+        .unwrap()
+}
+
+#[test]
+fn constructs_come_out_in_dependency_order() {
+    let source = program().build();
+
+    let position = |text: &str| source.find(text).unwrap();
+    assert!(position("(deftemplate alarm") < position("(deftemplate reading"));
+    assert!(position("(deftemplate reading") < position("(defglobal"));
+    assert!(position("(defglobal") < position("(deffunction"));
+    assert!(position("(deffunction") < position("(defrule"));
+    assert!(position("(defrule") < position("(deffacts"));
+}
+
+#[test]
+fn a_program_with_interdependent_constructs_loads_and_runs() {
+    let env = Environment::new();
+    env.load_from_str(program().build()).unwrap();
+    reset(&env);
+
+    assert_eq!(env.run().unwrap(), 1);
+    let alarms = env.find_all_facts("alarm", "TRUE").unwrap();
+    assert_eq!(alarms.len(), 1);
+    assert_eq!(alarms[0].slot("value"), Some(&CLIPSValue::Int(15)));
+}
+
+#[test]
+fn the_same_constructs_in_the_order_added_do_not_load() {
+    let mut constructs: Vec<_> = program()
+        .build()
+        .split("\n\n")
+        .map(str::to_string)
+        .collect();
+    constructs.reverse();
+
+    let env = Environment::new();
+    assert!(env.load_from_str(constructs.join("\n\n")).is_err());
+}
+
+#[test]
+fn malformed_constructs_are_rejected() {
+    for construct in [
+        "",
+        "; only a comment",
+        "(deftemplate a) (deftemplate b)",
+        "(deftemplate a (slot x)",
+        "(deftemplate a))",
+        "(deftemplate a (slot x (default \"unterminated)))",
+        "deftemplate a",
+        "(assert (a))",
+    ] {
+        assert!(
+            matches!(
+                ProgramBuilder::new().construct(construct),
+                Err(CLIPSError::InvalidConstruct(_))
+            ),
+            "{construct:?}"
+        );
+    }
+
+    // Parentheses in strings and comments don't count.
+    ProgramBuilder::new()
+        .construct("(defglobal ?*x* = \"(\") ; )")
+        .unwrap();
+}
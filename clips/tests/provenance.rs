@@ -0,0 +1,83 @@
+use clips::{CLIPSValue, Environment, SlotMap};
+
+fn env() -> Environment {
+    let env = Environment::new();
+    env.load_from_str(
+        "(deftemplate order (slot id))
+         (deftemplate invoice (slot order))
+         (defrule bill
+           (order (id ?id))
+           =>
+           (assert (invoice (order ?id))))",
+    )
+    .unwrap();
+    env
+}
+
+fn order(id: i64) -> SlotMap {
+    SlotMap::new("order").slot("id", id)
+}
+
+fn ids(env: &Environment, provenance: &str) -> Vec<CLIPSValue> {
+    env.facts_with_provenance(provenance)
+        .unwrap()
+        .iter()
+        .map(|fact| fact.slot("id").unwrap().clone())
+        .collect()
+}
+
+fn index_of(env: &Environment, template: &str, query: &str) -> i64 {
+    env.find_all_facts(template, query).unwrap()[0].index
+}
+
+#[test]
+fn facts_are_tagged_by_who_asserted_them() {
+    let env = env();
+    env.assert_fact_with_provenance(order(1), None, "import")
+        .unwrap();
+    let results = env
+        .assert_facts_with_provenance(vec![order(2), order(3)], None, "web")
+        .unwrap();
+    assert!(results.iter().all(Result::is_ok));
+    env.assert_fact(order(4), None).unwrap();
+    env.run().unwrap();
+
+    assert_eq!(ids(&env, "import"), [CLIPSValue::Int(1)]);
+    assert_eq!(ids(&env, "web"), [CLIPSValue::Int(2), CLIPSValue::Int(3)]);
+    assert!(ids(&env, "unknown").is_empty());
+
+    let tagged = index_of(&env, "order", "(eq ?f:id 2)");
+    assert_eq!(env.fact_provenance(tagged).unwrap().as_deref(), Some("web"));
+
+    // Facts asserted without a tag and facts derived by rules have none.
+    let untagged = index_of(&env, "order", "(eq ?f:id 4)");
+    assert_eq!(env.fact_provenance(untagged).unwrap(), None);
+    let derived = index_of(&env, "invoice", "(eq ?f:order 1)");
+    assert_eq!(env.fact_provenance(derived).unwrap(), None);
+}
+
+#[test]
+fn retracted_facts_lose_their_tag() {
+    let env = env();
+    env.assert_facts_with_provenance(vec![order(1), order(2)], None, "import")
+        .unwrap();
+    let retracted = index_of(&env, "order", "(eq ?f:id 1)");
+
+    assert_eq!(env.retract_where("order", "(eq ?f:id 1)").unwrap(), 1);
+
+    assert_eq!(env.fact_provenance(retracted).unwrap(), None);
+    assert_eq!(ids(&env, "import"), [CLIPSValue::Int(2)]);
+}
+
+#[test]
+fn nothing_is_tagged_until_a_tag_is_given() {
+    let env = env();
+    env.assert_fact(order(1), None).unwrap();
+
+    assert_eq!(
+        env.fact_provenance(index_of(&env, "order", "TRUE"))
+            .unwrap(),
+        None
+    );
+    assert!(ids(&env, "import").is_empty());
+}
@@ -0,0 +1,98 @@
+use std::sync::mpsc;
+
+use clips::{CLIPSEnvironment, CLIPSValue, SlotMap};
+
+const PROGRAM: &str = "
+    (defmodule A (export ?ALL))
+    (deftemplate A::t (slot a))
+    (defclass A::c (is-a USER) (slot a))
+    (defmodule B (export ?ALL))
+    (deftemplate B::t (slot b))
+    (defclass B::c (is-a USER) (slot b))";
+
+// `find_all_facts()` only sees the templates of the current module, which is `B` after loading.
+fn slots(env: &mut CLIPSEnvironment, template: &str) -> Vec<CLIPSValue> {
+    let mut values = Vec::new();
+    env.visit_facts(template, |fact| {
+        values.extend(
+            fact.to_retrieved_fact()
+                .slots
+                .into_iter()
+                .map(|(_, value)| value),
+        );
+    })
+    .unwrap();
+    values
+}
+
+#[test]
+fn templates_with_the_same_name_in_two_modules_get_their_own_builders() {
+    let mut env = CLIPSEnvironment::new().unwrap();
+    env.load_from_str(PROGRAM).unwrap();
+
+    // Alternating between the modules would reuse the wrong builder if they were keyed by the bare name.
+    env.assert_fact(Box::new(SlotMap::new("t").slot("a", 1)), Some("A"))
+        .unwrap();
+    env.assert_fact(Box::new(SlotMap::new("t").slot("b", 2)), Some("B"))
+        .unwrap();
+    env.assert_fact(Box::new(SlotMap::new("A::t").slot("a", 3)), None)
+        .unwrap();
+    env.assert_fact(Box::new(SlotMap::new("B::t").slot("b", 4)), None)
+        .unwrap();
+
+    assert!(env
+        .assert_fact(Box::new(SlotMap::new("A::t").slot("b", 5)), None)
+        .is_err());
+
+    assert_eq!(
+        slots(&mut env, "A::t"),
+        [CLIPSValue::Int(1), CLIPSValue::Int(3)]
+    );
+    assert_eq!(
+        slots(&mut env, "B::t"),
+        [CLIPSValue::Int(2), CLIPSValue::Int(4)]
+    );
+}
+
+#[test]
+fn classes_with_the_same_name_in_two_modules_get_their_own_builders() {
+    let mut env = CLIPSEnvironment::new().unwrap();
+    env.load_from_str(PROGRAM).unwrap();
+
+    env.make_instance(
+        Box::new(SlotMap::new("c").slot("a", 1)),
+        Some("x"),
+        Some("A"),
+    )
+    .unwrap();
+    env.make_instance(
+        Box::new(SlotMap::new("c").slot("b", 2)),
+        Some("y"),
+        Some("B"),
+    )
+    .unwrap();
+    env.make_instance(Box::new(SlotMap::new("A::c").slot("a", 3)), Some("z"), None)
+        .unwrap();
+
+    assert!(env
+        .make_instance(Box::new(SlotMap::new("B::c").slot("a", 4)), Some("w"), None)
+        .is_err());
+
+    let (tx, rx) = mpsc::sync_channel(16);
+    env.stream_instances(&tx);
+    drop(tx);
+    let mut instances: Vec<_> = rx
+        .into_iter()
+        .map(Result::unwrap)
+        .map(|instance| (instance.name, instance.slots))
+        .collect();
+    instances.sort_by(|a, b| a.0.cmp(&b.0));
+    assert_eq!(
+        instances,
+        [
+            ("x".to_string(), vec![("a".to_string(), CLIPSValue::Int(1))]),
+            ("y".to_string(), vec![("b".to_string(), CLIPSValue::Int(2))]),
+            ("z".to_string(), vec![("a".to_string(), CLIPSValue::Int(3))]),
+        ]
+    );
+}
@@ -0,0 +1,43 @@
+use std::sync::{Arc, Mutex};
+
+use clips::{CLIPSError, Environment, UDFType};
+
+#[test]
+fn a_udf_calling_back_into_its_handle_fails_right_away() {
+    let env = Environment::new();
+    let handle = Arc::new(Mutex::new(Some(env.clone())));
+    let results = Arc::new(Mutex::new(Vec::new()));
+
+    let handle_in_udf = handle.clone();
+    let results_in_udf = results.clone();
+    env.add_udf(
+        "run-again".to_string(),
+        0,
+        0,
+        UDFType::Void,
+        vec![],
+        Box::new(move |_| {
+            let handle = handle_in_udf.lock().unwrap();
+            results_in_udf
+                .lock()
+                .unwrap()
+                .push(handle.as_ref().unwrap().run());
+        }),
+    )
+    .unwrap();
+
+    env.load_from_str("(defrule reenter (go) => (run-again))")
+        .unwrap();
+    env.assert_string("(go)").unwrap();
+    assert_eq!(env.run().unwrap(), 1);
+
+    let results = results.lock().unwrap();
+    assert_eq!(results.len(), 1);
+    assert!(matches!(results[0], Err(CLIPSError::ReentrantCall)));
+
+    // The environment is still usable from outside the callback.
+    assert!(env.is_alive());
+    env.assert_string("(done)").unwrap();
+
+    handle.lock().unwrap().take();
+}
@@ -0,0 +1,65 @@
+use clips::{CLIPSValue, Environment};
+
+// Both rules are activated by the same fact, so the agenda is only ordered by their salience.
+fn env_with_activations() -> Environment {
+    let env = Environment::new();
+    env.load_from_str(
+        "
+        (defglobal ?*first-salience* = 10 ?*second-salience* = 20 ?*fired* = (create$))
+        (defrule first (declare (salience ?*first-salience*)) (go)
+          => (bind ?*fired* (create$ ?*fired* first)))
+        (defrule second (declare (salience ?*second-salience*)) (go)
+          => (bind ?*fired* (create$ ?*fired* second)))
+        (defglobal ?*go* = (fact-index (assert (go))))
+        (defglobal ?*raised* = (bind ?*first-salience* 30))
+        ",
+    )
+    .unwrap();
+    env
+}
+
+fn fired(env: &Environment) -> CLIPSValue {
+    env.retrieve_globals_values().unwrap()["MAIN"]["fired"].clone()
+}
+
+#[test]
+fn changed_saliences_take_effect_after_a_refresh() {
+    let env = env_with_activations();
+
+    env.refresh_agenda().unwrap();
+    env.run().unwrap();
+
+    assert_eq!(
+        fired(&env),
+        CLIPSValue::Multifield(vec![
+            CLIPSValue::Symbol("first".to_string()),
+            CLIPSValue::Symbol("second".to_string()),
+        ])
+    );
+}
+
+#[test]
+fn saliences_are_kept_without_a_refresh() {
+    let env = env_with_activations();
+
+    env.run().unwrap();
+
+    assert_eq!(
+        fired(&env),
+        CLIPSValue::Multifield(vec![
+            CLIPSValue::Symbol("second".to_string()),
+            CLIPSValue::Symbol("first".to_string()),
+        ])
+    );
+}
+
+// Rules that already fired are activated again by the facts still in working memory.
+#[test]
+fn working_memory_is_kept() {
+    let env = env_with_activations();
+    env.run().unwrap();
+
+    env.refresh_agenda().unwrap();
+
+    assert_eq!(env.run().unwrap(), 2);
+}
@@ -0,0 +1,94 @@
+use clips::{CLIPSError, CLIPSValue, Environment, ValueTarget};
+
+fn retrieved_multifield() -> CLIPSValue {
+    let source = Environment::new();
+    source
+        .load_from_str("(defglobal ?*readings* = (create$ a 1 \"two\" 3.5))")
+        .unwrap();
+
+    source.retrieve_globals_values().unwrap()["MAIN"]["readings"].clone_detached()
+}
+
+fn expected_multifield() -> CLIPSValue {
+    CLIPSValue::Multifield(vec![
+        CLIPSValue::Symbol("a".into()),
+        CLIPSValue::Int(1),
+        CLIPSValue::String("two".into()),
+        CLIPSValue::Float(3.5),
+    ])
+}
+
+// The environment the value came from is gone by the time it's put in the other one.
+#[test]
+fn multifields_move_into_a_global() {
+    let value = retrieved_multifield();
+    let target = Environment::new();
+    target
+        .load_from_str("(defglobal ?*readings* = (create$))")
+        .unwrap();
+
+    target
+        .reinsert_value(
+            ValueTarget::Global {
+                name: "readings".into(),
+            },
+            value,
+        )
+        .unwrap();
+
+    assert_eq!(
+        target.retrieve_globals_values().unwrap()["MAIN"]["readings"],
+        expected_multifield()
+    );
+}
+
+// `instance-name-to-symbol` makes a symbol with the same text as the instance's name, which the instance must still be found by.
+#[test]
+fn multifields_move_into_an_instance_slot() {
+    let value = retrieved_multifield();
+    let target = Environment::new();
+    target
+        .load_from_str(
+            "(defclass sensor (is-a USER) (multislot readings))
+             (defglobal ?*made* = (instance-name-to-symbol (make-instance probe of sensor)))",
+        )
+        .unwrap();
+
+    target
+        .reinsert_value(
+            ValueTarget::InstanceSlot {
+                instance: "probe".into(),
+                slot: "readings".into(),
+            },
+            value,
+        )
+        .unwrap();
+
+    let instances = target.find_all_instances("sensor", "TRUE").unwrap();
+    assert_eq!(instances[0].slot("readings"), Some(&expected_multifield()));
+}
+
+#[test]
+fn missing_targets_are_reported() {
+    let target = Environment::new();
+
+    assert!(matches!(
+        target.reinsert_value(
+            ValueTarget::Global {
+                name: "readings".into()
+            },
+            expected_multifield()
+        ),
+        Err(CLIPSError::DefglobalNotFound)
+    ));
+    assert!(matches!(
+        target.reinsert_value(
+            ValueTarget::InstanceSlot {
+                instance: "probe".into(),
+                slot: "readings".into()
+            },
+            expected_multifield()
+        ),
+        Err(CLIPSError::InstanceNotFound)
+    ));
+}
@@ -0,0 +1,52 @@
+use clips::{CLIPSError, ConflictResolutionStrategy, Environment};
+
+const PROGRAM: &str = "
+    (defrule simple (item ?x) =>)
+    (defrule tested (item ?x&:(> ?x 1)) =>)
+    (defrule joined (item ?x) (item ?y&~?x) (test (> ?y ?x)) =>)";
+
+#[test]
+fn more_specific_rules_get_higher_complexity() {
+    let env = Environment::new();
+    env.load_from_str(PROGRAM).unwrap();
+
+    // Every pattern and every test on top of the variable bindings adds to it.
+    assert_eq!(env.rule_complexity("simple").unwrap(), 1);
+    assert_eq!(env.rule_complexity("tested").unwrap(), 2);
+    assert_eq!(env.rule_complexity("joined").unwrap(), 4);
+    assert_eq!(env.rule_info("joined").unwrap().complexity, 4);
+
+    assert!(matches!(
+        env.rule_complexity("missing"),
+        Err(CLIPSError::RuleNotFound)
+    ));
+}
+
+#[test]
+fn activation_timetags_follow_creation_order() {
+    let env = Environment::new();
+    env.load_from_str(PROGRAM).unwrap();
+    env.set_conflict_resolution_strategy(ConflictResolutionStrategy::Breadth)
+        .unwrap();
+
+    env.assert_string("(item 1)").unwrap();
+    env.assert_string("(item 2)").unwrap();
+
+    let agenda = env.agenda().unwrap();
+    let timetags: Vec<_> = agenda.iter().map(|activation| activation.timetag).collect();
+    // Breadth fires the oldest activation first, so the agenda is in timetag order.
+    assert!(timetags.windows(2).all(|pair| pair[0] < pair[1]));
+    assert_eq!(agenda[0].rule, "simple");
+
+    // Depth orders the same activations newest first.
+    env.set_conflict_resolution_strategy(ConflictResolutionStrategy::Depth)
+        .unwrap();
+    let mut reversed: Vec<_> = env
+        .agenda()
+        .unwrap()
+        .iter()
+        .map(|activation| activation.timetag)
+        .collect();
+    reversed.reverse();
+    assert_eq!(reversed, timetags);
+}
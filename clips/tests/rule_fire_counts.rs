@@ -0,0 +1,124 @@
+use std::{collections::HashMap, fs};
+
+use clips::Environment;
+
+fn env() -> Environment {
+    let env = Environment::new();
+    env.load_from_str(
+        "(defmodule MAIN (export ?ALL))
+         (defrule count (tick ?n&:(> ?n 0)) => (assert (tick (- ?n 1))))
+         (defrule idle (never) => )
+         (defrule visit (visit ?) => (focus OTHER))
+         (defmodule OTHER (import MAIN ?ALL))
+         (defrule echo (visit ?) => )",
+    )
+    .unwrap();
+    env
+}
+
+fn counts(entries: &[(&str, u64)]) -> HashMap<String, u64> {
+    entries
+        .iter()
+        .map(|(name, count)| (name.to_string(), *count))
+        .collect()
+}
+
+#[test]
+fn counts_add_up_across_runs_by_qualified_name() {
+    let env = env();
+
+    env.assert_string("(tick 3)").unwrap();
+    env.assert_string("(visit 1)").unwrap();
+    env.run().unwrap();
+    assert_eq!(
+        env.rule_fire_counts().unwrap(),
+        counts(&[("MAIN::count", 3), ("MAIN::visit", 1), ("OTHER::echo", 1)])
+    );
+
+    env.retract_where("tick", "TRUE").unwrap();
+    env.retract_where("visit", "TRUE").unwrap();
+    env.assert_string("(tick 2)").unwrap();
+    env.assert_string("(visit 2)").unwrap();
+    env.run().unwrap();
+    assert_eq!(
+        env.rule_fire_counts().unwrap(),
+        counts(&[("MAIN::count", 5), ("MAIN::visit", 2), ("OTHER::echo", 2)])
+    );
+}
+
+#[test]
+fn counts_can_be_reset() {
+    let env = env();
+    env.assert_string("(tick 2)").unwrap();
+    env.run().unwrap();
+
+    env.reset_fire_counts().unwrap();
+    assert!(env.rule_fire_counts().unwrap().is_empty());
+
+    env.assert_string("(tick 3)").unwrap();
+    env.run().unwrap();
+    assert_eq!(
+        env.rule_fire_counts().unwrap(),
+        counts(&[("MAIN::count", 1)])
+    );
+}
+
+#[test]
+fn counts_are_dropped_by_clear() {
+    let env = env();
+    env.assert_string("(tick 2)").unwrap();
+    env.run().unwrap();
+
+    let path = std::env::temp_dir().join(format!(
+        "clips-rs-test-rule-fire-counts-{}",
+        std::process::id()
+    ));
+    fs::write(&path, "(clear)\n").unwrap();
+    let res = env.batch_star(path.clone());
+    fs::remove_file(path).unwrap();
+    res.unwrap();
+
+    assert!(env.rule_fire_counts().unwrap().is_empty());
+}
+
+#[test]
+fn firings_of_every_disjunct_are_counted() {
+    let env = Environment::new();
+    env.load_from_str("(defrule either (or (a ?x) (b ?x)) => )")
+        .unwrap();
+    env.assert_string("(a 1)").unwrap();
+    env.assert_string("(b 1)").unwrap();
+    env.assert_string("(b 2)").unwrap();
+    env.run().unwrap();
+
+    assert_eq!(
+        env.rule_fire_counts().unwrap(),
+        counts(&[("MAIN::either", 3)])
+    );
+}
+
+#[test]
+fn removed_rules_leave_no_count_behind() {
+    let env = Environment::new();
+    env.load_from_str("(defrule first (tick ?) => )").unwrap();
+    env.assert_string("(tick 1)").unwrap();
+    env.run().unwrap();
+
+    // The new rule is likely to be put where the removed one was.
+    env.load_from_str("(defrule first (never) => ) (defrule second (tick ?) => )")
+        .unwrap();
+    assert!(env.rule_fire_counts().unwrap().is_empty());
+}
+
+#[test]
+fn redefined_rules_start_over() {
+    let env = Environment::new();
+    env.load_from_str("(defrule count (tick ?n&:(> ?n 0)) => (assert (tick (- ?n 1))))")
+        .unwrap();
+    env.assert_string("(tick 2)").unwrap();
+    env.run().unwrap();
+
+    env.load_from_str("(defrule count (tick ?n&:(> ?n 0)) => (assert (tick (- ?n 1))))")
+        .unwrap();
+    assert!(env.rule_fire_counts().unwrap().is_empty());
+}
@@ -0,0 +1,72 @@
+use clips::{CLIPSError, Environment};
+
+const TEMPLATES: &str = "
+    (deftemplate a (slot x))
+    (deftemplate b (slot y))
+    (deftemplate c (slot x) (slot y))";
+
+// Every `a` with every `b` is a partial match before `c` narrows them down.
+const EXPENSIVE: &str = "(defrule expensive (a (x ?x)) (b (y ?y)) (c (x ?x) (y ?y)) =>)";
+// The same rule with `c` first only keeps the combinations `c` allows.
+const CHEAP: &str = "(defrule cheap (c (x ?x) (y ?y)) (a (x ?x)) (b (y ?y)) =>)";
+
+fn env(rules: &[&str]) -> Environment {
+    let env = Environment::new();
+    env.load_from_str(TEMPLATES).unwrap();
+    for rule in rules {
+        env.load_from_str(*rule).unwrap();
+    }
+
+    for n in 0..20 {
+        env.assert_string(&format!("(a (x {n}))")).unwrap();
+        env.assert_string(&format!("(b (y {n}))")).unwrap();
+    }
+    for n in 0..3 {
+        env.assert_string(&format!("(c (x {n}) (y {n}))")).unwrap();
+    }
+    env
+}
+
+#[test]
+fn a_large_intermediate_join_shows_up_in_its_count() {
+    let env = env(&[EXPENSIVE, CHEAP]);
+
+    assert_eq!(env.rule_partial_matches("expensive").unwrap(), [400, 3]);
+    assert_eq!(env.rule_partial_matches("cheap").unwrap(), [3, 3]);
+}
+
+#[test]
+fn counts_follow_changes_to_working_memory() {
+    let env = env(&[EXPENSIVE]);
+
+    env.retract_where("a", "(>= ?f:x 10)").unwrap();
+    assert_eq!(env.rule_partial_matches("expensive").unwrap(), [200, 3]);
+
+    env.retract_where("c", "TRUE").unwrap();
+    assert_eq!(env.rule_partial_matches("expensive").unwrap(), [200, 0]);
+}
+
+#[test]
+fn negated_conditions_have_their_own_join() {
+    let env = env(&["(defrule lonely (a (x ?x)) (not (c (x ?x))) (b (y ?x)) =>)"]);
+
+    // 17 of the 20 `a`s have no `c`, and each of them has a `b` with the same number.
+    assert_eq!(env.rule_partial_matches("lonely").unwrap(), [17, 17]);
+}
+
+#[test]
+fn each_disjunct_of_a_rule_with_or_has_its_own_counts() {
+    let env = env(&["(defrule either (or (a (x ?v)) (b (y ?v))) (c (x ?v)) =>)"]);
+
+    assert_eq!(env.rule_partial_matches("either").unwrap(), [3, 3]);
+}
+
+#[test]
+fn unknown_rules_are_rejected() {
+    let env = env(&[]);
+
+    assert!(matches!(
+        env.rule_partial_matches("missing"),
+        Err(CLIPSError::RuleNotFound)
+    ));
+}
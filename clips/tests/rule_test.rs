@@ -0,0 +1,88 @@
+use clips::{
+    testing::{FactPattern, RuleTest},
+    CLIPSValue, SlotMap,
+};
+
+const RULES: &str = "
+    (deftemplate order (slot id) (slot total))
+    (defrule discount
+      (order (id ?id) (total ?total&:(> ?total 100)))
+      =>
+      (assert (discount ?id 10))
+      (printout t \"discount for \" ?id crlf))
+    (defrule thank
+      (order (id ?id))
+      =>
+      (assert (thanked ?id)))";
+
+fn rules() -> RuleTest {
+    RuleTest::new().constructs(RULES)
+}
+
+#[test]
+fn met_expectations_pass() {
+    rules()
+        .given_fact(SlotMap::new("order").slot("id", 1).slot("total", 150))
+        .given_fact_str("(order (id 2) (total 50))")
+        .expect_fact(FactPattern::ordered(
+            "discount",
+            vec![CLIPSValue::Int(1), CLIPSValue::Int(10)],
+        ))
+        .expect_fact(FactPattern::new("order").slot("id", 2))
+        .expect_no_fact(FactPattern::ordered(
+            "discount",
+            vec![CLIPSValue::Int(2), CLIPSValue::Int(10)],
+        ))
+        .expect_fired_rules(["thank", "discount", "thank"])
+        .expect_output("discount for 1\n")
+        .assert_passes();
+}
+
+#[test]
+fn every_unmet_expectation_is_reported() {
+    let outcome = rules()
+        .given_fact_str("(order (id 1) (total 150))")
+        .expect_fact(FactPattern::new("order").slot("id", 3))
+        .expect_fact(FactPattern::new("refund"))
+        .expect_no_fact(FactPattern::new("discount"))
+        .expect_fired_rules(["discount"])
+        .expect_output("discount for 2")
+        .run()
+        .unwrap();
+
+    assert!(!outcome.passed());
+    assert_eq!(outcome.fired_rules, ["discount", "thank"]);
+    assert_eq!(outcome.output, "discount for 1\n");
+    assert_eq!(
+        outcome.failures,
+        [
+            "expected a fact matching (order (id 3)), but the order facts were:\n  f-1 (order (id 1) (total 150))",
+            "expected a fact matching (refund), but there were no refund facts",
+            "expected no fact matching (discount), but found:\n  f-2 (discount 1 10)",
+            "the rules didn't fire as expected:\n     expected  actual\n    1 discount  discount\n!   2 -         thank",
+            "expected the output to contain \"discount for 2\", but it was:\ndiscount for 1\n",
+        ]
+    );
+
+    let report = outcome.to_string();
+    assert!(report.starts_with("the rule test failed:\n"));
+    for failure in &outcome.failures {
+        assert!(report.contains(failure.as_str()));
+    }
+}
+
+#[test]
+#[should_panic(expected = "expected a fact matching (refund)")]
+fn assert_passes_panics_with_the_failures() {
+    rules()
+        .expect_fact(FactPattern::new("refund"))
+        .assert_passes();
+}
+
+#[test]
+fn constructs_that_dont_load_are_an_error() {
+    assert!(RuleTest::new()
+        .constructs("(defrule broken (x) => (")
+        .run()
+        .is_err());
+}
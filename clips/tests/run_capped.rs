@@ -0,0 +1,67 @@
+use clips::{CLIPSError, CLIPSValue, Environment};
+
+// `loop` keeps activating itself forever, while `countdown` runs out after `?n` firings.
+fn env() -> Environment {
+    let env = Environment::new();
+    env.load_from_str(
+        "(deftemplate tick (slot n))
+         (defrule loop
+           ?tick <- (tick (n ?n))
+           =>
+           (modify ?tick (n (+ ?n 1))))
+         (defrule countdown
+           (countdown ?n&:(> ?n 0))
+           =>
+           (assert (countdown (- ?n 1))))",
+    )
+    .unwrap();
+    env
+}
+
+fn tick(env: &Environment) -> i64 {
+    match env.find_all_facts("tick", "TRUE").unwrap()[0].slot("n") {
+        Some(CLIPSValue::Int(n)) => *n,
+        other => panic!("unexpected slot value {other:?}"),
+    }
+}
+
+#[test]
+fn a_self_perpetuating_rule_hits_the_cap() {
+    let env = env();
+    env.assert_string("(tick (n 0))").unwrap();
+
+    assert!(matches!(
+        env.run_capped(100),
+        Err(CLIPSError::FiringCapExceeded { max_firings: 100 })
+    ));
+    // The firings before the cap stay done.
+    assert_eq!(tick(&env), 100);
+}
+
+#[test]
+fn rules_that_quiesce_under_the_cap_return_normally() {
+    let env = env();
+    env.assert_string("(countdown 10)").unwrap();
+
+    assert_eq!(env.run_capped(100).unwrap(), 10);
+}
+
+#[test]
+fn quiescing_exactly_at_the_cap_is_not_an_error() {
+    let env = env();
+    env.assert_string("(countdown 10)").unwrap();
+
+    assert_eq!(env.run_capped(10).unwrap(), 10);
+}
+
+#[test]
+fn one_firing_short_of_quiescing_is_an_error() {
+    let env = env();
+    env.assert_string("(countdown 10)").unwrap();
+
+    assert!(matches!(
+        env.run_capped(9),
+        Err(CLIPSError::FiringCapExceeded { max_firings: 9 })
+    ));
+    assert_eq!(env.run_capped(9).unwrap(), 1);
+}
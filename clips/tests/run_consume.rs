@@ -0,0 +1,90 @@
+use clips::{CLIPSError, CLIPSValue, Environment};
+
+const PROGRAM: &str = "
+    (deftemplate query (slot n))
+    (deftemplate result (slot value))
+    (defrule answer
+      ?query <- (query (n ?n))
+      =>
+      (retract ?query)
+      (assert (result (value (* ?n 2))))
+      (assert (result (value (* ?n 3)))))";
+
+fn env() -> Environment {
+    let env = Environment::new();
+    env.load_from_str(PROGRAM).unwrap();
+    env
+}
+
+fn values(env: &Environment, query: i64) -> Vec<i64> {
+    env.assert_string(&format!("(query (n {query}))")).unwrap();
+    env.run_consume("result")
+        .unwrap()
+        .into_iter()
+        .map(|fact| match fact.slot("value") {
+            Some(CLIPSValue::Int(value)) => *value,
+            value => panic!("unexpected value {value:?}"),
+        })
+        .collect()
+}
+
+#[test]
+fn a_second_query_does_not_see_the_results_of_the_first() {
+    let env = env();
+
+    assert_eq!(values(&env, 1), [2, 3]);
+    assert_eq!(values(&env, 10), [20, 30]);
+    assert!(env.find_all_facts("result", "TRUE").unwrap().is_empty());
+}
+
+#[test]
+fn results_with_logical_support_are_all_returned() {
+    let env = env();
+    env.load_from_str(
+        "(defrule derive
+           (logical (result (value ?v&:(< ?v 10))))
+           =>
+           (assert (result (value (* ?v 100)))))",
+    )
+    .unwrap();
+
+    // Retracting 2 and 3 also retracts 200 and 300, which they support.
+    let mut values = values(&env, 1);
+    values.sort();
+    assert_eq!(values, [2, 3, 200, 300]);
+    assert!(env.find_all_facts("result", "TRUE").unwrap().is_empty());
+}
+
+#[test]
+fn rules_activated_by_the_retractions_wait_for_the_next_run() {
+    let env = env();
+    env.load_from_str(
+        "(deftemplate idle)
+         (defrule notice-idle
+           (not (result))
+           (not (idle))
+           =>
+           (assert (idle)))",
+    )
+    .unwrap();
+    env.run().unwrap();
+    env.retract_where("idle", "TRUE").unwrap();
+
+    assert_eq!(values(&env, 1), [2, 3]);
+    assert!(env.find_all_facts("idle", "TRUE").unwrap().is_empty());
+
+    assert_eq!(env.run().unwrap(), 1);
+    assert_eq!(env.find_all_facts("idle", "TRUE").unwrap().len(), 1);
+}
+
+#[test]
+fn unknown_templates_are_rejected_without_running() {
+    let env = env();
+    env.assert_string("(query (n 1))").unwrap();
+
+    assert!(matches!(
+        env.run_consume("answer"),
+        Err(CLIPSError::TemplateNotFound)
+    ));
+    assert!(env.find_all_facts("result", "TRUE").unwrap().is_empty());
+}
@@ -0,0 +1,79 @@
+use std::time::{Duration, Instant};
+
+use clips::Environment;
+
+const FIRINGS: usize = 1_000_000;
+
+// Counts up to `FIRINGS` one firing at a time, with some busy work so each firing takes a while.
+fn env() -> Environment {
+    let env = Environment::new();
+    env.load_from_str(format!(
+        "(deftemplate counter (slot value))
+         (defrule count
+           ?counter <- (counter (value ?value&:(< ?value {FIRINGS})))
+           =>
+           (loop-for-count 200 do (+ 1 1))
+           (modify ?counter (value (+ ?value 1))))"
+    ))
+    .unwrap();
+    env.assert_string("(counter (value 0))").unwrap();
+    env
+}
+
+#[test]
+fn a_short_budget_stops_the_run_early() {
+    let env = env();
+
+    let start = Instant::now();
+    let fired = env.run_for(Duration::from_millis(50)).unwrap();
+    let elapsed = start.elapsed();
+
+    assert!(fired > 0);
+    assert!(fired < FIRINGS);
+    assert!(elapsed >= Duration::from_millis(50));
+    assert!(elapsed < Duration::from_secs(1), "took {elapsed:?}");
+
+    // The rest of the work is still on the agenda for the next run.
+    let fired_next = env.run_for(Duration::from_millis(50)).unwrap();
+    assert!(fired_next > 0);
+    assert_eq!(
+        env.find_all_facts("counter", "TRUE").unwrap()[0].slot("value"),
+        Some(&((fired + fired_next) as i64).into())
+    );
+}
+
+#[test]
+fn a_run_that_finishes_within_the_budget_fires_everything() {
+    let env = Environment::new();
+    env.load_from_str("(defrule once (go) => (assert (done)))")
+        .unwrap();
+    env.assert_string("(go)").unwrap();
+
+    let start = Instant::now();
+    assert_eq!(env.run_for(Duration::from_secs(10)).unwrap(), 1);
+    assert!(start.elapsed() < Duration::from_secs(1));
+}
+
+#[test]
+fn a_zero_budget_fires_nothing() {
+    let env = env();
+    assert_eq!(env.run_for(Duration::ZERO).unwrap(), 0);
+}
+
+#[test]
+fn the_halt_does_not_stick_after_the_run() {
+    let env = Environment::new();
+    env.load_from_str(
+        "(deftemplate counter (slot value))
+         (defrule count
+           ?counter <- (counter (value ?value&:(< ?value 10)))
+           =>
+           (modify ?counter (value (+ ?value 1))))",
+    )
+    .unwrap();
+    env.assert_string("(counter (value 0))").unwrap();
+
+    assert_eq!(env.run_for(Duration::from_secs(10)).unwrap(), 10);
+    env.assert_string("(counter (value 5))").unwrap();
+    assert_eq!(env.run().unwrap(), 5);
+}
@@ -0,0 +1,104 @@
+use clips::{Environment, RunStopReason};
+
+const PROGRAM: &str = "
+    (deffunction fail () (div 1 0))
+    (defrule ok
+      (declare (salience 10))
+      (ok)
+      =>)
+    (defrule boom
+      (boom)
+      =>
+      (fail))
+    (defrule after
+      (declare (salience -10))
+      (after)
+      =>)";
+
+fn env() -> Environment {
+    let env = Environment::new();
+    env.load_from_str(PROGRAM).unwrap();
+    env
+}
+
+#[test]
+fn a_clean_run_has_no_evaluation_error() {
+    let env = env();
+    env.assert_string("(ok)").unwrap();
+
+    let outcome = env.run_detailed().unwrap();
+    assert_eq!(outcome.rules_fired, 1);
+    assert_eq!(outcome.last_fired_rule.as_deref(), Some("ok"));
+    assert_eq!(outcome.stop_reason, RunStopReason::Completed);
+    assert!(!outcome.evaluation_error);
+}
+
+#[test]
+fn a_failing_function_in_a_rule_action_is_reported() {
+    let env = env();
+    for fact in ["(ok)", "(boom)", "(after)"] {
+        env.assert_string(fact).unwrap();
+    }
+
+    let outcome = env.run_detailed().unwrap();
+    assert!(outcome.evaluation_error);
+    assert_eq!(outcome.rules_fired, 2);
+    assert_eq!(outcome.last_fired_rule.as_deref(), Some("boom"));
+    // The rule after the failing one is still on the agenda.
+    assert_eq!(outcome.stop_reason, RunStopReason::Halted);
+
+    // The error belongs to the run that had it.
+    let outcome = env.run_detailed().unwrap();
+    assert!(!outcome.evaluation_error);
+    assert_eq!(outcome.rules_fired, 1);
+    assert_eq!(outcome.last_fired_rule.as_deref(), Some("after"));
+    assert_eq!(outcome.stop_reason, RunStopReason::Completed);
+}
+
+#[test]
+fn an_error_in_the_last_activation_still_completes_the_run() {
+    let env = env();
+    env.assert_string("(boom)").unwrap();
+
+    let outcome = env.run_detailed().unwrap();
+    assert!(outcome.evaluation_error);
+    assert_eq!(outcome.rules_fired, 1);
+    assert_eq!(outcome.stop_reason, RunStopReason::Completed);
+}
+
+#[test]
+fn an_explicit_halt_is_reported() {
+    let env = env();
+    env.load_from_str("(defrule stop (stop) => (halt))")
+        .unwrap();
+    for fact in ["(stop)", "(after)"] {
+        env.assert_string(fact).unwrap();
+    }
+
+    let outcome = env.run_detailed().unwrap();
+    assert!(!outcome.evaluation_error);
+    assert_eq!(outcome.rules_fired, 1);
+    assert_eq!(outcome.last_fired_rule.as_deref(), Some("stop"));
+    assert_eq!(outcome.stop_reason, RunStopReason::Halted);
+}
+
+#[test]
+fn a_breakpoint_on_the_next_rule_is_reported() {
+    let env = env();
+    env.load_from_str("(defglobal ?*unused* = (set-break after))")
+        .unwrap();
+    for fact in ["(ok)", "(after)"] {
+        env.assert_string(fact).unwrap();
+    }
+
+    let outcome = env.run_detailed().unwrap();
+    assert_eq!(outcome.rules_fired, 1);
+    assert_eq!(outcome.last_fired_rule.as_deref(), Some("ok"));
+    assert_eq!(outcome.stop_reason, RunStopReason::Breakpoint);
+
+    // CLIPS doesn't stop at a breakpoint before the first rule of a run.
+    let outcome = env.run_detailed().unwrap();
+    assert_eq!(outcome.rules_fired, 1);
+    assert_eq!(outcome.last_fired_rule.as_deref(), Some("after"));
+    assert_eq!(outcome.stop_reason, RunStopReason::Completed);
+}
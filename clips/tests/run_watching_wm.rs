@@ -0,0 +1,61 @@
+use std::sync::mpsc;
+
+use clips::{Environment, WmChange};
+
+fn asserted(index: i64, template: &str) -> WmChange {
+    WmChange::Asserted {
+        index,
+        template: template.to_string(),
+    }
+}
+
+fn retracted(index: i64, template: &str) -> WmChange {
+    WmChange::Retracted {
+        index,
+        template: template.to_string(),
+    }
+}
+
+#[test]
+fn assertions_and_retractions_are_streamed_in_order() {
+    let env = Environment::new();
+    env.load_from_str(
+        "
+        (deftemplate step (slot number))
+        (defrule begin ?start <- (start)
+          => (retract ?start) (assert (step (number 1))))
+        (defrule finish ?step <- (step)
+          => (retract ?step) (assert (done)))
+        (defglobal ?*start* = (fact-index (assert (start))))
+        ",
+    )
+    .unwrap();
+    let (tx, rx) = mpsc::channel();
+
+    let fired = env.run_watching_wm(tx).unwrap();
+
+    assert_eq!(fired, 2);
+    // Ordered facts are reported with the name of the template CLIPS makes for them.
+    assert_eq!(
+        rx.iter().collect::<Vec<_>>(),
+        vec![
+            retracted(1, "start"),
+            asserted(2, "step"),
+            retracted(2, "step"),
+            asserted(3, "done"),
+        ]
+    );
+}
+
+// The sender is dropped when the run is over, and changes made outside of it aren't sent.
+#[test]
+fn changes_outside_of_the_run_are_not_streamed() {
+    let env = Environment::new();
+    let (tx, rx) = mpsc::channel();
+
+    env.run_watching_wm(tx).unwrap();
+    env.load_from_str("(defglobal ?*later* = (fact-index (assert (later))))")
+        .unwrap();
+
+    assert_eq!(rx.iter().count(), 0);
+}
@@ -0,0 +1,57 @@
+use std::fs;
+
+use clips::{CLIPSError, CLIPSValue, Environment};
+
+fn env_with_modules() -> Environment {
+    let env = Environment::new();
+    env.load_from_str(
+        "
+        (defmodule A (export ?ALL))
+        (deftemplate A::reading (slot value))
+        (defrule A::high (reading (value ?v&:(> ?v 10))) =>)
+        (defmodule B (import A ?ALL))
+        (defrule B::low (reading (value ?v&:(< ?v 0))) =>)
+        ",
+    )
+    .unwrap();
+    env
+}
+
+fn rules(env: &Environment) -> CLIPSValue {
+    env.load_from_str("(defglobal MAIN ?*rules* = (get-defrule-list *))")
+        .unwrap();
+    env.retrieve_globals_values().unwrap()["MAIN"]["rules"].clone()
+}
+
+#[test]
+fn only_the_saved_module_is_loaded_elsewhere() {
+    let env = env_with_modules();
+    let path = std::env::temp_dir().join(format!("clips-save-module-{}.clp", std::process::id()));
+
+    let saved = env.save_module("A", path.clone()).unwrap();
+    let text = fs::read_to_string(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+
+    // The defmodule, the deftemplate and the defrule.
+    assert_eq!(saved, 3);
+
+    let other = Environment::new();
+    other.load_from_str(text.as_str()).unwrap();
+
+    assert_eq!(
+        rules(&other),
+        CLIPSValue::Multifield(vec![CLIPSValue::Symbol("A::high".into())])
+    );
+}
+
+#[test]
+fn unknown_modules_are_reported() {
+    let env = env_with_modules();
+    let path = std::env::temp_dir().join("clips-save-module-unknown.clp");
+
+    assert!(matches!(
+        env.save_module("C", path.clone()),
+        Err(CLIPSError::ModuleNotFound)
+    ));
+    assert!(!path.exists());
+}
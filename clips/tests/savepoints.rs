@@ -0,0 +1,92 @@
+use std::collections::HashMap;
+
+use clips::{CLIPSEnvironment, CLIPSError, CLIPSValue, SlotMap};
+
+const PROGRAM: &str = r#"
+(defglobal ?*count* = 0)
+(deftemplate point (slot x))
+(deffunction double (?x) (* ?x 2))
+"#;
+
+fn point_xs(env: &mut CLIPSEnvironment) -> Vec<CLIPSValue> {
+    let mut xs = env
+        .find_all_facts("point", "TRUE")
+        .unwrap()
+        .iter()
+        .map(|fact| fact.slot("x").unwrap().clone())
+        .collect::<Vec<_>>();
+    xs.sort_by_key(|x| format!("{:?}", x));
+    xs
+}
+
+#[test]
+fn rollback_restores_facts_and_globals() {
+    let mut env = CLIPSEnvironment::new().unwrap();
+    env.load_from_str(PROGRAM).unwrap();
+    env.assert_string("(point (x 1))").unwrap();
+    env.savepoint("start").unwrap();
+
+    env.assert_string("(point (x 2))").unwrap();
+    env.restore_globals(HashMap::from([(
+        "MAIN".to_string(),
+        HashMap::from([("count".to_string(), CLIPSValue::Int(5))]),
+    )]))
+    .unwrap();
+
+    env.rollback_to("start").unwrap();
+
+    assert_eq!(point_xs(&mut env), vec![CLIPSValue::Int(1)]);
+    assert_eq!(
+        env.retrieve_globals_values().unwrap()["MAIN"]["count"],
+        CLIPSValue::Int(0)
+    );
+
+    // The savepoint is kept, so it can be rolled back to again.
+    env.assert_string("(point (x 3))").unwrap();
+    env.rollback_to("start").unwrap();
+    assert_eq!(point_xs(&mut env), vec![CLIPSValue::Int(1)]);
+}
+
+#[test]
+fn rollback_restores_facts_in_other_modules() {
+    let mut env = CLIPSEnvironment::new().unwrap();
+    env.load_into_module("M", "(deftemplate point (slot x))")
+        .unwrap();
+    env.assert_fact(Box::new(SlotMap::new("point").slot("x", 1)), Some("M"))
+        .unwrap();
+    env.savepoint("start").unwrap();
+
+    env.assert_fact(Box::new(SlotMap::new("point").slot("x", 2)), Some("M"))
+        .unwrap();
+    env.rollback_to("start").unwrap();
+
+    assert_eq!(env.construct_summary().unwrap().facts, 1);
+}
+
+#[test]
+fn redefined_construct_bodies_are_noticed() {
+    let mut env = CLIPSEnvironment::new().unwrap();
+    env.load_from_str(PROGRAM).unwrap();
+    env.savepoint("start").unwrap();
+
+    env.load_from_str("(deffunction double (?x) (* ?x 3))")
+        .unwrap();
+
+    assert!(matches!(
+        env.rollback_to("start"),
+        Err(CLIPSError::SavepointConstructsChanged)
+    ));
+}
+
+#[test]
+fn released_savepoints_are_gone() {
+    let mut env = CLIPSEnvironment::new().unwrap();
+    env.savepoint("start").unwrap();
+
+    assert!(env.release_savepoint("start"));
+    assert!(!env.release_savepoint("start"));
+    assert!(matches!(
+        env.rollback_to("start"),
+        Err(CLIPSError::SavepointNotFound)
+    ));
+}
@@ -0,0 +1,69 @@
+use clips::{CLIPSValue, Environment};
+
+// `$?x` either expands into separate arguments or is passed as a single multifield, which ends up in `?first`.
+const PROGRAM: &str = "
+    (deftemplate split (multislot first) (multislot rest))
+    (deffunction first-arg (?first $?rest) ?first)
+    (deffunction rest-args (?first $?rest) ?rest)
+    (defrule split
+      (items $?x)
+      =>
+      (assert (split (first (first-arg $?x)) (rest (rest-args $?x)))))";
+
+fn symbols(values: &[&str]) -> CLIPSValue {
+    CLIPSValue::Multifield(
+        values
+            .iter()
+            .map(|value| CLIPSValue::Symbol(value.to_string()))
+            .collect(),
+    )
+}
+
+fn split(env: &Environment) -> (CLIPSValue, CLIPSValue) {
+    env.assert_string("(items a b c)").unwrap();
+    env.run().unwrap();
+
+    let facts = env.find_all_facts("split", "TRUE").unwrap();
+    assert_eq!(facts.len(), 1);
+    (
+        facts[0].slot("first").unwrap().clone(),
+        facts[0].slot("rest").unwrap().clone(),
+    )
+}
+
+#[test]
+fn recognition_is_off_by_default() {
+    let env = Environment::new();
+    assert!(!env.get_sequence_operator_recognition().unwrap());
+
+    env.set_sequence_operator_recognition(true).unwrap();
+    assert!(env.get_sequence_operator_recognition().unwrap());
+}
+
+#[test]
+fn without_recognition_the_multifield_is_one_argument() {
+    let env = Environment::new();
+    env.set_sequence_operator_recognition(false).unwrap();
+    env.load_from_str(PROGRAM).unwrap();
+
+    assert_eq!(split(&env), (symbols(&["a", "b", "c"]), symbols(&[])));
+}
+
+#[test]
+fn with_recognition_the_multifield_is_expanded() {
+    let env = Environment::new();
+    env.set_sequence_operator_recognition(true).unwrap();
+    env.load_from_str(PROGRAM).unwrap();
+
+    assert_eq!(split(&env), (symbols(&["a"]), symbols(&["b", "c"])));
+}
+
+#[test]
+fn the_setting_applies_when_constructs_are_parsed() {
+    let env = Environment::new();
+    env.set_sequence_operator_recognition(true).unwrap();
+    env.load_from_str(PROGRAM).unwrap();
+    env.set_sequence_operator_recognition(false).unwrap();
+
+    assert_eq!(split(&env), (symbols(&["a"]), symbols(&["b", "c"])));
+}
@@ -0,0 +1,97 @@
+use std::sync::mpsc::TryRecvError;
+
+use clips::{CLIPSError, CLIPSValue, Environment, SlotChange};
+
+const PROGRAM: &str = "
+    (deftemplate order (slot status) (slot count (default 0)))
+    (deftemplate shipment (slot status))
+    (defrule count
+      ?order <- (order (count ?count&:(< ?count 1000)))
+      =>
+      (modify ?order (count (+ ?count 1))))
+    (defrule close
+      ?order <- (order (status open) (count 1000))
+      =>
+      (modify ?order (status closed)))
+    (defrule ship
+      ?shipment <- (shipment (status open))
+      =>
+      (modify ?shipment (status sent)))";
+
+fn env() -> Environment {
+    let env = Environment::new();
+    env.load_from_str(PROGRAM).unwrap();
+    env
+}
+
+fn symbol(value: &str) -> CLIPSValue {
+    CLIPSValue::Symbol(value.to_string())
+}
+
+#[test]
+fn only_changes_of_the_watched_slot_are_sent() {
+    let env = env();
+    let (_, changes) = env.watch_slot("order", "status").unwrap();
+
+    let order = env.assert_string("(order (status open))").unwrap();
+    env.assert_string("(shipment (status open))").unwrap();
+    env.run().unwrap();
+
+    // A thousand modifications of `count` and one of another template's `status` go unnoticed.
+    assert_eq!(
+        changes.try_iter().collect::<Vec<_>>(),
+        [SlotChange {
+            index: order,
+            old: symbol("open"),
+            new: symbol("closed"),
+        }]
+    );
+}
+
+#[test]
+fn modifications_that_keep_the_value_are_not_sent() {
+    let env = env();
+    env.load_from_str(
+        "(defrule touch
+           ?order <- (order (status pending) (count 0))
+           =>
+           (modify ?order (status pending) (count -1)))",
+    )
+    .unwrap();
+    let (_, changes) = env.watch_slot("order", "status").unwrap();
+
+    env.assert_string("(order (status pending))").unwrap();
+    env.run().unwrap();
+
+    assert_eq!(changes.try_recv(), Err(TryRecvError::Empty));
+}
+
+#[test]
+fn unwatching_closes_the_channel() {
+    let env = env();
+    let (id, changes) = env.watch_slot("order", "status").unwrap();
+    let (_, other_changes) = env.watch_slot("order", "status").unwrap();
+
+    assert!(env.unwatch_slot(id).unwrap());
+    assert!(!env.unwatch_slot(id).unwrap());
+
+    env.assert_string("(order (status open))").unwrap();
+    env.run().unwrap();
+
+    assert_eq!(changes.try_recv(), Err(TryRecvError::Disconnected));
+    assert_eq!(other_changes.try_iter().count(), 1);
+}
+
+#[test]
+fn unknown_templates_and_slots_are_rejected() {
+    let env = env();
+
+    assert!(matches!(
+        env.watch_slot("invoice", "status"),
+        Err(CLIPSError::TemplateNotFound)
+    ));
+    assert!(matches!(
+        env.watch_slot("order", "colour"),
+        Err(CLIPSError::SlotNotFound)
+    ));
+}
@@ -0,0 +1,72 @@
+use std::collections::HashMap;
+
+use clips::{CLIPSValue, EnvStats, Environment, SlotMap};
+
+fn env() -> Environment {
+    let env = Environment::new();
+    env.load_from_str(
+        "(deftemplate order (slot id) (slot status (default open)))
+         (defclass point (is-a USER) (slot x))
+         (defrule close
+           ?order <- (order (status open))
+           =>
+           (modify ?order (status closed)))",
+    )
+    .unwrap();
+    env
+}
+
+#[test]
+fn assertions_and_retractions_are_counted() {
+    let env = env();
+    assert_eq!(env.stats().unwrap(), EnvStats::default());
+
+    for id in 1..=3 {
+        env.assert_fact(SlotMap::new("order").slot("id", id), None)
+            .unwrap();
+    }
+    env.assert_string("(note)").unwrap();
+    // Already there, so nothing new is asserted.
+    env.assert_string("(note)").unwrap();
+    env.retract_where("note", "TRUE").unwrap();
+
+    let stats = env.stats().unwrap();
+    assert_eq!(stats.assertions, 4);
+    assert_eq!(stats.retractions, 1);
+
+    // Each of the three `modify`s is a retraction and an assertion.
+    env.run().unwrap();
+    env.retract_where("order", "(eq ?f:id 1)").unwrap();
+
+    assert_eq!(
+        env.stats().unwrap(),
+        EnvStats {
+            assertions: 7,
+            retractions: 5,
+            instances_made: 0,
+        }
+    );
+}
+
+#[test]
+fn instances_made_through_the_library_are_counted() {
+    let env = env();
+
+    env.make_instance(SlotMap::new("point").slot("x", 1), None, None)
+        .unwrap();
+    env.make_instance_from_map(
+        "point",
+        Some("b".to_string()),
+        HashMap::from([("x".to_string(), CLIPSValue::Int(2))]),
+    )
+    .unwrap();
+    // Failing to make one doesn't count.
+    assert!(env
+        .make_instance(SlotMap::new("missing").slot("x", 1), None, None)
+        .is_err());
+    // Neither does `make-instance` in CLIPS code.
+    env.load_from_str("(defglobal ?*made* = (make-instance c of point))")
+        .unwrap();
+
+    assert_eq!(env.stats().unwrap().instances_made, 2);
+}
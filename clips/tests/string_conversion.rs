@@ -0,0 +1,40 @@
+use std::fs;
+
+use clips::{set_string_conversion, CLIPSEnvironment, CLIPSValue, StringConversion};
+
+// CLIPS code given as `&str` can't hold invalid UTF-8, so the slot is filled with a line read from a file.
+fn env_with_invalid_slot() -> CLIPSEnvironment {
+    let path = std::env::temp_dir().join(format!("clips-invalid-utf8-{}.txt", std::process::id()));
+    fs::write(&path, [0xff, b'a', b'\n']).unwrap();
+
+    let mut env = CLIPSEnvironment::new().unwrap();
+    env.load_from_str(&format!(
+        "
+        (deftemplate line (slot text))
+        (defglobal ?*opened* = (open \"{}\" input \"r\"))
+        (defglobal ?*text* = (fact-slot-value (assert (line (text (readline input)))) text))
+        ",
+        path.display()
+    ))
+    .unwrap();
+    fs::remove_file(&path).unwrap();
+
+    env
+}
+
+// Both modes are in the same test because the conversion is set for the whole process.
+#[test]
+fn invalid_utf8_in_a_slot_follows_the_conversion() {
+    let env = env_with_invalid_slot();
+
+    set_string_conversion(StringConversion::Strict);
+    assert!(env.retrieve_globals_values().is_err());
+
+    set_string_conversion(StringConversion::Lossy);
+    assert_eq!(
+        env.retrieve_globals_values().unwrap()["MAIN"]["text"],
+        CLIPSValue::String("\u{fffd}a".into())
+    );
+
+    set_string_conversion(StringConversion::Strict);
+}
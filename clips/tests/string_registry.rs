@@ -0,0 +1,95 @@
+use std::{
+    ffi::CStr,
+    sync::{Arc, Mutex},
+};
+
+use clips::{CLIPSEnvironment, CLIPSError, CLIPSValue, Router, RouterSupport, UDFType, STDOUT};
+
+fn add_constant_udf(env: &mut CLIPSEnvironment, name: &str, value: i64) -> Result<(), CLIPSError> {
+    env.add_udf(
+        name,
+        UDFType::Integer,
+        0,
+        0,
+        vec![],
+        Box::new(move |mut data| data.set_result(CLIPSValue::Int(value)).unwrap()),
+    )
+}
+
+fn global(env: &CLIPSEnvironment, name: &str) -> CLIPSValue {
+    env.retrieve_globals_values().unwrap()["MAIN"][name].clone()
+}
+
+struct Collect(Arc<Mutex<String>>);
+
+impl Router for Collect {
+    fn supports(&self) -> RouterSupport {
+        RouterSupport::WRITE
+    }
+
+    fn query(&mut self, logical_name: &str) -> bool {
+        logical_name == STDOUT
+    }
+
+    fn write(&mut self, _logical_name: &str, data: &CStr) {
+        self.0.lock().unwrap().push_str(&data.to_string_lossy());
+    }
+}
+
+#[test]
+fn udfs_can_be_removed_and_added_again() {
+    let mut env = CLIPSEnvironment::new().unwrap();
+
+    for round in 0..50 {
+        add_constant_udf(&mut env, "answer", round).unwrap();
+        env.load_from_str("(defglobal ?*answer* = (answer))")
+            .unwrap();
+        assert_eq!(global(&env, "answer"), CLIPSValue::Int(round));
+
+        // The global's expression is gone once it's evaluated, so nothing refers to the UDF anymore.
+        assert!(env.remove_udf("answer"));
+        assert!(!env.remove_udf("answer"));
+    }
+}
+
+#[test]
+fn a_udf_with_a_name_in_use_is_rejected() {
+    let mut env = CLIPSEnvironment::new().unwrap();
+    add_constant_udf(&mut env, "answer", 1).unwrap();
+
+    for _ in 0..10 {
+        assert!(matches!(
+            add_constant_udf(&mut env, "answer", 2),
+            Err(CLIPSError::NameInUse)
+        ));
+    }
+
+    env.load_from_str("(defglobal ?*answer* = (answer))")
+        .unwrap();
+    assert_eq!(global(&env, "answer"), CLIPSValue::Int(1));
+
+    assert!(env.remove_udf("answer"));
+    add_constant_udf(&mut env, "answer", 3).unwrap();
+}
+
+#[test]
+fn a_router_with_a_name_in_use_is_rejected() {
+    let mut env = CLIPSEnvironment::new().unwrap();
+    let first = Arc::new(Mutex::new(String::new()));
+    let second = Arc::new(Mutex::new(String::new()));
+
+    env.add_router("collect", 100, Box::new(Collect(first.clone())))
+        .unwrap();
+    assert!(matches!(
+        env.add_router("collect", 100, Box::new(Collect(second.clone()))),
+        Err(CLIPSError::AddRouter)
+    ));
+
+    env.load_from_str("(defrule greet (go) => (printout t \"hello\" crlf))")
+        .unwrap();
+    env.assert_string("(go)").unwrap();
+    env.run().unwrap();
+
+    assert_eq!(*first.lock().unwrap(), "hello\n");
+    assert!(second.lock().unwrap().is_empty());
+}
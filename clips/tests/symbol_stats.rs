@@ -0,0 +1,27 @@
+use clips::CLIPSEnvironment;
+
+#[test]
+fn counts_loaded_lexemes() {
+    let mut env = CLIPSEnvironment::new().unwrap();
+    let before = env.symbol_stats(0).unwrap();
+
+    env.load_from_str("(defglobal ?*name* = a-symbol-that-is-much-longer-than-any-of-the-names-clips-defines-itself)")
+        .unwrap();
+    let after = env.symbol_stats(5).unwrap();
+
+    assert!(after.symbols.entries > before.symbols.entries);
+    assert!(after.longest_lexemes.iter().any(|lexeme| lexeme.contents
+        == "a-symbol-that-is-much-longer-than-any-of-the-names-clips-defines-itself"));
+}
+
+#[test]
+fn bytes_include_the_hash_tables() {
+    let env = CLIPSEnvironment::new().unwrap();
+    let stats = env.symbol_stats(0).unwrap();
+    let pointer = std::mem::size_of::<usize>();
+
+    assert!(stats.symbols.bytes >= clips_sys::SYMBOL_HASH_SIZE as usize * pointer);
+    assert!(stats.floats.bytes >= clips_sys::FLOAT_HASH_SIZE as usize * pointer);
+    assert!(stats.integers.bytes >= clips_sys::INTEGER_HASH_SIZE as usize * pointer);
+    assert!(stats.bitmaps.bytes >= clips_sys::BITMAP_HASH_SIZE as usize * pointer);
+}
@@ -0,0 +1,77 @@
+use std::{
+    ffi::CStr,
+    sync::{Arc, Mutex},
+};
+
+use clips::{CLIPSEnvironment, Router, RouterSupport, TeeRouter, STDOUT, STDWRN};
+
+struct Capture {
+    logical_names: Vec<&'static str>,
+    output: Arc<Mutex<Vec<u8>>>,
+}
+
+impl Router for Capture {
+    fn supports(&self) -> RouterSupport {
+        RouterSupport::WRITE
+    }
+
+    fn query(&mut self, logical_name: &str) -> bool {
+        self.logical_names.contains(&logical_name)
+    }
+
+    fn write(&mut self, _logical_name: &str, data: &CStr) {
+        self.output
+            .lock()
+            .unwrap()
+            .extend_from_slice(data.to_bytes());
+    }
+}
+
+fn capture(logical_names: Vec<&'static str>) -> (Arc<Mutex<Vec<u8>>>, Box<Capture>) {
+    let output = Arc::new(Mutex::new(Vec::new()));
+    let router = Box::new(Capture {
+        logical_names,
+        output: output.clone(),
+    });
+
+    (output, router)
+}
+
+#[test]
+fn both_routers_receive_the_same_writes() {
+    let mut env = CLIPSEnvironment::new().unwrap();
+    let (first, first_router) = capture(vec![STDOUT]);
+    let (second, second_router) = capture(vec![STDOUT]);
+    let tee = TeeRouter::new()
+        .with_router(10, first_router)
+        .with_router(20, second_router);
+    env.add_router("tee", 30, Box::new(tee)).unwrap();
+
+    env.load_from_str(
+        "(defglobal ?*printed* = (progn (printout t \"hello\" crlf) (printout t 42 crlf)))",
+    )
+    .unwrap();
+
+    assert_eq!(*first.lock().unwrap(), b"hello\n42\n");
+    assert_eq!(*first.lock().unwrap(), *second.lock().unwrap());
+}
+
+// Each inner router only gets the writes for names it claims itself.
+#[test]
+fn writes_only_reach_routers_claiming_the_name() {
+    let mut env = CLIPSEnvironment::new().unwrap();
+    let (everything, everything_router) = capture(vec![STDOUT, STDWRN]);
+    let (stdout_only, stdout_only_router) = capture(vec![STDOUT]);
+    let tee = TeeRouter::new()
+        .with_router(10, everything_router)
+        .with_router(20, stdout_only_router);
+    env.add_router("tee", 30, Box::new(tee)).unwrap();
+
+    env.load_from_str(
+        "(defglobal ?*printed* = (progn (printout t \"out\" crlf) (printout stdwrn \"warn\" crlf)))",
+    )
+    .unwrap();
+
+    assert_eq!(*everything.lock().unwrap(), b"out\nwarn\n");
+    assert_eq!(*stdout_only.lock().unwrap(), b"out\n");
+}
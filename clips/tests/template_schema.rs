@@ -0,0 +1,167 @@
+use clips::{CLIPSError, CLIPSValue, Environment, TemplateSlotInfo};
+
+const TEMPLATE: &str = "
+    (deftemplate reading
+      (slot sensor (type SYMBOL) (allowed-symbols north south))
+      (slot value (type NUMBER) (range 0 100) (default ?NONE))
+      (slot note (type STRING) (default \"none\"))
+      (multislot samples (type INTEGER) (cardinality 1 3) (default 0)))";
+
+fn env() -> Environment {
+    let env = Environment::new();
+    env.load_from_str(TEMPLATE).unwrap();
+    env
+}
+
+#[test]
+fn template_info_describes_each_slot() {
+    let info = env().template_info("reading").unwrap();
+    assert_eq!(info.name, "reading");
+    assert_eq!(info.module, "MAIN");
+
+    assert_eq!(
+        info.slots,
+        vec![
+            TemplateSlotInfo {
+                name: "sensor".into(),
+                types: vec!["SYMBOL".into()],
+                multislot: false,
+                cardinality: None,
+                allowed_values: Some(vec![
+                    CLIPSValue::Symbol("north".into()),
+                    CLIPSValue::Symbol("south".into()),
+                ]),
+                range: (None, None),
+                default: Some(CLIPSValue::Symbol("north".into())),
+            },
+            TemplateSlotInfo {
+                name: "value".into(),
+                types: vec!["FLOAT".into(), "INTEGER".into()],
+                multislot: false,
+                cardinality: None,
+                allowed_values: None,
+                range: (Some(0.0), Some(100.0)),
+                default: None,
+            },
+            TemplateSlotInfo {
+                name: "note".into(),
+                types: vec!["STRING".into()],
+                multislot: false,
+                cardinality: None,
+                allowed_values: None,
+                range: (None, None),
+                default: Some(CLIPSValue::String("none".into())),
+            },
+            TemplateSlotInfo {
+                name: "samples".into(),
+                types: vec!["INTEGER".into()],
+                multislot: true,
+                cardinality: Some((1, Some(3))),
+                allowed_values: None,
+                range: (None, None),
+                default: Some(CLIPSValue::Multifield(vec![CLIPSValue::Int(0)])),
+            },
+        ]
+    );
+}
+
+#[test]
+fn unknown_templates_are_reported() {
+    assert!(matches!(
+        env().template_info("missing"),
+        Err(CLIPSError::TemplateNotFound)
+    ));
+}
+
+#[test]
+fn slot_allowed_values_lists_the_restricted_values() {
+    let env = env();
+    env.load_from_str(
+        "(deftemplate order
+           (slot status (allowed-values open 2 \"closed\"))
+           (multislot codes (allowed-integers 1 2 3)))",
+    )
+    .unwrap();
+
+    assert_eq!(
+        env.slot_allowed_values("reading", "sensor").unwrap(),
+        Some(vec![
+            CLIPSValue::Symbol("north".into()),
+            CLIPSValue::Symbol("south".into()),
+        ])
+    );
+    assert_eq!(
+        env.slot_allowed_values("order", "status").unwrap(),
+        Some(vec![
+            CLIPSValue::Symbol("open".into()),
+            CLIPSValue::Int(2),
+            CLIPSValue::String("closed".into()),
+        ])
+    );
+    assert_eq!(
+        env.slot_allowed_values("order", "codes").unwrap(),
+        Some(vec![
+            CLIPSValue::Int(1),
+            CLIPSValue::Int(2),
+            CLIPSValue::Int(3)
+        ])
+    );
+}
+
+#[test]
+fn slot_allowed_values_is_none_for_unrestricted_slots() {
+    let env = env();
+
+    // A type or range restriction doesn't list the values.
+    assert_eq!(env.slot_allowed_values("reading", "note").unwrap(), None);
+    assert_eq!(env.slot_allowed_values("reading", "value").unwrap(), None);
+
+    assert!(matches!(
+        env.slot_allowed_values("reading", "missing"),
+        Err(CLIPSError::SlotNotFound)
+    ));
+    assert!(matches!(
+        env.slot_allowed_values("missing", "sensor"),
+        Err(CLIPSError::TemplateNotFound)
+    ));
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn template_schema_matches_the_slots() {
+    use serde_json::json;
+
+    assert_eq!(
+        env().template_schema("reading").unwrap(),
+        json!({
+            "$schema": "https://json-schema.org/draft/2020-12/schema",
+            "title": "MAIN::reading",
+            "type": "object",
+            "properties": {
+                "sensor": {
+                    "type": "string",
+                    "enum": ["north", "south"],
+                    "default": "north",
+                },
+                "value": {
+                    "type": "number",
+                    "minimum": 0.0,
+                    "maximum": 100.0,
+                },
+                "note": {
+                    "type": "string",
+                    "default": "none",
+                },
+                "samples": {
+                    "type": "array",
+                    "items": { "type": "integer" },
+                    "minItems": 1,
+                    "maxItems": 3,
+                    "default": [0],
+                },
+            },
+            "required": ["value"],
+            "additionalProperties": false,
+        })
+    );
+}
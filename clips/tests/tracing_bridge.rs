@@ -0,0 +1,89 @@
+#![cfg(feature = "tracing")]
+
+use std::{
+    io::{self, Write},
+    sync::{Arc, Mutex},
+};
+
+use clips::Environment;
+use tracing_subscriber::fmt::MakeWriter;
+
+// The events come from the CLIPS thread, so they're collected in a buffer shared with the subscriber instead of going through the test's captured output.
+#[derive(Clone, Default)]
+struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<'a> MakeWriter<'a> for SharedBuffer {
+    type Writer = SharedBuffer;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        self.clone()
+    }
+}
+
+#[test]
+fn rules_facts_and_output_become_events_inside_the_run_span() {
+    let buffer = SharedBuffer::default();
+    tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::DEBUG)
+        .with_ansi(false)
+        .with_writer(buffer.clone())
+        .init();
+
+    let env = Environment::new();
+    env.install_tracing_bridge().unwrap();
+    env.load_from_str(
+        r#"
+        (deftemplate item (slot id))
+        (defrule announce
+          ?item <- (item (id ?id))
+          =>
+          (printout t "item " ?id crlf)
+          (printout stdwrn "careful" crlf)
+          (retract ?item))"#,
+    )
+    .unwrap();
+    env.assert_string("(item (id 7))").unwrap();
+    assert_eq!(env.run().unwrap(), 1);
+
+    let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+    let line_with = |text: &str| {
+        output
+            .lines()
+            .find(|line| line.contains(text))
+            .unwrap_or_else(|| panic!("no event with `{}` in:\n{}", text, output))
+    };
+
+    let asserted = line_with("fact asserted");
+    assert!(asserted.contains("DEBUG"));
+    assert!(asserted.contains("template=\"item\""));
+    // The fact was asserted before the run started.
+    assert!(!asserted.contains("clips_run"));
+
+    let fired = line_with("rule fired");
+    assert!(fired.contains("clips_run{limit=-1}"));
+    assert!(fired.contains("rule=announce"));
+
+    let printed = line_with("item 7");
+    assert!(printed.contains("INFO"));
+    assert!(printed.contains("clips_run{limit=-1}"));
+    assert!(printed.contains("logical_name=\"stdout\""));
+
+    let warned = line_with("careful");
+    assert!(warned.contains("WARN"));
+    assert!(warned.contains("logical_name=\"stdwrn\""));
+
+    let retracted = line_with("fact retracted");
+    assert!(retracted.contains("clips_run{limit=-1}"));
+    assert!(retracted.contains("template=\"item\""));
+}
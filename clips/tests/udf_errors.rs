@@ -0,0 +1,73 @@
+use clips::{CLIPSEnvironment, CLIPSValue, CaptureHandle, CaptureRouter, LogicalName, UDFType};
+
+fn env_with_parse_udf() -> (CLIPSEnvironment, CaptureHandle) {
+    let mut env = CLIPSEnvironment::new().unwrap();
+
+    let router = CaptureRouter::new(&[LogicalName::Stderr]);
+    let errors = router.handle();
+    env.add_router("capture", 30, Box::new(router)).unwrap();
+
+    env.add_udf(
+        "parse-int",
+        UDFType::Integer,
+        1,
+        1,
+        vec![UDFType::String],
+        Box::new(|mut data| {
+            let text = match data.first_arg::<CLIPSValue>() {
+                Ok(CLIPSValue::String(text)) => text,
+                other => panic!("unexpected argument {other:?}"),
+            };
+            let Some(parsed) = data.try_or_throw(text.parse::<i64>()) else {
+                return;
+            };
+            data.set_result(CLIPSValue::Int(parsed)).unwrap();
+        }),
+    )
+    .unwrap();
+
+    env.load_from_str(
+        "(defglobal ?*parsed* = none)
+         (defrule parse
+           (text ?text)
+           =>
+           (bind ?parsed (parse-int ?text))
+           (bind ?*parsed* ?parsed))",
+    )
+    .unwrap();
+
+    (env, errors)
+}
+
+fn parsed(env: &CLIPSEnvironment) -> CLIPSValue {
+    env.retrieve_globals_values().unwrap()["MAIN"]["parsed"].clone()
+}
+
+#[test]
+fn ok_results_are_returned() {
+    let (mut env, errors) = env_with_parse_udf();
+    env.assert_string("(text \"42\")").unwrap();
+
+    let outcome = env.run_detailed().unwrap();
+    assert!(!outcome.evaluation_error);
+    assert_eq!(parsed(&env), CLIPSValue::Int(42));
+    assert_eq!(errors.contents(), "");
+}
+
+#[test]
+fn rust_errors_become_clips_errors() {
+    let (mut env, errors) = env_with_parse_udf();
+    env.assert_string("(text \"forty-two\")").unwrap();
+
+    let outcome = env.run_detailed().unwrap();
+    assert!(outcome.evaluation_error);
+    // The error stops the rule's actions.
+    assert_eq!(parsed(&env), CLIPSValue::Symbol("none".into()));
+    assert!(
+        errors
+            .contents()
+            .starts_with("invalid digit found in string\n"),
+        "{}",
+        errors.contents()
+    );
+}
@@ -0,0 +1,115 @@
+use std::sync::{Arc, Mutex};
+
+use clips::{CLIPSEnvironment, CLIPSError, CLIPSValue, UDFType};
+
+fn add_bump_udf(env: &mut CLIPSEnvironment) {
+    env.add_udf(
+        "bump",
+        UDFType::Integer,
+        0,
+        0,
+        vec![],
+        Box::new(|mut data| {
+            let next = match data.get_global("counter").unwrap() {
+                CLIPSValue::Int(count) => count + 1,
+                other => panic!("unexpected counter {other:?}"),
+            };
+            data.set_global("counter", next).unwrap();
+            data.set_result(CLIPSValue::Int(next)).unwrap();
+        }),
+    )
+    .unwrap();
+}
+
+fn global(env: &CLIPSEnvironment, name: &str) -> CLIPSValue {
+    env.retrieve_globals_values().unwrap()["MAIN"][name].clone()
+}
+
+#[test]
+fn a_udf_increments_a_global_each_call() {
+    let mut env = CLIPSEnvironment::new().unwrap();
+    add_bump_udf(&mut env);
+    env.load_from_str(
+        "(defglobal ?*counter* = 0)
+         (deftemplate seen (slot returned) (slot global))
+         (defrule count
+           (tick ?)
+           =>
+           (bind ?returned (bump))
+           (assert (seen (returned ?returned) (global ?*counter*))))",
+    )
+    .unwrap();
+
+    for tick in 0..3 {
+        env.assert_string(&format!("(tick {tick})")).unwrap();
+    }
+    assert_eq!(env.run().unwrap(), 3);
+
+    assert_eq!(global(&env, "counter"), CLIPSValue::Int(3));
+
+    // The rest of the rule sees the value the UDF stored.
+    let mut seen: Vec<_> = env
+        .find_all_facts("seen", "TRUE")
+        .unwrap()
+        .iter()
+        .map(|fact| {
+            (
+                fact.slot("returned").unwrap().clone(),
+                fact.slot("global").unwrap().clone(),
+            )
+        })
+        .collect();
+    seen.sort_by_key(|(returned, _)| format!("{returned:?}"));
+    assert_eq!(
+        seen,
+        (1..=3)
+            .map(|count| (CLIPSValue::Int(count), CLIPSValue::Int(count)))
+            .collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn a_global_set_from_a_udf_can_be_read_from_a_later_global() {
+    let mut env = CLIPSEnvironment::new().unwrap();
+    add_bump_udf(&mut env);
+    env.load_from_str(
+        "(defglobal ?*counter* = 41)
+         (defglobal ?*returned* = (bump))
+         (defglobal ?*copy* = ?*counter*)",
+    )
+    .unwrap();
+
+    assert_eq!(global(&env, "returned"), CLIPSValue::Int(42));
+    assert_eq!(global(&env, "copy"), CLIPSValue::Int(42));
+}
+
+#[test]
+fn unknown_globals_are_reported() {
+    let mut env = CLIPSEnvironment::new().unwrap();
+
+    let errors = Arc::new(Mutex::new(Vec::new()));
+    let errors_in_udf = errors.clone();
+    env.add_udf(
+        "touch",
+        UDFType::Void,
+        0,
+        0,
+        vec![],
+        Box::new(move |mut data| {
+            let mut errors = errors_in_udf.lock().unwrap();
+            errors.push(data.get_global("missing").unwrap_err());
+            errors.push(data.set_global("missing", 1).unwrap_err());
+            errors.push(data.get_global("nul\0name").unwrap_err());
+            data.set_void();
+        }),
+    )
+    .unwrap();
+    env.load_from_str("(defglobal ?*unused* = (touch))")
+        .unwrap();
+
+    let errors = errors.lock().unwrap();
+    assert_eq!(errors.len(), 3);
+    assert!(errors
+        .iter()
+        .all(|error| matches!(error, CLIPSError::DefglobalNotFound)));
+}
@@ -0,0 +1,83 @@
+use clips::{CLIPSValue, Environment, UDFType};
+
+fn env() -> Environment {
+    let env = Environment::new();
+    env.add_udf(
+        "even".to_string(),
+        1,
+        1,
+        UDFType::Boolean,
+        vec![UDFType::Integer],
+        Box::new(|mut data| {
+            let CLIPSValue::Int(value) = data.first_arg::<CLIPSValue>().unwrap() else {
+                unreachable!("CLIPS checks the argument is an integer")
+            };
+            data.set_boolean_result(value % 2 == 0);
+        }),
+    )
+    .unwrap();
+    // Gives back 0 for odd numbers, which CLIPS doesn't treat as false.
+    env.add_udf(
+        "even-as-integer".to_string(),
+        1,
+        1,
+        UDFType::Integer,
+        vec![UDFType::Integer],
+        Box::new(|mut data| {
+            let CLIPSValue::Int(value) = data.first_arg::<CLIPSValue>().unwrap() else {
+                unreachable!("CLIPS checks the argument is an integer")
+            };
+            data.set_result(CLIPSValue::Int((value % 2 == 0) as i64))
+                .unwrap();
+        }),
+    )
+    .unwrap();
+    env.load_from_str(
+        "(defrule keep-even (number ?x) (test (even ?x)) => (assert (even ?x)))
+         (defrule keep-even-as-integer (number ?x) (test (even-as-integer ?x)) => (assert (even-as-integer ?x)))",
+    )
+    .unwrap();
+    env
+}
+
+fn matched(env: &Environment, head: &str) -> Vec<CLIPSValue> {
+    let mut values: Vec<_> = env
+        .find_all_facts(head, "TRUE")
+        .unwrap()
+        .into_iter()
+        .map(|fact| fact.slot("implied").unwrap().clone())
+        .collect();
+    values.sort_by_key(|value| format!("{value:?}"));
+    values
+}
+
+fn numbers(values: &[i64]) -> Vec<CLIPSValue> {
+    values
+        .iter()
+        .map(|&value| CLIPSValue::Multifield(vec![CLIPSValue::Int(value)]))
+        .collect()
+}
+
+#[test]
+fn a_false_result_keeps_the_test_ce_from_matching() {
+    let env = env();
+    for x in 1..=4 {
+        env.assert_string(&format!("(number {x})")).unwrap();
+    }
+    env.run().unwrap();
+
+    assert_eq!(matched(&env, "even"), numbers(&[2, 4]));
+    // Every number matches when the predicate gives back an integer.
+    assert_eq!(matched(&env, "even-as-integer"), numbers(&[1, 2, 3, 4]));
+}
+
+#[test]
+fn the_result_is_a_boolean_symbol() {
+    let env = env();
+    env.load_from_str("(defglobal ?*two* = (even 2) ?*three* = (even 3))")
+        .unwrap();
+
+    let globals = env.retrieve_globals_values().unwrap();
+    assert_eq!(globals["MAIN"]["two"], CLIPSValue::Bool(true));
+    assert_eq!(globals["MAIN"]["three"], CLIPSValue::Bool(false));
+}
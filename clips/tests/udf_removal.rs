@@ -0,0 +1,43 @@
+use clips::{CLIPSEnvironment, CLIPSResult, CLIPSValue, UDFType};
+
+fn add_constant(env: &mut CLIPSEnvironment, name: &str, value: i64) -> CLIPSResult<()> {
+    env.add_udf(
+        name,
+        UDFType::Integer,
+        0,
+        0,
+        vec![],
+        Box::new(move |mut data| data.set_result(CLIPSValue::Int(value)).unwrap()),
+    )
+}
+
+fn results(env: &mut CLIPSEnvironment) -> CLIPSValue {
+    env.load_from_str("(defglobal ?*results* = (create$ (first) (second) (third)))")
+        .unwrap();
+    env.retrieve_globals_values().unwrap()["MAIN"]["results"].clone()
+}
+
+// UDFs are called by their index, so removing or refusing one must leave the indexes of the others alone.
+#[test]
+fn removed_udfs_leave_the_others_in_place() {
+    let mut env = CLIPSEnvironment::new().unwrap();
+    for (name, value) in [("first", 1), ("second", 2), ("third", 3)] {
+        add_constant(&mut env, name, value).unwrap();
+    }
+
+    assert!(env.remove_udf("second"));
+    assert!(env.load_from_str("(defglobal ?*x* = (second))").is_err());
+
+    // CLIPS refuses a name that's already in use and keeps calling the existing UDF.
+    assert!(add_constant(&mut env, "first", 10).is_err());
+    add_constant(&mut env, "second", 20).unwrap();
+
+    assert_eq!(
+        results(&mut env),
+        CLIPSValue::Multifield(vec![
+            CLIPSValue::Int(1),
+            CLIPSValue::Int(20),
+            CLIPSValue::Int(3),
+        ])
+    );
+}
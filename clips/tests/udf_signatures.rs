@@ -0,0 +1,98 @@
+use clips::{CLIPSEnvironment, CLIPSError, UDFType};
+
+const UNBOUNDED: u16 = u16::MAX;
+
+fn return_types() -> Vec<UDFType> {
+    vec![
+        UDFType::empty(),
+        UDFType::Void,
+        UDFType::Any,
+        UDFType::Integer,
+        UDFType::Number | UDFType::Lexeme,
+        UDFType::Multifield | UDFType::Void,
+    ]
+}
+
+fn arities() -> Vec<(u16, u16)> {
+    vec![(0, 0), (1, 1), (0, 2), (2, 1), (1, UNBOUNDED)]
+}
+
+fn arg_types() -> Vec<Vec<UDFType>> {
+    vec![
+        vec![],
+        vec![UDFType::Integer],
+        vec![UDFType::Any, UDFType::Lexeme],
+        vec![UDFType::Void],
+        vec![UDFType::Integer | UDFType::Multifield; 3],
+    ]
+}
+
+// The type string CLIPS gets for `arg_types`, with `*` as the type of the arguments without one.
+fn type_string(arg_types: &[UDFType]) -> String {
+    std::iter::once("*".to_string())
+        .chain(arg_types.iter().map(UDFType::as_character_code))
+        .collect::<Vec<_>>()
+        .join(";")
+}
+
+#[test]
+fn every_signature_registers_or_gives_an_error() {
+    let mut env = CLIPSEnvironment::new().unwrap();
+    let mut registered = 0;
+
+    for (r, return_types) in return_types().into_iter().enumerate() {
+        for (a, (min_args, max_args)) in arities().into_iter().enumerate() {
+            for (t, arg_types) in arg_types().into_iter().enumerate() {
+                let name = format!("udf-{r}-{a}-{t}");
+                let res = env.add_udf(
+                    &name,
+                    return_types,
+                    min_args,
+                    max_args,
+                    arg_types.clone(),
+                    Box::new(|_| {}),
+                );
+                let context =
+                    format!("{name}: {return_types:?} {min_args}..{max_args} {arg_types:?}");
+
+                // Extra argument types are caught before CLIPS gets to compare the arities.
+                if max_args != UNBOUNDED && arg_types.len() > max_args as usize {
+                    match &res {
+                        Err(CLIPSError::InvalidUDFArgumentTypes(types)) => {
+                            assert_eq!(types, &type_string(&arg_types), "{context}")
+                        }
+                        res => panic!("{context}: {res:?}"),
+                    }
+                } else if min_args > max_args {
+                    assert!(
+                        matches!(res, Err(CLIPSError::MinArgumentsExceedsMax)),
+                        "{context}: {res:?}"
+                    );
+                } else {
+                    assert!(res.is_ok(), "{context}: {res:?}");
+                    registered += 1;
+
+                    // CLIPS knows the function and checks its arity when parsing calls to it.
+                    let args = " 1".repeat(min_args as usize);
+                    if !arg_types.contains(&UDFType::Void) {
+                        env.load_from_str(&format!("(deffunction call-{name} () ({name}{args}))"))
+                            .unwrap_or_else(|e| panic!("{context}: {e:?}"));
+                    }
+                    if max_args != UNBOUNDED {
+                        let too_many = " 1".repeat(max_args as usize + 1);
+                        assert!(env
+                            .load_from_str(&format!(
+                                "(deffunction over-{name} () ({name}{too_many}))"
+                            ))
+                            .is_err());
+                    }
+                }
+
+                let listed = env.list_udfs().iter().any(|udf| udf.name == name);
+                assert_eq!(listed, res.is_ok(), "{context}");
+            }
+        }
+    }
+
+    assert_eq!(registered, 6 * 13);
+}
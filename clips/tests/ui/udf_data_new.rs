@@ -0,0 +1,7 @@
+use std::ptr;
+
+use clips::UDFData;
+
+fn main() {
+    let _data = UDFData::new(ptr::null_mut(), ptr::null_mut(), ptr::null_mut());
+}
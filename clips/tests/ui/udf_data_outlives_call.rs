@@ -0,0 +1,7 @@
+use clips::UDFData;
+
+fn keep(data: UDFData<'_>) -> UDFData<'static> {
+    data
+}
+
+fn main() {}
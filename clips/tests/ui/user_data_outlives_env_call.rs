@@ -0,0 +1,9 @@
+use clips::UDFData;
+
+fn replace_while_borrowed(mut data: UDFData<'_>) {
+    let counter = data.user_data::<u32>();
+    data.env().set_user_data(Box::new(0u32));
+    let _ = counter;
+}
+
+fn main() {}
@@ -0,0 +1,87 @@
+use clips::{CLIPSEnvironment, CLIPSValue, UDFType};
+
+struct Counter(i64);
+
+fn env_with_counter() -> CLIPSEnvironment {
+    let mut env = CLIPSEnvironment::new().unwrap();
+    env.set_user_data(Box::new(Counter(0)));
+
+    env.add_udf(
+        "bump",
+        UDFType::Integer,
+        0,
+        0,
+        vec![],
+        Box::new(|mut data| {
+            let counter = data.user_data_mut::<Counter>().unwrap();
+            counter.0 += 1;
+            let value = counter.0;
+            data.set_result(CLIPSValue::Int(value)).unwrap();
+        }),
+    )
+    .unwrap();
+
+    env
+}
+
+#[test]
+fn udfs_share_the_user_data() {
+    let mut env = env_with_counter();
+
+    env.load_from_str("(defglobal ?*a* = (bump) ?*b* = (bump))")
+        .unwrap();
+
+    let globals = env.retrieve_globals_values().unwrap();
+    assert_eq!(globals["MAIN"]["a"], CLIPSValue::Int(1));
+    assert_eq!(globals["MAIN"]["b"], CLIPSValue::Int(2));
+}
+
+#[test]
+fn user_data_of_another_type_is_none() {
+    let mut env = env_with_counter();
+
+    env.add_udf(
+        "has-string",
+        UDFType::Boolean,
+        0,
+        0,
+        vec![],
+        Box::new(|mut data| {
+            let has_string = data.user_data::<String>().is_some();
+            data.set_boolean_result(has_string);
+        }),
+    )
+    .unwrap();
+    env.load_from_str("(defglobal ?*has-string* = (has-string))")
+        .unwrap();
+
+    assert_eq!(
+        env.retrieve_globals_values().unwrap()["MAIN"]["has-string"],
+        CLIPSValue::Bool(false)
+    );
+}
+
+#[test]
+fn replacing_the_user_data_from_a_udf() {
+    let mut env = env_with_counter();
+
+    env.add_udf(
+        "restart",
+        UDFType::Boolean,
+        0,
+        0,
+        vec![],
+        Box::new(|mut data| {
+            data.env().set_user_data(Box::new(Counter(10)));
+            data.set_boolean_result(true);
+        }),
+    )
+    .unwrap();
+    env.load_from_str("(defglobal ?*a* = (bump) ?*r* = (restart) ?*b* = (bump))")
+        .unwrap();
+
+    assert_eq!(
+        env.retrieve_globals_values().unwrap()["MAIN"]["b"],
+        CLIPSValue::Int(11)
+    );
+}
@@ -0,0 +1,176 @@
+use std::{
+    ffi::{CStr, CString},
+    sync::{Arc, Mutex},
+};
+
+use clips::{
+    CLIPSEnvironment, CLIPSError, CLIPSResult, CLIPSValue, Router, RouterSupport, StringConversion,
+    UDFType, ValueExtractionPolicy, STDOUT,
+};
+
+// CLIPS code given as `&str` can't hold invalid UTF-8, so a UDF makes the string.
+fn env_with_invalid_string(conversion: StringConversion) -> CLIPSEnvironment {
+    let mut env = CLIPSEnvironment::new().unwrap();
+    env.set_value_policy(ValueExtractionPolicy {
+        string_conversion: Some(conversion),
+        ..Default::default()
+    });
+
+    env.add_udf(
+        "invalid-string",
+        UDFType::String,
+        0,
+        0,
+        vec![],
+        Box::new(|mut data| {
+            let bytes = CString::new(vec![0xff, b'a']).unwrap();
+            data.set_result(CLIPSValue::Lexeme(bytes)).unwrap();
+        }),
+    )
+    .unwrap();
+    env.load_from_str("(defglobal ?*s* = (invalid-string))")
+        .unwrap();
+
+    env
+}
+
+fn global(env: &CLIPSEnvironment) -> CLIPSResult<CLIPSValue> {
+    env.retrieve_globals_values()
+        .map(|globals| globals["MAIN"]["s"].clone())
+}
+
+#[test]
+fn strict_fails_on_invalid_utf8() {
+    let env = env_with_invalid_string(StringConversion::Strict);
+
+    assert!(matches!(
+        global(&env),
+        Err(CLIPSError::InvalidUtf8 {
+            context: "a string"
+        })
+    ));
+}
+
+#[test]
+fn lossy_replaces_invalid_utf8() {
+    let env = env_with_invalid_string(StringConversion::Lossy);
+
+    assert_eq!(
+        global(&env).unwrap(),
+        CLIPSValue::String("\u{fffd}a".into())
+    );
+}
+
+#[test]
+fn bytes_keeps_invalid_utf8() {
+    let env = env_with_invalid_string(StringConversion::Bytes);
+
+    assert_eq!(
+        global(&env).unwrap(),
+        CLIPSValue::Lexeme(CString::new(vec![0xff, b'a']).unwrap())
+    );
+}
+
+#[test]
+fn string_udf_arguments_follow_the_policy() {
+    for (conversion, expected) in [
+        (StringConversion::Strict, None),
+        (StringConversion::Lossy, Some("\u{fffd}a".to_string())),
+    ] {
+        let mut env = env_with_invalid_string(conversion);
+        let (tx, rx) = std::sync::mpsc::channel();
+
+        env.add_udf(
+            "keep",
+            UDFType::Void,
+            1,
+            1,
+            vec![],
+            Box::new(move |mut data| {
+                tx.send(data.first_arg::<String>().ok()).unwrap();
+                data.set_void();
+            }),
+        )
+        .unwrap();
+        env.load_from_str("(defglobal ?*unused* = (keep ?*s*))")
+            .unwrap();
+
+        assert_eq!(rx.recv().unwrap(), expected);
+    }
+}
+
+fn fact_slot(env: &mut CLIPSEnvironment) -> CLIPSResult<CLIPSValue> {
+    env.load_from_str(
+        "(deftemplate holder (slot value)) (defglobal ?*unused* = (assert (holder (value ?*s*))))",
+    )
+    .unwrap();
+
+    env.find_all_facts("holder", "TRUE")
+        .map(|facts| facts[0].slot("value").unwrap().clone())
+}
+
+#[test]
+fn fact_slots_follow_the_policy() {
+    let mut env = env_with_invalid_string(StringConversion::Strict);
+    assert!(matches!(
+        fact_slot(&mut env),
+        Err(CLIPSError::InvalidUtf8 {
+            context: "a string"
+        })
+    ));
+
+    let mut env = env_with_invalid_string(StringConversion::Lossy);
+    assert_eq!(
+        fact_slot(&mut env).unwrap(),
+        CLIPSValue::String("\u{fffd}a".into())
+    );
+
+    let mut env = env_with_invalid_string(StringConversion::Bytes);
+    assert_eq!(
+        fact_slot(&mut env).unwrap(),
+        CLIPSValue::Lexeme(CString::new(vec![0xff, b'a']).unwrap())
+    );
+}
+
+struct Capture(Arc<Mutex<Vec<u8>>>);
+
+impl Router for Capture {
+    fn supports(&self) -> RouterSupport {
+        RouterSupport::WRITE
+    }
+
+    fn query(&mut self, logical_name: &str) -> bool {
+        logical_name == STDOUT
+    }
+
+    fn write(&mut self, _logical_name: &str, data: &CStr) {
+        self.0.lock().unwrap().extend_from_slice(data.to_bytes());
+    }
+}
+
+// Routers get what CLIPS writes as it is, so the bytes make it through whatever the policy is.
+#[test]
+fn printout_of_invalid_utf8_reaches_routers_under_every_policy() {
+    for conversion in [
+        StringConversion::Strict,
+        StringConversion::Lossy,
+        StringConversion::Bytes,
+    ] {
+        let mut env = env_with_invalid_string(conversion);
+        let output = Arc::new(Mutex::new(Vec::new()));
+        env.add_router("capture", 30, Box::new(Capture(output.clone())))
+            .unwrap();
+
+        env.load_from_str("(defglobal ?*printed* = (printout t ?*s* crlf))")
+            .unwrap();
+
+        assert_eq!(*output.lock().unwrap(), vec![0xff, b'a', b'\n']);
+    }
+}
+
+#[test]
+fn lexeme_with_nul_is_rejected_when_deserialized() {
+    let res = serde_json::from_str::<CLIPSValue>(r#"{"Lexeme": [97, 0, 98]}"#);
+
+    assert!(res.is_err());
+}
@@ -0,0 +1,82 @@
+use clips::{CLIPSError, CLIPSValue, Environment, SlotMap};
+
+fn env() -> Environment {
+    let env = Environment::new();
+    env.load_from_str(
+        "(deftemplate order
+           (slot id (type INTEGER))
+           (slot status (type SYMBOL) (allowed-symbols open closed))
+           (slot quantity (type INTEGER) (range 1 10))
+           (multislot tags))",
+    )
+    .unwrap();
+    env
+}
+
+fn order() -> SlotMap {
+    SlotMap::new("order")
+        .slot("id", 1)
+        .slot("status", CLIPSValue::Symbol("open".into()))
+        .slot("quantity", 3)
+}
+
+fn fact_count(env: &Environment) -> usize {
+    env.find_all_facts("order", "TRUE").unwrap().len()
+}
+
+#[test]
+fn a_conforming_fact_is_valid_and_not_asserted() {
+    let env = env();
+
+    env.validate_fact(order(), None).unwrap();
+    assert_eq!(fact_count(&env), 0);
+
+    // The builder is left ready for the next fact.
+    env.assert_fact(order(), None).unwrap();
+    assert_eq!(fact_count(&env), 1);
+}
+
+#[test]
+fn constraint_violations_are_detailed_and_not_asserted() {
+    let env = env();
+
+    let cases = [
+        (order().slot("id", "one"), "type"),
+        (
+            order().slot("status", CLIPSValue::Symbol("lost".into())),
+            "allowed values",
+        ),
+        (order().slot("quantity", 11), "range"),
+        // A single value can't go into a multislot, nor a multifield into a slot.
+        (order().slot("tags", 1), "cardinality"),
+        (
+            order().slot("id", CLIPSValue::Multifield(vec![CLIPSValue::Int(1)])),
+            "cardinality",
+        ),
+        (order().slot("colour", 1), "unknown slot"),
+    ];
+
+    for (fact, case) in cases {
+        let error = env.validate_fact(fact, None).expect_err(case);
+        let expected = match case {
+            "type" => matches!(error, CLIPSError::SlotTypeViolated),
+            "allowed values" => matches!(error, CLIPSError::SlotAllowedValuesViolated),
+            "range" => matches!(error, CLIPSError::SlotRangeViolated),
+            "cardinality" => matches!(error, CLIPSError::SlotCardinalityViolated),
+            _ => matches!(error, CLIPSError::SlotNotFound),
+        };
+        assert!(expected, "{case}: {error:?}");
+    }
+
+    assert_eq!(fact_count(&env), 0);
+}
+
+#[test]
+fn unknown_templates_are_reported() {
+    let env = env();
+
+    assert!(matches!(
+        env.validate_fact(SlotMap::new("missing").slot("id", 1), None),
+        Err(CLIPSError::TemplateNotFound)
+    ));
+}
@@ -0,0 +1,111 @@
+use std::collections::HashMap;
+
+use clips::{
+    CLIPSError, CLIPSValue, ConstraintViolationKind, Environment, SlotMismatch, SlotMismatchKind,
+};
+
+fn env() -> Environment {
+    let env = Environment::new();
+    env.load_from_str(
+        "(deftemplate order
+           (slot id (type INTEGER) (default ?NONE))
+           (slot status (type SYMBOL) (allowed-symbols open closed))
+           (slot quantity (type INTEGER) (range 1 10))
+           (multislot tags))
+         (defclass customer (is-a USER)
+           (slot nickname (type STRING))
+           (slot age (type INTEGER) (range 0 150)))",
+    )
+    .unwrap();
+    env
+}
+
+fn slots<const N: usize>(slots: [(&str, CLIPSValue); N]) -> HashMap<String, CLIPSValue> {
+    slots
+        .into_iter()
+        .map(|(slot, value)| (slot.to_string(), value))
+        .collect()
+}
+
+fn mismatch(slot: &str, kind: SlotMismatchKind) -> SlotMismatch {
+    SlotMismatch {
+        slot: slot.to_string(),
+        kind,
+    }
+}
+
+fn constraint(kind: ConstraintViolationKind) -> SlotMismatchKind {
+    SlotMismatchKind::Constraint(kind)
+}
+
+#[test]
+fn a_conforming_map_has_no_mismatches() {
+    let env = env();
+    let order = slots([
+        ("id", CLIPSValue::Int(1)),
+        ("status", CLIPSValue::Symbol("open".to_string())),
+        ("tags", CLIPSValue::Multifield(vec![])),
+    ]);
+
+    assert_eq!(env.validate_slot_map("order", &order).unwrap(), []);
+    assert!(env.find_all_facts("order", "TRUE").unwrap().is_empty());
+}
+
+#[test]
+fn every_bad_template_slot_is_reported() {
+    let env = env();
+    let order = slots([
+        ("status", CLIPSValue::Symbol("lost".to_string())),
+        ("quantity", CLIPSValue::Int(11)),
+        ("tags", CLIPSValue::Int(1)),
+        ("colour", CLIPSValue::Symbol("red".to_string())),
+    ]);
+
+    assert_eq!(
+        env.validate_slot_map("order", &order).unwrap(),
+        [
+            mismatch("colour", SlotMismatchKind::UnknownSlot),
+            mismatch("id", SlotMismatchKind::MissingValue),
+            mismatch("quantity", constraint(ConstraintViolationKind::Range)),
+            mismatch("status", constraint(ConstraintViolationKind::AllowedValues)),
+            mismatch("tags", constraint(ConstraintViolationKind::Cardinality)),
+        ]
+    );
+
+    // The builder is left ready for the next fact.
+    env.assert_string("(order (id 1))").unwrap();
+    assert_eq!(env.find_all_facts("order", "TRUE").unwrap().len(), 1);
+}
+
+#[test]
+fn every_bad_class_slot_is_reported() {
+    let env = env();
+    let customer = slots([
+        ("nickname", CLIPSValue::Int(1)),
+        ("age", CLIPSValue::Int(200)),
+        ("email", CLIPSValue::String("a@b.c".to_string())),
+    ]);
+
+    assert_eq!(
+        env.validate_slot_map("customer", &customer).unwrap(),
+        [
+            mismatch("age", constraint(ConstraintViolationKind::Range)),
+            mismatch("email", SlotMismatchKind::UnknownSlot),
+            mismatch("nickname", constraint(ConstraintViolationKind::Type)),
+        ]
+    );
+    assert!(env
+        .find_all_instances("customer", "TRUE")
+        .unwrap()
+        .is_empty());
+}
+
+#[test]
+fn unknown_templates_and_classes_are_errors() {
+    let env = env();
+
+    assert!(matches!(
+        env.validate_slot_map("invoice", &HashMap::new()),
+        Err(CLIPSError::ClassNotFound)
+    ));
+}
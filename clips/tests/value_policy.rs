@@ -0,0 +1,170 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use clips::{CLIPSEnvironment, CLIPSError, CLIPSValue, SlotMap, UDFType, ValueExtractionPolicy};
+
+fn global(env: &CLIPSEnvironment, name: &str) -> CLIPSValue {
+    env.retrieve_globals_values().unwrap()["MAIN"][name].clone()
+}
+
+#[test]
+fn boolean_symbols_are_bools_unless_turned_off() {
+    let mut env = CLIPSEnvironment::new().unwrap();
+    env.load_from_str(
+        "(defglobal ?*yes* = TRUE ?*no* = FALSE)
+         (deftemplate flag (slot value))",
+    )
+    .unwrap();
+    env.assert_string("(flag (value TRUE))").unwrap();
+
+    let flag = |env: &mut CLIPSEnvironment| {
+        env.find_all_facts("flag", "TRUE").unwrap()[0]
+            .slot("value")
+            .unwrap()
+            .clone()
+    };
+
+    assert_eq!(global(&env, "yes"), CLIPSValue::Bool(true));
+    assert_eq!(global(&env, "no"), CLIPSValue::Bool(false));
+    assert_eq!(flag(&mut env), CLIPSValue::Bool(true));
+
+    env.set_treat_boolean_symbols_as_bool(false);
+    assert_eq!(global(&env, "yes"), CLIPSValue::Symbol("TRUE".into()));
+    assert_eq!(global(&env, "no"), CLIPSValue::Symbol("FALSE".into()));
+    assert_eq!(flag(&mut env), CLIPSValue::Symbol("TRUE".into()));
+
+    env.set_treat_boolean_symbols_as_bool(true);
+    assert_eq!(global(&env, "yes"), CLIPSValue::Bool(true));
+}
+
+#[test]
+fn policy_is_kept_per_environment() {
+    let mut symbols = CLIPSEnvironment::new().unwrap();
+    let mut bools = CLIPSEnvironment::new().unwrap();
+
+    symbols.set_value_policy(ValueExtractionPolicy {
+        booleans_as_symbols: true,
+        ..Default::default()
+    });
+
+    for env in [&mut symbols, &mut bools] {
+        env.load_from_str("(defglobal ?*flag* = TRUE)").unwrap();
+    }
+
+    assert_eq!(global(&symbols, "flag"), CLIPSValue::Symbol("TRUE".into()));
+    assert_eq!(global(&bools, "flag"), CLIPSValue::Bool(true));
+    assert!(symbols.value_policy().booleans_as_symbols);
+    assert!(!bools.value_policy().booleans_as_symbols);
+}
+
+#[test]
+fn udf_arguments_follow_the_environment_policy() {
+    let mut env = CLIPSEnvironment::new().unwrap();
+    env.set_treat_boolean_symbols_as_bool(false);
+
+    let seen = Arc::new(Mutex::new(None));
+    let seen_in_udf = seen.clone();
+    env.add_udf(
+        "keep",
+        UDFType::Void,
+        1,
+        1,
+        vec![],
+        Box::new(move |mut data| {
+            *seen_in_udf.lock().unwrap() = Some(data.first_arg::<CLIPSValue>().unwrap());
+            data.set_void();
+        }),
+    )
+    .unwrap();
+
+    env.load_from_str("(defglobal ?*unused* = (keep FALSE))")
+        .unwrap();
+
+    assert_eq!(
+        seen.lock().unwrap().take(),
+        Some(CLIPSValue::Symbol("FALSE".into()))
+    );
+}
+
+#[test]
+fn unsupported_udf_argument_is_an_error() {
+    let mut env = CLIPSEnvironment::new().unwrap();
+    env.load_from_str("(deftemplate point (slot x))").unwrap();
+
+    let seen = Arc::new(Mutex::new(None));
+    let seen_in_udf = seen.clone();
+    env.add_udf(
+        "keep",
+        UDFType::Void,
+        1,
+        1,
+        vec![],
+        Box::new(move |mut data| {
+            *seen_in_udf.lock().unwrap() = Some(data.first_arg::<CLIPSValue>());
+            data.set_void();
+        }),
+    )
+    .unwrap();
+
+    env.load_from_str("(defglobal ?*unused* = (keep (assert (point (x 1)))))")
+        .unwrap();
+
+    assert!(matches!(
+        seen.lock().unwrap().take(),
+        Some(Err(CLIPSError::UnsupportedValueType("FACT-ADDRESS")))
+    ));
+}
+
+#[test]
+fn values_round_trip_under_both_policies() {
+    for booleans_as_symbols in [false, true] {
+        let mut env = CLIPSEnvironment::new().unwrap();
+        env.set_value_policy(ValueExtractionPolicy {
+            booleans_as_symbols,
+            ..Default::default()
+        });
+        env.load_from_str(
+            "(deftemplate flag (slot value))
+             (defglobal ?*flag* = nil)",
+        )
+        .unwrap();
+
+        // Each policy reads back what it would give, whichever way the value was written.
+        let (yes, no) = if booleans_as_symbols {
+            (
+                CLIPSValue::Symbol("TRUE".into()),
+                CLIPSValue::Symbol("FALSE".into()),
+            )
+        } else {
+            (CLIPSValue::Bool(true), CLIPSValue::Bool(false))
+        };
+
+        for (written, read) in [
+            (CLIPSValue::Bool(true), &yes),
+            (CLIPSValue::Symbol("TRUE".into()), &yes),
+            (CLIPSValue::Bool(false), &no),
+            (
+                CLIPSValue::Symbol("label".into()),
+                &CLIPSValue::Symbol("label".into()),
+            ),
+        ] {
+            env.assert_fact(
+                Box::new(SlotMap::new("flag").slot("value", written.clone())),
+                None,
+            )
+            .unwrap();
+            let facts = env.find_all_facts("flag", "TRUE").unwrap();
+            assert_eq!(facts.last().unwrap().slot("value"), Some(read));
+            env.retract_where("flag", "TRUE").unwrap();
+
+            env.restore_globals(HashMap::from([(
+                "MAIN".to_string(),
+                HashMap::from([("flag".to_string(), written)]),
+            )]))
+            .unwrap();
+            assert_eq!(&global(&env, "flag"), read);
+        }
+    }
+}
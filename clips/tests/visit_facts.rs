@@ -0,0 +1,38 @@
+use clips::{CLIPSEnvironment, CLIPSError, CLIPSValue, CLIPSValueRef};
+
+#[test]
+fn visits_every_fact_of_the_template() {
+    let mut env = CLIPSEnvironment::new().unwrap();
+    env.load_from_str("(deftemplate point (slot x))").unwrap();
+    env.assert_string("(point (x 1))").unwrap();
+    env.assert_string("(point (x 2))").unwrap();
+
+    let mut seen = Vec::new();
+    env.visit_facts("point", |fact| {
+        seen.push(fact.slot("x").cloned().map(CLIPSValueRef::into_owned));
+    })
+    .unwrap();
+
+    assert_eq!(
+        seen,
+        vec![Some(CLIPSValue::Int(1)), Some(CLIPSValue::Int(2))]
+    );
+}
+
+#[test]
+fn unsupported_slot_value_is_an_error() {
+    let mut env = CLIPSEnvironment::new().unwrap();
+    env.load_from_str(
+        "(deftemplate point (slot x))
+         (deftemplate link (slot target))
+         (defglobal ?*link* = (assert (link (target (assert (point (x 1)))))))",
+    )
+    .unwrap();
+
+    let res = env.visit_facts("link", |_| {});
+
+    assert!(matches!(
+        res,
+        Err(CLIPSError::UnsupportedValueType("FACT-ADDRESS"))
+    ));
+}
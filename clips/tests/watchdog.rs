@@ -0,0 +1,104 @@
+use std::{
+    collections::HashMap,
+    sync::mpsc,
+    time::{Duration, Instant},
+};
+
+use clips::{Environment, WatchdogAction, WatchdogConfig};
+
+// `spin` never lets the agenda empty, and `stuck` never finishes firing.
+const PROGRAM: &str = "
+    (deftemplate counter (slot value))
+    (defrule spin
+      ?counter <- (counter (value ?value))
+      =>
+      (modify ?counter (value (+ ?value 1))))
+    (defrule stuck
+      (stuck)
+      =>
+      (while TRUE do (+ 1 1)))
+    (defrule finish
+      (finish ?n&:(> ?n 0))
+      =>
+      (assert (finish (- ?n 1))))";
+
+fn env(action: WatchdogAction) -> Environment {
+    let env = Environment::new();
+    env.load_from_str(PROGRAM).unwrap();
+    env.set_watchdog(WatchdogConfig::new(Duration::from_millis(100), action))
+        .unwrap();
+    env
+}
+
+fn run_promptly(env: &Environment) -> usize {
+    let start = Instant::now();
+    let fired = env.run().unwrap();
+    assert!(
+        start.elapsed() < Duration::from_secs(5),
+        "took {:?}",
+        start.elapsed()
+    );
+    fired
+}
+
+#[test]
+fn a_run_that_never_quiesces_is_halted_with_a_report() {
+    let env = env(WatchdogAction::DumpAndHalt);
+    env.assert_string("(counter (value 0))").unwrap();
+    env.assert_string("(note)").unwrap();
+
+    let fired = run_promptly(&env);
+
+    let report = env.last_watchdog_report().unwrap().unwrap();
+    assert!(report.elapsed >= Duration::from_millis(100));
+    assert_eq!(report.recent_rules, vec!["spin".to_string(); 10]);
+    assert_eq!(report.facts, 2);
+    assert_eq!(
+        report.facts_by_template,
+        HashMap::from([("counter".to_string(), 1), ("note".to_string(), 1)])
+    );
+    assert_eq!(report.agenda.len(), 1);
+    assert_eq!(report.agenda[0].rule, "spin");
+    assert!(fired >= 10);
+}
+
+#[test]
+fn a_rule_stuck_in_a_loop_is_halted() {
+    let env = env(WatchdogAction::Halt);
+    env.assert_string("(stuck)").unwrap();
+
+    run_promptly(&env);
+
+    // `Halt` makes no report, and the environment keeps working afterwards.
+    assert_eq!(env.last_watchdog_report().unwrap(), None);
+    env.assert_string("(finish 3)").unwrap();
+    assert_eq!(env.run().unwrap(), 3);
+}
+
+#[test]
+fn a_callback_can_let_the_run_go_on() {
+    let (tx, rx) = mpsc::channel();
+    let env = env(WatchdogAction::Callback(Box::new(move |report| {
+        tx.send(report.recent_rules.clone()).unwrap();
+        false
+    })));
+    env.load_from_str(
+        "(defrule slow (slow) => (loop-for-count 5000000 do (+ 1 1)) (assert (finish 2)))",
+    )
+    .unwrap();
+    env.assert_string("(slow)").unwrap();
+
+    // The watchdog goes off while `slow` is still firing, and the run finishes on its own.
+    assert_eq!(run_promptly(&env), 3);
+    assert_eq!(rx.try_iter().collect::<Vec<_>>(), [Vec::<String>::new()]);
+    assert!(env.last_watchdog_report().unwrap().is_some());
+}
+
+#[test]
+fn runs_within_the_limit_are_left_alone() {
+    let env = env(WatchdogAction::DumpAndHalt);
+    env.assert_string("(finish 5)").unwrap();
+
+    assert_eq!(env.run().unwrap(), 5);
+    assert_eq!(env.last_watchdog_report().unwrap(), None);
+}
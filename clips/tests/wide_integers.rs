@@ -0,0 +1,24 @@
+use clips::{CLIPSError, CLIPSValue};
+
+#[test]
+fn integers_that_fit_in_64_bits_are_converted() {
+    assert_eq!(
+        CLIPSValue::try_from_i128(i64::MIN as i128).unwrap(),
+        CLIPSValue::Int(i64::MIN)
+    );
+    assert_eq!(CLIPSValue::try_from(-42i128).unwrap(), CLIPSValue::Int(-42));
+}
+
+#[test]
+fn integers_that_dont_fit_are_rejected() {
+    let too_big = i64::MAX as i128 + 1;
+
+    assert!(matches!(
+        CLIPSValue::try_from_i128(too_big),
+        Err(CLIPSError::IntOutOfRange(value)) if value == too_big
+    ));
+    assert!(matches!(
+        CLIPSValue::try_from(i128::MIN),
+        Err(CLIPSError::IntOutOfRange(i128::MIN))
+    ));
+}